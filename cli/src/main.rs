@@ -0,0 +1,223 @@
+//! Runs commands on a crab from the host.
+//!
+//! `crab-cli set 7 high` runs a line of console commands and prints their
+//! responses as `key=value` pairs, and `crab-cli adc 0 --watch` runs it
+//! over and over. Without `--port` the serial ports are probed with
+//! `id get`, and the first crab to answer is used, or the one with the
+//! name given with `--id`. `crab-cli ports` lists the crabs it finds.
+//!
+//! The crab is driven through [`client`], which switches its console to
+//! machine mode, and only 8N1 serial settings are supported.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+use client::protocol::{split_command, COMMANDS};
+use client::{Crab, Response};
+
+const USAGE: &str = "usage: crab-cli [--port <path>] [--baud <rate>] [--id <name>] \
+    [--timeout <ms>] [--watch] [--interval <ms>] <command...>
+       crab-cli [--baud <rate>] ports";
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    port: Option<PathBuf>,
+    baud: u32,
+    id: Option<String>,
+    /// How long to wait for the response to each command.
+    timeout: Duration,
+    watch: bool,
+    /// How long to wait between runs with `--watch`.
+    interval: Duration,
+    /// The command line, split into words by the shell.
+    words: Vec<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        port: None,
+        baud: Crab::DEFAULT_BAUD,
+        id: None,
+        timeout: Duration::from_secs(10),
+        watch: false,
+        interval: Duration::from_secs(1),
+        words: Vec::new(),
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--port" => options.port = Some(value()?.into()),
+            "--baud" => options.baud = number(&arg, &value()?)?,
+            "--id" => options.id = Some(value()?),
+            "--timeout" => options.timeout = Duration::from_millis(number(&arg, &value()?)?),
+            "--watch" => options.watch = true,
+            "--interval" => options.interval = Duration::from_millis(number(&arg, &value()?)?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {arg}")),
+            _ => options.words.push(arg),
+        }
+    }
+    Ok(options)
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {option}: {value}"))
+}
+
+/// Splits a line into its `;`-separated commands, which the crab answers
+/// one at a time, and checks that each starts with a command the firmware
+/// knows so that typos don't reset the board for nothing.
+fn split(line: &str) -> Result<Vec<&str>, String> {
+    let mut commands = Vec::new();
+    let mut rest = line;
+    while !rest.is_empty() {
+        let (command, tail) = split_command(rest);
+        rest = tail;
+        if command.is_empty() {
+            continue;
+        }
+        let word = command.split_whitespace().next().unwrap_or_default();
+        if !COMMANDS
+            .split(' ')
+            .any(|known| known.eq_ignore_ascii_case(word))
+        {
+            return Err(format!("unknown command: {word}"));
+        }
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+/// Connects to the crab on the given port, or finds one.
+fn connect(options: &Options) -> client::Result<Crab> {
+    let mut crab = match &options.port {
+        Some(path) => Crab::open(path, options.baud)?,
+        None => Crab::discover(options.baud, options.id.as_deref())?,
+    };
+    crab.set_timeout(options.timeout);
+    Ok(crab)
+}
+
+/// Lists the ports with a crab on them, and the crabs' names.
+fn list_ports(options: &Options) -> ExitCode {
+    let crabs = Crab::scan(options.baud);
+    for (path, id) in &crabs {
+        println!("{} {}", path.display(), id);
+    }
+    if crabs.is_empty() {
+        eprintln!("crab-cli: no crabs found");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) if !options.words.is_empty() => options,
+        Ok(_) => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+        Err(message) => {
+            eprintln!("crab-cli: {message}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    if options.words == ["ports"] {
+        return list_ports(&options);
+    }
+    let line = options.words.join(" ");
+    let commands = match split(&line) {
+        Ok(commands) => commands,
+        Err(message) => {
+            eprintln!("crab-cli: {message}");
+            return ExitCode::from(2);
+        }
+    };
+    let mut crab = match connect(&options) {
+        Ok(crab) => crab,
+        Err(error) => {
+            eprintln!("crab-cli: {error}");
+            return ExitCode::from(2);
+        }
+    };
+    loop {
+        for command in &commands {
+            match crab.command(command) {
+                Ok(Response {
+                    error: None,
+                    output,
+                }) => {
+                    if !output.is_empty() {
+                        println!("{output}");
+                    }
+                }
+                Ok(Response {
+                    error: Some(code),
+                    output,
+                }) => {
+                    if !output.is_empty() {
+                        eprintln!("{output}");
+                    }
+                    eprintln!("error: {code}");
+                    return ExitCode::FAILURE;
+                }
+                Err(error) => {
+                    eprintln!("crab-cli: {error}");
+                    return ExitCode::from(2);
+                }
+            }
+        }
+        if !options.watch {
+            return ExitCode::SUCCESS;
+        }
+        thread::sleep(options.interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<Options, String> {
+        parse_args(line.split(' ').map(String::from))
+    }
+
+    #[test]
+    fn options_go_anywhere() {
+        let options = args("adc 0 --watch --interval 250 --port /dev/ttyACM0").unwrap();
+        assert_eq!(options.words, ["adc", "0"]);
+        assert!(options.watch);
+        assert_eq!(options.interval, Duration::from_millis(250));
+        assert_eq!(options.port, Some(PathBuf::from("/dev/ttyACM0")));
+        assert_eq!(options.baud, Crab::DEFAULT_BAUD);
+    }
+
+    #[test]
+    fn bad_options() {
+        assert_eq!(args("--baud fast"), Err("invalid value for --baud: fast".into()));
+        assert_eq!(args("set 7 high --id"), Err("missing value for --id".into()));
+        assert_eq!(args("--verbose"), Err("unknown option: --verbose".into()));
+    }
+
+    #[test]
+    fn lines_are_split_into_commands() {
+        assert_eq!(
+            split("set 7 high; delay 100; SET 7 low"),
+            Ok(vec!["set 7 high", "delay 100", "SET 7 low"])
+        );
+        assert_eq!(
+            split("macro define blink set 7 high; set 7 low"),
+            Ok(vec!["macro define blink set 7 high; set 7 low"])
+        );
+        assert_eq!(split("set 7 high; frob"), Err("unknown command: frob".into()));
+    }
+}
@@ -0,0 +1,380 @@
+//! A connection to the crab's console, in machine mode.
+//!
+//! Each command is answered by a line starting with `OK` or `ERR <code>`,
+//! see [`Response`]. Anything else the crab prints, such as events, alerts,
+//! and the banner after a reset, is skipped.
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use protocol::args::console::Mode;
+use protocol::format::write_command;
+use protocol::Command;
+
+use crate::port::{self, Port};
+use crate::{Error, Level, Result};
+
+/// How long to wait for the banner after opening the port. The Uno resets
+/// when the port opens, and its bootloader waits a moment before starting
+/// the firmware, while boards that don't reset never print one.
+const BOOT_TIMEOUT: Duration = Duration::from_millis(2500);
+
+/// The start of the line the firmware prints once it is ready.
+const BANNER: &str = "mechanical crab ready";
+
+/// How long to wait for the response to a command, unless set otherwise.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a crab has to answer `id get` during discovery. A port with
+/// something else on it shouldn't hold up the search for long.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The commands that set up the console for [`Crab`].
+const SETUP: [Command<'static>; 4] = [
+    Command::Mode(Mode::Machine),
+    Command::Echo(false),
+    Command::Prompt(false),
+    Command::Timestamps(false),
+];
+
+/// The response to a command.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Response {
+    /// The error code if the command failed, such as `bad_pin`.
+    pub error: Option<String>,
+    /// The command's output as `key=value` pairs, or the error message.
+    pub output: String,
+}
+
+impl Response {
+    /// Parses a line of machine mode output, or returns `None` if it is not
+    /// the end of a command.
+    pub fn parse(line: &str) -> Option<Response> {
+        let mut line = line.trim();
+        // The prompt of a console that still had it on.
+        while let Some(rest) = line.strip_prefix('>') {
+            line = rest.trim_start();
+        }
+        if let Some(rest) = word(line, "OK") {
+            return Some(Response {
+                error: None,
+                output: rest.into(),
+            });
+        }
+        let rest = word(line, "ERR")?;
+        let (code, message) = rest.split_once(' ').unwrap_or((rest, ""));
+        Some(Response {
+            error: Some(code.into()),
+            output: message.into(),
+        })
+    }
+
+    /// Returns the value of a `key=value` pair in the output.
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.output.split(' ').find_map(|pair| {
+            let (k, value) = pair.split_once('=')?;
+            (k == key).then_some(value)
+        })
+    }
+
+    /// Turns a failure into an [`Error`].
+    fn into_result(self) -> Result<Response> {
+        match self.error {
+            None => Ok(self),
+            Some(code) => Err(Error::Failed {
+                error: protocol::Error::from_code(&code),
+                code,
+                message: self.output,
+            }),
+        }
+    }
+}
+
+/// Strips a word off the start of a line, and the space after it.
+fn word<'a>(line: &'a str, word: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(word)?;
+    if rest.is_empty() {
+        return Some(rest);
+    }
+    rest.strip_prefix(' ')
+}
+
+/// Parses a number as printed in any base.
+fn number(value: &str) -> Option<u16> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        return u16::from_str_radix(hex, 16).ok();
+    }
+    if let Some(bin) = value.strip_prefix("0b") {
+        return u16::from_str_radix(bin, 2).ok();
+    }
+    value.parse().ok()
+}
+
+pub struct Crab {
+    port: Port,
+    /// Bytes received after the last complete line.
+    input: Vec<u8>,
+    /// How long to wait for the response to a command.
+    timeout: Duration,
+}
+
+impl Crab {
+    /// The firmware's baud rate, unless it was built for another or told to
+    /// switch with `serial`.
+    pub const DEFAULT_BAUD: u32 = 57_600;
+
+    /// Connects to the crab on a serial port.
+    pub fn open(path: &Path, baud: u32) -> Result<Crab> {
+        Crab::connect(Port::open(path, baud)?, DEFAULT_TIMEOUT)
+    }
+
+    /// Connects to the first crab found on the serial ports, or the first
+    /// named `id` if given.
+    pub fn discover(baud: u32, id: Option<&str>) -> Result<Crab> {
+        let mut reason = "no serial ports".to_string();
+        for path in port::candidates() {
+            match Crab::probe(&path, baud) {
+                Ok((mut crab, name)) if id.map_or(true, |id| id == name) => {
+                    crab.set_timeout(DEFAULT_TIMEOUT);
+                    return Ok(crab);
+                }
+                Ok((_, name)) => reason = format!("{}: crab {name:?}", path.display()),
+                Err(error) => reason = format!("{}: {error}", path.display()),
+            }
+        }
+        Err(Error::NotFound(reason))
+    }
+
+    /// Lists the serial ports with a crab on them, and the crabs' names.
+    pub fn scan(baud: u32) -> Vec<(PathBuf, String)> {
+        port::candidates()
+            .into_iter()
+            .filter_map(|path| {
+                let (_, name) = Crab::probe(&path, baud).ok()?;
+                Some((path, name))
+            })
+            .collect()
+    }
+
+    /// Connects to a port, and asks the crab on it for its name.
+    fn probe(path: &Path, baud: u32) -> Result<(Crab, String)> {
+        let mut crab = Crab::connect(Port::open(path, baud)?, PROBE_TIMEOUT)?;
+        let name = crab.id()?;
+        Ok((crab, name))
+    }
+
+    /// Waits for the crab to start up and switches its console to machine
+    /// mode, without echo, prompts, or timestamps.
+    fn connect(port: Port, timeout: Duration) -> Result<Crab> {
+        let mut crab = Crab {
+            port,
+            input: Vec::new(),
+            timeout,
+        };
+        let deadline = Instant::now() + BOOT_TIMEOUT;
+        while let Some(line) = crab.read_line(deadline)? {
+            if line.starts_with(BANNER) {
+                break;
+            }
+        }
+        for command in &SETUP {
+            crab.run(command)?;
+        }
+        Ok(crab)
+    }
+
+    /// Sets how long to wait for the response to a command.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Runs a single line of text and waits for its response. The crab
+    /// answers each command of a `;`-separated line in turn, so lines have
+    /// to be split up first.
+    pub fn command(&mut self, line: &str) -> io::Result<Response> {
+        self.port.write_all(line.as_bytes())?;
+        self.port.write_all(b"\n")?;
+        self.port.flush()?;
+        let deadline = Instant::now() + self.timeout;
+        while let Some(line) = self.read_line(deadline)? {
+            if let Some(response) = Response::parse(&line) {
+                return Ok(response);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "no response from the crab",
+        ))
+    }
+
+    /// Runs a command, failing if the crab reports an error, or with
+    /// [`Error::Unsupported`] for commands that [`protocol::format`] doesn't
+    /// cover.
+    pub fn run(&mut self, command: &Command<'_>) -> Result<Response> {
+        let mut line = String::new();
+        let Some(Ok(())) = write_command(&mut line, command) else {
+            return Err(Error::Unsupported);
+        };
+        self.command(&line)?.into_result()
+    }
+
+    /// Runs a command and returns a value from its output.
+    fn value(&mut self, command: &Command<'_>, key: &str) -> Result<String> {
+        let response = self.run(command)?;
+        match response.value(key) {
+            Some(value) => Ok(value.into()),
+            None => Err(Error::Unexpected(response.output)),
+        }
+    }
+
+    pub fn led(&mut self, on: bool) -> Result<()> {
+        self.run(&Command::Led(on)).map(drop)
+    }
+
+    /// Reads the level of a digital pin, or the level it is driven to if it
+    /// is an output.
+    pub fn get_pin(&mut self, pin: u8) -> Result<Level> {
+        match self.value(&Command::GetPin { pin }, &format!("d{pin}"))?.as_str() {
+            "true" => Ok(Level::High),
+            "false" => Ok(Level::Low),
+            other => Err(Error::Unexpected(other.into())),
+        }
+    }
+
+    /// Makes a digital pin an output and drives it.
+    pub fn set_pin(&mut self, pin: u8, level: Level) -> Result<()> {
+        let value = level.into();
+        self.run(&Command::SetPin { pin, value }).map(drop)
+    }
+
+    /// Reads an analog input, from 0 to 1023.
+    pub fn adc(&mut self, pin: u8) -> Result<u16> {
+        let value = self.value(&Command::Adc { pin }, &format!("a{pin}"))?;
+        number(&value).ok_or(Error::Unexpected(value))
+    }
+
+    /// Sets the duty cycle of the hardware PWM output.
+    pub fn pwm(&mut self, duty_cycle: u8) -> Result<()> {
+        self.run(&Command::Pwm { duty_cycle }).map(drop)
+    }
+
+    /// Moves a servo to an angle from 0 to 180 degrees.
+    pub fn servo(&mut self, pin: u8, angle: u8) -> Result<()> {
+        self.run(&Command::Servo { pin, angle }).map(drop)
+    }
+
+    /// Returns the device name stored with [`Crab::set_id`].
+    pub fn id(&mut self) -> Result<String> {
+        let response = self.run(&Command::IdGet)?;
+        match response.output.strip_prefix("id=") {
+            Some(name) => Ok(name.into()),
+            None => Err(Error::Unexpected(response.output)),
+        }
+    }
+
+    /// Stores a device name of up to 15 characters.
+    pub fn set_id(&mut self, name: &str) -> Result<()> {
+        self.run(&Command::IdSet { name }).map(drop)
+    }
+
+    /// Returns the firmware's version.
+    pub fn version(&mut self) -> Result<String> {
+        self.value(&Command::Version, "version")
+    }
+
+    /// Reads the next line, or returns `None` if none came before the
+    /// deadline.
+    fn read_line(&mut self, deadline: Instant) -> io::Result<Option<String>> {
+        loop {
+            if let Some(end) = self.input.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.input.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                return Ok(Some(line.trim_end_matches(['\r', '\n']).into()));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            let mut buf = [0; 64];
+            let n = self.port.read(&mut buf)?;
+            self.input.extend_from_slice(&buf[..n]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(output: &str) -> Option<Response> {
+        Some(Response {
+            error: None,
+            output: output.into(),
+        })
+    }
+
+    #[test]
+    fn successes() {
+        assert_eq!(Response::parse("OK"), ok(""));
+        assert_eq!(Response::parse("OK a0=512\r"), ok("a0=512"));
+        assert_eq!(Response::parse("> OK d7=true"), ok("d7=true"));
+    }
+
+    #[test]
+    fn failures() {
+        assert_eq!(
+            Response::parse("ERR bad_pin unknown pin: 5"),
+            Some(Response {
+                error: Some("bad_pin".into()),
+                output: "unknown pin: 5".into(),
+            })
+        );
+        assert_eq!(
+            Response::parse("ERR busy"),
+            Some(Response {
+                error: Some("busy".into()),
+                output: String::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn other_lines_are_skipped() {
+        assert_eq!(Response::parse("mechanical crab ready, last reset: power"), None);
+        assert_eq!(Response::parse("OKAY"), None);
+        assert_eq!(Response::parse("alert: a0 above 512"), None);
+        assert_eq!(Response::parse("set 7 high"), None);
+    }
+
+    #[test]
+    fn values() {
+        let response = Response::parse("OK d9=true name=pump").unwrap();
+        assert_eq!(response.value("d9"), Some("true"));
+        assert_eq!(response.value("name"), Some("pump"));
+        assert_eq!(response.value("d1"), None);
+        assert_eq!(number("512"), Some(512));
+        assert_eq!(number("0x200"), Some(512));
+        assert_eq!(number("0b1000000000"), Some(512));
+    }
+
+    #[test]
+    fn failures_carry_the_error() {
+        let response = Response::parse("ERR bad_pin unknown pin: 5").unwrap();
+        match response.into_result() {
+            Err(Error::Failed {
+                error, message, ..
+            }) => {
+                assert_eq!(error, Some(protocol::Error::BadPin));
+                assert_eq!(message, "unknown pin: 5");
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn setup_is_covered_by_format() {
+        for command in &SETUP {
+            assert!(write_command(&mut String::new(), command).is_some());
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Typed access to a mechanical crab over its serial port.
+//!
+//! [`Crab`] connects to the crab's console and runs commands on it, typed
+//! ones such as [`Crab::set_pin`] and [`Crab::adc`], or lines of text with
+//! [`Crab::command`]. The typed methods build [`protocol::Command`]s and
+//! write them out with [`protocol::format`], so they use the very
+//! definitions the firmware parses its input into.
+//!
+//! ```no_run
+//! use mechanical_crab_client::{Crab, Level};
+//!
+//! let mut crab = Crab::discover(Crab::DEFAULT_BAUD, None)?;
+//! crab.set_pin(7, Level::High)?;
+//! println!("a0: {}", crab.adc(0)?);
+//! # Ok::<(), mechanical_crab_client::Error>(())
+//! ```
+
+mod crab;
+pub mod port;
+
+use std::fmt;
+use std::io;
+
+pub use protocol;
+
+pub use crate::crab::{Crab, Response};
+
+/// The level of a digital pin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Low,
+    High,
+}
+
+impl From<bool> for Level {
+    fn from(high: bool) -> Self {
+        if high {
+            Level::High
+        } else {
+            Level::Low
+        }
+    }
+}
+
+impl From<Level> for bool {
+    fn from(level: Level) -> Self {
+        level == Level::High
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// No crab answered on any port, or none with the name asked for. The
+    /// message says why the last port tried didn't do.
+    NotFound(String),
+    /// The command failed on the crab.
+    Failed {
+        /// The error code, or `None` for a code this version doesn't know.
+        error: Option<protocol::Error>,
+        code: String,
+        message: String,
+    },
+    /// The crab's output is not what the command was expected to print.
+    Unexpected(String),
+    /// [`protocol::format`] can't write the command out as text.
+    Unsupported,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{error}"),
+            Error::NotFound(message) => write!(f, "no crab found: {message}"),
+            Error::Failed { code, message, .. } if message.is_empty() => write!(f, "{code}"),
+            Error::Failed { code, message, .. } => write!(f, "{code}: {message}"),
+            Error::Unexpected(output) => write!(f, "unexpected response: {output}"),
+            Error::Unsupported => write!(f, "command not covered by protocol::format"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
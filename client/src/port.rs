@@ -0,0 +1,83 @@
+//! The serial port, opened through the `serialport` crate.
+//!
+//! Reads return after a tenth of a second without input, so that callers
+//! can give up on a crab that doesn't answer.
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serialport::{SerialPort, SerialPortType};
+
+/// The prefixes of the device names that USB serial adapters and boards
+/// with native USB show up as, on Linux and macOS. Without udev the type of
+/// a port is unknown, so its name is all there is to go by.
+const DEVICE_PREFIXES: &[&str] = &["ttyACM", "ttyUSB", "cu.usbmodem", "cu.usbserial"];
+
+/// The baud rates the firmware's `serial` command offers.
+const BAUD_RATES: [u32; 8] = [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115_200];
+
+/// How long a read waits for the first byte.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub struct Port {
+    port: Box<dyn SerialPort>,
+}
+
+impl Port {
+    pub fn open(path: &Path, baud: u32) -> io::Result<Port> {
+        if !BAUD_RATES.contains(&baud) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported baud rate: {baud}"),
+            ));
+        }
+        let port = serialport::new(path.to_string_lossy(), baud)
+            .timeout(READ_TIMEOUT)
+            .open()?;
+        port.clear(serialport::ClearBuffer::All)?;
+        Ok(Port { port })
+    }
+}
+
+impl Read for Port {
+    /// Returns zero bytes if nothing arrived for a tenth of a second.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.port.read(buf) {
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => Ok(0),
+            result => result,
+        }
+    }
+}
+
+impl Write for Port {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+/// Lists the serial ports a crab could be on, in order of their names: the
+/// USB ones, or all of them on Windows.
+pub fn candidates() -> Vec<PathBuf> {
+    let Ok(ports) = serialport::available_ports() else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = ports
+        .into_iter()
+        .filter(|port| {
+            let name = port.port_name.rsplit('/').next().unwrap_or_default();
+            cfg!(windows)
+                || matches!(port.port_type, SerialPortType::UsbPort(_))
+                || DEVICE_PREFIXES
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+        })
+        .map(|port| PathBuf::from(port.port_name))
+        .collect();
+    paths.sort();
+    paths
+}
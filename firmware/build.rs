@@ -0,0 +1,123 @@
+//! Embeds build information for the `version` command, and the details of
+//! each command for `help <command>`.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let git_hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=CRAB_GIT_HASH={git_hash}");
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_owned))
+        .map(|feature| feature.to_lowercase().replace('_', "-"))
+        // The board is listed on its own, whether it is the default or not.
+        .filter(|feature| feature != "default")
+        .collect();
+    features.sort();
+    let features = if features.is_empty() {
+        "none".to_owned()
+    } else {
+        features.join(",")
+    };
+    println!("cargo:rustc-env=CRAB_FEATURES={features}");
+
+    write_help_topics();
+
+    // The repository is above the crate, so its paths come from git.
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        println!("cargo:rerun-if-changed={git_dir}/refs/heads");
+    }
+    println!("cargo:rerun-if-changed=src/main.rs");
+}
+
+/// Turns the command table in the crate documentation into help topics, one
+/// per command keyword with all of its rows, and writes them out as a blob
+/// for flash. Each topic is its keyword and its text, both terminated by a
+/// zero byte, and an empty keyword ends the blob.
+///
+/// A row is only its syntax, unless the `help-details` feature adds its
+/// description and example.
+fn write_help_topics() {
+    let details = env::var_os("CARGO_FEATURE_HELP_DETAILS").is_some();
+    let main = fs::read_to_string("src/main.rs").expect("src/main.rs is readable");
+    let rows = main
+        .lines()
+        .filter_map(|line| line.strip_prefix("//! | "))
+        // Skips the header and the separator.
+        .skip(2);
+
+    let mut topics: Vec<(String, String)> = Vec::new();
+    for row in rows {
+        let cells: Vec<String> = split_cells(row).iter().map(|cell| plain(cell)).collect();
+        let (syntax, description) = (&cells[0], &cells[1]);
+        let keyword = syntax.split(' ').next().unwrap_or_default().to_owned();
+        let mut text = format!("{syntax}\n");
+        if details {
+            let _ = writeln!(text, "  {description}");
+            if let Some(example) = cells.get(2).filter(|example| !example.is_empty()) {
+                let _ = writeln!(text, "  e.g. {example}");
+            }
+        }
+        match topics.iter_mut().find(|(k, _)| *k == keyword) {
+            Some((_, topic)) => topic.push_str(&text),
+            None => topics.push((keyword, text)),
+        }
+    }
+
+    let mut blob = String::new();
+    for (keyword, text) in &topics {
+        let _ = write!(blob, "{keyword}\0{text}\0");
+    }
+    blob.push('\0');
+    assert!(blob.is_ascii(), "help topics have to be ASCII");
+    let escaped: String = blob
+        .bytes()
+        .map(|b| std::ascii::escape_default(b).to_string())
+        .collect();
+    let code = format!(
+        "pub const TOPICS_LEN: usize = {};\n\
+         #[link_section = \".progmem.data\"]\n\
+         static TOPICS: [u8; TOPICS_LEN] = *b\"{escaped}\";\n",
+        blob.len()
+    );
+    let out = env::var("OUT_DIR").expect("cargo sets OUT_DIR");
+    fs::write(Path::new(&out).join("help.rs"), code).expect("OUT_DIR is writable");
+}
+
+/// Runs git, and returns its output without the trailing newline.
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|output| output.trim().to_owned())
+}
+
+/// Splits a table row into its cells, leaving escaped bars alone.
+fn split_cells(row: &str) -> Vec<&str> {
+    let mut cells = Vec::new();
+    let mut start = 0;
+    let bytes = row.as_bytes();
+    for (i, &byte) in bytes.iter().enumerate() {
+        if byte == b'|' && (i == 0 || bytes[i - 1] != b'\\') {
+            cells.push(row[start..i].trim());
+            start = i + 1;
+        }
+    }
+    cells
+}
+
+/// Turns the Markdown of a cell into plain ASCII text.
+fn plain(cell: &str) -> String {
+    cell.replace("\\|", "|")
+        .replace('`', "")
+        .replace('…', "...")
+        .replace('°', " deg")
+}
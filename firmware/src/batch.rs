@@ -0,0 +1,54 @@
+//! Batch mode, which collects pasted lines and runs them once they are all
+//! in.
+//!
+//! The UART only holds a couple of received bytes, so a script pasted at the
+//! prompt loses characters whenever a command takes longer than that to run
+//! and print its response. While a batch is collected nothing runs and
+//! nothing is echoed, and the lines run one after another after the end
+//! marker.
+
+use heapless::String;
+
+/// How many bytes of lines a batch holds.
+pub const CAPACITY: usize = 256;
+
+/// The line that ends a batch.
+pub const END: &str = "end";
+
+pub struct Batch {
+    /// The lines, each ending in a newline.
+    lines: String<CAPACITY>,
+    /// Whether a line was dropped, for not fitting into the batch or into
+    /// the line editor.
+    pub overflowed: bool,
+    /// Whether input was echoed before the batch began.
+    pub echo: bool,
+}
+
+impl Batch {
+    pub fn new(echo: bool) -> Self {
+        Self {
+            lines: String::new(),
+            overflowed: false,
+            echo,
+        }
+    }
+
+    /// Adds a line. Once one doesn't fit, the rest are dropped and the batch
+    /// doesn't run.
+    pub fn push(&mut self, line: &str) {
+        if self.overflowed || line.trim().is_empty() {
+            return;
+        }
+        if self.lines.len() + line.len() >= CAPACITY {
+            self.overflowed = true;
+            return;
+        }
+        let _ = self.lines.push_str(line);
+        let _ = self.lines.push('\n');
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.lines()
+    }
+}
@@ -0,0 +1,164 @@
+//! Framed binary protocol, for programs that drive the crab at high rates.
+//!
+//! A frame holds `<opcode> <payload…> <crc>`, where the CRC is the CRC-16
+//! (CCITT, initial value 0xFFFF) of the opcode and payload, least
+//! significant byte first. Frames are COBS encoded and each ends with a zero
+//! byte, so that a receiver finds the start of the next frame again after
+//! losing bytes.
+//!
+//! The response to a request repeats its opcode, and its payload starts with
+//! a status byte, which is zero on success and an [`protocol::Error`]
+//! number otherwise. A request that arrives corrupted is answered with
+//! [`NAK`], and should be sent again. Text the crab prints outside of a
+//! request, such as events, arrives in [`EVENT`] frames.
+
+use embedded_hal::serial::Write;
+use heapless::Vec;
+use nb::block;
+
+use crate::LINE_LENGTH;
+
+/// Ends every frame.
+const DELIMITER: u8 = 0x00;
+
+/// Does nothing, to check that the crab is listening.
+pub const PING: u8 = 0x00;
+/// Runs a line of text commands. The response carries their output, joined
+/// into one line as in machine mode.
+pub const TEXT: u8 = 0x01;
+/// Reads a digital pin: `[pin]`, responding with `[level]`.
+pub const GET: u8 = 0x02;
+/// Sets a digital output: `[pin, level]`.
+pub const SET: u8 = 0x03;
+/// Reads an analog pin: `[pin]`, responding with the value in little endian.
+pub const ADC: u8 = 0x04;
+/// Sets the PWM duty cycle: `[duty]`.
+pub const PWM: u8 = 0x05;
+/// Switches back to the text console.
+pub const TEXT_MODE: u8 = 0x06;
+/// Response to a request that arrived corrupted or too long.
+pub const NAK: u8 = 0xFE;
+/// A line of text printed outside of a request.
+pub const EVENT: u8 = 0xFF;
+
+/// Longest payload accepted in a request.
+pub const MAX_PAYLOAD: usize = LINE_LENGTH;
+/// Longest data sent in a response, after the status byte.
+const MAX_DATA: usize = 128;
+
+/// An encoded request: the opcode, payload, and CRC, and a byte of COBS
+/// overhead.
+const MAX_ENCODED: usize = MAX_PAYLOAD + 4;
+/// A response before encoding.
+const MAX_RESPONSE: usize = MAX_DATA + 4;
+
+pub struct Frame {
+    pub opcode: u8,
+    pub payload: Vec<u8, MAX_PAYLOAD>,
+}
+
+/// Assembles request frames from the bytes received.
+pub struct Decoder {
+    encoded: Vec<u8, MAX_ENCODED>,
+    /// The current frame is too long, and dropped.
+    overlong: bool,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            encoded: Vec::new(),
+            overlong: false,
+        }
+    }
+
+    /// Takes the next byte, and returns the frame it completes, if any, or an
+    /// error if that frame is corrupted or too long.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Frame, ()>> {
+        if byte != DELIMITER {
+            self.overlong |= self.encoded.push(byte).is_err();
+            return None;
+        }
+        // Delimiters between frames are allowed, and the first one sent
+        // flushes out whatever noise came before.
+        if self.encoded.is_empty() && !self.overlong {
+            return None;
+        }
+        let result = if self.overlong {
+            Err(())
+        } else {
+            decode(&self.encoded)
+        };
+        self.encoded.clear();
+        self.overlong = false;
+        Some(result)
+    }
+}
+
+/// Decodes a frame and checks its CRC.
+fn decode(encoded: &[u8]) -> Result<Frame, ()> {
+    let mut decoded = Vec::<u8, MAX_ENCODED>::new();
+    let mut rest = encoded;
+    while let Some((&code, tail)) = rest.split_first() {
+        let len = usize::from(code).saturating_sub(1);
+        if len > tail.len() {
+            return Err(());
+        }
+        decoded.extend_from_slice(&tail[..len]).map_err(|_| ())?;
+        rest = &tail[len..];
+        // A full block of 254 bytes is not followed by a zero, and neither
+        // is the last block.
+        if code != 0xFF && !rest.is_empty() {
+            decoded.push(0).map_err(|_| ())?;
+        }
+    }
+    let [ref content @ .., low, high] = decoded[..] else {
+        return Err(());
+    };
+    let [opcode, ref payload @ ..] = *content else {
+        return Err(());
+    };
+    if crc16(content) != u16::from_le_bytes([low, high]) {
+        return Err(());
+    }
+    Ok(Frame {
+        opcode,
+        payload: Vec::from_slice(payload).map_err(|_| ())?,
+    })
+}
+
+/// CRC-16/CCITT with an initial value of 0xFFFF.
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    for &byte in bytes {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Sends a frame with a status byte ahead of the data, which is cut short
+/// after 128 bytes.
+pub fn write_frame<W: Write<u8>>(serial: &mut W, opcode: u8, status: u8, data: &[u8]) {
+    let mut content = Vec::<u8, MAX_RESPONSE>::new();
+    let _ = content.extend_from_slice(&[opcode, status]);
+    let _ = content.extend_from_slice(&data[..data.len().min(MAX_DATA)]);
+    let crc = crc16(&content);
+    let _ = content.extend_from_slice(&crc.to_le_bytes());
+    // Each run of non-zero bytes is sent after its length plus one, which
+    // takes the place of the zero that ends it. Responses are too short to
+    // need splitting runs at 254 bytes.
+    for run in content.split(|&byte| byte == 0) {
+        let _ = block!(serial.write(run.len() as u8 + 1));
+        for &byte in run {
+            let _ = block!(serial.write(byte));
+        }
+    }
+    let _ = block!(serial.write(DELIMITER));
+}
@@ -0,0 +1,268 @@
+//! BMP280 pressure and temperature sensors, and BME280 sensors which add
+//! humidity, over I2C or SPI.
+//!
+//! Every sensor comes with its own calibration constants, and the raw
+//! readings only mean something once run through Bosch's compensation
+//! formulas. These are the fixed-point versions from the datasheets.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+use crate::gpio::RawPin;
+use crate::{millis, spi};
+
+pub use protocol::args::bme280::Interface;
+
+const CALIBRATION: u8 = 0x88;
+const HUMIDITY_H1: u8 = 0xA1;
+const CHIP_ID: u8 = 0xD0;
+const HUMIDITY_CALIBRATION: u8 = 0xE1;
+const CTRL_HUM: u8 = 0xF2;
+const STATUS: u8 = 0xF3;
+const CTRL_MEAS: u8 = 0xF4;
+const PRESS_MSB: u8 = 0xF7;
+
+const BMP280_ID: u8 = 0x58;
+const BME280_ID: u8 = 0x60;
+
+/// One sample of each quantity.
+const OVERSAMPLING_1: u8 = 0b001;
+/// Takes a single measurement, then goes back to sleep.
+const FORCED_MODE: u8 = 0b01;
+/// Status bit set while a measurement is running.
+const MEASURING: u8 = 1 << 3;
+
+const TIMEOUT_MS: u32 = 100;
+
+/// A connected sensor, on either bus.
+pub enum Bus {
+    I2c { address: u8 },
+    Spi { cs: RawPin, config: spi::Config },
+}
+
+pub enum Error {
+    I2c(arduino_hal::i2c::Error),
+    /// Something answered that is neither a BMP280 nor a BME280.
+    UnknownDevice(u8),
+    Timeout,
+}
+
+impl From<arduino_hal::i2c::Error> for Error {
+    fn from(error: arduino_hal::i2c::Error) -> Self {
+        Self::I2c(error)
+    }
+}
+
+pub struct Reading {
+    /// Tenths of a degree Celsius.
+    pub temperature: i16,
+    /// Pascals.
+    pub pressure: u32,
+    /// Tenths of a percent relative humidity, only measured by the BME280.
+    pub humidity: Option<u16>,
+}
+
+impl Bus {
+    fn read(
+        &mut self,
+        i2c: &mut arduino_hal::I2c,
+        register: u8,
+        data: &mut [u8],
+    ) -> Result<(), Error> {
+        match self {
+            Bus::I2c { address } => i2c.write_read(*address, &[register], data)?,
+            Bus::Spi { cs, config } => {
+                data.fill(0);
+                cs.set_level(false);
+                let bus = spi::Bus::begin(config);
+                // The top bit of the register address marks a read.
+                bus.exchange(register | 0x80);
+                bus.transfer(data);
+                bus.end();
+                cs.set_level(true);
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, i2c: &mut arduino_hal::I2c, register: u8, value: u8) -> Result<(), Error> {
+        match self {
+            Bus::I2c { address } => i2c.write(*address, &[register, value])?,
+            Bus::Spi { cs, config } => {
+                cs.set_level(false);
+                let bus = spi::Bus::begin(config);
+                bus.transfer(&mut [register & 0x7F, value]);
+                bus.end();
+                cs.set_level(true);
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p: [i16; 8],
+    humidity: Option<HumidityCalibration>,
+}
+
+struct HumidityCalibration {
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+/// Takes a single measurement.
+pub fn read(mut bus: Bus, i2c: &mut arduino_hal::I2c) -> Result<Reading, Error> {
+    let mut id = [0];
+    bus.read(i2c, CHIP_ID, &mut id)?;
+    let has_humidity = match id[0] {
+        BMP280_ID => false,
+        BME280_ID => true,
+        id => return Err(Error::UnknownDevice(id)),
+    };
+    let calibration = read_calibration(&mut bus, i2c, has_humidity)?;
+
+    if has_humidity {
+        // Only takes effect with the following write to the measurement
+        // control register.
+        bus.write(i2c, CTRL_HUM, OVERSAMPLING_1)?;
+    }
+    bus.write(
+        i2c,
+        CTRL_MEAS,
+        OVERSAMPLING_1 << 5 | OVERSAMPLING_1 << 2 | FORCED_MODE,
+    )?;
+    let start = millis::millis();
+    loop {
+        let mut status = [0];
+        bus.read(i2c, STATUS, &mut status)?;
+        if status[0] & MEASURING == 0 {
+            break;
+        }
+        if millis::millis().wrapping_sub(start) > TIMEOUT_MS {
+            return Err(Error::Timeout);
+        }
+    }
+
+    // Pressure and temperature are 20 bits each, humidity 16 bits.
+    let mut raw = [0; 8];
+    let len = if has_humidity { 8 } else { 6 };
+    bus.read(i2c, PRESS_MSB, &mut raw[..len])?;
+    let twenty_bits = |i: usize| {
+        i32::from(raw[i]) << 12 | i32::from(raw[i + 1]) << 4 | i32::from(raw[i + 2]) >> 4
+    };
+    let adc_p = twenty_bits(0);
+    let adc_t = twenty_bits(3);
+    let adc_h = i32::from(raw[6]) << 8 | i32::from(raw[7]);
+
+    let t_fine = calibration.t_fine(adc_t);
+    let centi_celsius = (t_fine * 5 + 128) >> 8;
+    Ok(Reading {
+        temperature: (centi_celsius / 10) as i16,
+        pressure: calibration.pressure(t_fine, adc_p),
+        humidity: calibration
+            .humidity
+            .as_ref()
+            .map(|humidity| humidity.humidity(t_fine, adc_h)),
+    })
+}
+
+fn read_calibration(
+    bus: &mut Bus,
+    i2c: &mut arduino_hal::I2c,
+    has_humidity: bool,
+) -> Result<Calibration, Error> {
+    let mut data = [0; 24];
+    bus.read(i2c, CALIBRATION, &mut data)?;
+    let word = |i: usize| u16::from_le_bytes([data[2 * i], data[2 * i + 1]]);
+    let mut p = [0; 8];
+    for (i, value) in p.iter_mut().enumerate() {
+        *value = word(4 + i) as i16;
+    }
+    let humidity = if has_humidity {
+        let mut h1 = [0];
+        bus.read(i2c, HUMIDITY_H1, &mut h1)?;
+        let mut data = [0; 7];
+        bus.read(i2c, HUMIDITY_CALIBRATION, &mut data)?;
+        // H4 and H5 are 12 bits each, sharing the nibbles of the middle byte.
+        Some(HumidityCalibration {
+            h1: h1[0],
+            h2: i16::from_le_bytes([data[0], data[1]]),
+            h3: data[2],
+            h4: i16::from(data[3] as i8) << 4 | i16::from(data[4] & 0x0F),
+            h5: i16::from(data[5] as i8) << 4 | i16::from(data[4] >> 4),
+            h6: data[6] as i8,
+        })
+    } else {
+        None
+    };
+    Ok(Calibration {
+        t1: word(0),
+        t2: word(1) as i16,
+        t3: word(2) as i16,
+        p1: word(3),
+        p,
+        humidity,
+    })
+}
+
+impl Calibration {
+    /// The fine temperature the pressure and humidity compensation depend
+    /// on, in 1/5120 °C.
+    fn t_fine(&self, adc_t: i32) -> i32 {
+        let t1 = i32::from(self.t1);
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * i32::from(self.t2)) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * i32::from(self.t3)) >> 14;
+        var1 + var2
+    }
+
+    fn pressure(&self, t_fine: i32, adc_p: i32) -> u32 {
+        let [p2, p3, p4, p5, p6, p7, p8, p9] = self.p.map(i32::from);
+        let mut var1 = (t_fine >> 1) - 64000;
+        let mut var2 = (((var1 >> 2) * (var1 >> 2)) >> 11) * p6;
+        var2 += (var1 * p5) << 1;
+        var2 = (var2 >> 2) + (p4 << 16);
+        var1 = (((p3 * (((var1 >> 2) * (var1 >> 2)) >> 13)) >> 3) + ((p2 * var1) >> 1)) >> 18;
+        var1 = ((32768 + var1) * i32::from(self.p1)) >> 15;
+        if var1 == 0 {
+            // Avoids dividing by zero on a missing or broken calibration.
+            return 0;
+        }
+        let mut p = ((1048576 - adc_p) as u32)
+            .wrapping_sub((var2 >> 12) as u32)
+            .wrapping_mul(3125);
+        p = if p < 0x8000_0000 {
+            (p << 1) / var1 as u32
+        } else {
+            (p / var1 as u32) * 2
+        };
+        let var1 = (p9 * (((p >> 3) * (p >> 3)) >> 13) as i32) >> 12;
+        let var2 = ((p >> 2) as i32 * p8) >> 13;
+        (p as i32 + ((var1 + var2 + p7) >> 4)) as u32
+    }
+}
+
+impl HumidityCalibration {
+    fn humidity(&self, t_fine: i32, adc_h: i32) -> u16 {
+        let x = t_fine - 76800;
+        let x = (((adc_h << 14) - (i32::from(self.h4) << 20) - (i32::from(self.h5) * x) + 16384)
+            >> 15)
+            * (((((((x * i32::from(self.h6)) >> 10)
+                * (((x * i32::from(self.h3)) >> 11) + 32768))
+                >> 10)
+                + 2097152)
+                * i32::from(self.h2)
+                + 8192)
+                >> 14);
+        let x = x - (((((x >> 15) * (x >> 15)) >> 7) * i32::from(self.h1)) >> 4);
+        // Percent in 22.10 fixed point.
+        let q10 = x.clamp(0, 419430400) >> 12;
+        ((q10 * 10) >> 10) as u16
+    }
+}
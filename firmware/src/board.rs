@@ -0,0 +1,31 @@
+//! The pins of the boards the crab runs on.
+//!
+//! Exactly one board is built for, the Uno by default, the Mega 2560 with
+//! the `mega2560` feature, or the Leonardo and the other 32U4 boards with
+//! the `leonardo` feature. Each board module hands out the same set of
+//! constants and a [`Board`] with its pins split up the way [`crate::Crab`]
+//! uses them, so that the commands only differ in the pin numbers they
+//! accept.
+
+#[cfg(any(
+    all(feature = "uno", feature = "mega2560"),
+    all(feature = "uno", feature = "leonardo"),
+    all(feature = "mega2560", feature = "leonardo"),
+))]
+compile_error!("only one of the uno, mega2560, and leonardo features can be enabled");
+#[cfg(not(any(feature = "uno", feature = "mega2560", feature = "leonardo")))]
+compile_error!("one of the uno, mega2560, and leonardo features has to be enabled");
+
+#[cfg(feature = "leonardo")]
+mod leonardo;
+#[cfg(feature = "mega2560")]
+mod mega2560;
+#[cfg(feature = "uno")]
+mod uno;
+
+#[cfg(feature = "leonardo")]
+pub use leonardo::*;
+#[cfg(feature = "mega2560")]
+pub use mega2560::*;
+#[cfg(feature = "uno")]
+pub use uno::*;
@@ -0,0 +1,196 @@
+//! The Arduino Leonardo and the other ATmega32U4 boards, such as the Micro
+//! and the Pro Micro.
+//!
+//! The console is the chip's own USB, see [`crate::usb`], which leaves d0
+//! and d1 as general purpose pins. The pins are numbered like the Leonardo's:
+//! d14-d16 are the SPI bus on the ICSP header, and d17 is the slave select,
+//! which drives the RX LED. The Pro Micro doesn't break out d11-d13, so its
+//! PWM output and LED go nowhere. Commands that need a peripheral the 32U4
+//! doesn't have fail with `not available on this board`.
+
+use arduino_hal::hal::port::{PB7, PC7};
+use arduino_hal::port::mode::{Output, PwmOutput};
+use arduino_hal::port::Pin;
+use arduino_hal::simple_pwm::{IntoPwmPin, Timer0Pwm};
+use arduino_hal::{adc, pac};
+
+use crate::{usb, AnyPin};
+
+/// The general purpose digital pins, as accepted by `get` and `set`. d2 and
+/// d3 are the I2C bus, d11 the PWM output, and d13 the LED.
+pub const DIGITAL_PINS: [u8; 14] = [0, 1, 4, 5, 6, 7, 8, 9, 10, 12, 14, 15, 16, 17];
+/// [`DIGITAL_PINS`], for error messages.
+pub const PIN_NAMES: &str = "0, 1, 4-10, 12, 14-17";
+/// [`DIGITAL_PINS`] and the LED, for error messages.
+pub const OUTPUT_PIN_NAMES: &str = "0, 1, 4-10, 12, 14-17, led";
+
+/// The analog inputs, a0-a5.
+pub const ANALOG_INPUTS: usize = 6;
+/// The analog inputs that are nothing else, all of them.
+pub const ANALOG_CHANNELS: usize = ANALOG_INPUTS;
+/// The analog inputs, for error messages.
+pub const ANALOG_PIN_NAMES: &str = "0-5";
+
+/// Returns the ADC channel of an analog input, which run backwards from a0
+/// on ADC7 and skip ADC2 and ADC3, which the 32U4 doesn't have.
+pub fn adc_channel(input: u8) -> u8 {
+    [7, 6, 5, 4, 1, 0][usize::from(input)]
+}
+
+/// The hardware PWM output, OC0A. Timer0's other output is the I2C clock.
+pub const PWM_PIN: u8 = 11;
+pub type PwmPin = Pin<PwmOutput<Timer0Pwm>, PB7>;
+pub type LedPin = Pin<Output, PC7>;
+
+/// The pins with a Timer1 output, OC1A and OC1B.
+pub const TIMER1_PINS: [u8; 2] = [9, 10];
+/// [`TIMER1_PINS`], for error messages.
+pub const TIMER1_PIN_NAMES: &str = "9, 10";
+
+/// The SPI bus, MOSI, MISO, and SCK.
+pub const SPI_BUS_PINS: [u8; 3] = [16, 14, 15];
+/// The SPI slave select, which has to stay an output in master mode.
+pub const SPI_SS_PIN: u8 = 17;
+/// The bits of MOSI and SCK in `DDRB`.
+pub const SPI_OUTPUTS: u8 = 1 << 2 | 1 << 1;
+/// The digital pins that can be an SPI chip select, for error messages.
+pub const SPI_CS_PIN_NAMES: &str = "0, 1, 4-10, 12, 17";
+
+/// The number of ports the digital pins are spread over, PORTB-PORTE.
+/// PORTF is the analog inputs.
+pub const PORTS: usize = 4;
+
+#[derive(Clone, Copy)]
+enum Port {
+    B,
+    C,
+    D,
+    E,
+}
+
+use Port::*;
+
+/// The port and bit of each digital pin, d0-d17.
+const PORT_BITS: [(Port, u8); 18] = [
+    (D, 2), // d0
+    (D, 3), // d1
+    (D, 1), // d2
+    (D, 0), // d3
+    (D, 4), // d4
+    (C, 6), // d5
+    (D, 7), // d6
+    (E, 6), // d7
+    (B, 4), // d8
+    (B, 5), // d9
+    (B, 6), // d10
+    (B, 7), // d11
+    (D, 6), // d12
+    (C, 7), // d13
+    (B, 3), // d14
+    (B, 1), // d15
+    (B, 2), // d16
+    (B, 0), // d17
+];
+
+/// Returns the address of the `PINx` register of a digital pin (0-17), and
+/// the pin's bit in it.
+pub fn port_bit(pin: u8) -> Option<(*mut u8, u8)> {
+    let &(port, bit) = PORT_BITS.get(usize::from(pin))?;
+    let base = match port {
+        B => pac::PORTB::ptr() as *mut u8,
+        C => pac::PORTC::ptr() as *mut u8,
+        D => pac::PORTD::ptr() as *mut u8,
+        E => pac::PORTE::ptr() as *mut u8,
+    };
+    Some((base, bit))
+}
+
+/// Returns the pin change interrupt bank of a digital pin, and the pin's bit
+/// in the bank's mask. The 32U4 only has bank 0, the pins on PORTB.
+pub fn pin_change_bit(pin: u8) -> Option<(u8, u8)> {
+    match PORT_BITS.get(usize::from(pin))? {
+        &(B, bit) => Some((0, bit)),
+        _ => None,
+    }
+}
+
+/// The digital pins with a pin change interrupt, for error messages.
+pub const PIN_CHANGE_PIN_NAMES: &str = "8-10, 14-17";
+
+/// The I2C bus on d2 and d3, which are nothing else.
+pub struct I2cBus(arduino_hal::I2c);
+
+impl I2cBus {
+    pub fn claim(&mut self, _adc: &mut arduino_hal::Adc) -> &mut arduino_hal::I2c {
+        &mut self.0
+    }
+
+    /// Whether `input` is a bus pin no longer usable as an analog input,
+    /// which none is.
+    pub fn holds(&self, _input: u8) -> bool {
+        false
+    }
+
+    /// Reads an analog input shared with the bus, of which there are none.
+    pub fn analog_read(&self, _adc: &mut arduino_hal::Adc, _input: u8) -> Option<u16> {
+        None
+    }
+}
+
+/// The pins, split up into the console, the LED, the PWM output, the I2C
+/// bus, and the general purpose ones.
+pub struct Board {
+    pub serial: usb::Serial,
+    pub led: LedPin,
+    pub pwm: PwmPin,
+    pub i2c: I2cBus,
+    /// Indexed like [`DIGITAL_PINS`].
+    pub digital: [AnyPin; DIGITAL_PINS.len()],
+    pub analog: [adc::Channel; ANALOG_CHANNELS],
+}
+
+impl Board {
+    pub fn new(
+        pins: arduino_hal::Pins,
+        (usb, pll): (pac::USB_DEVICE, pac::PLL),
+        twi: pac::TWI,
+        adc: &mut arduino_hal::Adc,
+        timer0: &Timer0Pwm,
+    ) -> Self {
+        Board {
+            serial: usb::Serial::new(usb, pll),
+            led: pins.d13.into_output(),
+            pwm: pins.d11.into_output().into_pwm(timer0),
+            i2c: I2cBus(arduino_hal::I2c::new(
+                twi,
+                pins.d2.into_pull_up_input(),
+                pins.d3.into_pull_up_input(),
+                crate::I2C_SPEED,
+            )),
+            digital: [
+                AnyPin::DigitalIn(pins.d0.downgrade()),
+                AnyPin::DigitalIn(pins.d1.downgrade()),
+                AnyPin::DigitalIn(pins.d4.downgrade()),
+                AnyPin::DigitalIn(pins.d5.downgrade()),
+                AnyPin::DigitalIn(pins.d6.downgrade()),
+                AnyPin::DigitalIn(pins.d7.downgrade()),
+                AnyPin::DigitalIn(pins.d8.downgrade()),
+                AnyPin::DigitalIn(pins.d9.downgrade()),
+                AnyPin::DigitalIn(pins.d10.downgrade()),
+                AnyPin::DigitalIn(pins.d12.downgrade()),
+                AnyPin::DigitalIn(pins.miso.downgrade()),
+                AnyPin::DigitalIn(pins.sck.downgrade()),
+                AnyPin::DigitalIn(pins.mosi.downgrade()),
+                AnyPin::DigitalIn(pins.led_rx.downgrade()),
+            ],
+            analog: [
+                pins.a0.into_analog_input(adc).into_channel(),
+                pins.a1.into_analog_input(adc).into_channel(),
+                pins.a2.into_analog_input(adc).into_channel(),
+                pins.a3.into_analog_input(adc).into_channel(),
+                pins.a4.into_analog_input(adc).into_channel(),
+                pins.a5.into_analog_input(adc).into_channel(),
+            ],
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! The Arduino Mega 2560, with an ATmega2560.
+//!
+//! The same commands work as on the Uno, on more pins. Those that need a
+//! peripheral the Mega doesn't have, or a pin it doesn't break out, fail
+//! with `not available on this board`.
+
+use arduino_hal::hal::port::{PB7, PG5};
+use arduino_hal::hal::usart::Baudrate;
+use arduino_hal::port::mode::{Output, PwmOutput};
+use arduino_hal::port::Pin;
+use arduino_hal::simple_pwm::{IntoPwmPin, Timer0Pwm};
+use arduino_hal::{adc, pac};
+
+use crate::{uart, AnyPin, Usart};
+
+/// The general purpose digital pins, as accepted by `get` and `set`. d0 and
+/// d1 are the console, d4 the PWM output, d13 the LED, and d20 and d21 the
+/// I2C bus.
+pub const DIGITAL_PINS: [u8; 48] = [
+    2, 3, 5, 6, 7, 8, 9, 10, 11, 12, 14, 15, 16, 17, 18, 19, 22, 23, 24, 25, 26, 27, 28, 29, 30,
+    31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53,
+];
+/// [`DIGITAL_PINS`], for error messages.
+pub const PIN_NAMES: &str = "2, 3, 5-12, 14-19, 22-53";
+/// [`DIGITAL_PINS`] and the LED, for error messages.
+pub const OUTPUT_PIN_NAMES: &str = "2, 3, 5-12, 14-19, 22-53, led";
+
+/// The analog inputs, a0-a15.
+pub const ANALOG_INPUTS: usize = 16;
+/// The analog inputs that are nothing else, all of them.
+pub const ANALOG_CHANNELS: usize = ANALOG_INPUTS;
+/// The analog inputs, for error messages.
+pub const ANALOG_PIN_NAMES: &str = "0-15";
+
+/// The hardware PWM output, OC0B. Timer0's other output is the LED.
+pub const PWM_PIN: u8 = 4;
+pub type PwmPin = Pin<PwmOutput<Timer0Pwm>, PG5>;
+pub type LedPin = Pin<Output, PB7>;
+
+/// The pins with a Timer1 output, OC1A and OC1B.
+pub const TIMER1_PINS: [u8; 2] = [11, 12];
+/// [`TIMER1_PINS`], for error messages.
+pub const TIMER1_PIN_NAMES: &str = "11, 12";
+
+/// The SPI bus, MOSI, MISO, and SCK.
+pub const SPI_BUS_PINS: [u8; 3] = [51, 50, 52];
+/// The SPI slave select, which has to stay an output in master mode.
+pub const SPI_SS_PIN: u8 = 53;
+/// The bits of MOSI and SCK in `DDRB`.
+pub const SPI_OUTPUTS: u8 = 1 << 2 | 1 << 1;
+/// The digital pins that can be an SPI chip select, for error messages.
+pub const SPI_CS_PIN_NAMES: &str = "2, 3, 5-12, 14-19, 22-49, 53";
+
+/// The number of ports the digital pins are spread over, PORTA-PORTL except
+/// PORTF and PORTK, which are the analog inputs.
+pub const PORTS: usize = 9;
+
+#[derive(Clone, Copy)]
+enum Port {
+    A,
+    B,
+    C,
+    D,
+    E,
+    G,
+    H,
+    J,
+    L,
+}
+
+use Port::*;
+
+/// The port and bit of each digital pin, d0-d53.
+const PORT_BITS: [(Port, u8); 54] = [
+    (E, 0), // d0
+    (E, 1), // d1
+    (E, 4), // d2
+    (E, 5), // d3
+    (G, 5), // d4
+    (E, 3), // d5
+    (H, 3), // d6
+    (H, 4), // d7
+    (H, 5), // d8
+    (H, 6), // d9
+    (B, 4), // d10
+    (B, 5), // d11
+    (B, 6), // d12
+    (B, 7), // d13
+    (J, 1), // d14
+    (J, 0), // d15
+    (H, 1), // d16
+    (H, 0), // d17
+    (D, 3), // d18
+    (D, 2), // d19
+    (D, 1), // d20
+    (D, 0), // d21
+    (A, 0), // d22
+    (A, 1), // d23
+    (A, 2), // d24
+    (A, 3), // d25
+    (A, 4), // d26
+    (A, 5), // d27
+    (A, 6), // d28
+    (A, 7), // d29
+    (C, 7), // d30
+    (C, 6), // d31
+    (C, 5), // d32
+    (C, 4), // d33
+    (C, 3), // d34
+    (C, 2), // d35
+    (C, 1), // d36
+    (C, 0), // d37
+    (D, 7), // d38
+    (G, 2), // d39
+    (G, 1), // d40
+    (G, 0), // d41
+    (L, 7), // d42
+    (L, 6), // d43
+    (L, 5), // d44
+    (L, 4), // d45
+    (L, 3), // d46
+    (L, 2), // d47
+    (L, 1), // d48
+    (L, 0), // d49
+    (B, 3), // d50
+    (B, 2), // d51
+    (B, 1), // d52
+    (B, 0), // d53
+];
+
+/// Returns the address of the `PINx` register of a digital pin (0-53), and
+/// the pin's bit in it.
+pub fn port_bit(pin: u8) -> Option<(*mut u8, u8)> {
+    let &(port, bit) = PORT_BITS.get(usize::from(pin))?;
+    let base = match port {
+        A => pac::PORTA::ptr() as *mut u8,
+        B => pac::PORTB::ptr() as *mut u8,
+        C => pac::PORTC::ptr() as *mut u8,
+        D => pac::PORTD::ptr() as *mut u8,
+        E => pac::PORTE::ptr() as *mut u8,
+        G => pac::PORTG::ptr() as *mut u8,
+        H => pac::PORTH::ptr() as *mut u8,
+        J => pac::PORTJ::ptr() as *mut u8,
+        L => pac::PORTL::ptr() as *mut u8,
+    };
+    Some((base, bit))
+}
+
+/// Returns the pin change interrupt bank of a digital pin, and the pin's bit
+/// in the bank's mask. Only d0, d10-d15, and d50-d53 have one.
+pub fn pin_change_bit(pin: u8) -> Option<(u8, u8)> {
+    match pin {
+        0 => Some((1, 0)),
+        10..=13 => Some((0, pin - 6)),
+        14 => Some((1, 2)),
+        15 => Some((1, 1)),
+        50..=53 => Some((0, 53 - pin)),
+        _ => None,
+    }
+}
+
+/// The digital pins with a pin change interrupt, for error messages.
+pub const PIN_CHANGE_PIN_NAMES: &str = "10-12, 14, 15, 50-53";
+
+/// The I2C bus on d20 and d21, which are nothing else.
+pub struct I2cBus(arduino_hal::I2c);
+
+impl I2cBus {
+    pub fn claim(&mut self, _adc: &mut arduino_hal::Adc) -> &mut arduino_hal::I2c {
+        &mut self.0
+    }
+
+    /// Whether `input` is a bus pin no longer usable as an analog input,
+    /// which none is.
+    pub fn holds(&self, _input: u8) -> bool {
+        false
+    }
+
+    /// Reads an analog input shared with the bus, of which there are none.
+    pub fn analog_read(&self, _adc: &mut arduino_hal::Adc, _input: u8) -> Option<u16> {
+        None
+    }
+}
+
+/// The pins, split up into the console, the LED, the PWM output, the I2C
+/// bus, and the general purpose ones.
+pub struct Board {
+    pub serial: Usart,
+    pub led: LedPin,
+    pub pwm: PwmPin,
+    pub i2c: I2cBus,
+    /// Indexed like [`DIGITAL_PINS`].
+    pub digital: [AnyPin; DIGITAL_PINS.len()],
+    pub analog: [adc::Channel; ANALOG_CHANNELS],
+}
+
+impl Board {
+    pub fn new(
+        pins: arduino_hal::Pins,
+        usart: pac::USART0,
+        twi: pac::TWI,
+        adc: &mut arduino_hal::Adc,
+        timer0: &Timer0Pwm,
+    ) -> Self {
+        Board {
+            serial: arduino_hal::Usart::new(
+                usart,
+                pins.d0,
+                pins.d1.into_output(),
+                Baudrate::new(uart::DEFAULT_BAUD),
+            ),
+            led: pins.d13.into_output(),
+            pwm: pins.d4.into_output().into_pwm(timer0),
+            i2c: I2cBus(arduino_hal::I2c::new(
+                twi,
+                pins.d20.into_pull_up_input(),
+                pins.d21.into_pull_up_input(),
+                crate::I2C_SPEED,
+            )),
+            digital: [
+                AnyPin::DigitalIn(pins.d2.downgrade()),
+                AnyPin::DigitalIn(pins.d3.downgrade()),
+                AnyPin::DigitalIn(pins.d5.downgrade()),
+                AnyPin::DigitalIn(pins.d6.downgrade()),
+                AnyPin::DigitalIn(pins.d7.downgrade()),
+                AnyPin::DigitalIn(pins.d8.downgrade()),
+                AnyPin::DigitalIn(pins.d9.downgrade()),
+                AnyPin::DigitalIn(pins.d10.downgrade()),
+                AnyPin::DigitalIn(pins.d11.downgrade()),
+                AnyPin::DigitalIn(pins.d12.downgrade()),
+                AnyPin::DigitalIn(pins.d14.downgrade()),
+                AnyPin::DigitalIn(pins.d15.downgrade()),
+                AnyPin::DigitalIn(pins.d16.downgrade()),
+                AnyPin::DigitalIn(pins.d17.downgrade()),
+                AnyPin::DigitalIn(pins.d18.downgrade()),
+                AnyPin::DigitalIn(pins.d19.downgrade()),
+                AnyPin::DigitalIn(pins.d22.downgrade()),
+                AnyPin::DigitalIn(pins.d23.downgrade()),
+                AnyPin::DigitalIn(pins.d24.downgrade()),
+                AnyPin::DigitalIn(pins.d25.downgrade()),
+                AnyPin::DigitalIn(pins.d26.downgrade()),
+                AnyPin::DigitalIn(pins.d27.downgrade()),
+                AnyPin::DigitalIn(pins.d28.downgrade()),
+                AnyPin::DigitalIn(pins.d29.downgrade()),
+                AnyPin::DigitalIn(pins.d30.downgrade()),
+                AnyPin::DigitalIn(pins.d31.downgrade()),
+                AnyPin::DigitalIn(pins.d32.downgrade()),
+                AnyPin::DigitalIn(pins.d33.downgrade()),
+                AnyPin::DigitalIn(pins.d34.downgrade()),
+                AnyPin::DigitalIn(pins.d35.downgrade()),
+                AnyPin::DigitalIn(pins.d36.downgrade()),
+                AnyPin::DigitalIn(pins.d37.downgrade()),
+                AnyPin::DigitalIn(pins.d38.downgrade()),
+                AnyPin::DigitalIn(pins.d39.downgrade()),
+                AnyPin::DigitalIn(pins.d40.downgrade()),
+                AnyPin::DigitalIn(pins.d41.downgrade()),
+                AnyPin::DigitalIn(pins.d42.downgrade()),
+                AnyPin::DigitalIn(pins.d43.downgrade()),
+                AnyPin::DigitalIn(pins.d44.downgrade()),
+                AnyPin::DigitalIn(pins.d45.downgrade()),
+                AnyPin::DigitalIn(pins.d46.downgrade()),
+                AnyPin::DigitalIn(pins.d47.downgrade()),
+                AnyPin::DigitalIn(pins.d48.downgrade()),
+                AnyPin::DigitalIn(pins.d49.downgrade()),
+                AnyPin::DigitalIn(pins.d50.downgrade()),
+                AnyPin::DigitalIn(pins.d51.downgrade()),
+                AnyPin::DigitalIn(pins.d52.downgrade()),
+                AnyPin::DigitalIn(pins.d53.downgrade()),
+            ],
+            analog: [
+                pins.a0.into_analog_input(adc).into_channel(),
+                pins.a1.into_analog_input(adc).into_channel(),
+                pins.a2.into_analog_input(adc).into_channel(),
+                pins.a3.into_analog_input(adc).into_channel(),
+                pins.a4.into_analog_input(adc).into_channel(),
+                pins.a5.into_analog_input(adc).into_channel(),
+                pins.a6.into_analog_input(adc).into_channel(),
+                pins.a7.into_analog_input(adc).into_channel(),
+                pins.a8.into_analog_input(adc).into_channel(),
+                pins.a9.into_analog_input(adc).into_channel(),
+                pins.a10.into_analog_input(adc).into_channel(),
+                pins.a11.into_analog_input(adc).into_channel(),
+                pins.a12.into_analog_input(adc).into_channel(),
+                pins.a13.into_analog_input(adc).into_channel(),
+                pins.a14.into_analog_input(adc).into_channel(),
+                pins.a15.into_analog_input(adc).into_channel(),
+            ],
+        }
+    }
+}
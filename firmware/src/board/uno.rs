@@ -0,0 +1,176 @@
+//! The Arduino Uno, with an ATmega328P.
+
+use core::mem;
+
+use arduino_hal::hal::port::{PB5, PC4, PC5, PD5};
+use arduino_hal::hal::usart::Baudrate;
+use arduino_hal::port::mode::{Analog, Output, PwmOutput};
+use arduino_hal::port::Pin;
+use arduino_hal::simple_pwm::{IntoPwmPin, Timer0Pwm};
+use arduino_hal::{adc, pac};
+
+use crate::{uart, AnyPin, Usart};
+
+/// The general purpose digital pins, as accepted by `get` and `set`. d0 and
+/// d1 are the console, d5 the PWM output, and d13 the LED.
+pub const DIGITAL_PINS: [u8; 10] = [2, 3, 4, 6, 7, 8, 9, 10, 11, 12];
+/// [`DIGITAL_PINS`], for error messages.
+pub const PIN_NAMES: &str = "2-4, 6-12";
+/// [`DIGITAL_PINS`] and the LED, for error messages.
+pub const OUTPUT_PIN_NAMES: &str = "2-4, 6-12, led";
+
+/// The analog inputs, a0-a5. a4 and a5 are the I2C bus too, and stop being
+/// analog inputs once anything uses the bus.
+pub const ANALOG_INPUTS: usize = 6;
+/// The analog inputs that are nothing else, a0-a3.
+pub const ANALOG_CHANNELS: usize = 4;
+/// The analog inputs, for error messages.
+pub const ANALOG_PIN_NAMES: &str = "0-3, and 4-5 until I2C is used";
+
+/// The hardware PWM output, OC0B.
+pub const PWM_PIN: u8 = 5;
+pub type PwmPin = Pin<PwmOutput<Timer0Pwm>, PD5>;
+pub type LedPin = Pin<Output, PB5>;
+
+/// The pins with a Timer1 output, OC1A and OC1B.
+pub const TIMER1_PINS: [u8; 2] = [9, 10];
+/// [`TIMER1_PINS`], for error messages.
+pub const TIMER1_PIN_NAMES: &str = "9, 10";
+
+/// The SPI bus, MOSI, MISO, and SCK.
+pub const SPI_BUS_PINS: [u8; 3] = [11, 12, 13];
+/// The SPI slave select, which has to stay an output in master mode.
+pub const SPI_SS_PIN: u8 = 10;
+/// The bits of MOSI and SCK in `DDRB`.
+pub const SPI_OUTPUTS: u8 = 1 << 3 | 1 << 5;
+/// The digital pins that can be an SPI chip select, for error messages.
+pub const SPI_CS_PIN_NAMES: &str = "2-4, 6-10";
+
+/// The number of ports the digital pins are spread over, PORTB and PORTD.
+pub const PORTS: usize = 2;
+
+/// Returns the address of the `PINx` register of a digital pin (0-13), and
+/// the pin's bit in it.
+pub fn port_bit(pin: u8) -> Option<(*mut u8, u8)> {
+    match pin {
+        0..=7 => Some((pac::PORTD::ptr() as *mut u8, pin)),
+        8..=13 => Some((pac::PORTB::ptr() as *mut u8, pin - 8)),
+        _ => None,
+    }
+}
+
+/// Returns the pin change interrupt bank of a digital pin, and the pin's bit
+/// in the bank's mask. Every digital pin has one.
+pub fn pin_change_bit(pin: u8) -> Option<(u8, u8)> {
+    match pin {
+        0..=7 => Some((2, pin)),
+        8..=13 => Some((0, pin - 8)),
+        _ => None,
+    }
+}
+
+/// The digital pins with a pin change interrupt, for error messages.
+pub const PIN_CHANGE_PIN_NAMES: &str = PIN_NAMES;
+
+/// The I2C bus on a4 and a5, which stay analog inputs until the first I2C
+/// transfer claims them.
+pub enum I2cBus {
+    Unclaimed {
+        twi: pac::TWI,
+        sda: Pin<Analog, PC4>,
+        scl: Pin<Analog, PC5>,
+    },
+    Claimed(arduino_hal::I2c),
+    /// Only seen in the middle of [`I2cBus::claim`].
+    Claiming,
+}
+
+impl I2cBus {
+    /// Returns the bus, turning a4 and a5 into it on the first call.
+    pub fn claim(&mut self, adc: &mut arduino_hal::Adc) -> &mut arduino_hal::I2c {
+        if let I2cBus::Unclaimed { .. } = self {
+            if let I2cBus::Unclaimed { twi, sda, scl } = mem::replace(self, I2cBus::Claiming) {
+                *self = I2cBus::Claimed(arduino_hal::I2c::new(
+                    twi,
+                    sda.into_digital(adc).into_pull_up_input(),
+                    scl.into_digital(adc).into_pull_up_input(),
+                    crate::I2C_SPEED,
+                ));
+            }
+        }
+        match self {
+            I2cBus::Claimed(i2c) => i2c,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Whether `input` is a bus pin no longer usable as an analog input.
+    pub fn holds(&self, input: u8) -> bool {
+        matches!(self, I2cBus::Claimed(_)) && matches!(input, 4 | 5)
+    }
+
+    /// Reads a4 or a5 while they are not the bus yet.
+    pub fn analog_read(&self, adc: &mut arduino_hal::Adc, input: u8) -> Option<u16> {
+        match (self, input) {
+            (I2cBus::Unclaimed { sda, .. }, 4) => Some(sda.analog_read(adc)),
+            (I2cBus::Unclaimed { scl, .. }, 5) => Some(scl.analog_read(adc)),
+            _ => None,
+        }
+    }
+}
+
+/// The pins, split up into the console, the LED, the PWM output, the I2C
+/// bus, and the general purpose ones.
+pub struct Board {
+    pub serial: Usart,
+    pub led: LedPin,
+    pub pwm: PwmPin,
+    pub i2c: I2cBus,
+    /// Indexed like [`DIGITAL_PINS`].
+    pub digital: [AnyPin; DIGITAL_PINS.len()],
+    pub analog: [adc::Channel; ANALOG_CHANNELS],
+}
+
+impl Board {
+    pub fn new(
+        pins: arduino_hal::Pins,
+        usart: pac::USART0,
+        twi: pac::TWI,
+        adc: &mut arduino_hal::Adc,
+        timer0: &Timer0Pwm,
+    ) -> Self {
+        Board {
+            serial: arduino_hal::Usart::new(
+                usart,
+                pins.d0,
+                pins.d1.into_output(),
+                Baudrate::new(uart::DEFAULT_BAUD),
+            ),
+            led: pins.d13.into_output(),
+            pwm: pins.d5.into_output().into_pwm(timer0),
+            i2c: I2cBus::Unclaimed {
+                twi,
+                sda: pins.a4.into_analog_input(adc),
+                scl: pins.a5.into_analog_input(adc),
+            },
+            digital: [
+                AnyPin::DigitalIn(pins.d2.downgrade()),
+                AnyPin::DigitalIn(pins.d3.downgrade()),
+                AnyPin::DigitalIn(pins.d4.downgrade()),
+                AnyPin::DigitalIn(pins.d6.downgrade()),
+                AnyPin::DigitalIn(pins.d7.downgrade()),
+                AnyPin::DigitalIn(pins.d8.downgrade()),
+                AnyPin::DigitalIn(pins.d9.downgrade()),
+                AnyPin::DigitalIn(pins.d10.downgrade()),
+                AnyPin::DigitalIn(pins.d11.downgrade()),
+                AnyPin::DigitalIn(pins.d12.downgrade()),
+            ],
+            analog: [
+                pins.a0.into_analog_input(adc).into_channel(),
+                pins.a1.into_analog_input(adc).into_channel(),
+                pins.a2.into_analog_input(adc).into_channel(),
+                pins.a3.into_analog_input(adc).into_channel(),
+            ],
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Handing over to the Optiboot bootloader, so the board can be reflashed
+//! without pressing its reset button.
+//!
+//! Optiboot skips itself after a watchdog reset, so a reset does not help.
+//! Since version 6 it does run when entered with `MCUSR` cleared, which is
+//! how an application asks for it, so the peripherals are put back the way
+//! it expects them after a reset and it is jumped to directly. Older
+//! versions start the application again right away.
+
+use core::arch::asm;
+
+use arduino_hal::pac;
+
+/// Disables interrupts and the peripherals that raise them, and jumps to the
+/// bootloader. The watchdog has to be stopped beforehand.
+pub fn enter() -> ! {
+    avr_device::interrupt::disable();
+    // SAFETY: Interrupts are off and the firmware never runs again, bar a
+    // reset, so nothing depends on the state of the peripherals any more.
+    unsafe {
+        let tc0 = &*pac::TC0::ptr();
+        tc0.timsk0.reset();
+        tc0.tccr0a.reset();
+        tc0.tccr0b.reset();
+        let tc1 = &*pac::TC1::ptr();
+        tc1.timsk1.reset();
+        tc1.tccr1a.reset();
+        tc1.tccr1b.reset();
+        let tc2 = &*pac::TC2::ptr();
+        tc2.timsk2.reset();
+        tc2.tccr2a.reset();
+        tc2.tccr2b.reset();
+        (*pac::EXINT::ptr()).pcicr.reset();
+        (*pac::EXINT::ptr()).eimsk.reset();
+        (*pac::TWI::ptr()).twcr.reset();
+        (*pac::SPI::ptr()).spcr.reset();
+        (*pac::ADC::ptr()).adcsra.reset();
+        (*pac::AC::ptr()).acsr.reset();
+        (*pac::USART0::ptr()).ucsr0b.reset();
+        (*pac::CPU::ptr()).mcusr.reset();
+        // The bootloader takes up the last 512 bytes of flash, as the Uno's
+        // fuses set it up.
+        asm!("jmp 0x7E00", options(noreturn));
+    }
+}
@@ -0,0 +1,98 @@
+//! Debounced pushbuttons, wired between a pin and ground with the internal
+//! pull-up enabled, so that a pressed button reads low.
+//!
+//! A reading only counts once the pin has held the same level for the
+//! debounce time.
+
+use heapless::Vec;
+
+use crate::gpio::RawPin;
+use crate::millis;
+
+/// How many buttons can send events at the same time.
+pub const MAX_BUTTONS: usize = 4;
+
+/// How long a read waits for the bouncing to stop, in debounce periods.
+const TIMEOUT_PERIODS: u32 = 10;
+
+/// Waits for the pin to settle and returns whether the button is pressed.
+/// Returns `None` if it does not settle.
+pub fn read(pin: &RawPin, debounce_ms: u16) -> Option<bool> {
+    let debounce_ms = u32::from(debounce_ms);
+    let start = millis::millis();
+    let mut level = pin.is_high();
+    let mut since = start;
+    loop {
+        let now = millis::millis();
+        if pin.is_high() != level {
+            level = !level;
+            since = now;
+        } else if now.wrapping_sub(since) >= debounce_ms {
+            return Some(!level);
+        }
+        if now.wrapping_sub(start) > debounce_ms * TIMEOUT_PERIODS {
+            return None;
+        }
+    }
+}
+
+struct Button {
+    pin: u8,
+    raw: RawPin,
+    debounce_ms: u16,
+    pressed: bool,
+    /// The level last seen, and since when.
+    level: bool,
+    since: u32,
+}
+
+/// Buttons sending press and release events.
+pub struct Buttons {
+    entries: Vec<Button, MAX_BUTTONS>,
+}
+
+impl Buttons {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Starts sending events for a button, replacing any earlier settings for
+    /// the same pin. Fails if the table is full.
+    pub fn watch(&mut self, pin: u8, raw: RawPin, debounce_ms: u16) -> Result<(), ()> {
+        self.unwatch(pin);
+        let level = raw.is_high();
+        let button = Button {
+            pin,
+            raw,
+            debounce_ms,
+            pressed: !level,
+            level,
+            since: millis::millis(),
+        };
+        self.entries.push(button).map_err(|_| ())
+    }
+
+    pub fn unwatch(&mut self, pin: u8) {
+        self.entries.retain(|button| button.pin != pin);
+    }
+
+    /// Calls `f` with the pin and new state of each button that settled in a
+    /// different state since the last call.
+    pub fn poll(&mut self, mut f: impl FnMut(u8, bool)) {
+        let now = millis::millis();
+        for button in self.entries.iter_mut() {
+            let level = button.raw.is_high();
+            if level != button.level {
+                button.level = level;
+                button.since = now;
+            } else if button.pressed == level
+                && now.wrapping_sub(button.since) >= u32::from(button.debounce_ms)
+            {
+                button.pressed = !level;
+                f(button.pin, button.pressed);
+            }
+        }
+    }
+}
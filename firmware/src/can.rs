@@ -0,0 +1,227 @@
+//! MCP2515 CAN controllers on the SPI bus.
+//!
+//! The controller is set up to receive every frame on the bus into its two
+//! receive buffers, with the first rolling over into the second, and sends
+//! from its first transmit buffer. Frames are polled for rather than
+//! waited on through the controller's interrupt line.
+
+use arduino_hal::delay_ms;
+use heapless::Vec;
+
+use crate::gpio::RawPin;
+use crate::{millis, spi};
+
+const RESET: u8 = 0xC0;
+const READ: u8 = 0x03;
+const WRITE: u8 = 0x02;
+const BIT_MODIFY: u8 = 0x05;
+const READ_STATUS: u8 = 0xA0;
+/// Reads a receive buffer from its `SIDH` register, and clears its
+/// interrupt flag once the chip select goes high.
+const READ_RX_BUFFER: [u8; 2] = [0x90, 0x94];
+const RTS_TXB0: u8 = 0x81;
+
+const CANSTAT: u8 = 0x0E;
+const CANCTRL: u8 = 0x0F;
+const CNF3: u8 = 0x28;
+const RXB0CTRL: u8 = 0x60;
+const RXB1CTRL: u8 = 0x70;
+const TXB0CTRL: u8 = 0x30;
+const TXB0SIDH: u8 = 0x31;
+
+/// `CANSTAT` and `CANCTRL`: the operation mode bits.
+const MODE_MASK: u8 = 0xE0;
+const MODE_NORMAL: u8 = 0x00;
+const MODE_CONFIG: u8 = 0x80;
+/// `RXBnCTRL`: receive any frame, and roll buffer 0 over into buffer 1.
+const RECEIVE_ANY: u8 = 0x60;
+const BUKT: u8 = 0x04;
+/// `TXBnCTRL`: the buffer is waiting to be sent.
+const TXREQ: u8 = 0x08;
+/// `SIDL`: the frame has an extended identifier.
+const EXIDE: u8 = 0x08;
+/// `READ STATUS`: a frame arrived in receive buffer 0 or 1.
+const RX_FLAGS: [u8; 2] = [0x01, 0x02];
+
+/// The highest standard and extended identifiers.
+pub const MAX_STANDARD_ID: u32 = 0x7FF;
+pub const MAX_EXTENDED_ID: u32 = 0x1FFF_FFFF;
+pub const MAX_DATA: usize = 8;
+
+/// The frequencies of the crystals supported.
+const CRYSTALS_MHZ: [u8; 2] = [8, 16];
+
+/// How long a frame gets to go out, including retries while the bus is
+/// busy or nobody acknowledges it.
+const SEND_TIMEOUT_MS: u32 = 10;
+
+pub enum Error {
+    /// No controller answered on the bus.
+    NotFound,
+    /// The controller didn't switch to normal mode.
+    Mode,
+    /// The frame wasn't sent in time, usually for lack of an
+    /// acknowledgement from another node, and was aborted.
+    NotSent,
+}
+
+pub struct Frame {
+    pub id: u32,
+    pub extended: bool,
+    pub data: Vec<u8, MAX_DATA>,
+}
+
+pub struct Mcp2515 {
+    cs: RawPin,
+    spi: spi::Config,
+}
+
+/// Returns `CNF3`, `CNF2`, and `CNF1` for a bit rate with an 8 MHz crystal,
+/// in the order they follow each other in the register map. 16 MHz takes
+/// twice the prescaler.
+fn timing(bitrate: u32) -> Option<[u8; 3]> {
+    // Time quanta of 250 ns, or 500 ns at 125 kbit/s, with the sample point
+    // at 50-62.5%.
+    Some(match bitrate {
+        1_000_000 => [0x80, 0x80, 0x00],
+        500_000 => [0x82, 0x90, 0x00],
+        250_000 => [0x85, 0xB1, 0x00],
+        125_000 => [0x85, 0xB1, 0x01],
+        _ => return None,
+    })
+}
+
+impl Mcp2515 {
+    /// Resets the controller behind a chip select pin, which has to be an
+    /// output driven high, and joins the bus at a bit rate that
+    /// [`is_supported`].
+    pub fn init(cs: RawPin, cs_pin: u8, bitrate: u32, crystal_mhz: u8) -> Result<Self, Error> {
+        let controller = Mcp2515 {
+            cs,
+            spi: spi::Config {
+                mode: 0,
+                divider: 4,
+                cs: cs_pin,
+            },
+        };
+        let Some(mut cnf) = timing(bitrate) else {
+            unreachable!("bit rate is validated by the caller");
+        };
+        if crystal_mhz == 16 {
+            cnf[2] = cnf[2] * 2 + 1;
+        }
+        controller.transaction(&mut [RESET]);
+        delay_ms(1);
+        if controller.read(CANSTAT) & MODE_MASK != MODE_CONFIG {
+            return Err(Error::NotFound);
+        }
+        controller.write(CNF3, &cnf);
+        controller.write(RXB0CTRL, &[RECEIVE_ANY | BUKT]);
+        controller.write(RXB1CTRL, &[RECEIVE_ANY]);
+        controller.write(CANCTRL, &[MODE_NORMAL]);
+        if controller.read(CANSTAT) & MODE_MASK != MODE_NORMAL {
+            return Err(Error::Mode);
+        }
+        Ok(controller)
+    }
+
+    /// Sends a frame of up to [`MAX_DATA`] bytes, with an extended
+    /// identifier for those above [`MAX_STANDARD_ID`].
+    pub fn send(&self, id: u32, data: &[u8]) -> Result<(), Error> {
+        if !self.wait_sent() {
+            self.abort();
+        }
+        let len = data.len().min(MAX_DATA);
+        let mut buffer = [0; 5 + MAX_DATA];
+        if id > MAX_STANDARD_ID {
+            buffer[0] = (id >> 21) as u8;
+            buffer[1] = ((id >> 13) & 0xE0) as u8 | EXIDE | ((id >> 16) & 0x03) as u8;
+            buffer[2] = (id >> 8) as u8;
+            buffer[3] = id as u8;
+        } else {
+            buffer[0] = (id >> 3) as u8;
+            buffer[1] = ((id & 0x07) << 5) as u8;
+        }
+        buffer[4] = len as u8;
+        buffer[5..5 + len].copy_from_slice(&data[..len]);
+        self.write(TXB0SIDH, &buffer[..5 + len]);
+        self.transaction(&mut [RTS_TXB0]);
+        if !self.wait_sent() {
+            self.abort();
+            return Err(Error::NotSent);
+        }
+        Ok(())
+    }
+
+    /// Waits for the transmit buffer to go out, and returns whether it did.
+    fn wait_sent(&self) -> bool {
+        let start = millis::millis();
+        while self.read(TXB0CTRL) & TXREQ != 0 {
+            if millis::millis().wrapping_sub(start) > SEND_TIMEOUT_MS {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn abort(&self) {
+        self.transaction(&mut [BIT_MODIFY, TXB0CTRL, TXREQ, 0]);
+    }
+
+    /// Takes a frame that arrived, if any.
+    pub fn receive(&self) -> Option<Frame> {
+        let mut status = [READ_STATUS, 0];
+        self.transaction(&mut status);
+        let buffer = RX_FLAGS.iter().position(|&flag| status[1] & flag != 0)?;
+        let mut frame = [0; 1 + 5 + MAX_DATA];
+        frame[0] = READ_RX_BUFFER[buffer];
+        self.transaction(&mut frame);
+        let [_, sidh, sidl, eid8, eid0, dlc, ref data @ ..] = frame;
+        let standard = u32::from(sidh) << 3 | u32::from(sidl) >> 5;
+        let extended = sidl & EXIDE != 0;
+        let id = if extended {
+            standard << 18 | u32::from(sidl & 0x03) << 16 | u32::from(eid8) << 8 | u32::from(eid0)
+        } else {
+            standard
+        };
+        let len = usize::from(dlc & 0x0F).min(MAX_DATA);
+        Some(Frame {
+            id,
+            extended,
+            data: Vec::from_slice(&data[..len]).ok()?,
+        })
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        let mut data = [READ, register, 0];
+        self.transaction(&mut data);
+        data[2]
+    }
+
+    fn write(&self, register: u8, values: &[u8]) {
+        let bus = spi::Bus::begin(&self.spi);
+        self.cs.set_level(false);
+        bus.exchange(WRITE);
+        bus.exchange(register);
+        for &value in values {
+            bus.exchange(value);
+        }
+        self.cs.set_level(true);
+        bus.end();
+    }
+
+    /// Clocks out `data` with the chip selected, replacing it with the bytes
+    /// clocked back in.
+    fn transaction(&self, data: &mut [u8]) {
+        let bus = spi::Bus::begin(&self.spi);
+        self.cs.set_level(false);
+        bus.transfer(data);
+        self.cs.set_level(true);
+        bus.end();
+    }
+}
+
+/// Whether a bit rate and crystal can be set up.
+pub fn is_supported(bitrate: u32, crystal_mhz: u8) -> bool {
+    timing(bitrate).is_some() && CRYSTALS_MHZ.contains(&crystal_mhz)
+}
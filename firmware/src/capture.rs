@@ -0,0 +1,61 @@
+//! Logic analyzer captures of up to eight digital pins.
+//!
+//! Samples are taken against the microsecond clock with interrupts left on,
+//! so the millisecond tick shifts a sample by a few microseconds now and
+//! then. Each sample is a byte with a bit per pin, in the order the pins
+//! were given.
+
+use embedded_hal::serial::Write;
+use nb::block;
+
+use crate::gpio::RawPin;
+use crate::millis;
+
+pub use protocol::args::capture::Format;
+
+/// How many pins fit into a sample.
+pub const MAX_PINS: usize = 8;
+
+pub const MAX_SAMPLES: u16 = 256;
+
+/// The fastest sample rate in Hz, which leaves the loop time to read all
+/// the pins.
+pub const MAX_RATE: u32 = 10_000;
+
+/// Fills the buffer with samples of the pins, taken `rate_hz` times a second.
+pub fn sample(pins: &[RawPin], rate_hz: u32, buffer: &mut [u8]) {
+    let period_us = 1_000_000 / rate_hz;
+    let mut next = millis::micros();
+    for slot in buffer {
+        while (millis::micros().wrapping_sub(next) as i32) < 0 {}
+        next = next.wrapping_add(period_us);
+        *slot = pins
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, pin)| bits | u8::from(pin.is_high()) << i);
+    }
+}
+
+/// Calls `f` with each run of equal samples and its length.
+pub fn runs(samples: &[u8], mut f: impl FnMut(u8, u16)) {
+    let mut iter = samples.iter();
+    let Some(&first) = iter.next() else {
+        return;
+    };
+    let (mut value, mut length) = (first, 1);
+    for &sample in iter {
+        if sample == value {
+            length += 1;
+        } else {
+            f(value, length);
+            (value, length) = (sample, 1);
+        }
+    }
+    f(value, length);
+}
+
+pub fn write_sump<S: Write<u8>>(serial: &mut S, samples: &[u8]) {
+    for &sample in samples.iter().rev() {
+        let _ = block!(serial.write(sample));
+    }
+}
@@ -0,0 +1,77 @@
+//! Analog comparator, comparing d6 (AIN0) against d7 (AIN1) or the 1.1 V
+//! bandgap reference.
+//!
+//! The comparator raises an interrupt within a couple of clock cycles of its
+//! output changing, much faster than the ADC could be polled.
+
+use core::cell::RefCell;
+
+use avr_device::interrupt::{self, Mutex};
+use heapless::Deque;
+
+use crate::millis;
+
+pub use protocol::args::comparator::Reference;
+
+#[derive(Clone, Copy)]
+pub struct Event {
+    /// Whether the positive input is now above the negative one.
+    pub high: bool,
+    /// Uptime in milliseconds.
+    pub time: u32,
+}
+
+/// Changes not yet printed. Further changes are dropped until there is room.
+const QUEUE_SIZE: usize = 8;
+
+static EVENTS: Mutex<RefCell<Deque<Event, QUEUE_SIZE>>> = Mutex::new(RefCell::new(Deque::new()));
+
+fn ac() -> &'static arduino_hal::pac::ac::RegisterBlock {
+    // SAFETY: The analog comparator is only used from here.
+    unsafe { &*arduino_hal::pac::AC::ptr() }
+}
+
+/// Starts comparing, with the inputs' digital buffers disabled to save power.
+/// The pins have to be floating inputs.
+pub fn enable(reference: Reference) {
+    interrupt::free(|cs| {
+        EVENTS.borrow(cs).borrow_mut().clear();
+        let bandgap = matches!(reference, Reference::Bandgap);
+        ac().didr1
+            .write(|w| w.ain0d().bit(!bandgap).ain1d().set_bit());
+        ac().acsr
+            .write(|w| w.acbg().bit(bandgap).acis().on_toggle());
+        // Changing the inputs can raise a spurious interrupt, so the flag is
+        // cleared before enabling it.
+        ac().acsr.modify(|_, w| w.aci().set_bit().acie().set_bit());
+    })
+}
+
+/// Stops comparing and powers the comparator down.
+pub fn disable() {
+    interrupt::free(|_| {
+        ac().acsr.write(|w| w.acd().set_bit());
+        ac().didr1.reset();
+    })
+}
+
+/// Whether the positive input is currently above the negative one.
+pub fn is_high() -> bool {
+    ac().acsr.read().aco().bit_is_set()
+}
+
+/// Returns the oldest queued change, if any.
+pub fn take_event() -> Option<Event> {
+    interrupt::free(|cs| EVENTS.borrow(cs).borrow_mut().pop_front())
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn ANALOG_COMP() {
+    interrupt::free(|cs| {
+        let event = Event {
+            high: is_high(),
+            time: millis::millis(),
+        };
+        let _ = EVENTS.borrow(cs).borrow_mut().push_back(event);
+    })
+}
@@ -0,0 +1,247 @@
+//! Serial console wrapper.
+//!
+//! All command responses go through [`Console`], which takes care of output
+//! settings that apply across commands, such as timestamping each line, or
+//! collecting a command's response into a single line or frame for programs.
+
+use embedded_hal::serial::{Read, Write};
+use heapless::String;
+use nb::block;
+use ufmt::uWrite;
+
+use protocol::{json, response};
+
+use crate::progmem::fwrite;
+use crate::{binary, firmata, millis};
+
+pub use protocol::args::console::{Base, Mode, Value};
+
+pub struct Console<S> {
+    serial: S,
+    /// Prefix every response line with the uptime in milliseconds.
+    pub timestamps: bool,
+    pub mode: Mode,
+    /// Print the input prompt, which programs usually have no use for.
+    pub show_prompt: bool,
+    /// Echo input back as it is typed.
+    pub echo_input: bool,
+    pub base: Base,
+    line_start: bool,
+    /// Nesting depth of the commands running, as macros run commands of
+    /// their own.
+    depth: u8,
+    /// Output of the running command in machine and binary mode, or of the
+    /// current line outside of commands in binary and Firmata mode.
+    response: String<RESPONSE_LENGTH>,
+    /// Keyword of the running command, for JSON responses.
+    command: String<COMMAND_LENGTH>,
+}
+
+/// Longest command keyword kept for JSON responses.
+const COMMAND_LENGTH: usize = 12;
+
+/// Maximum length of a response in machine and binary mode, beyond which it
+/// is cut.
+const RESPONSE_LENGTH: usize = 96;
+
+impl<S: uWrite> Console<S> {
+    pub fn new(serial: S) -> Self {
+        Self {
+            serial,
+            timestamps: false,
+            mode: Mode::Human,
+            show_prompt: true,
+            echo_input: true,
+            base: Base::Decimal,
+            line_start: true,
+            depth: 0,
+            response: String::new(),
+            command: String::new(),
+        }
+    }
+
+    /// Wraps a value to print in the current base.
+    pub fn value(&self, value: u16) -> Value {
+        self.base.value(value)
+    }
+
+    /// Prints the input prompt, which is never timestamped.
+    pub fn prompt(&mut self) -> Result<(), S::Error> {
+        if !self.show_prompt || matches!(self.mode, Mode::Binary | Mode::Firmata | Mode::Modbus) {
+            return Ok(());
+        }
+        self.serial.write_str("> ")
+    }
+
+    /// Echoes input back, which is never timestamped either.
+    pub fn echo(&mut self, s: &str) -> Result<(), S::Error> {
+        if !self.echo_input {
+            return Ok(());
+        }
+        self.serial.write_str(s)
+    }
+
+    /// Starts the response to a command.
+    pub fn begin_response(&mut self, command: &str) {
+        if self.depth == 0 {
+            self.response.clear();
+            let keyword = command.split(' ').next().unwrap_or_default();
+            self.command = String::try_from(keyword).unwrap_or_default();
+        }
+        self.depth = self.depth.saturating_add(1);
+    }
+
+    /// Takes the response collected so far, for a binary response frame.
+    pub fn take_response(&mut self) -> String<RESPONSE_LENGTH> {
+        let mut response = core::mem::take(&mut self.response);
+        response.truncate(response.trim_end().len());
+        response
+    }
+
+    fn collect(&mut self, s: &str) {
+        response::collect(&mut self.response, self.mode == Mode::Json, s);
+    }
+}
+
+impl<S: uWrite + Write<u8>> Console<S> {
+    /// Finishes the response to a command, which failed if there is an error
+    /// code. In machine mode this prints the response collected since the
+    /// outermost command began, and in human mode just the error code. In
+    /// binary and Firmata mode any response not taken with
+    /// [`Console::take_response`] goes out as an event.
+    pub fn end_response(&mut self, error: Option<&str>) -> Result<(), <S as uWrite>::Error> {
+        self.finish_response(error, false)
+    }
+
+    /// Finishes the response to a command that ran in the background, such
+    /// as a rule's action. Nobody is waiting for it, so in machine mode it
+    /// goes out as an event, `event OK …` or `event ERR <code> …`, and not at
+    /// all if the command succeeded without output.
+    pub fn end_event(&mut self, error: Option<&str>) -> Result<(), <S as uWrite>::Error> {
+        self.finish_response(error, true)
+    }
+
+    fn finish_response(
+        &mut self,
+        error: Option<&str>,
+        event: bool,
+    ) -> Result<(), <S as uWrite>::Error> {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth > 0 {
+            return Ok(());
+        }
+        match self.mode {
+            Mode::Human => {
+                if let Some(code) = error {
+                    fwrite!(self, "error: {}\n", code)?;
+                }
+                return Ok(());
+            }
+            Mode::Machine => {}
+            Mode::Json => {
+                let response = self.take_response();
+                return json::write_response(&mut self.serial, &self.command, error, &response);
+            }
+            Mode::Binary | Mode::Firmata | Mode::Modbus => {
+                let response = self.take_response();
+                self.send_event(&response);
+                return Ok(());
+            }
+        }
+        let response = self.take_response();
+        if event {
+            if error.is_none() && response.is_empty() {
+                return Ok(());
+            }
+            fwrite!(self, "event ")?;
+        }
+        response::write_line(self, error, &response)
+    }
+
+    /// Sends a frame of the binary protocol.
+    pub fn send_frame(&mut self, opcode: u8, status: u8, data: &[u8]) {
+        binary::write_frame(&mut self.serial, opcode, status, data);
+    }
+
+    /// Sends a line of text in the frames of the current protocol.
+    fn send_event(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        match self.mode {
+            Mode::Binary => self.send_frame(binary::EVENT, 0, line.as_bytes()),
+            Mode::Firmata => firmata::write_string(&mut self.serial, line),
+            Mode::Json => {
+                let _ = json::write_event(&mut self.serial, line);
+            }
+            Mode::Human | Mode::Machine | Mode::Modbus => {}
+        }
+    }
+
+    /// Hands out the serial port, for protocols that don't send text.
+    pub fn raw(&mut self) -> &mut S {
+        &mut self.serial
+    }
+}
+
+impl<S: Write<u8>> Console<S> {
+    /// Waits until all pending output has been sent.
+    pub fn flush(&mut self) {
+        let _ = block!(self.serial.flush());
+    }
+}
+
+impl<S: uWrite + Write<u8>> uWrite for Console<S> {
+    type Error = <S as uWrite>::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        match self.mode {
+            Mode::Machine | Mode::Binary | Mode::Firmata | Mode::Json | Mode::Modbus
+                if self.depth > 0 =>
+            {
+                self.collect(s);
+                return Ok(());
+            }
+            Mode::Binary | Mode::Firmata | Mode::Json | Mode::Modbus => {
+                // Each line goes out as an event of its own.
+                let mut rest = s;
+                while let Some(end) = rest.find('\n') {
+                    self.collect(&rest[..end]);
+                    let line = self.take_response();
+                    self.send_event(&line);
+                    rest = &rest[end + 1..];
+                }
+                self.collect(rest);
+                return Ok(());
+            }
+            Mode::Human | Mode::Machine => {}
+        }
+        let mut rest = s;
+        while !rest.is_empty() {
+            if self.line_start && self.timestamps {
+                fwrite!(self.serial, "[{}] ", millis::millis())?;
+            }
+            match rest.find('\n') {
+                Some(end) => {
+                    self.serial.write_str(&rest[..=end])?;
+                    self.line_start = true;
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    self.serial.write_str(rest)?;
+                    self.line_start = false;
+                    rest = "";
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: Read<u8>> Read<u8> for Console<S> {
+    type Error = S::Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.serial.read()
+    }
+}
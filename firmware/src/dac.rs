@@ -0,0 +1,25 @@
+//! MCP4725 12-bit DAC.
+//!
+//! The output spans 0 V to the DAC's supply, which is assumed to be the
+//! crab's own, so millivolts are converted with the supply voltage measured
+//! at the time.
+
+use embedded_hal::blocking::i2c::Write;
+
+/// The address with A0 low, as on most breakout boards.
+pub const ADDRESS: u8 = 0x60;
+
+pub const MAX_VALUE: u16 = 4095;
+
+/// Sets the output with the fast write command, which leaves the value
+/// stored in the EEPROM alone.
+pub fn write(i2c: &mut arduino_hal::I2c, value: u16) -> Result<(), arduino_hal::i2c::Error> {
+    let [high, low] = value.min(MAX_VALUE).to_be_bytes();
+    i2c.write(ADDRESS, &[high, low])
+}
+
+/// Returns the value closest to a voltage, given the supply voltage.
+pub fn from_millivolts(millivolts: u32, vcc_millivolts: u32) -> u16 {
+    let value = (millivolts * 4096 + vcc_millivolts / 2) / vcc_millivolts.max(1);
+    value.min(u32::from(MAX_VALUE)) as u16
+}
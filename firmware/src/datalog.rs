@@ -0,0 +1,183 @@
+//! Analog data logger in EEPROM.
+//!
+//! Samples go into a ring buffer of 16-bit entries. The next entry to write
+//! is not stored anywhere, which would wear out its cell within a day of
+//! logging every second. Instead each entry carries a lap bit that flips
+//! every time the logger wraps around, and the logger finds its place again
+//! after a reset by looking for the end of the current lap. There it writes
+//! a [`RESTART`] entry, since the time that passed during the reset is
+//! unknown.
+
+use crate::{millis, storage};
+
+const ACTIVE: u8 = 0xD1;
+const STOPPED: u8 = 0xD0;
+
+/// How many samples the ring buffer holds.
+pub const CAPACITY: u16 = storage::DATALOG_SAMPLES_SIZE / 2;
+
+const LAP: u16 = 1 << 14;
+const ERASED: u16 = 0xFFFF;
+/// Marks where a log resumed after a reset. Samples only have 10 bits.
+const RESTART: u16 = LAP - 1;
+
+pub enum Entry {
+    Sample(u16),
+    /// The log resumed after a reset.
+    Restart,
+}
+
+pub struct Datalog {
+    pin: u8,
+    interval_ms: u32,
+    next_sample: u32,
+    /// Index of the next entry to write.
+    next: u16,
+    /// Lap bit of the entries being written.
+    lap: bool,
+}
+
+/// Settings of the last log, which may still be running.
+struct Header {
+    active: bool,
+    pin: u8,
+    interval_s: u16,
+}
+
+/// Erases the previous log and starts logging an analog pin, with the first
+/// sample taken right away.
+///
+/// Erasing the whole ring buffer takes over a second.
+pub fn start(eeprom: &mut arduino_hal::Eeprom, pin: u8, interval_s: u16) -> Datalog {
+    let _ = eeprom.erase(
+        storage::DATALOG_SAMPLES,
+        storage::DATALOG_SAMPLES + storage::DATALOG_SAMPLES_SIZE,
+    );
+    let [low, high] = interval_s.to_le_bytes();
+    let _ = eeprom.write(storage::DATALOG, &[ACTIVE, pin, low, high]);
+    Datalog::new(pin, interval_s, 0, false)
+}
+
+/// Picks up a log that was still running at the last reset, and marks the
+/// restart in it.
+pub fn resume(eeprom: &mut arduino_hal::Eeprom) -> Option<Datalog> {
+    let header = read_header(eeprom).filter(|header| header.active)?;
+    let (next, lap) = position(eeprom);
+    let mut datalog = Datalog::new(header.pin, header.interval_s, next, lap);
+    datalog.write(eeprom, RESTART);
+    Some(datalog)
+}
+
+/// Marks the log as stopped, so that it is not resumed after a reset. Its
+/// samples are kept.
+pub fn stop(eeprom: &mut arduino_hal::Eeprom) {
+    if eeprom.read_byte(storage::DATALOG) == ACTIVE {
+        eeprom.write_byte(storage::DATALOG, STOPPED);
+    }
+}
+
+/// Returns the analog pin and interval in seconds of the last log, if any.
+pub fn settings(eeprom: &arduino_hal::Eeprom) -> Option<(u8, u16)> {
+    read_header(eeprom).map(|header| (header.pin, header.interval_s))
+}
+
+/// Calls `f` with each entry of the last log, from oldest to newest.
+pub fn dump(eeprom: &arduino_hal::Eeprom, mut f: impl FnMut(Entry)) {
+    // Once the buffer has wrapped around, the oldest sample is the one that
+    // will be overwritten next.
+    let start = match position(eeprom) {
+        (next, _) if read_entry(eeprom, next) != ERASED => next,
+        _ => 0,
+    };
+    for i in (start..CAPACITY).chain(0..start) {
+        let entry = read_entry(eeprom, i);
+        if entry == ERASED {
+            break;
+        }
+        f(match entry & (LAP - 1) {
+            RESTART => Entry::Restart,
+            value => Entry::Sample(value),
+        });
+    }
+}
+
+/// Finds the next entry to write and its lap bit.
+fn position(eeprom: &arduino_hal::Eeprom) -> (u16, bool) {
+    let first = read_entry(eeprom, 0);
+    let lap = first != ERASED && first & LAP != 0;
+    // The current lap ends at the first entry that is erased, or left over
+    // from the previous lap.
+    let end = (1..CAPACITY).find(|&i| {
+        let entry = read_entry(eeprom, i);
+        entry == ERASED || (entry & LAP != 0) != lap
+    });
+    match end {
+        Some(next) => (next, lap),
+        None if first == ERASED => (0, false),
+        None => (0, !lap),
+    }
+}
+
+fn read_header(eeprom: &arduino_hal::Eeprom) -> Option<Header> {
+    let mut header = [0; 4];
+    eeprom.read(storage::DATALOG, &mut header).ok()?;
+    if header[0] != ACTIVE && header[0] != STOPPED {
+        return None;
+    }
+    Some(Header {
+        active: header[0] == ACTIVE,
+        pin: header[1],
+        interval_s: u16::from_le_bytes([header[2], header[3]]),
+    })
+}
+
+fn read_entry(eeprom: &arduino_hal::Eeprom, index: u16) -> u16 {
+    let mut entry = [0; 2];
+    let _ = eeprom.read(storage::DATALOG_SAMPLES + index * 2, &mut entry);
+    u16::from_le_bytes(entry)
+}
+
+impl Datalog {
+    fn new(pin: u8, interval_s: u16, next: u16, lap: bool) -> Self {
+        Datalog {
+            pin,
+            interval_ms: u32::from(interval_s) * 1000,
+            next_sample: millis::millis(),
+            next,
+            lap,
+        }
+    }
+
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Returns whether the next sample should be taken, and schedules the
+    /// one after.
+    pub fn is_due(&mut self) -> bool {
+        let now = millis::millis();
+        if (now.wrapping_sub(self.next_sample) as i32) < 0 {
+            return false;
+        }
+        self.next_sample = self.next_sample.wrapping_add(self.interval_ms);
+        true
+    }
+
+    /// Stores a 10-bit sample in the next entry.
+    pub fn record(&mut self, eeprom: &mut arduino_hal::Eeprom, value: u16) {
+        self.write(eeprom, value & 0x3FF);
+    }
+
+    fn write(&mut self, eeprom: &mut arduino_hal::Eeprom, value: u16) {
+        let entry = value | if self.lap { LAP } else { 0 };
+        let _ = eeprom.write(
+            storage::DATALOG_SAMPLES + self.next * 2,
+            &entry.to_le_bytes(),
+        );
+        self.next += 1;
+        if self.next == CAPACITY {
+            self.next = 0;
+            self.lap = !self.lap;
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! DHT11 and DHT22 temperature and humidity sensors.
+//!
+//! The sensor answers a start pulse with 40 bits, each a fixed low period
+//! followed by a short high period for a zero or a long one for a one. The
+//! line is sampled partway through the high period to tell them apart.
+
+use arduino_hal::{delay_ms, delay_us};
+use avr_device::interrupt;
+
+use crate::gpio::RawPin;
+
+pub use protocol::args::dht::Model;
+
+pub enum Error {
+    /// The sensor did not answer the start pulse.
+    NoResponse,
+    /// The sensor stopped sending partway through.
+    Timeout,
+    Checksum,
+}
+
+pub struct Reading {
+    /// Tenths of a degree Celsius.
+    pub temperature: i16,
+    /// Tenths of a percent relative humidity.
+    pub humidity: u16,
+}
+
+/// Reads the sensor on a pin, which has to be a floating input with a pull-up
+/// resistor on the line.
+///
+/// Interrupts are disabled for the 5 ms or so the transfer takes, so the
+/// uptime falls behind by that much.
+pub fn read(pin: RawPin, model: Model) -> Result<Reading, Error> {
+    pin.set_level(false);
+    pin.pull_low();
+    delay_ms(match model {
+        Model::Dht11 => 20,
+        Model::Dht22 => 2,
+    });
+
+    let mut data = [0u8; 5];
+    interrupt::free(|_| {
+        pin.release();
+        // The sensor acknowledges with 80 µs low and 80 µs high.
+        wait_for(pin, false, 100).map_err(|_| Error::NoResponse)?;
+        wait_for(pin, true, 100).map_err(|_| Error::NoResponse)?;
+        wait_for(pin, false, 100).map_err(|_| Error::NoResponse)?;
+        for bit in 0..40 {
+            wait_for(pin, true, 70)?;
+            // A zero is high for 26-28 µs and a one for 70 µs.
+            delay_us(40);
+            if pin.is_high() {
+                data[bit / 8] |= 0x80 >> (bit % 8);
+                wait_for(pin, false, 50)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    let sum = data[..4]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    if sum != data[4] {
+        return Err(Error::Checksum);
+    }
+    Ok(match model {
+        Model::Dht11 => Reading {
+            temperature: i16::from(data[2]) * 10 + i16::from(data[3] % 10),
+            humidity: u16::from(data[0]) * 10 + u16::from(data[1] % 10),
+        },
+        Model::Dht22 => {
+            let magnitude = i16::from(data[2] & 0x7F) << 8 | i16::from(data[3]);
+            Reading {
+                temperature: if data[2] & 0x80 != 0 {
+                    -magnitude
+                } else {
+                    magnitude
+                },
+                humidity: u16::from(data[0]) << 8 | u16::from(data[1]),
+            }
+        }
+    })
+}
+
+/// Waits for the line to reach a level, for roughly up to `timeout_us`.
+fn wait_for(pin: RawPin, high: bool, timeout_us: u8) -> Result<(), Error> {
+    for _ in 0..timeout_us {
+        if pin.is_high() == high {
+            return Ok(());
+        }
+        delay_us(1);
+    }
+    Err(Error::Timeout)
+}
@@ -0,0 +1,51 @@
+//! DS18B20 temperature sensors on a 1-Wire bus.
+
+use arduino_hal::delay_ms;
+
+use crate::onewire::{crc8, Error, OneWire, Rom};
+
+/// The first byte of every DS18B20 ROM ID.
+pub const FAMILY_CODE: u8 = 0x28;
+
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xBE;
+
+/// Upper bound for a 12-bit conversion, with some margin over the datasheet's
+/// 750 ms.
+const CONVERSION_TIMEOUT_MS: u16 = 1000;
+
+/// Starts a conversion on the addressed sensors and waits for it to finish.
+///
+/// Sensors hold the bus low while they are busy, so this does not work with
+/// parasite-powered sensors, which need the bus held high instead.
+pub fn convert(bus: &OneWire, rom: Option<&Rom>) -> Result<(), Error> {
+    bus.select(rom)?;
+    bus.write_byte(CONVERT_T);
+    for _ in 0..CONVERSION_TIMEOUT_MS {
+        if bus.read_bit() {
+            return Ok(());
+        }
+        delay_ms(1);
+    }
+    Err(Error::Timeout)
+}
+
+/// Reads the last converted temperature from a sensor, in hundredths of a
+/// degree Celsius.
+///
+/// Without a ROM ID this only works with a single sensor on the bus.
+pub fn read_centi_celsius(bus: &OneWire, rom: Option<&Rom>) -> Result<i32, Error> {
+    bus.select(rom)?;
+    bus.write_byte(READ_SCRATCHPAD);
+    let mut scratchpad = [0; 9];
+    for byte in scratchpad.iter_mut() {
+        *byte = bus.read_byte();
+    }
+    // A shorted bus reads as all zeros, which passes the checksum.
+    if scratchpad == [0; 9] || crc8(&scratchpad) != 0 {
+        return Err(Error::Crc);
+    }
+    // The raw value is in sixteenths of a degree.
+    let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]);
+    Ok(i32::from(raw) * 100 / 16)
+}
@@ -0,0 +1,86 @@
+//! Duty cycle measurement of a PWM or servo signal on any digital pin.
+//!
+//! The pin is polled against the microsecond clock, so times come out to
+//! 4 µs, plus a few more whenever the millisecond tick interrupts. That is
+//! fine for servo pulses and fan PWM up to a kHz or so. On the Uno's d8 the
+//! edges are timed by [`crate::icp`] instead, to 62.5 ns, unless Timer1 is
+//! busy with the servos.
+
+use crate::gpio::RawPin;
+#[cfg(feature = "uno")]
+use crate::icp;
+use crate::millis;
+
+/// How many periods are averaged.
+pub const PERIODS: u32 = 4;
+
+pub struct Measurement {
+    pub period_us: u32,
+    pub high_us: u32,
+}
+
+impl Measurement {
+    pub fn frequency_hz(&self) -> u32 {
+        1_000_000 / self.period_us.max(1)
+    }
+
+    /// The share of each period spent high, in tenths of a percent.
+    pub fn duty_permille(&self) -> u32 {
+        (u64::from(self.high_us) * 1000 / u64::from(self.period_us.max(1))) as u32
+    }
+}
+
+/// The pin stayed at a level for the whole timeout.
+pub struct Timeout {
+    pub high: bool,
+}
+
+/// Waits for the pin to reach a level, and returns the time it did.
+fn wait_for(pin: RawPin, high: bool, start: u32, timeout_ms: u32) -> Result<u32, Timeout> {
+    while pin.is_high() != high {
+        if millis::millis().wrapping_sub(start) > timeout_ms {
+            return Err(Timeout { high: !high });
+        }
+    }
+    Ok(millis::micros())
+}
+
+/// Measures the signal on a pin, which has to be an input, from one rising
+/// edge to the one [`PERIODS`] later.
+#[cfg_attr(not(feature = "uno"), allow(unused_variables))]
+pub fn measure(number: u8, pin: RawPin, timeout_ms: u32) -> Result<Measurement, Timeout> {
+    #[cfg(feature = "uno")]
+    if number == icp::PIN {
+        // From a rising edge, every other interval is high.
+        match icp::measure(icp::Edges::Both, 2 * PERIODS as u8, timeout_ms) {
+            Ok(stats) => {
+                let high = stats.alternate_average(false);
+                let low = stats.alternate_average(true);
+                return Ok(Measurement {
+                    period_us: (high + low) / icp::COUNTS_PER_US,
+                    high_us: high / icp::COUNTS_PER_US,
+                });
+            }
+            Err(icp::Error::Timeout) => {
+                return Err(Timeout {
+                    high: pin.is_high(),
+                })
+            }
+            Err(icp::Error::Busy) => {}
+        }
+    }
+    let start = millis::millis();
+    wait_for(pin, false, start, timeout_ms)?;
+    let first = wait_for(pin, true, start, timeout_ms)?;
+    let mut rise = first;
+    let mut high_us = 0;
+    for _ in 0..PERIODS {
+        let fall = wait_for(pin, false, start, timeout_ms)?;
+        high_us += fall.wrapping_sub(rise);
+        rise = wait_for(pin, true, start, timeout_ms)?;
+    }
+    Ok(Measurement {
+        period_us: rise.wrapping_sub(first) / PERIODS,
+        high_us: high_us / PERIODS,
+    })
+}
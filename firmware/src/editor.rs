@@ -0,0 +1,231 @@
+//! Line editor for the console input.
+//!
+//! Input is echoed back, backspace deletes the last character, and the up and
+//! down arrow keys recall the last few lines entered. Tab completes the word
+//! being typed, or lists the candidates if there is more than one.
+
+use embedded_hal::serial::Read;
+use heapless::{Deque, String};
+use ufmt::uWrite;
+
+use crate::console::Console;
+use crate::INPUT_LENGTH;
+
+/// How many lines are kept for recall.
+const HISTORY_SIZE: usize = 4;
+
+pub type Line = String<INPUT_LENGTH>;
+
+pub enum Error {
+    /// The serial port failed to receive a byte.
+    Serial,
+    /// The line didn't fit, and has been discarded up to its end.
+    TooLong,
+}
+
+/// Progress through an ANSI escape sequence.
+enum Escape {
+    None,
+    /// Seen ESC.
+    Start,
+    /// Seen ESC and `[` or `O`, waiting for the final byte.
+    Sequence,
+}
+
+pub struct Editor {
+    line: Line,
+    /// Earlier lines, most recent first.
+    history: Deque<Line, HISTORY_SIZE>,
+    /// The history entry currently shown, if any.
+    recalled: Option<usize>,
+    escape: Escape,
+    /// Whether the last byte was a carriage return, so that a line feed
+    /// right after it doesn't end another line.
+    after_cr: bool,
+    /// Whether the line has overflowed, so the rest of it is dropped.
+    overflowed: bool,
+    /// Words to complete, separated by spaces, at the start of a command and
+    /// after it.
+    commands: &'static str,
+    keywords: &'static str,
+}
+
+impl Editor {
+    pub const fn new(commands: &'static str, keywords: &'static str) -> Self {
+        Self {
+            line: String::new(),
+            history: Deque::new(),
+            recalled: None,
+            escape: Escape::None,
+            after_cr: false,
+            overflowed: false,
+            commands,
+            keywords,
+        }
+    }
+
+    /// Handles the input that has arrived, without waiting for more.
+    ///
+    /// Returns the line once it is complete, with the terminating CR, LF, or
+    /// CR LF left out. A line that doesn't fit is not echoed past its limit,
+    /// and fails once its end arrives.
+    pub fn read<S>(&mut self, console: &mut Console<S>) -> nb::Result<Line, Error>
+    where
+        S: Read<u8> + uWrite,
+    {
+        loop {
+            let mut byte = console
+                .read()
+                .map_err(|error| error.map(|_| Error::Serial))?;
+            if byte == b'\n' && self.after_cr {
+                self.after_cr = false;
+                continue;
+            }
+            self.after_cr = byte == b'\r';
+            if self.after_cr {
+                byte = b'\n';
+            }
+            if self.overflowed {
+                if byte == b'\n' {
+                    let _ = console.echo("\n");
+                    self.overflowed = false;
+                    return Err(nb::Error::Other(Error::TooLong));
+                }
+                continue;
+            }
+            match self.escape {
+                Escape::None => {}
+                Escape::Start => {
+                    self.escape = match byte {
+                        b'[' | b'O' => Escape::Sequence,
+                        _ => Escape::None,
+                    };
+                    continue;
+                }
+                Escape::Sequence => {
+                    // Parameters come before the final byte, which is a
+                    // letter or one of a few symbols.
+                    if (0x40..=0x7E).contains(&byte) {
+                        self.escape = Escape::None;
+                        match byte {
+                            b'A' => self.recall(console, true),
+                            b'B' => self.recall(console, false),
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+            }
+            match byte {
+                b'\n' => {
+                    let _ = console.echo("\n");
+                    self.recalled = None;
+                    let line = core::mem::take(&mut self.line);
+                    self.remember(&line);
+                    return Ok(line);
+                }
+                0x1B => self.escape = Escape::Start,
+                b'\t' => self.complete(console),
+                // Backspace and delete, depending on the terminal.
+                0x08 | 0x7F => {
+                    if self.line.pop().is_some() {
+                        let _ = console.echo("\x08 \x08");
+                    }
+                }
+                _ => {
+                    if self.line.push(byte as char).is_err() {
+                        self.line.clear();
+                        self.recalled = None;
+                        self.overflowed = true;
+                        continue;
+                    }
+                    let mut buf = [0; 4];
+                    let _ = console.echo((byte as char).encode_utf8(&mut buf));
+                }
+            }
+        }
+    }
+
+    /// Replaces the line with an older or newer one from the history, or an
+    /// empty line after the newest.
+    fn recall<S: uWrite>(&mut self, console: &mut Console<S>, older: bool) {
+        let recalled = match (self.recalled, older) {
+            (None, true) => Some(0),
+            (None, false) => return,
+            (Some(i), true) if i + 1 < self.history.len() => Some(i + 1),
+            (Some(i), true) => Some(i),
+            (Some(0), false) => None,
+            (Some(i), false) => Some(i - 1),
+        };
+        let Some(line) =
+            recalled.map_or(Some(Line::new()), |i| self.history.iter().nth(i).cloned())
+        else {
+            return;
+        };
+        self.recalled = recalled;
+        for _ in 0..self.line.len() {
+            let _ = console.echo("\x08");
+        }
+        let _ = console.echo("\x1b[K");
+        let _ = console.echo(&line);
+        self.line = line;
+    }
+
+    /// Completes the last word of the line as far as the candidates agree.
+    fn complete<S: uWrite>(&mut self, console: &mut Console<S>) {
+        let start = self.line.rfind([' ', ';']).map_or(0, |i| i + 1);
+        let (before, word) = self.line.split_at(start);
+        let before = before.trim_end();
+        let words = if before.is_empty() || before.ends_with(';') {
+            self.commands
+        } else {
+            self.keywords
+        };
+        let mut candidates = words.split(' ').filter(|w| w.starts_with(word));
+        let Some(first) = candidates.next() else {
+            return;
+        };
+        let mut common = first;
+        let mut unique = true;
+        for candidate in candidates.clone() {
+            let len = common
+                .bytes()
+                .zip(candidate.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            common = &common[..len];
+            unique = false;
+        }
+        let typed = word.len();
+        if unique || common.len() > typed {
+            let rest = &common[typed..];
+            if self.line.push_str(rest).is_ok() {
+                let _ = console.echo(rest);
+            }
+            // A complete word is followed by its arguments.
+            if unique && self.line.push(' ').is_ok() {
+                let _ = console.echo(" ");
+            }
+        } else {
+            let _ = console.echo("\n");
+            let _ = console.echo(first);
+            for candidate in candidates {
+                let _ = console.echo(" ");
+                let _ = console.echo(candidate);
+            }
+            let _ = console.echo("\n");
+            let _ = console.prompt();
+            let _ = console.echo(&self.line);
+        }
+    }
+
+    fn remember(&mut self, line: &Line) {
+        if line.is_empty() || self.history.front() == Some(line) {
+            return;
+        }
+        if self.history.is_full() {
+            self.history.pop_back();
+        }
+        let _ = self.history.push_front(line.clone());
+    }
+}
@@ -0,0 +1,70 @@
+//! Quadrature rotary encoder decoding.
+//!
+//! Both encoder pins raise pin change interrupts, and every change of either
+//! one moves the position by a quarter step in the direction given by the
+//! order of the changes.
+
+use core::cell::Cell;
+
+use avr_device::interrupt::{CriticalSection, Mutex};
+
+use crate::gpio::RawPin;
+use crate::pcint;
+
+#[derive(Clone, Copy)]
+struct Encoder {
+    a: u8,
+    b: u8,
+    /// The last levels of both pins, A in bit 1 and B in bit 0.
+    last: u8,
+    position: i32,
+}
+
+static ENCODER: Mutex<Cell<Option<Encoder>>> = Mutex::new(Cell::new(None));
+
+/// Position changes indexed by the previous and current levels of both pins.
+/// Invalid transitions, where both pins changed at once, are ignored.
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+fn levels(a: u8, b: u8) -> u8 {
+    let high = |pin| RawPin::new(pin).map_or(false, |pin| pin.is_high());
+    u8::from(high(a)) << 1 | u8::from(high(b))
+}
+
+/// Starts decoding an encoder on two digital pins, which have to be inputs,
+/// from position zero. Any previously attached encoder is detached.
+pub fn attach(a: u8, b: u8) {
+    avr_device::interrupt::free(|cs| {
+        if let Some(old) = ENCODER.borrow(cs).get() {
+            pcint::disable(old.a);
+            pcint::disable(old.b);
+        }
+        ENCODER.borrow(cs).set(Some(Encoder {
+            a,
+            b,
+            last: levels(a, b),
+            position: 0,
+        }));
+        pcint::enable(a);
+        pcint::enable(b);
+    })
+}
+
+/// Returns the position in quarter steps, if an encoder is attached.
+pub fn position() -> Option<i32> {
+    avr_device::interrupt::free(|cs| ENCODER.borrow(cs).get().map(|encoder| encoder.position))
+}
+
+/// Updates the position after a pin change. Called from the pin change
+/// interrupt handlers.
+pub fn update(cs: CriticalSection) {
+    let cell = ENCODER.borrow(cs);
+    let Some(mut encoder) = cell.get() else {
+        return;
+    };
+    let current = levels(encoder.a, encoder.b);
+    let step = TRANSITIONS[usize::from(encoder.last << 2 | current)];
+    encoder.position = encoder.position.wrapping_add(i32::from(step));
+    encoder.last = current;
+    cell.set(Some(encoder));
+}
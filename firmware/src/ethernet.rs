@@ -0,0 +1,381 @@
+//! The console over TCP, on a WIZnet W5500 Ethernet controller on the SPI
+//! bus.
+//!
+//! The controller runs the TCP/IP stack itself, so all the firmware does is
+//! move bytes in and out of the buffers of one of its sockets. [`Mirror`]
+//! wraps the serial port: input comes from either side, and output goes to
+//! both. One client can connect at a time, and the socket listens again once
+//! it disconnects.
+//!
+//! Output for the network is buffered until the console waits for input, or
+//! the buffer fills up. While another SPI device is in the middle of a
+//! transaction, such as the SD card during `log dump`, the controller can't
+//! be reached, and what doesn't fit into the buffer only goes to the serial
+//! port.
+
+use core::cell::RefCell;
+
+use arduino_hal::delay_ms;
+use avr_device::interrupt::{self, Mutex};
+use embedded_hal::serial::{Read, Write};
+use heapless::{Deque, Vec};
+use ufmt::uWrite;
+
+use crate::gpio::RawPin;
+use crate::spi;
+
+/// Blocks of the controller's address space, selected in each frame.
+const COMMON: u8 = 0;
+const SOCKET: u8 = 1;
+const TX_BUFFER: u8 = 2;
+const RX_BUFFER: u8 = 3;
+/// The read/write bit of the control byte.
+const WRITE: u8 = 1 << 2;
+
+const MR: u16 = 0x0000;
+const GAR: u16 = 0x0001;
+const SUBR: u16 = 0x0005;
+const SHAR: u16 = 0x0009;
+const SIPR: u16 = 0x000F;
+const PHYCFGR: u16 = 0x002E;
+const VERSIONR: u16 = 0x0039;
+
+const SN_MR: u16 = 0x0000;
+const SN_CR: u16 = 0x0001;
+const SN_SR: u16 = 0x0003;
+const SN_PORT: u16 = 0x0004;
+const SN_TX_FSR: u16 = 0x0020;
+const SN_TX_WR: u16 = 0x0024;
+const SN_RX_RSR: u16 = 0x0026;
+const SN_RX_RD: u16 = 0x0028;
+
+/// `MR`: resets the controller.
+const RESET: u8 = 0x80;
+/// `PHYCFGR`: the link is up.
+const LINK: u8 = 1 << 0;
+/// `VERSIONR` of every W5500.
+const VERSION: u8 = 0x04;
+/// `Sn_MR`: a TCP socket.
+const TCP: u8 = 0x01;
+
+/// `Sn_CR` commands.
+const OPEN: u8 = 0x01;
+const LISTEN: u8 = 0x02;
+const DISCONNECT: u8 = 0x08;
+const SEND: u8 = 0x20;
+const RECV: u8 = 0x40;
+
+/// `Sn_SR` states.
+const CLOSED: u8 = 0x00;
+const INIT: u8 = 0x13;
+const LISTENING: u8 = 0x14;
+const ESTABLISHED: u8 = 0x17;
+const CLOSE_WAIT: u8 = 0x1C;
+
+/// Output held back for the network, sent in one go.
+const OUTPUT_SIZE: usize = 64;
+/// Input taken from the controller at a time.
+const INPUT_SIZE: usize = 16;
+
+pub enum Error {
+    /// No controller answered on the bus.
+    NotFound,
+    /// The socket didn't open.
+    Socket,
+}
+
+/// What `net status` prints.
+pub struct Status {
+    pub ip: [u8; 4],
+    pub port: u16,
+    pub link: bool,
+    pub connected: bool,
+}
+
+struct Controller {
+    cs: RawPin,
+    spi: spi::Config,
+    ip: [u8; 4],
+    port: u16,
+}
+
+// SAFETY: The chip select is only driven from the main loop, never from an
+// interrupt handler.
+unsafe impl Send for Controller {}
+
+static CONTROLLER: Mutex<RefCell<Option<Controller>>> = Mutex::new(RefCell::new(None));
+
+/// Runs `f` on the controller, if one is set up. It is taken out for the
+/// duration, so that interrupts stay on during the transfers.
+fn with<R>(f: impl FnOnce(&Controller) -> R) -> Option<R> {
+    let controller = interrupt::free(|cs| CONTROLLER.borrow(cs).borrow_mut().take())?;
+    let result = f(&controller);
+    interrupt::free(|cs| *CONTROLLER.borrow(cs).borrow_mut() = Some(controller));
+    Some(result)
+}
+
+/// Sets up the controller behind a chip select, which has to be an output
+/// driven high, and starts listening on a TCP port. The gateway is assumed
+/// to be the first address of a /24 network, and the MAC address is a
+/// locally administered one made from the IP address.
+pub fn start(cs: RawPin, cs_pin: u8, ip: [u8; 4], port: u16) -> Result<(), Error> {
+    stop();
+    let controller = Controller {
+        cs,
+        spi: spi::Config {
+            mode: 0,
+            divider: 2,
+            cs: cs_pin,
+        },
+        ip,
+        port,
+    };
+    controller.write(COMMON, MR, &[RESET]);
+    delay_ms(2);
+    if controller.read_u8(COMMON, VERSIONR) != VERSION {
+        return Err(Error::NotFound);
+    }
+    let [a, b, c, d] = ip;
+    controller.write(COMMON, GAR, &[a, b, c, 1]);
+    controller.write(COMMON, SUBR, &[255, 255, 255, 0]);
+    controller.write(COMMON, SHAR, &[0x02, 0x00, a, b, c, d]);
+    controller.write(COMMON, SIPR, &ip);
+    if !controller.listen() {
+        return Err(Error::Socket);
+    }
+    interrupt::free(|cs| *CONTROLLER.borrow(cs).borrow_mut() = Some(controller));
+    Ok(())
+}
+
+/// Closes the socket, and disconnects the client if there is one.
+pub fn stop() {
+    let controller = interrupt::free(|cs| CONTROLLER.borrow(cs).borrow_mut().take());
+    if let Some(controller) = controller {
+        controller.command(DISCONNECT);
+        controller.write(COMMON, MR, &[RESET]);
+    }
+}
+
+/// Returns the state of the controller, if one is set up.
+pub fn status() -> Option<Status> {
+    with(|controller| Status {
+        ip: controller.ip,
+        port: controller.port,
+        link: controller.read_u8(COMMON, PHYCFGR) & LINK != 0,
+        connected: controller.read_u8(SOCKET, SN_SR) == ESTABLISHED,
+    })
+}
+
+impl Controller {
+    /// Opens the socket and listens for a client.
+    fn listen(&self) -> bool {
+        self.write(SOCKET, SN_MR, &[TCP]);
+        self.write(SOCKET, SN_PORT, &self.port.to_be_bytes());
+        self.command(OPEN);
+        if self.read_u8(SOCKET, SN_SR) != INIT {
+            return false;
+        }
+        self.command(LISTEN);
+        self.read_u8(SOCKET, SN_SR) == LISTENING
+    }
+
+    /// Takes input from the client into `buffer`, and returns how much
+    /// there was. Also listens again once a client has gone.
+    fn receive(&self, buffer: &mut [u8]) -> usize {
+        match self.read_u8(SOCKET, SN_SR) {
+            ESTABLISHED => {}
+            CLOSE_WAIT => {
+                self.command(DISCONNECT);
+                return 0;
+            }
+            CLOSED => {
+                self.listen();
+                return 0;
+            }
+            _ => return 0,
+        }
+        let len = usize::from(self.read_u16(SN_RX_RSR)).min(buffer.len());
+        if len == 0 {
+            return 0;
+        }
+        let pointer = self.read_u16(SN_RX_RD);
+        self.read(RX_BUFFER, pointer, &mut buffer[..len]);
+        self.write(
+            SOCKET,
+            SN_RX_RD,
+            &pointer.wrapping_add(len as u16).to_be_bytes(),
+        );
+        self.command(RECV);
+        len
+    }
+
+    /// Sends output to the client, if one is connected and has taken enough
+    /// of what was sent before.
+    fn send(&self, data: &[u8]) {
+        if self.read_u8(SOCKET, SN_SR) != ESTABLISHED
+            || usize::from(self.read_u16(SN_TX_FSR)) < data.len()
+        {
+            return;
+        }
+        let pointer = self.read_u16(SN_TX_WR);
+        self.write(TX_BUFFER, pointer, data);
+        self.write(
+            SOCKET,
+            SN_TX_WR,
+            &pointer.wrapping_add(data.len() as u16).to_be_bytes(),
+        );
+        self.command(SEND);
+    }
+
+    /// Runs a socket command, and waits for the controller to take it.
+    fn command(&self, command: u8) {
+        self.write(SOCKET, SN_CR, &[command]);
+        for _ in 0..100 {
+            if self.read_u8(SOCKET, SN_CR) == 0 {
+                break;
+            }
+        }
+    }
+
+    fn read_u8(&self, block: u8, address: u16) -> u8 {
+        let mut value = [0];
+        self.read(block, address, &mut value);
+        value[0]
+    }
+
+    /// Reads a socket counter, which can change between its two bytes, until
+    /// it reads the same twice.
+    fn read_u16(&self, address: u16) -> u16 {
+        let mut previous = None;
+        loop {
+            let mut value = [0; 2];
+            self.read(SOCKET, address, &mut value);
+            let value = u16::from_be_bytes(value);
+            if previous == Some(value) {
+                return value;
+            }
+            previous = Some(value);
+        }
+    }
+
+    fn read(&self, block: u8, address: u16, data: &mut [u8]) {
+        let bus = self.begin(block << 3, address);
+        for byte in data.iter_mut() {
+            *byte = bus.exchange(0);
+        }
+        self.end(bus);
+    }
+
+    fn write(&self, block: u8, address: u16, data: &[u8]) {
+        let bus = self.begin(block << 3 | WRITE, address);
+        for &byte in data {
+            bus.exchange(byte);
+        }
+        self.end(bus);
+    }
+
+    fn begin(&self, control: u8, address: u16) -> spi::Bus {
+        let bus = spi::Bus::begin(&self.spi);
+        self.cs.set_level(false);
+        let [high, low] = address.to_be_bytes();
+        bus.exchange(high);
+        bus.exchange(low);
+        bus.exchange(control);
+        bus
+    }
+
+    fn end(&self, bus: spi::Bus) {
+        self.cs.set_level(true);
+        bus.end();
+    }
+}
+
+/// The serial port, and the network client if there is one.
+pub struct Mirror<S> {
+    serial: S,
+    output: Vec<u8, OUTPUT_SIZE>,
+    input: Deque<u8, INPUT_SIZE>,
+}
+
+impl<S> From<S> for Mirror<S> {
+    fn from(serial: S) -> Self {
+        Mirror {
+            serial,
+            output: Vec::new(),
+            input: Deque::new(),
+        }
+    }
+}
+
+impl<S> Mirror<S> {
+    fn queue(&mut self, byte: u8) {
+        if self.output.is_full() {
+            self.send();
+        }
+        // Still full if the bus is in use.
+        let _ = self.output.push(byte);
+    }
+
+    /// Sends the output held back, unless the bus is in use.
+    fn send(&mut self) {
+        if self.output.is_empty() || spi::is_busy() {
+            return;
+        }
+        with(|controller| controller.send(&self.output));
+        self.output.clear();
+    }
+
+    fn receive(&mut self) -> Option<u8> {
+        if self.input.is_empty() && !spi::is_busy() {
+            let mut buffer = [0; INPUT_SIZE];
+            let len = with(|controller| controller.receive(&mut buffer)).unwrap_or(0);
+            for &byte in &buffer[..len] {
+                let _ = self.input.push_back(byte);
+            }
+        }
+        self.input.pop_front()
+    }
+}
+
+impl<S: uWrite> uWrite for Mirror<S> {
+    type Error = S::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.serial.write_str(s)?;
+        for byte in s.bytes() {
+            self.queue(byte);
+        }
+        Ok(())
+    }
+}
+
+impl<S: Write<u8>> Write<u8> for Mirror<S> {
+    type Error = S::Error;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.serial.write(byte)?;
+        self.queue(byte);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.send();
+        self.serial.flush()
+    }
+}
+
+impl<S: Read<u8>> Read<u8> for Mirror<S> {
+    type Error = S::Error;
+
+    /// Reads from the serial port first. With nothing there, this sends the
+    /// output held back, and reads from the client.
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        match self.serial.read() {
+            Err(nb::Error::WouldBlock) => {
+                self.send();
+                self.receive().ok_or(nb::Error::WouldBlock)
+            }
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Ramps of a PWM duty cycle, stepped from the main loop.
+//!
+//! The duty cycle is worked out from the time since the start on every step,
+//! so a slow main loop makes the ramp coarser but doesn't stretch it.
+
+use crate::millis;
+
+/// How many pins can fade at the same time.
+pub const MAX_FADES: usize = 4;
+
+/// The longest ramp, an hour, which keeps the arithmetic within 32 bits.
+pub const MAX_DURATION_MS: u32 = 3_600_000;
+
+pub struct Fade {
+    pub pin: u8,
+    from: u8,
+    to: u8,
+    started: u32,
+    duration_ms: u32,
+    /// The duty cycle last set.
+    duty: u8,
+}
+
+impl Fade {
+    /// Starts a ramp, with the pin already at `from`.
+    pub fn new(pin: u8, from: u8, to: u8, duration_ms: u32) -> Self {
+        Fade {
+            pin,
+            from,
+            to,
+            started: millis::millis(),
+            duration_ms,
+            duty: from,
+        }
+    }
+
+    /// Returns the duty cycle if it has changed since the last step, and
+    /// whether the ramp has come to its end.
+    pub fn step(&mut self) -> (Option<u8>, bool) {
+        let elapsed = millis::millis().wrapping_sub(self.started);
+        let (duty, done) = if elapsed >= self.duration_ms {
+            (self.to, true)
+        } else {
+            let span = u32::from(self.from.abs_diff(self.to));
+            let progress = (span * elapsed / self.duration_ms) as u8;
+            let duty = if self.to > self.from {
+                self.from + progress
+            } else {
+                self.from - progress
+            };
+            (duty, false)
+        };
+        if duty == self.duty {
+            return (None, done);
+        }
+        self.duty = duty;
+        (Some(duty), done)
+    }
+}
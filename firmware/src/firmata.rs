@@ -0,0 +1,287 @@
+//! The core message set of the Firmata protocol, so that hosts and GUIs that
+//! already speak it can drive the crab.
+//!
+//! Firmata numbers the digital pins 0-13 and the analog pins 14-19, and
+//! groups the digital pins into ports of eight for reading and writing. Only
+//! the pins the crab controls anyway are offered in the capability response.
+
+use embedded_hal::serial::Write;
+use heapless::Vec;
+use nb::block;
+
+/// Protocol version implemented.
+const VERSION: (u8, u8) = (2, 5);
+
+const DIGITAL_MESSAGE: u8 = 0x90;
+const ANALOG_MESSAGE: u8 = 0xE0;
+const REPORT_ANALOG: u8 = 0xC0;
+const REPORT_DIGITAL: u8 = 0xD0;
+const SET_PIN_MODE: u8 = 0xF4;
+const SET_DIGITAL_PIN_VALUE: u8 = 0xF5;
+const REPORT_VERSION: u8 = 0xF9;
+const SYSTEM_RESET: u8 = 0xFF;
+const START_SYSEX: u8 = 0xF0;
+const END_SYSEX: u8 = 0xF7;
+
+const ANALOG_MAPPING_QUERY: u8 = 0x69;
+const ANALOG_MAPPING_RESPONSE: u8 = 0x6A;
+const CAPABILITY_QUERY: u8 = 0x6B;
+const CAPABILITY_RESPONSE: u8 = 0x6C;
+const STRING_DATA: u8 = 0x71;
+const REPORT_FIRMWARE: u8 = 0x79;
+const SAMPLING_INTERVAL: u8 = 0x7A;
+
+pub const MODE_INPUT: u8 = 0;
+pub const MODE_OUTPUT: u8 = 1;
+const MODE_ANALOG: u8 = 2;
+const MODE_PWM: u8 = 3;
+
+/// Total number of pins, digital and analog.
+pub const PIN_COUNT: u8 = 20;
+/// Firmata number of analog pin 0.
+pub const FIRST_ANALOG_PIN: u8 = 14;
+/// Analog pins the crab reads, a0-a3. The others are the I2C bus.
+pub const ANALOG_PINS: u8 = 4;
+/// Reporting covers ports 0 and 1, which hold d0-d13.
+pub const PORTS: u8 = 2;
+
+/// Longest sysex message accepted. Longer ones are skipped.
+const MAX_SYSEX: usize = 8;
+
+pub enum Message {
+    ReportVersion,
+    ReportFirmware,
+    CapabilityQuery,
+    AnalogMappingQuery,
+    SetPinMode {
+        pin: u8,
+        mode: u8,
+    },
+    /// Levels for the output pins of a port.
+    DigitalPort {
+        port: u8,
+        levels: u8,
+    },
+    SetDigitalPin {
+        pin: u8,
+        high: bool,
+    },
+    ReportDigital {
+        port: u8,
+        enabled: bool,
+    },
+    ReportAnalog {
+        pin: u8,
+        enabled: bool,
+    },
+    AnalogWrite {
+        pin: u8,
+        value: u16,
+    },
+    SamplingInterval {
+        ms: u16,
+    },
+    Reset,
+}
+
+/// Assembles messages from the bytes received.
+pub struct Decoder {
+    command: u8,
+    data: Vec<u8, MAX_SYSEX>,
+    /// The current sysex message is too long, and skipped.
+    overlong: bool,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            command: 0,
+            data: Vec::new(),
+            overlong: false,
+        }
+    }
+
+    /// Takes the next byte, and returns the message it completes, if any.
+    /// Unsupported messages are skipped.
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        if byte == END_SYSEX {
+            let complete = self.command == START_SYSEX && !self.overlong;
+            self.command = 0;
+            return if complete { self.sysex() } else { None };
+        }
+        if byte & 0x80 != 0 {
+            self.command = byte;
+            self.data.clear();
+            self.overlong = false;
+        } else if self.command == START_SYSEX {
+            self.overlong |= self.data.push(byte).is_err();
+            return None;
+        } else if self.command != 0 {
+            let _ = self.data.push(byte);
+        } else {
+            return None;
+        }
+        let message = self.message()?;
+        // Channel messages may repeat without the command byte, but the
+        // others have to send it again.
+        if self.command < 0xF0 {
+            self.data.clear();
+        } else {
+            self.command = 0;
+        }
+        Some(message)
+    }
+
+    /// Decodes a complete message other than sysex.
+    fn message(&self) -> Option<Message> {
+        let channel = self.command & 0x0F;
+        let message = match (self.command & 0xF0, &self.data[..]) {
+            (DIGITAL_MESSAGE, &[low, high]) => Message::DigitalPort {
+                port: channel,
+                levels: low | high << 7,
+            },
+            (ANALOG_MESSAGE, &[low, high]) => Message::AnalogWrite {
+                pin: channel,
+                value: u16::from(low) | u16::from(high) << 7,
+            },
+            (REPORT_ANALOG, &[enabled]) => Message::ReportAnalog {
+                pin: channel,
+                enabled: enabled != 0,
+            },
+            (REPORT_DIGITAL, &[enabled]) => Message::ReportDigital {
+                port: channel,
+                enabled: enabled != 0,
+            },
+            (0xF0, data) => match (self.command, data) {
+                (SET_PIN_MODE, &[pin, mode]) => Message::SetPinMode { pin, mode },
+                (SET_DIGITAL_PIN_VALUE, &[pin, value]) => Message::SetDigitalPin {
+                    pin,
+                    high: value != 0,
+                },
+                (REPORT_VERSION, []) => Message::ReportVersion,
+                (SYSTEM_RESET, []) => Message::Reset,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    fn sysex(&self) -> Option<Message> {
+        let message = match self.data[..] {
+            [REPORT_FIRMWARE] => Message::ReportFirmware,
+            [CAPABILITY_QUERY] => Message::CapabilityQuery,
+            [ANALOG_MAPPING_QUERY] => Message::AnalogMappingQuery,
+            [SAMPLING_INTERVAL, low, high] => Message::SamplingInterval {
+                ms: u16::from(low) | u16::from(high) << 7,
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+fn send<W: Write<u8>>(serial: &mut W, bytes: &[u8]) {
+    for &byte in bytes {
+        let _ = block!(serial.write(byte));
+    }
+}
+
+/// Sends a 14-bit value as two 7-bit bytes, least significant first.
+fn send_14bit<W: Write<u8>>(serial: &mut W, value: u16) {
+    send(serial, &[(value & 0x7F) as u8, (value >> 7 & 0x7F) as u8]);
+}
+
+pub fn write_version<W: Write<u8>>(serial: &mut W) {
+    send(serial, &[REPORT_VERSION, VERSION.0, VERSION.1]);
+}
+
+pub fn write_firmware<W: Write<u8>>(serial: &mut W) {
+    send(
+        serial,
+        &[START_SYSEX, REPORT_FIRMWARE, VERSION.0, VERSION.1],
+    );
+    for byte in b"mechanical-crab" {
+        send_14bit(serial, u16::from(*byte));
+    }
+    send(serial, &[END_SYSEX]);
+}
+
+/// Pin modes and their resolutions, by Firmata pin number.
+fn capabilities(pin: u8) -> &'static [(u8, u8)] {
+    match pin {
+        2..=4 | 6..=12 => &[(MODE_INPUT, 1), (MODE_OUTPUT, 1)],
+        5 => &[(MODE_OUTPUT, 1), (MODE_PWM, 8)],
+        13 => &[(MODE_OUTPUT, 1)],
+        14..=17 => &[(MODE_ANALOG, 10)],
+        _ => &[],
+    }
+}
+
+pub fn write_capabilities<W: Write<u8>>(serial: &mut W) {
+    send(serial, &[START_SYSEX, CAPABILITY_RESPONSE]);
+    for pin in 0..PIN_COUNT {
+        for &(mode, resolution) in capabilities(pin) {
+            send(serial, &[mode, resolution]);
+        }
+        // Ends the list of modes of each pin.
+        send(serial, &[0x7F]);
+    }
+    send(serial, &[END_SYSEX]);
+}
+
+pub fn write_analog_mapping<W: Write<u8>>(serial: &mut W) {
+    send(serial, &[START_SYSEX, ANALOG_MAPPING_RESPONSE]);
+    for pin in 0..PIN_COUNT {
+        let channel = match pin.checked_sub(FIRST_ANALOG_PIN) {
+            Some(channel) if channel < ANALOG_PINS => channel,
+            // Not an analog pin.
+            _ => 0x7F,
+        };
+        send(serial, &[channel]);
+    }
+    send(serial, &[END_SYSEX]);
+}
+
+pub fn write_digital_port<W: Write<u8>>(serial: &mut W, port: u8, levels: u8) {
+    send(serial, &[DIGITAL_MESSAGE | port]);
+    send_14bit(serial, u16::from(levels));
+}
+
+pub fn write_analog<W: Write<u8>>(serial: &mut W, channel: u8, value: u16) {
+    send(serial, &[ANALOG_MESSAGE | channel]);
+    send_14bit(serial, value);
+}
+
+/// Sends text, which hosts usually show as a log message.
+pub fn write_string<W: Write<u8>>(serial: &mut W, text: &str) {
+    send(serial, &[START_SYSEX, STRING_DATA]);
+    for byte in text.bytes() {
+        send_14bit(serial, u16::from(byte));
+    }
+    send(serial, &[END_SYSEX]);
+}
+
+/// What the host has asked to be reported.
+pub struct Reporting {
+    /// Ports to report, one bit each.
+    pub ports: u8,
+    /// Analog channels to report, one bit each.
+    pub analog: u8,
+    pub interval_ms: u16,
+    pub next_sample: u32,
+    /// Levels last reported for each port.
+    pub levels: [u8; PORTS as usize],
+}
+
+impl Reporting {
+    pub const fn new() -> Self {
+        Self {
+            ports: 0,
+            analog: 0,
+            interval_ms: 19,
+            next_sample: 0,
+            levels: [0; PORTS as usize],
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Free-running conversions of the analog inputs, so that reading one
+//! doesn't keep the main loop waiting for the ADC.
+//!
+//! The ADC converts continuously, and its interrupt takes each result and
+//! moves the multiplexer on to the next input in turn. A free-running
+//! conversion starts as soon as the one before ends, with the multiplexer as
+//! it was then, so a change only applies to the conversion after next, and
+//! each result is of the input selected two interrupts earlier.
+//!
+//! At a prescaler of 128 a conversion takes 104 µs, and each input is
+//! sampled that often times the number of inputs. The last few results of
+//! each input are kept in a ring, and a reading is their mean.
+
+use core::cell::RefCell;
+
+use avr_device::interrupt::{self, Mutex};
+
+use crate::board::ANALOG_INPUTS;
+
+/// Results kept of each input.
+const RING_SIZE: usize = 4;
+
+/// The input selection bits of `ADMUX`.
+const MUX_MASK: u8 = 0x1F;
+/// `ADCSRB`: the high bit of the input on the Mega, for a8-a15.
+#[cfg(feature = "mega2560")]
+const MUX5: u8 = 1 << 3;
+
+struct State {
+    running: bool,
+    rings: [[u16; RING_SIZE]; ANALOG_INPUTS],
+    /// How many results of each input there are, up to the ring's size.
+    filled: [u8; ANALOG_INPUTS],
+    /// Where the next result of each input goes.
+    next: [u8; ANALOG_INPUTS],
+    /// The input of the conversion running, and of the one after it.
+    converting: u8,
+    selected: u8,
+}
+
+static STATE: Mutex<RefCell<State>> = Mutex::new(RefCell::new(State {
+    running: false,
+    rings: [[0; RING_SIZE]; ANALOG_INPUTS],
+    filled: [0; ANALOG_INPUTS],
+    next: [0; ANALOG_INPUTS],
+    converting: 0,
+    selected: 0,
+}));
+
+fn adc() -> &'static arduino_hal::pac::adc::RegisterBlock {
+    // SAFETY: The ADC driver is not used while conversions run free, and
+    // finds the multiplexer and the trigger as it sets them.
+    unsafe { &*arduino_hal::pac::ADC::ptr() }
+}
+
+/// Points the multiplexer at an analog input.
+fn select(input: u8) {
+    #[cfg(not(feature = "leonardo"))]
+    let channel = input & 0x07;
+    // The 32U4's inputs aren't in the order of its channels.
+    #[cfg(feature = "leonardo")]
+    let channel = crate::board::adc_channel(input);
+    // SAFETY: Only the input selection bits change, to a single-ended
+    // input.
+    adc()
+        .admux
+        .modify(|r, w| unsafe { w.bits(r.bits() & !MUX_MASK | channel) });
+    #[cfg(feature = "mega2560")]
+    adc().adcsrb.modify(|r, w| unsafe {
+        w.bits(if input >= 8 {
+            r.bits() | MUX5
+        } else {
+            r.bits() & !MUX5
+        })
+    });
+}
+
+/// Starts converting the analog inputs in turn.
+pub fn start() {
+    interrupt::free(|cs| {
+        let mut state = STATE.borrow(cs).borrow_mut();
+        state.filled = [0; ANALOG_INPUTS];
+        state.next = [0; ANALOG_INPUTS];
+        state.converting = 0;
+        state.selected = 0;
+        state.running = true;
+        select(0);
+        // Free-running is the trigger source 0.
+        adc().adcsrb.modify(|_, w| w.adts().val_0x00());
+        adc().adcsra.write(|w| {
+            w.aden()
+                .set_bit()
+                .adate()
+                .set_bit()
+                .adie()
+                .set_bit()
+                .adif()
+                .set_bit()
+                .adsc()
+                .set_bit()
+                .adps()
+                .prescaler_128()
+        });
+    })
+}
+
+/// Stops converting, once the conversion running has ended, so that the
+/// ADC driver can take over.
+pub fn stop() {
+    interrupt::free(|cs| {
+        STATE.borrow(cs).borrow_mut().running = false;
+        adc()
+            .adcsra
+            .modify(|_, w| w.adate().clear_bit().adie().clear_bit());
+    });
+    while adc().adcsra.read().adsc().bit_is_set() {}
+    adc().adcsra.modify(|_, w| w.adif().set_bit());
+}
+
+pub fn is_running() -> bool {
+    interrupt::free(|cs| STATE.borrow(cs).borrow().running)
+}
+
+/// Returns the mean of the latest results of an analog input, waiting for
+/// the first one after a start.
+pub fn read(input: u8) -> Option<u16> {
+    let index = usize::from(input);
+    if index >= ANALOG_INPUTS {
+        return None;
+    }
+    loop {
+        let mean = interrupt::free(|cs| {
+            let state = STATE.borrow(cs).borrow();
+            let filled = state.filled[index];
+            let sum: u32 = state.rings[index][..usize::from(filled)]
+                .iter()
+                .map(|&value| u32::from(value))
+                .sum();
+            (filled > 0).then(|| (sum / u32::from(filled)) as u16)
+        });
+        if mean.is_some() || !is_running() {
+            return mean;
+        }
+    }
+}
+
+#[cfg_attr(feature = "uno", avr_device::interrupt(atmega328p))]
+#[cfg_attr(feature = "mega2560", avr_device::interrupt(atmega2560))]
+#[cfg_attr(feature = "leonardo", avr_device::interrupt(atmega32u4))]
+fn ADC() {
+    interrupt::free(|cs| {
+        let mut state = STATE.borrow(cs).borrow_mut();
+        let value = adc().adc.read().bits();
+        let input = usize::from(state.converting);
+        let next = usize::from(state.next[input]);
+        state.rings[input][next] = value;
+        state.next[input] = ((next + 1) % RING_SIZE) as u8;
+        if usize::from(state.filled[input]) < RING_SIZE {
+            state.filled[input] += 1;
+        }
+        state.converting = state.selected;
+        state.selected = (state.selected + 1) % ANALOG_INPUTS as u8;
+        select(state.selected);
+    })
+}
@@ -0,0 +1,136 @@
+//! Direct register access to digital pins.
+//!
+//! Bit-banged protocols need to switch pins faster than the HAL's typestate
+//! conversions allow. Callers put the pin into a known state through its
+//! [`crate::AnyPin`] first, and leave it in a state matching that afterwards.
+
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::board;
+
+/// Offsets of the `DDRx` and `PORTx` registers from `PINx`.
+const DDR: usize = 1;
+const PORT: usize = 2;
+
+pub use protocol::args::gpio::Port;
+
+#[derive(Clone, Copy)]
+pub struct RawPin {
+    /// Address of the port's `PINx` register, which the other two follow.
+    base: *mut u8,
+    mask: u8,
+}
+
+impl RawPin {
+    /// Looks up a digital pin by its Arduino pin number.
+    pub fn new(pin: u8) -> Option<Self> {
+        let (base, bit) = board::port_bit(pin)?;
+        Some(RawPin {
+            base,
+            mask: 1 << bit,
+        })
+    }
+
+    fn modify(&self, offset: usize, set: bool) {
+        // SAFETY: `base` points at a port register block, and the interrupt
+        // handlers do not touch the ports.
+        unsafe {
+            let register = self.base.add(offset);
+            let value = read_volatile(register);
+            let value = if set {
+                value | self.mask
+            } else {
+                value & !self.mask
+            };
+            write_volatile(register, value);
+        }
+    }
+
+    /// Address of the pin's `PORTx` register, for timing-critical code that
+    /// writes the whole port at once.
+    pub fn port_register(&self) -> *mut u8 {
+        // SAFETY: The offset stays within the port's register block.
+        unsafe { self.base.add(PORT) }
+    }
+
+    pub fn mask(&self) -> u8 {
+        self.mask
+    }
+
+    pub fn set_output(&self, output: bool) {
+        self.modify(DDR, output);
+    }
+
+    pub fn set_level(&self, high: bool) {
+        self.modify(PORT, high);
+    }
+
+    pub fn is_high(&self) -> bool {
+        // SAFETY: See `modify`; reading the input register has no side effects.
+        unsafe { read_volatile(self.base) & self.mask != 0 }
+    }
+
+    /// Pulls an open-drain line low, with the output latch already cleared.
+    pub fn pull_low(&self) {
+        self.set_output(true);
+    }
+
+    /// Lets an open-drain line float up to its pull-up resistor.
+    pub fn release(&self) {
+        self.set_output(false);
+    }
+}
+
+/// Drives digital outputs to the same level, writing each port once with
+/// interrupts disabled, so that the pins on a port change on the same clock
+/// cycle and those on different ports a few cycles apart.
+pub fn set_levels(pins: &[u8], high: bool) {
+    let mut ports: [(*mut u8, u8); board::PORTS] = [(core::ptr::null_mut(), 0); board::PORTS];
+    for pin in pins.iter().filter_map(|&pin| RawPin::new(pin)) {
+        let register = pin.port_register();
+        if let Some(port) = ports
+            .iter_mut()
+            .find(|(r, _)| *r == register || r.is_null())
+        {
+            *port = (register, port.1 | pin.mask());
+        }
+    }
+    avr_device::interrupt::free(|_| {
+        for &(register, mask) in ports.iter().filter(|(r, _)| !r.is_null()) {
+            // SAFETY: See `RawPin::modify`.
+            unsafe {
+                let value = read_volatile(register);
+                write_volatile(register, if high { value | mask } else { value & !mask });
+            }
+        }
+    })
+}
+
+fn port_base(port: Port) -> *mut u8 {
+    match port {
+        Port::B => arduino_hal::pac::PORTB::ptr() as *mut u8,
+        Port::C => arduino_hal::pac::PORTC::ptr() as *mut u8,
+        Port::D => arduino_hal::pac::PORTD::ptr() as *mut u8,
+    }
+}
+
+/// Reads the levels of all pins on a port.
+pub fn read_port(port: Port) -> u8 {
+    // SAFETY: See `RawPin::is_high`.
+    unsafe { read_volatile(port_base(port)) }
+}
+
+/// Makes the pins in `mask` outputs and drives them to the levels in
+/// `value`, all on the same clock cycle.
+#[cfg(not(feature = "leonardo"))]
+pub fn write_port(port: Port, mask: u8, value: u8) {
+    avr_device::interrupt::free(|_| {
+        // SAFETY: See `RawPin::modify`.
+        unsafe {
+            let register = port_base(port).add(PORT);
+            write_volatile(register, read_volatile(register) & !mask | value & mask);
+            let ddr = port_base(port).add(DDR);
+            write_volatile(ddr, read_volatile(ddr) | mask);
+        }
+    })
+}
@@ -0,0 +1,52 @@
+//! Named groups of digital pins, which `set` and `get` take as a unit.
+//!
+//! Like macros, groups are kept in RAM and lost on reset.
+
+use heapless::{String, Vec};
+
+use crate::Pins;
+
+/// How many groups can be defined at the same time.
+pub const MAX_GROUPS: usize = 4;
+
+/// Longest group name.
+pub const MAX_NAME_LENGTH: usize = 8;
+
+pub type Name = String<MAX_NAME_LENGTH>;
+
+pub struct Groups {
+    entries: Vec<(Name, Pins), MAX_GROUPS>,
+}
+
+impl Groups {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Defines a group, replacing any existing one of the same name.
+    ///
+    /// Fails if the name is too long, or if the table is full.
+    pub fn define(&mut self, name: &str, pins: &Pins) -> Result<(), ()> {
+        let name = Name::try_from(name)?;
+        match self.entries.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = pins.clone(),
+            None => self.entries.push((name, pins.clone())).map_err(|_| ())?,
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Pins> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n.as_str() == name)
+            .map(|(_, pins)| pins)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Pins)> {
+        self.entries
+            .iter()
+            .map(|(name, pins)| (name.as_str(), pins))
+    }
+}
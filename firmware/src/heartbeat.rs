@@ -0,0 +1,89 @@
+//! The built-in LED as a status light.
+//!
+//! With the heartbeat on, the LED beats twice every second, and flickers for a
+//! moment whenever a command runs. After a command fails, it blinks the
+//! error's number instead, over and over, until a command succeeds. In the
+//! error mode only the error codes are shown, and the LED is left alone
+//! otherwise.
+
+use protocol::args::heartbeat::Mode;
+
+use crate::millis;
+
+/// A beat, and the pause before the second of a pair.
+const BEAT_MS: u32 = 60;
+const BEAT_GAP_MS: u32 = 200;
+const BEAT_PERIOD_MS: u32 = 1000;
+
+const FLICKER_MS: u32 = 40;
+
+/// One blink of an error code, on and then off.
+const BLINK_ON_MS: u32 = 150;
+const BLINK_MS: u32 = 400;
+/// The pause between repeats of an error code.
+const CODE_PAUSE_MS: u32 = 1200;
+
+pub struct Heartbeat {
+    mode: Mode,
+    /// The number of the error being blinked, and since when.
+    error: Option<(u8, u32)>,
+    /// When the last command ran.
+    activity: Option<u32>,
+    /// Whether the LED has to be turned off once, as an error code ended
+    /// in the error mode.
+    release: bool,
+}
+
+impl Heartbeat {
+    pub const fn new() -> Self {
+        Heartbeat {
+            mode: Mode::Off,
+            error: None,
+            activity: None,
+            release: false,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.error = None;
+        self.activity = None;
+        self.release = mode == Mode::Off;
+    }
+
+    /// Notes a command that ran, and the number of the error it failed with.
+    pub fn command_ran(&mut self, error: Option<u8>) {
+        if self.mode == Mode::Off {
+            return;
+        }
+        let now = millis::millis();
+        self.activity = Some(now);
+        match error {
+            Some(number) => self.error = Some((number, now)),
+            None => {
+                if self.error.take().is_some() {
+                    self.release = true;
+                }
+            }
+        }
+    }
+
+    /// The level the LED should have now, or `None` to leave it alone.
+    pub fn level(&mut self) -> Option<bool> {
+        let now = millis::millis();
+        if let Some((number, since)) = self.error {
+            let code_ms = u32::from(number) * BLINK_MS;
+            let t = now.wrapping_sub(since) % (code_ms + CODE_PAUSE_MS);
+            return Some(t < code_ms && t % BLINK_MS < BLINK_ON_MS);
+        }
+        if self.mode != Mode::On {
+            return core::mem::take(&mut self.release).then_some(false);
+        }
+        let t = now % BEAT_PERIOD_MS;
+        let beat = t < BEAT_MS || (BEAT_GAP_MS..BEAT_GAP_MS + BEAT_MS).contains(&t);
+        let flicker = self
+            .activity
+            .map_or(false, |at| now.wrapping_sub(at) < FLICKER_MS);
+        Some(beat != flicker)
+    }
+}
@@ -0,0 +1,71 @@
+//! Details of each command for `help <command>`, from the command table in
+//! the crate documentation.
+//!
+//! The build script puts the topics into a blob in flash, see
+//! [`crate::progmem`], and a topic is found by scanning the blob.
+
+use ufmt::{uDisplay, uWrite, Formatter};
+
+use crate::progmem;
+
+include!(concat!(env!("OUT_DIR"), "/help.rs"));
+
+/// The text of a topic in flash.
+pub struct Topic {
+    start: usize,
+}
+
+fn byte(index: usize) -> u8 {
+    if index >= TOPICS.len() {
+        return 0;
+    }
+    // SAFETY: The index is within the blob, which is in flash. The blob is
+    // only ever addressed through raw pointers.
+    unsafe { progmem::read_byte(core::ptr::addr_of!(TOPICS).cast::<u8>().add(index)) }
+}
+
+/// Returns the index just past the next zero byte.
+fn skip(mut index: usize) -> usize {
+    while byte(index) != 0 {
+        index += 1;
+    }
+    index + 1
+}
+
+/// Finds the topic of a command keyword.
+pub fn topic(keyword: &str) -> Option<Topic> {
+    let mut index = 0;
+    while byte(index) != 0 {
+        let text = skip(index);
+        let matches = keyword
+            .bytes()
+            .chain([0])
+            .enumerate()
+            .all(|(i, b)| byte(index + i) == b);
+        if matches {
+            return Some(Topic { start: text });
+        }
+        index = skip(text);
+    }
+    None
+}
+
+impl uDisplay for Topic {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        let mut buf = [0; 16];
+        let mut index = self.start;
+        loop {
+            let mut len = 0;
+            while len < buf.len() && byte(index) != 0 {
+                buf[len] = byte(index);
+                len += 1;
+                index += 1;
+            }
+            if len == 0 {
+                return Ok(());
+            }
+            // The build script only lets ASCII through.
+            f.write_str(core::str::from_utf8(&buf[..len]).unwrap_or_default())?;
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! HX711 load cell amplifiers.
+//!
+//! The chip signals a finished conversion by pulling DOUT low, then shifts
+//! out 24 bits on the rising edges of SCK. One or more extra pulses select
+//! the channel and gain of the next conversion, and holding SCK high for
+//! longer than 60 µs powers the chip down, so the clock pulses are kept short
+//! with interrupts disabled.
+
+use arduino_hal::delay_us;
+use avr_device::interrupt;
+
+use crate::gpio::RawPin;
+use crate::millis;
+
+/// Conversions arrive at 10 Hz with the RATE pin low.
+const TIMEOUT_MS: u32 = 500;
+
+pub struct Hx711 {
+    dout: RawPin,
+    sck: RawPin,
+}
+
+impl Hx711 {
+    /// Takes a floating input for DOUT and an output driven low for SCK.
+    pub fn new(dout: RawPin, sck: RawPin) -> Self {
+        Hx711 { dout, sck }
+    }
+
+    /// Waits for a conversion on channel A at a gain of 128 and returns it.
+    pub fn read(&self) -> Option<i32> {
+        let start = millis::millis();
+        while self.dout.is_high() {
+            if millis::millis().wrapping_sub(start) > TIMEOUT_MS {
+                return None;
+            }
+        }
+        let value = interrupt::free(|_| {
+            let mut value = 0u32;
+            for _ in 0..24 {
+                self.sck.set_level(true);
+                delay_us(1);
+                value = value << 1 | u32::from(self.dout.is_high());
+                self.sck.set_level(false);
+                delay_us(1);
+            }
+            // The 25th pulse keeps channel A at a gain of 128.
+            self.sck.set_level(true);
+            delay_us(1);
+            self.sck.set_level(false);
+            value
+        });
+        // Sign-extends the 24-bit two's complement value.
+        Some(((value << 8) as i32) >> 8)
+    }
+
+    /// Averages several conversions, which at 10 Hz takes a tenth of a second
+    /// each.
+    pub fn average(&self, samples: u8) -> Option<i32> {
+        let mut sum = 0i32;
+        for _ in 0..samples {
+            sum += self.read()?;
+        }
+        Some(sum / i32::from(samples.max(1)))
+    }
+}
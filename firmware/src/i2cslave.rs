@@ -0,0 +1,177 @@
+//! I2C slave mode, in which another controller reads and drives the pins
+//! through a map of byte registers.
+//!
+//! | Register | Contents |
+//! |----------|----------|
+//! | 0x00-0x01 | Levels of d0-d13, as a little-endian bitmask (read-only) |
+//! | 0x02-0x03 | Pin directions, 1 for an output |
+//! | 0x04-0x05 | Levels of the outputs, of which writes set the outputs only |
+//! | 0x06-0x0D | Readings of a0-a3, 16 bits little-endian each (read-only) |
+//! | 0x0E | Duty cycle of the PWM output on d5 |
+//!
+//! The first byte the controller writes selects a register, and any further
+//! bytes go into it and those after it. Reads start at the selected register
+//! and carry on through the following ones, all taken from the same moment.
+//!
+//! The TWI interrupt only serves a copy of the registers, which the main
+//! loop brings up to date and applies writes from. I2C transfers of the crab
+//! itself take over the bus and end slave mode.
+
+use core::cell::RefCell;
+
+use avr_device::interrupt::{self, Mutex};
+
+pub const INPUTS: usize = 0x00;
+pub const DIRECTIONS: usize = 0x02;
+pub const OUTPUTS: usize = 0x04;
+pub const ANALOG: usize = 0x06;
+pub const PWM: usize = 0x0E;
+
+/// Number of registers.
+pub const REGISTERS: usize = 15;
+
+/// Registers the controller can write, as a bitmask.
+const WRITABLE: u16 = 0b11 << DIRECTIONS | 0b11 << OUTPUTS | 1 << PWM;
+
+/// Addresses outside of the ones reserved by the I2C specification.
+pub const ADDRESSES: core::ops::RangeInclusive<u8> = 0x08..=0x77;
+
+struct State {
+    registers: [u8; REGISTERS],
+    /// The registers as of the start of the current read.
+    latched: [u8; REGISTERS],
+    pointer: usize,
+    /// Whether the next byte received selects a register.
+    selecting: bool,
+    /// Registers written since the main loop last took them, as a bitmask.
+    written: u16,
+}
+
+static STATE: Mutex<RefCell<State>> = Mutex::new(RefCell::new(State {
+    registers: [0; REGISTERS],
+    latched: [0; REGISTERS],
+    pointer: 0,
+    selecting: false,
+    written: 0,
+}));
+
+// Status codes of the slave receiver and transmitter.
+const OWN_ADDRESS_WRITE: u8 = 0x60;
+const LOST_TO_OWN_ADDRESS_WRITE: u8 = 0x68;
+const DATA_RECEIVED: u8 = 0x80;
+const OWN_ADDRESS_READ: u8 = 0xA8;
+const LOST_TO_OWN_ADDRESS_READ: u8 = 0xB0;
+const DATA_SENT: u8 = 0xB8;
+const BUS_ERROR: u8 = 0x00;
+
+fn twi() -> &'static arduino_hal::pac::twi::RegisterBlock {
+    // SAFETY: The master driver only touches the TWI between its own
+    // transfers, which end slave mode first.
+    unsafe { &*arduino_hal::pac::TWI::ptr() }
+}
+
+/// Starts answering at a 7-bit address.
+pub fn enable(address: u8) {
+    interrupt::free(|cs| {
+        let mut state = STATE.borrow(cs).borrow_mut();
+        state.selecting = false;
+        state.written = 0;
+    });
+    let twi = twi();
+    twi.twar.write(|w| w.twa().bits(address));
+    twi.twcr.write(|w| {
+        w.twint()
+            .set_bit()
+            .twea()
+            .set_bit()
+            .twen()
+            .set_bit()
+            .twie()
+            .set_bit()
+    });
+}
+
+pub fn disable() {
+    twi().twcr.write(|w| w.twen().set_bit());
+}
+
+/// Whether slave mode is on, which the master driver turns off by leaving
+/// out the interrupt.
+pub fn is_enabled() -> bool {
+    twi().twcr.read().twie().bit_is_set()
+}
+
+/// Returns the registers the controller has written since the last call, as
+/// a bitmask, along with all values.
+pub fn take_written() -> (u16, [u8; REGISTERS]) {
+    interrupt::free(|cs| {
+        let mut state = STATE.borrow(cs).borrow_mut();
+        (core::mem::take(&mut state.written), state.registers)
+    })
+}
+
+/// Brings the registers up to date, except for any that the controller has
+/// written since the last [`take_written`].
+pub fn publish(registers: &[u8; REGISTERS]) {
+    interrupt::free(|cs| {
+        let mut state = STATE.borrow(cs).borrow_mut();
+        for (i, &value) in registers.iter().enumerate() {
+            if state.written & (1 << i) == 0 {
+                state.registers[i] = value;
+            }
+        }
+    })
+}
+
+#[cfg_attr(feature = "uno", avr_device::interrupt(atmega328p))]
+#[cfg_attr(feature = "mega2560", avr_device::interrupt(atmega2560))]
+#[cfg_attr(feature = "leonardo", avr_device::interrupt(atmega32u4))]
+fn TWI() {
+    interrupt::free(|cs| {
+        let twi = twi();
+        let mut state = STATE.borrow(cs).borrow_mut();
+        let mut stop = false;
+        match twi.twsr.read().bits() & 0xF8 {
+            OWN_ADDRESS_WRITE | LOST_TO_OWN_ADDRESS_WRITE => state.selecting = true,
+            DATA_RECEIVED => {
+                let byte = twi.twdr.read().bits();
+                if state.selecting {
+                    state.selecting = false;
+                    state.pointer = usize::from(byte);
+                } else {
+                    let pointer = state.pointer;
+                    if pointer < REGISTERS && WRITABLE & (1 << pointer) != 0 {
+                        state.registers[pointer] = byte;
+                        state.written |= 1 << pointer;
+                    }
+                    state.pointer = pointer.saturating_add(1);
+                }
+            }
+            status @ (OWN_ADDRESS_READ | LOST_TO_OWN_ADDRESS_READ | DATA_SENT) => {
+                if status != DATA_SENT {
+                    state.latched = state.registers;
+                }
+                let pointer = state.pointer;
+                let byte = state.latched.get(pointer).copied().unwrap_or(0xFF);
+                twi.twdr.write(|w| w.bits(byte));
+                state.pointer = pointer.saturating_add(1);
+            }
+            BUS_ERROR => stop = true,
+            // Stop conditions, and the end of reads and of writes that were
+            // not acknowledged.
+            _ => {}
+        }
+        twi.twcr.write(|w| {
+            w.twint()
+                .set_bit()
+                .twea()
+                .set_bit()
+                .twen()
+                .set_bit()
+                .twie()
+                .set_bit()
+                .twsto()
+                .bit(stop)
+        });
+    })
+}
@@ -0,0 +1,115 @@
+//! Interval measurement with Timer1's input capture unit on d8 (ICP1).
+//!
+//! The timer runs at the full 16 MHz, and the hardware latches the count on
+//! each edge, so intervals come out to 62.5 ns no matter how late the loop
+//! gets around to reading them. Counting overflows extends the 16-bit timer
+//! to intervals of over four minutes.
+//!
+//! Timer1 drives the servos too, so a measurement fails while they run.
+
+use crate::millis;
+
+pub use protocol::args::icp::Edges;
+
+/// The input capture pin.
+pub const PIN: u8 = 8;
+
+/// Timer counts per microsecond.
+pub const COUNTS_PER_US: u32 = 16;
+
+pub enum Error {
+    /// Timer1 is in use.
+    Busy,
+    /// Fewer intervals than asked for arrived in time.
+    Timeout,
+}
+
+/// Intervals in timer counts.
+pub struct Stats {
+    pub count: u8,
+    pub min: u32,
+    pub max: u32,
+    /// Sums of the first, third, fifth... intervals and of the others. With
+    /// both edges, those are the times at each level.
+    sums: [u64; 2],
+}
+
+impl Stats {
+    pub fn average(&self) -> u32 {
+        ((self.sums[0] + self.sums[1]) / u64::from(self.count.max(1))) as u32
+    }
+
+    /// The average of every other interval, from the first or the second.
+    pub fn alternate_average(&self, second: bool) -> u32 {
+        let count = (self.count + u8::from(!second)) / 2;
+        (self.sums[usize::from(second)] / u64::from(count.max(1))) as u32
+    }
+}
+
+fn tc1() -> &'static arduino_hal::pac::tc1::RegisterBlock {
+    // SAFETY: Timer1 is only borrowed while it is stopped, and stopped again
+    // before returning.
+    unsafe { &*arduino_hal::pac::TC1::ptr() }
+}
+
+/// Measures `count` intervals between edges on [`PIN`], which has to be an
+/// input.
+pub fn measure(edges: Edges, count: u8, timeout_ms: u32) -> Result<Stats, Error> {
+    let tc1 = tc1();
+    if !tc1.tccr1b.read().cs1().is_no_clock() {
+        return Err(Error::Busy);
+    }
+    let mut rising = !matches!(edges, Edges::Falling);
+    tc1.tccr1a.reset();
+    tc1.tcnt1.write(|w| w.bits(0));
+    tc1.tifr1.write(|w| w.icf1().set_bit().tov1().set_bit());
+    tc1.tccr1b.write(|w| w.ices1().bit(rising).cs1().direct());
+
+    let mut stats = Stats {
+        count: 0,
+        min: u32::MAX,
+        max: 0,
+        sums: [0; 2],
+    };
+    let mut overflows = 0u32;
+    let mut last = None;
+    let start = millis::millis();
+    let result = loop {
+        if stats.count == count {
+            break Ok(stats);
+        }
+        if millis::millis().wrapping_sub(start) > timeout_ms {
+            break Err(Error::Timeout);
+        }
+        let flags = tc1.tifr1.read();
+        if flags.icf1().bit_is_set() {
+            let capture = tc1.icr1.read().bits();
+            // An overflow that is still pending with a small capture value
+            // happened before the capture.
+            if tc1.tifr1.read().tov1().bit_is_set() && capture < 0x8000 {
+                overflows += 1;
+                tc1.tifr1.write(|w| w.tov1().set_bit());
+            }
+            let time = overflows << 16 | u32::from(capture);
+            if let Edges::Both = edges {
+                // Changing the edge can set the flag, so it is cleared after.
+                rising = !rising;
+                tc1.tccr1b.modify(|_, w| w.ices1().bit(rising));
+            }
+            tc1.tifr1.write(|w| w.icf1().set_bit());
+            if let Some(last) = last {
+                let interval = time.wrapping_sub(last);
+                stats.sums[usize::from(stats.count % 2)] += u64::from(interval);
+                stats.count += 1;
+                stats.min = stats.min.min(interval);
+                stats.max = stats.max.max(interval);
+            }
+            last = Some(time);
+        } else if flags.tov1().bit_is_set() {
+            overflows += 1;
+            tc1.tifr1.write(|w| w.tov1().set_bit());
+        }
+    };
+    tc1.tccr1b.reset();
+    result
+}
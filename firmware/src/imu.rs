@@ -0,0 +1,72 @@
+//! MPU6050 accelerometer and gyroscope.
+//!
+//! The sensor is left at its power-on ranges of ±2 g and ±250 °/s.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+pub use protocol::args::imu::DEFAULT_ADDRESS;
+
+const ACCEL_XOUT_H: u8 = 0x3B;
+const PWR_MGMT_1: u8 = 0x6B;
+const WHO_AM_I: u8 = 0x75;
+
+/// Selects the X gyro's clock, which is more stable than the internal
+/// oscillator, and clears the sleep bit.
+const CLOCK_PLL_X_GYRO: u8 = 0x01;
+
+/// Raw readings per g at ±2 g.
+const ACCEL_PER_G: i32 = 16384;
+/// Raw readings per °/s at ±250 °/s.
+const GYRO_PER_DPS: i32 = 131;
+
+pub enum Error {
+    I2c(arduino_hal::i2c::Error),
+    /// Something answered that does not identify as an MPU6050.
+    UnknownDevice(u8),
+}
+
+impl From<arduino_hal::i2c::Error> for Error {
+    fn from(error: arduino_hal::i2c::Error) -> Self {
+        Self::I2c(error)
+    }
+}
+
+pub struct Reading {
+    /// Acceleration in milli-g.
+    pub accel: [i16; 3],
+    /// Rotation in tenths of a degree per second.
+    pub gyro: [i16; 3],
+}
+
+/// Checks the sensor's identity and wakes it from sleep.
+pub fn init(i2c: &mut arduino_hal::I2c, address: u8) -> Result<(), Error> {
+    let mut id = [0];
+    i2c.write_read(address, &[WHO_AM_I], &mut id)?;
+    // The register holds the default address regardless of AD0.
+    if id[0] & 0x7E != DEFAULT_ADDRESS {
+        return Err(Error::UnknownDevice(id[0]));
+    }
+    i2c.write(address, &[PWR_MGMT_1, CLOCK_PLL_X_GYRO])?;
+    Ok(())
+}
+
+pub fn read(i2c: &mut arduino_hal::I2c, address: u8) -> Result<Reading, Error> {
+    // Accelerometer, temperature, and gyroscope, each big-endian.
+    let mut registers = [0; 14];
+    i2c.write_read(address, &[ACCEL_XOUT_H], &mut registers)?;
+    let axis = |offset: usize| {
+        i32::from(i16::from_be_bytes([
+            registers[offset],
+            registers[offset + 1],
+        ]))
+    };
+    let mut reading = Reading {
+        accel: [0; 3],
+        gyro: [0; 3],
+    };
+    for i in 0..3 {
+        reading.accel[i] = (axis(2 * i) * 1000 / ACCEL_PER_G) as i16;
+        reading.gyro[i] = (axis(8 + 2 * i) * 10 / GYRO_PER_DPS) as i16;
+    }
+    Ok(reading)
+}
@@ -0,0 +1,134 @@
+//! NEC infrared remote decoding.
+//!
+//! A TSOP-style receiver demodulates the 38 kHz carrier and pulls its output
+//! low for every burst. The pin change interrupt times each low mark and high
+//! space, and a frame is complete after the 32nd bit.
+
+use core::cell::Cell;
+
+use avr_device::interrupt::{CriticalSection, Mutex};
+
+use crate::gpio::RawPin;
+use crate::{millis, pcint};
+
+#[derive(Clone, Copy)]
+pub enum Frame {
+    /// A key press. Extended NEC uses the whole 16 bits for the address,
+    /// the original protocol only the low byte, with the high byte its
+    /// complement.
+    Key { address: u16, command: u8 },
+    /// The key from the last frame is still held.
+    Repeat,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Idle,
+    /// The 9 ms leading mark has been seen.
+    Leader,
+    /// Receiving data bits, least significant first.
+    Data {
+        bits: u8,
+        code: u32,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct Receiver {
+    pin: u8,
+    high: bool,
+    last_edge: u32,
+    state: State,
+}
+
+static RECEIVER: Mutex<Cell<Option<Receiver>>> = Mutex::new(Cell::new(None));
+static FRAME: Mutex<Cell<Option<Frame>>> = Mutex::new(Cell::new(None));
+
+/// Whether a duration in microseconds is within 25% of the nominal one.
+fn about(duration: u32, nominal: u32) -> bool {
+    duration > nominal - nominal / 4 && duration < nominal + nominal / 4
+}
+
+/// Starts decoding frames from a receiver on a digital pin, which has to be
+/// an input.
+pub fn listen(pin: u8) {
+    avr_device::interrupt::free(|cs| {
+        if let Some(old) = RECEIVER.borrow(cs).get() {
+            pcint::disable(old.pin);
+        }
+        RECEIVER.borrow(cs).set(Some(Receiver {
+            pin,
+            high: RawPin::new(pin).map_or(true, |p| p.is_high()),
+            last_edge: millis::micros(),
+            state: State::Idle,
+        }));
+        FRAME.borrow(cs).set(None);
+        pcint::enable(pin);
+    })
+}
+
+pub fn stop() {
+    avr_device::interrupt::free(|cs| {
+        if let Some(old) = RECEIVER.borrow(cs).take() {
+            pcint::disable(old.pin);
+        }
+    })
+}
+
+/// Returns the last frame received since the previous call, if any.
+pub fn take_frame() -> Option<Frame> {
+    avr_device::interrupt::free(|cs| FRAME.borrow(cs).take())
+}
+
+/// Times the edge on the receiver pin, if it changed. Called from the pin
+/// change interrupt handlers.
+pub fn update(cs: CriticalSection) {
+    let cell = RECEIVER.borrow(cs);
+    let Some(mut receiver) = cell.get() else {
+        return;
+    };
+    let high = RawPin::new(receiver.pin).map_or(true, |p| p.is_high());
+    if high == receiver.high {
+        return;
+    }
+    let now = millis::micros();
+    let duration = now.wrapping_sub(receiver.last_edge);
+    receiver.high = high;
+    receiver.last_edge = now;
+
+    // A rising edge ends a mark, a falling one ends a space.
+    let mark = high;
+    receiver.state = match receiver.state {
+        _ if mark && about(duration, 9000) => State::Leader,
+        State::Leader if !mark && about(duration, 4500) => State::Data { bits: 0, code: 0 },
+        State::Leader if !mark && about(duration, 2250) => {
+            FRAME.borrow(cs).set(Some(Frame::Repeat));
+            State::Idle
+        }
+        State::Data { bits, code } if mark && about(duration, 560) => State::Data { bits, code },
+        State::Data { bits, code } if !mark && (about(duration, 560) || about(duration, 1690)) => {
+            let code = code | u32::from(about(duration, 1690)) << bits;
+            if bits == 31 {
+                FRAME.borrow(cs).set(Some(decode(code)));
+                State::Idle
+            } else {
+                State::Data {
+                    bits: bits + 1,
+                    code,
+                }
+            }
+        }
+        _ => State::Idle,
+    };
+    cell.set(Some(receiver));
+}
+
+fn decode(code: u32) -> Frame {
+    let [address_low, address_high, command, _] = code.to_le_bytes();
+    let address = if address_high == !address_low {
+        u16::from(address_low)
+    } else {
+        u16::from_le_bytes([address_low, address_high])
+    };
+    Frame::Key { address, command }
+}
@@ -0,0 +1,100 @@
+//! Matrix keypads of up to 4x4 keys.
+//!
+//! The columns are inputs with the pull-up enabled, and the rows are left
+//! floating except for the one being scanned, which is driven low, so that a
+//! pressed key pulls its column low. Floating rather than driving the other
+//! rows high keeps two keys pressed in the same column from shorting a row
+//! driven high to one driven low.
+//!
+//! Only one key is reported at a time, once it has been the only one
+//! pressed for the debounce time.
+
+use arduino_hal::delay_us;
+use heapless::Vec;
+
+use crate::gpio::RawPin;
+use crate::millis;
+
+/// The most rows, and the most columns.
+pub const MAX_LINES: usize = 4;
+
+/// The usual lettering of a 4x4 keypad, of which smaller ones use the top
+/// left part.
+const KEYS: [[char; MAX_LINES]; MAX_LINES] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+
+const SCAN_INTERVAL_MS: u32 = 5;
+const DEBOUNCE_MS: u32 = 20;
+
+pub struct Keypad {
+    rows: Vec<RawPin, MAX_LINES>,
+    columns: Vec<RawPin, MAX_LINES>,
+    /// The key last reported as pressed.
+    pressed: Option<char>,
+    /// The key last scanned, and since when.
+    scanned: Option<char>,
+    since: u32,
+    last_scan: u32,
+}
+
+impl Keypad {
+    /// Scans a keypad, with the rows floating inputs and the columns inputs
+    /// with the pull-up enabled.
+    pub fn new(rows: Vec<RawPin, MAX_LINES>, columns: Vec<RawPin, MAX_LINES>) -> Self {
+        let now = millis::millis();
+        Keypad {
+            rows,
+            columns,
+            pressed: None,
+            scanned: None,
+            since: now,
+            last_scan: now,
+        }
+    }
+
+    /// Scans the keypad if it is due, and returns a key that has just been
+    /// pressed.
+    pub fn poll(&mut self) -> Option<char> {
+        let now = millis::millis();
+        if now.wrapping_sub(self.last_scan) < SCAN_INTERVAL_MS {
+            return None;
+        }
+        self.last_scan = now;
+        let key = self.scan();
+        if key != self.scanned {
+            self.scanned = key;
+            self.since = now;
+            return None;
+        }
+        if key == self.pressed || now.wrapping_sub(self.since) < DEBOUNCE_MS {
+            return None;
+        }
+        self.pressed = key;
+        key
+    }
+
+    /// Returns the key pressed, or `None` if no key or several are.
+    fn scan(&self) -> Option<char> {
+        let mut found = None;
+        for (r, row) in self.rows.iter().enumerate() {
+            row.set_output(true);
+            // Lets the column settle through the pull-up.
+            delay_us(5);
+            for (c, column) in self.columns.iter().enumerate() {
+                if !column.is_high() {
+                    if found.is_some() {
+                        row.set_output(false);
+                        return None;
+                    }
+                    found = Some(KEYS[r][c]);
+                }
+            }
+            row.set_output(false);
+        }
+        found
+    }
+}
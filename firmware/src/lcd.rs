@@ -0,0 +1,147 @@
+//! HD44780 character LCDs in 4-bit mode.
+//!
+//! The display is either wired to six digital pins directly, or sits behind a
+//! PCF8574 I2C backpack, which maps its eight outputs to the control lines and
+//! the upper four data lines.
+
+use arduino_hal::{delay_ms, delay_us};
+use embedded_hal::blocking::i2c::Write;
+
+use crate::gpio::RawPin;
+
+/// DDRAM addresses of the start of each row. Rows 2 and 3 only exist on 20x4
+/// displays, where they continue on from rows 0 and 1.
+const ROW_OFFSETS: [u8; 4] = [0x00, 0x40, 0x14, 0x54];
+
+const CLEAR_DISPLAY: u8 = 0x01;
+const ENTRY_MODE_INCREMENT: u8 = 0x06;
+const DISPLAY_ON: u8 = 0x0C;
+/// 4-bit interface, two lines, 5x8 dots.
+const FUNCTION_SET: u8 = 0x28;
+const SET_DDRAM_ADDRESS: u8 = 0x80;
+
+/// Backpack outputs. The read/write line on bit 1 is kept low.
+const BACKPACK_RS: u8 = 1 << 0;
+const BACKPACK_EN: u8 = 1 << 2;
+const BACKPACK_BACKLIGHT: u8 = 1 << 3;
+
+pub enum Bus {
+    Parallel {
+        rs: RawPin,
+        en: RawPin,
+        /// D4 to D7.
+        data: [RawPin; 4],
+    },
+    I2c {
+        address: u8,
+    },
+}
+
+pub struct Lcd {
+    bus: Bus,
+}
+
+impl Lcd {
+    /// Resets the display into 4-bit mode and clears it. Parallel pins have to
+    /// be outputs already.
+    pub fn new(bus: Bus, i2c: &mut arduino_hal::I2c) -> Result<Self, arduino_hal::i2c::Error> {
+        let mut lcd = Lcd { bus };
+        // The controller may be in either 8-bit or 4-bit mode after power-up,
+        // so it is put into 8-bit mode first in a way that works from both.
+        delay_ms(50);
+        lcd.write_nibble(i2c, 0x3, false)?;
+        delay_ms(5);
+        lcd.write_nibble(i2c, 0x3, false)?;
+        delay_ms(5);
+        lcd.write_nibble(i2c, 0x3, false)?;
+        delay_us(150);
+        lcd.write_nibble(i2c, 0x2, false)?;
+
+        lcd.command(i2c, FUNCTION_SET)?;
+        lcd.command(i2c, DISPLAY_ON)?;
+        lcd.command(i2c, ENTRY_MODE_INCREMENT)?;
+        lcd.clear(i2c)?;
+        Ok(lcd)
+    }
+
+    pub fn clear(&mut self, i2c: &mut arduino_hal::I2c) -> Result<(), arduino_hal::i2c::Error> {
+        self.command(i2c, CLEAR_DISPLAY)?;
+        // Clearing takes much longer than the other instructions.
+        delay_ms(2);
+        Ok(())
+    }
+
+    /// Moves the cursor, with rows past the end of the display wrapping back
+    /// to the top.
+    pub fn set_position(
+        &mut self,
+        i2c: &mut arduino_hal::I2c,
+        row: u8,
+        column: u8,
+    ) -> Result<(), arduino_hal::i2c::Error> {
+        let offset = ROW_OFFSETS[usize::from(row) % ROW_OFFSETS.len()];
+        self.command(i2c, SET_DDRAM_ADDRESS | offset.wrapping_add(column) & 0x7F)
+    }
+
+    /// Prints text at the cursor. Characters outside of ASCII show up as
+    /// whatever the display's character ROM has in their place.
+    pub fn print(
+        &mut self,
+        i2c: &mut arduino_hal::I2c,
+        text: &str,
+    ) -> Result<(), arduino_hal::i2c::Error> {
+        for byte in text.bytes() {
+            self.write_byte(i2c, byte, true)?;
+        }
+        Ok(())
+    }
+
+    fn command(
+        &mut self,
+        i2c: &mut arduino_hal::I2c,
+        command: u8,
+    ) -> Result<(), arduino_hal::i2c::Error> {
+        self.write_byte(i2c, command, false)
+    }
+
+    fn write_byte(
+        &mut self,
+        i2c: &mut arduino_hal::I2c,
+        byte: u8,
+        data: bool,
+    ) -> Result<(), arduino_hal::i2c::Error> {
+        self.write_nibble(i2c, byte >> 4, data)?;
+        self.write_nibble(i2c, byte & 0x0F, data)?;
+        // Long enough for every instruction except clearing.
+        delay_us(50);
+        Ok(())
+    }
+
+    fn write_nibble(
+        &mut self,
+        i2c: &mut arduino_hal::I2c,
+        nibble: u8,
+        data: bool,
+    ) -> Result<(), arduino_hal::i2c::Error> {
+        match self.bus {
+            Bus::Parallel { rs, en, data: pins } => {
+                rs.set_level(data);
+                for (i, pin) in pins.iter().enumerate() {
+                    pin.set_level(nibble & (1 << i) != 0);
+                }
+                en.set_level(true);
+                delay_us(1);
+                en.set_level(false);
+                Ok(())
+            }
+            Bus::I2c { address } => {
+                let mut outputs = nibble << 4 | BACKPACK_BACKLIGHT;
+                if data {
+                    outputs |= BACKPACK_RS;
+                }
+                // The display latches the data on the falling edge of enable.
+                i2c.write(address, &[outputs | BACKPACK_EN, outputs])
+            }
+        }
+    }
+}
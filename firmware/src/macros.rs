@@ -0,0 +1,54 @@
+//! User-defined command macros.
+//!
+//! A macro is a named line of commands kept in RAM, so it is lost on reset.
+
+use heapless::{String, Vec};
+
+use crate::LINE_LENGTH;
+
+/// How many macros can be defined at the same time.
+pub const MAX_MACROS: usize = 4;
+
+/// How many macros can invoke each other before giving up.
+pub const MAX_DEPTH: u8 = 4;
+
+pub type Name = String<8>;
+pub type Body = String<LINE_LENGTH>;
+
+pub struct Macros {
+    entries: Vec<(Name, Body), MAX_MACROS>,
+}
+
+impl Macros {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Defines a macro, replacing any existing one of the same name.
+    ///
+    /// Fails if the name or body are too long, or if the table is full.
+    pub fn define(&mut self, name: &str, body: &str) -> Result<(), ()> {
+        let name = Name::try_from(name)?;
+        let body = Body::try_from(body)?;
+        match self.entries.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = body,
+            None => self.entries.push((name, body)).map_err(|_| ())?,
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Body> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n.as_str() == name)
+            .map(|(_, body)| body)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, body)| (name.as_str(), body.as_str()))
+    }
+}
@@ -0,0 +1,4769 @@
+//! Mechanical Crab
+//!
+//! This is a simple program that allows you to control the Arduino board using a serial terminal.
+//!
+//! The following commands are supported:
+//! | Command | Description | Example |
+//! | ------- | ----------- | ------- |
+//! | help [command] | Print the list of commands, or the details of one | `help pwm` |
+//! | led on  | Turn on the built-in LED, same as `set led high` |
+//! | led off | Turn off the built-in LED |
+//! | heartbeat on | Beat the built-in LED twice a second, flicker it for each command, and blink the number of the error after one fails until a command succeeds | `heartbeat on` |
+//! | heartbeat error | Only blink error numbers on the built-in LED |
+//! | heartbeat off | Leave the built-in LED alone, and turn it off |
+//! | get <pin> | Read the value of a digital pin | `get 7` |
+//! | set <pin> high | Set a digital pin to high | `set 7 high` |
+//! | set <pin> low  | Set a digital pin to low |
+//! | set <pin> high\|low for <ms> | Set a pin, or a list of them, and set it back after a while | `set 7 high for 500` |
+//! | set <pin>,<pin>… high\|low | Set several pins at once, those on the same port on the same clock cycle | `set 2,3,7 low` |
+//! | group define <name> <pins…> | Name a group of pins, which `set` and `get` take in place of a pin | `group define bar 2 3 4` |
+//! | group list  | List the defined groups |
+//! | capture <pin>[,<pin>…] <hz> <samples> [hex\|rle\|sump] | Sample up to eight pins like a logic analyzer, then print the runs of equal samples, a hex dump, or raw SUMP data | `capture 2,3 1000 200` |
+//! | port read b\|c\|d | Read all pins of PORTB (d8-d13), PORTC (a0-a5), or PORTD (d0-d7) at once | `port read d` |
+//! | port write b\|c\|d <mask> <value> | Make the pins in the mask outputs and drive them at once | `port write b 0x03 0x01` |
+//! | pwm <0-255> | Set the duty cycle of the PWM output | `pwm 128` |
+//! | pwm16 <9\|10> <0-65535> | Set a 16-bit duty cycle on a Timer1 output, unless servos use it | `pwm16 9 32768` |
+//! | softpwm <pin> <0-255> | Run 1 kHz software PWM on up to four digital pins, 0 turns it off | `softpwm 7 64` |
+//! | fade <pin> <from> <to> <ms> | Ramp a duty cycle of 0-255 in the background, on the PWM output or with software PWM on another pin | `fade 7 0 255 2000` |
+//! | wave <9\|10> sine\|triangle\|square <hz> | Generate a waveform of 1-250 Hz as PWM, for an RC filter to smooth | `wave 9 sine 10` |
+//! | wave off    | Stop the waveform |
+//! | tone <9\|10> <hz> | Play a square wave of 31-20000 Hz on a Timer1 output, for a buzzer | `tone 9 440` |
+//! | tone off    | Stop the tone |
+//! | melody <9\|10> <rtttl> | Play an RTTTL melody in the background, or one of the jingles `pass`, `fail`, and `ready` | `melody 9 beep:d=8,o=5,b=120:c,p,c6` |
+//! | melody off  | Stop the melody |
+//! | dac <0-4095> [mv] | Set the output of an MCP4725 DAC at I2C address 0x60, or set it in millivolts | `dac 1650 mv` |
+//! | adc <0-5>   | Read the value of an analog pin | `adc 0` |
+//! | adc freerun on\|off | Sample the analog pins continuously from the ADC interrupt, so that readings are quick and evenly spaced, each the mean of the last 4 samples |
+//! | temp        | Read the temperature sensor value |
+//! | temp c      | Read the temperature in degrees Celsius |
+//! | tempcal <offset> | Store a temperature calibration offset in EEPROM | `tempcal -3` |
+//! | vcc         | Measure the supply voltage in millivolts |
+//! | powermon on <mV> [safe] | Warn when the supply sags below a threshold, and with `safe` drive the outputs low and turn on safe mode | `powermon on 4500 safe` |
+//! | powermon [off] | Print the supply monitor's threshold, or stop it |
+//! | random [max] | Print a random number up to `max`, from the noise of the analog pins | `random 6` |
+//! | uptime      | Print the milliseconds since reset (also `millis`) |
+//! | timestamps on\|off | Prefix every response line with the uptime |
+//! | mode human\|machine\|binary | Switch to single line responses or binary frames for programs |
+//! | mode firmata | Speak the Firmata protocol until the next reset |
+//! | mode modbus <1-247> | Act as a Modbus RTU slave with the given address until the next reset | `mode modbus 1` |
+//! | format text\|json | Respond with a JSON object per command, or switch back to text |
+//! | prompt on\|off | Print the `> ` prompt before each line of input |
+//! | echo on\|off | Echo input back as it is typed |
+//! | serial [<baud> [format]] | Print the console's serial settings, or store new ones such as `7E1` and switch to them | `serial 9600 7E1` |
+//! | serial default | Go back to 8N1 at the default baud rate, 57600 unless built with `baud-9600` or `baud-115200` |
+//! | base dec\|hex\|bin | Print readings such as `adc` in decimal, hex, or binary | `base hex` |
+//! | delay <ms>  | Pause for up to 65535 milliseconds | `delay 100` |
+//! | batch       | Collect pasted lines without echo or prompts, and run them after a line `end` (also `paste`) |
+//! | macro define <name> <commands> | Define a named command sequence | `macro define b led on; led off` |
+//! | macro run <name> | Run a previously defined macro | `macro run b` |
+//! | macro list  | List all defined macros |
+//! | startup set <commands> | Store commands in EEPROM to run at boot | `startup set led on` |
+//! | startup show | Print the stored startup commands |
+//! | startup clear | Remove the stored startup commands |
+//! | eeprom read <addr> [len] | Dump bytes from the EEPROM in hex | `eeprom read 0x10 16` |
+//! | eeprom write <addr> <byte…> | Write bytes to the EEPROM | `eeprom write 0x3F0 1 2 3` |
+//! | save        | Store pin directions, levels, and PWM duty in EEPROM |
+//! | load        | Restore the stored pin configuration, also done at boot |
+//! | reset       | Reboot the board via the watchdog |
+//! | bootload    | Start the Optiboot bootloader for avrdude to reflash the board (also `dfu`) |
+//! | lock [pin] | Refuse commands that change a pin, or list the locked pins | `lock 9` |
+//! | unlock <pin> [code] | Allow changes to a pin again | `unlock 9 1234` |
+//! | lock code <code> | Store a code that `unlock` asks for from then on | `lock code 1234` |
+//! | unlock code <code> | Remove the stored code |
+//! | name <pin> [name] | Name a digital pin, or remove its name | `name 9 pump` |
+//! | name        | List the named pins |
+//! | safemode [on\|off] | Only allow commands that read, or show whether safe mode is on |
+//! | safemode jumper <pin>\|off | Turn safe mode on at boot while a pin is jumpered to ground | `safemode jumper 12` |
+//! | wdt on <ms> | Enable the watchdog, resetting unless kicked within 16-8000ms | `wdt on 500` |
+//! | wdt off     | Disable the watchdog |
+//! | wdt kick    | Restart the watchdog timeout |
+//! | sleep idle\|powerdown [pin] | Sleep until serial input or a change on the pin | `sleep powerdown 2` |
+//! | selftest [<out>,<in>…] | Check pairs of jumpered pins, the ADC, EEPROM, and timers, and print pass or fail for each | `selftest 2,3,4,6` |
+//! | stats       | Print counters of commands, errors, and events since reset |
+//! | expect <input> high\|low | Print `PASS` or `FAIL` with the reading of a digital input such as `d4`, counting failures in `stats` | `expect d4 high` |
+//! | expect <input> between <low> <high> | Print `PASS` or `FAIL` with the reading of an analog input such as `a0`, both ends included | `expect a0 between 400 600` |
+//! | free        | Print the bytes of SRAM left between statics and the stack |
+//! | lastreset   | Print what caused the last reset |
+//! | version     | Print the firmware version, git hash, and build features |
+//! | id get      | Print the device name stored in EEPROM |
+//! | id set <name> | Store a device name of up to 15 characters in EEPROM | `id set crab1` |
+//! | i2c scan    | List the addresses of all devices on the I2C bus |
+//! | i2c write <addr> <byte…> | Write bytes to an I2C device | `i2c write 0x3C 0x00 0xAF` |
+//! | i2c read <addr> <len> | Read up to 16 bytes from an I2C device | `i2c read 0x68 2` |
+//! | i2c rreg <addr> <reg> <len> | Read device registers, using a repeated start | `i2c rreg 0x68 0x75 1` |
+//! | i2c wreg <addr> <reg> <byte…> | Write bytes to device registers | `i2c wreg 0x68 0x6B 0` |
+//! | i2c slave <addr>\|off | Answer as an I2C slave with a register map of the pins | `i2c slave 0x42` |
+//! | spi xfer <byte…> | Clock bytes out over SPI and print the bytes clocked back | `spi xfer 0x9F 0 0 0` |
+//! | spi mode <0-3> | Set the SPI clock polarity and phase | `spi mode 0` |
+//! | spi div <2-128> | Set the SPI clock divider, a power of two | `spi div 16` |
+//! | spi cs <pin> | Set the SPI chip select pin, 10 by default | `spi cs 10` |
+//! | onewire search <pin> | List the ROM IDs of all 1-Wire devices on a pin | `onewire search 2` |
+//! | onewire reset <pin> | Send a 1-Wire reset pulse and report whether a device answered |
+//! | onewire write <pin> <byte…> | Write bytes to the 1-Wire bus | `onewire write 2 0xCC 0x44` |
+//! | onewire read <pin> <len> | Read up to 16 bytes from the 1-Wire bus | `onewire read 2 9` |
+//! | ds18b20 <pin> [rom] | Read DS18B20 sensors in hundredths of a degree Celsius | `ds18b20 2` |
+//! | dht <pin> [11\|22] | Read a DHT11 or DHT22 (the default) humidity sensor | `dht 4 22` |
+//! | sonar <trig> <echo> | Measure the distance with an HC-SR04 in millimetres | `sonar 7 8` |
+//! | neopixel init <pin> <count> | Set up a strip of up to 32 WS2812 LEDs | `neopixel init 6 8` |
+//! | neopixel set <i> <r> <g> <b> | Set the colour of one LED | `neopixel set 0 255 0 0` |
+//! | neopixel fill <r> <g> <b> | Set the colour of all LEDs | `neopixel fill 0 0 32` |
+//! | neopixel show | Send the colours to the strip |
+//! | lcd init <rs> <en> <d4> <d5> <d6> <d7> | Set up an HD44780 LCD in 4-bit mode |
+//! | lcd init i2c [addr] | Set up an HD44780 LCD behind a PCF8574 I2C backpack | `lcd init i2c 0x27` |
+//! | lcd print <text> | Print text at the cursor | `lcd print hello` |
+//! | lcd clear   | Clear the display and return the cursor home |
+//! | lcd pos <row> <col> | Move the cursor | `lcd pos 1 0` |
+//! | encoder attach <a> <b> | Start counting a quadrature encoder's steps, with pull-ups | `encoder attach 2 3` |
+//! | encoder read | Print the encoder position in quarter steps |
+//! | ir listen <pin> | Print NEC remote codes from an IR receiver as they arrive | `ir listen 2` |
+//! | ir stop     | Stop listening for IR remote codes |
+//! | stepper init <in1> <in2> <in3> <in4> | Set up a four-wire unipolar stepper motor | `stepper init 8 9 10 11` |
+//! | stepper init <step> <dir> | Set up a stepper driver board |
+//! | stepper move <steps> <speed> | Move in the background, negative steps reverse, speed in steps/s | `stepper move 200 100` |
+//! | stepper stop | Stop the stepper motor |
+//! | motor a\|b forward\|reverse\|brake <0-255> | Drive a DC motor through an H-bridge | `motor a forward 200` |
+//! | servo <pin> <0-180> | Move a servo on pin 9 or 10 to an angle | `servo 9 90` |
+//! | servo sweep <pin> <from> <to> <ms> | Move a servo smoothly between angles in the background | `servo sweep 9 0 180 2000` |
+//! | rtc get     | Print the date and time from a DS3231 or DS1307 |
+//! | rtc set <YYYY-MM-DDTHH:MM:SS> | Set the real-time clock | `rtc set 2024-03-22T12:00:00` |
+//! | imu init [addr] | Wake an MPU6050 accelerometer and gyroscope |
+//! | imu read    | Print the acceleration in milli-g and rotation in °/s |
+//! | baro read [i2c [addr]\|spi] | Read a BMP280 or BME280 pressure sensor, over I2C by default | `baro read i2c 0x76` |
+//! | scale init <dout> <sck> | Set up an HX711 load cell amplifier | `scale init 2 3` |
+//! | scale read [samples] | Print the average of up to 255 readings, less the tare offset | `scale read 10` |
+//! | scale tare  | Save the current reading as the zero point in EEPROM |
+//! | shiftout <data> <clock> <latch> <bytes…> [msb\|lsb] | Shift bytes into a chain of 74HC595s, the last byte ending up in the first register | `shiftout 2 3 4 0xFF 0x00` |
+//! | shiftin <data> <clock> <load> <len> [msb\|lsb] | Read up to 16 bytes from a chain of 74HC165s, starting with the register nearest the crab | `shiftin 2 3 4 1` |
+//! | sserial open <rx> <tx> <baud> | Open a software serial port at 1200-19200 baud, printing what it receives | `sserial open 2 3 9600` |
+//! | sserial send <text> | Send a line of text, ending in CR LF | `sserial send hello` |
+//! | sserial close | Close the software serial port |
+//! | gps start <rx> <tx> [baud] | Read NMEA sentences from a GPS receiver on a software serial port, 9600 baud by default, instead of printing them | `gps start 8 9` |
+//! | gps read    | Print whether there is a fix, the latitude and longitude in degrees, the UTC time, and the satellites in use | `gps read` |
+//! | gps stop    | Stop reading, and close the software serial port |
+//! | when <input> > <n> <command> | Run a command when an input such as `a0` rises above a value | `when a0 > 512 led on` |
+//! | when <input> < <n> <command> | Run a command when an input falls below a value | `when a0 < 100 led off` |
+//! | when <input> rises\|falls <command> | Run a command when a digital input such as `d2` changes | `when d2 rises set 7 high` |
+//! | when list   | Print the trigger rules |
+//! | when clear  | Remove all trigger rules |
+//! | watch <input> [ms] | Print an input such as `a0` or `d2` every 100 ms or the given interval, in lines such as `watch 0 a0 512 123456` with the watch's number and the uptime in ms, up to four at a time | `watch a0 50` |
+//! | watch list  | Print the watched inputs |
+//! | watch stop <n> | Stop a watch | `watch stop 0` |
+//! | schedule add <every_ms> <command> | Run a command periodically, also after a reset | `schedule add 1000 led on` |
+//! | schedule list | Print the scheduled commands |
+//! | schedule del <n> | Remove a scheduled command | `schedule del 0` |
+//! | pid setup <adc_pin> <pwm_pin> <kp> <ki> <kd> <setpoint> | Set up a PID loop from an analog input to a PWM output, with gains such as `1.5` | `pid setup 0 9 2 0.5 0.1 512` |
+//! | pid interval <ms> | Set how often the PID loop runs, 100 ms by default | `pid interval 50` |
+//! | pid start\|stop | Start or stop the PID loop, stopping drives the output low |
+//! | pid status  | Print the PID loop's settings, input, and output |
+//! | alert <0-5> above\|below <n> [hysteresis] | Print a line when an analog reading crosses a threshold, and when it comes back | `alert 0 above 800 16` |
+//! | alert <0-5> off | Stop watching an analog pin |
+//! | datalog start <0-5> <s> | Log an analog pin to EEPROM, the last 256 samples kept across resets | `datalog start 0 60` |
+//! | datalog stop | Stop logging to EEPROM |
+//! | datalog dump | Print the EEPROM log as CSV, with the seconds since the oldest sample or the last `restart` row |
+//! | notify <pin> on\|off | Print a line such as `event d2 high 123456` whenever a pin changes, with the uptime in ms | `notify 2 on` |
+//! | button <pin> [ms] | Read a pushbutton to ground, debounced for 20 ms by default | `button 2 20` |
+//! | button notify <pin> on\|off [ms] | Print a line such as `event d2 pressed 123456` whenever a button settles | `button notify 2 on 20` |
+//! | keypad init <rows> <cols> | Scan a keypad of up to 4x4 keys, printing a line such as `event key 5 123456` for each key pressed | `keypad init 2,3,4,6 7,8,9,10` |
+//! | keypad off  | Stop scanning the keypad |
+//! | matrix init <rows> <cols> | Refresh a multiplexed LED matrix of up to 8x8, with the anodes on the row pins and the cathodes on the column pins | `matrix init 2,3,4,5 6,7,8,9` |
+//! | matrix init charlie <pins> | Refresh a charlieplexed LED matrix of 2-8 pins, a row of one fewer LEDs per pin | `matrix init charlie 2,3,4,5` |
+//! | matrix set <x> <y> on\|off | Turn a pixel on or off, counting from the top left | `matrix set 0 0 on` |
+//! | matrix clear | Turn every pixel off |
+//! | matrix text <text> | Scroll up to 32 characters of text through the matrix, over and over | `matrix text hello` |
+//! | matrix off  | Stop refreshing the matrix, and turn it off |
+//! | touch <send> <sense> | Measure a capacitive touch pad, higher counts when touched | `touch 4 2` |
+//! | comparator on d7\|bandgap | Compare d6, or 1.1 V, against d7, printing a line such as `event comparator high 123456` on every change | `comparator on bandgap` |
+//! | comparator off | Stop the analog comparator |
+//! | icp [count] [rising\|falling\|both] | Time up to 255 intervals between edges on d8 in 62.5 ns counts | `icp 16 rising` |
+//! | duty <pin>  | Measure the frequency, duty cycle, and high time of a PWM or servo signal | `duty 2` |
+//! | log start <ms> <channel…> | Log channels such as `a0` or `d7` to an SD card (`sd-log` feature) | `log start 1000 a0 d7` |
+//! | log stop    | Stop logging and close the log |
+//! | log dump    | Print the log from the SD card |
+//! | radio init <ce> <csn> | Set up an nRF24L01 on the SPI bus, on channel 76 (`radio` feature) | `radio init 7 8` |
+//! | radio channel <0-125> | Switch the radio to another channel, 2400 MHz plus the number | `radio channel 100` |
+//! | radio listen <address> | Receive payloads sent to an address of up to five characters, printing lines such as `radio: 01 02` | `radio listen crab1` |
+//! | radio send <address> <bytes…> | Send up to 16 bytes and wait for the acknowledgement | `radio send crab2 7 1` |
+//! | radio off   | Power the radio down |
+//! | can init <bitrate> [8\|16] | Set up an MCP2515 at 125000-1000000 bit/s, with an 8 or 16 MHz crystal (`can` feature) | `can init 500000` |
+//! | can send <id> [bytes…] | Send a frame of up to 8 bytes, with an extended identifier above 0x7FF | `can send 0x7DF 2 1 0x0C` |
+//! | can recv on\|off | Print each frame received, such as `can: 7E8 04 41 0C 1A F8` | `can recv on` |
+//! | net init <cs> <ip> [port] | Serve the console on a TCP port, 23 by default, from a W5500 on the SPI bus (`ethernet` feature) | `net init 9 192.168.1.20` |
+//! | net status  | Print the address, whether the link is up, and whether a client is connected |
+//! | net off     | Disconnect the client and stop listening |
+//!
+//! `tone` and `melody` use Timer1, like servos, `pwm16`, and `wave`, so only
+//! one of them runs at a time. A melody has to fit on the input line, which
+//! the `long-lines` feature makes room for, and ends on its own after the
+//! last note.
+//!
+//! `gps` uses the software serial port, so `sserial send` can still configure
+//! the receiver while it runs, e.g. `sserial send $PMTK220,1000*1F` for one
+//! fix a second. Latitudes south and longitudes west are negative. At 9600
+//! baud a receiver that talks all the time keeps interrupts off for much of
+//! it, so lower baud rates or fewer sentences leave more time for the rest.
+//!
+//! The SD card shares the SPI bus and uses the chip select set with `spi cs`.
+//!
+//! The nRF24L01 shares the bus as well, with a chip select of its own. Two
+//! crabs talk when they are on the same channel and each sends to the address
+//! the other listens on, so a rule such as `when d2 rises radio send crab2 1`
+//! relays a pin. A send fails with `device` if the other end doesn't
+//! acknowledge it, after 15 retries.
+//!
+//! The MCP2515 uses the chip select set with `spi cs`, and receives every
+//! frame on the bus. Extended identifiers print with eight digits. A frame
+//! that no other node acknowledges within 10 ms is aborted, and `can send`
+//! fails with `device`.
+//!
+//! The W5500 has a chip select of its own too. Once it is set up, the console
+//! reads from both the serial port and the network client, and prints to
+//! both, so that `telnet 192.168.1.20` or `nc 192.168.1.20 23` gets a REPL
+//! just like the serial port. The controller is on a /24 network, with the
+//! gateway at its first address, and a MAC address of `02:00` followed by
+//! the IP address.
+//!
+//! Several commands can be given on one line separated by `;`, e.g.
+//! `set 7 high; delay 100; set 7 low`. They run in order, stopping at the first one that
+//! fails. `macro define` and `startup set` take the rest of the line as their
+//! argument, `;` included.
+//!
+//! Commands are not case sensitive, and their words can be separated by any number of spaces or
+//! tabs. Text such as that of `lcd print` and `sserial send` is kept as typed.
+//!
+//! A command that fails prints a message and then `error: <code>`, where the code is one of
+//! `unknown_command`, `bad_pin`, `out_of_range`, `overflow`, `busy`, `device`, `not_set_up`, or
+//! `not_found`.
+//!
+//! In machine mode the response to each command is one line, `OK` or `ERR <code>` followed by
+//! the output as `key=value` pairs, e.g. `OK d7=true`. Responses are cut short after 96
+//! characters, ending in `...`. Events and alerts keep their usual lines, which never start
+//! with `OK` or `ERR`, and the actions of `when` and `schedule` answer with `event OK …` or
+//! `event ERR <code> …` lines, left out when an action succeeds without output.
+//!
+//! With `format json` the response is an object such as `{"cmd":"adc","ok":true,"a0":512}`
+//! instead, and a failed command carries `"error"` and `"message"` members. Events arrive as
+//! `{"event":"…"}`.
+//!
+//! Binary mode exchanges frames instead, with opcodes for the pin commands and one that runs
+//! any text command. Frames are COBS encoded and carry a CRC-16, and corrupted requests are
+//! answered with a NAK so that the host can send them again. The frame format is described in
+//! the `binary` module.
+//!
+//! Firmata mode supports the core digital and analog messages, and the capability, analog
+//! mapping, and firmware queries that host libraries use to find their way around the board.
+//! Put `mode firmata` in the startup script to have the crab start up in it.
+//!
+//! As a Modbus RTU slave the crab maps coils 0-13 to the digital outputs, discrete inputs 0-13
+//! to the levels of the digital pins, and input registers 0-3 to the analog pins. It supports
+//! the functions to read all three and to write single and multiple coils. Like Firmata mode,
+//! `mode modbus` can go in the startup script.
+//!
+//! Locked pins stay locked across resets, and the Firmata, Modbus, and I2C slave modes leave
+//! them alone too. Commands such as `set`, `pwm`, `servo`, and `motor` fail with `locked`, as do
+//! ones that set a locked pin up as an input, such as `button` or `sonar`, and so does `load`
+//! while any pin is locked. `eeprom write` refuses to touch the cells that hold the locks.
+//!
+//! Numbers can be given in hex as `0x3C` or in binary as `0b1010` as well.
+//!
+//! Digital pins can be given as `d7` or just `7`, and the built-in LED as `led`, so that
+//! `set led high` works. Analog pins are `a3` or `3`.
+//!
+//! Commands that take digital pins, such as `get`, `set`, `servo`, and `lock`, accept a pin's
+//! name in place of its number, so that `name 9 pump` allows `set pump high`. The names of up to
+//! eight pins are kept in EEPROM, and `get` prints a pin's name along with its level.
+//!
+//! Groups are lists of pins, so after `group define bar 2 3 4`, `set bar high` sets all three
+//! at once and `get bar` prints their levels as bits, the first pin in the lowest bit. Groups
+//! are kept in RAM like macros.
+//!
+//! In safe mode, commands that change pins, buses, or settings fail with `read_only`, and the
+//! Firmata, Modbus, and I2C slave modes leave the pins alone. Safe mode from the jumper skips
+//! restoring the saved pin configuration and serial settings at boot, and can only be turned off
+//! by removing the jumper and resetting.
+//!
+//! Input is echoed, the up and down arrow keys recall the last four lines entered, and tab
+//! completes command names and common keywords such as `high` and `low`. Lines can end in CR, LF,
+//! or CR LF, and be up to 64 characters long, or 128 with the `long-lines` feature; the rest of a
+//! longer line is discarded up to its end, which fails with `line too long`. `help <command>`
+//! prints the syntax of the command's rows of the table above, which the build script stores in
+//! flash, and their descriptions and examples too with the `help-details` feature.
+//!
+//! The following pins are available on the Uno:
+//! - Digital pins: 2, 3, 4, 6, 7, 8, 9, 10, 11, 12
+//! - Analog pins: 0, 1, 2, 3, 4, 5
+//! - I2C: analog pins 4 (SDA) and 5 (SCL), which stop being analog pins once a command uses the bus
+//! - SPI: digital pins 11 (MOSI), 12 (MISO), and 13 (SCK, shared with the LED)
+//! - Built-in LED: digital pin 13
+//! - PWM output: digital pin 5
+//! - DC motor A: digital pins 5 (ENA), 7 (IN1), and 8 (IN2)
+//! - DC motor B: digital pins 6 (ENB), 3 (IN3), and 4 (IN4)
+//! - Servos: digital pins 9 and 10
+//!
+//! The `mega2560` feature builds for the Arduino Mega 2560 instead, with the same commands on
+//! these pins:
+//! - Digital pins: 2, 3, 5-12, 14-19, 22-53, of which 10-12, 14, 15, and 50-53 have the pin
+//!   change interrupts that `notify`, `encoder`, `ir`, `sserial`, and `sleep` need
+//! - Analog pins: 0-15
+//! - I2C: digital pins 20 (SDA) and 21 (SCL)
+//! - SPI: digital pins 51 (MOSI), 50 (MISO), and 52 (SCK)
+//! - Built-in LED: digital pin 13
+//! - PWM output: digital pin 4
+//! - DC motor A: digital pins 4 (ENA), 7 (IN1), and 8 (IN2)
+//! - Servos, `pwm16`, and `wave`: digital pins 11 and 12
+//!
+//! The Mega has no temperature sensor, and no pins for the comparator, input capture, or motor
+//! B, so `temp`, `comparator`, `icp`, `motor b`, and `bootload` fail there. Pin locks, names,
+//! and the Firmata, Modbus, and I2C slave modes only cover d0-d13 and a0-a3, and `save` only
+//! d0-d15.
+//!
+//! The `leonardo` feature builds for the Leonardo, Micro, and Pro Micro, whose console is the
+//! ATmega32U4's own USB, on these pins:
+//! - Digital pins: 0, 1, 4-10, 12, 14-17, of which 8-10 and 14-17 have the pin change
+//!   interrupts that `notify`, `encoder`, `ir`, `sserial`, and `sleep` need
+//! - Analog pins: 0-5
+//! - I2C: digital pins 2 (SDA) and 3 (SCL)
+//! - SPI: digital pins 16 (MOSI), 14 (MISO), and 15 (SCK), on the ICSP header
+//! - Built-in LED: digital pin 13
+//! - PWM output: digital pin 11
+//! - DC motor A: digital pins 11 (ENA), 7 (IN1), and 8 (IN2)
+//! - Servos, `pwm16`, and `wave`: digital pins 9 and 10
+//!
+//! As on the Mega, `temp`, `comparator`, `icp`, `motor b`, and `bootload` fail there, and so do
+//! `serial`, which has no baud rate to set over USB, `port write`, and `sleep powerdown`.
+
+#![no_std]
+#![no_main]
+#![feature(abi_avr_interrupt)]
+#![feature(asm_experimental_arch)]
+
+mod batch;
+mod binary;
+mod bme280;
+mod board;
+#[cfg(feature = "uno")]
+mod bootloader;
+mod button;
+#[cfg(feature = "can")]
+mod can;
+mod capture;
+#[cfg(feature = "uno")]
+mod comparator;
+mod console;
+mod dac;
+mod datalog;
+mod dht;
+mod ds18b20;
+mod duty;
+mod editor;
+mod encoder;
+#[cfg(feature = "ethernet")]
+mod ethernet;
+mod fade;
+mod firmata;
+mod freerun;
+mod gpio;
+mod groups;
+mod heartbeat;
+mod help;
+mod hx711;
+mod i2cslave;
+#[cfg(feature = "uno")]
+mod icp;
+mod imu;
+mod ir;
+mod keypad;
+mod lcd;
+mod macros;
+mod matrix;
+mod melody;
+mod millis;
+mod modbus;
+mod motor;
+mod names;
+mod neopixel;
+mod notify;
+mod onewire;
+mod pcint;
+mod pid;
+mod powermon;
+mod progmem;
+mod pulse;
+mod pwm16;
+#[cfg(feature = "radio")]
+mod radio;
+mod random;
+mod reset;
+mod rtc;
+mod rules;
+mod schedule;
+#[cfg(feature = "sd-log")]
+mod sd;
+#[cfg(feature = "sd-log")]
+mod sdlog;
+mod selftest;
+mod servo;
+mod shift;
+mod sleep;
+mod softpwm;
+mod spi;
+mod sserial;
+mod stats;
+mod stepper;
+mod storage;
+mod tone;
+mod touch;
+mod uart;
+#[cfg(feature = "leonardo")]
+mod usb;
+mod watch;
+mod wave;
+
+use arduino_hal::hal::port::Dynamic;
+use arduino_hal::hal::wdt;
+use arduino_hal::i2c;
+use arduino_hal::port::mode::{Floating, Input, Output};
+use arduino_hal::port::Pin;
+use arduino_hal::{hal::adc, simple_pwm::*};
+use console::Console;
+use embedded_hal::blocking::i2c::{Read as _, Write as _, WriteRead as _};
+use embedded_hal::serial::Read as _;
+use groups::Groups;
+use heapless::{String, Vec};
+use macros::Macros;
+use onewire::{OneWire, Rom};
+#[allow(unused_imports)]
+use panic_halt as _;
+use progmem::{fwrite, fwriteln};
+use protocol::args::expect::Expectation;
+use protocol::io::{self, Io as _};
+use protocol::{
+    alert, nmea, rtttl, Command, Error, Pins, COMMANDS, KEYWORDS, LED_PIN, MAX_BYTES, PIN_COMMANDS,
+    RESERVED_WORDS,
+};
+use reset::ResetCause;
+use ufmt::{uwrite, uwriteln};
+
+const HELP: &str =
+    "commands: help [command], led on|off, heartbeat on|off|error, get <pin>, set <pin>[,<pin>...] high|low [for <ms>], port read b|c|d, port write b|c|d <mask> <value>, group define <name> <pins...>|list, capture <pin>[,<pin>...] <hz> <samples> [hex|rle|sump], pwm <0-255>, pwm16 <9|10> <0-65535>, softpwm <pin> <0-255>, fade <pin> <from> <to> <ms>, wave <9|10> sine|triangle|square <hz>|off, tone <9|10> <hz>|off, melody <9|10> <rtttl>|pass|fail|ready|off, dac <0-4095> [mv], adc <0-5>|freerun on|off, temp [c], tempcal <offset>, vcc, powermon [on <mV> [safe]|off], random [max], uptime, timestamps on|off, mode human|machine|binary|firmata|modbus <addr>, format text|json, prompt on|off, echo on|off, serial [<baud> [8N1|7E1|...]|default], base dec|hex|bin, delay <ms>, batch, macro define|run|list, startup set|show|clear, eeprom read|write, save, load, reset, bootload, lock [pin]|code <code>, unlock <pin> [code]|code <code>, name [<pin> [name]], safemode [on|off]|jumper <pin>|off, wdt on <ms>|off|kick, sleep idle|powerdown [pin], stats, expect <input> high|low|between <low> <high>, selftest [<out>,<in>...], free, lastreset, version, id get|set <name>, i2c scan|write|read|rreg|wreg|slave <addr>|off, spi xfer|mode|div|cs, onewire search|reset|write|read <pin>, ds18b20 <pin> [rom], dht <pin> [11|22], sonar <trig> <echo>, neopixel init|set|fill|show, lcd init|print|clear|pos, encoder attach <a> <b>|read, ir listen <pin>|stop, stepper init|move|stop, motor a|b forward|reverse|brake <0-255>, servo <pin> <angle>, servo sweep <pin> <from> <to> <ms>, rtc get|set <datetime>, imu init [addr]|read, baro read [i2c [addr]|spi], scale init <dout> <sck>|read [samples]|tare, shiftout <data> <clock> <latch> <bytes...> [msb|lsb], shiftin <data> <clock> <load> <len> [msb|lsb], sserial open <rx> <tx> <baud>|send <text>|close, gps start <rx> <tx> [baud]|read|stop, when <input> <condition> <command>|list|clear, watch <input> [ms]|list|stop <n>, schedule add <every_ms> <command>|list|del <n>, pid setup <adc_pin> <pwm_pin> <kp> <ki> <kd> <setpoint>|interval <ms>|start|stop|status, alert <0-5> above|below <n> [hysteresis]|off, datalog start <0-5> <s>|stop|dump, notify <pin> on|off, button <pin> [ms], button notify <pin> on|off [ms], keypad init <rows> <cols>|off, matrix init <rows> <cols>|init charlie <pins>|set <x> <y> on|off|clear|text <text>|off, touch <send> <sense>, comparator on d7|bandgap|off, icp [count] [rising|falling|both], duty <pin>";
+#[cfg(feature = "sd-log")]
+macro_rules! sd_log_help {
+    () => {
+        ", log start <ms> <channel...>|stop|dump"
+    };
+}
+#[cfg(not(feature = "sd-log"))]
+macro_rules! sd_log_help {
+    () => {
+        ""
+    };
+}
+#[cfg(feature = "radio")]
+macro_rules! radio_help {
+    () => {
+        ", radio init <ce> <csn>|channel <0-125>|listen <address>|send <address> <bytes...>|off"
+    };
+}
+#[cfg(not(feature = "radio"))]
+macro_rules! radio_help {
+    () => {
+        ""
+    };
+}
+#[cfg(feature = "can")]
+macro_rules! can_help {
+    () => {
+        ", can init <bitrate> [8|16]|send <id> [bytes...]|recv on|off"
+    };
+}
+#[cfg(not(feature = "can"))]
+macro_rules! can_help {
+    () => {
+        ""
+    };
+}
+#[cfg(feature = "ethernet")]
+macro_rules! ethernet_help {
+    () => {
+        ", net init <cs> <ip> [port]|status|off"
+    };
+}
+#[cfg(not(feature = "ethernet"))]
+macro_rules! ethernet_help {
+    () => {
+        ""
+    };
+}
+/// Commands that only exist with optional features, appended to [`HELP`].
+const FEATURE_HELP: &str = concat!(sd_log_help!(), radio_help!(), can_help!(), ethernet_help!());
+
+/// [`HELP`] and [`FEATURE_HELP`] in flash, rather than in RAM with the other
+/// strings.
+#[link_section = ".progmem.data"]
+static HELP_TEXT: [u8; HELP.len() + FEATURE_HELP.len()] = progmem::concat(&[HELP, FEATURE_HELP]);
+
+// [`HELP_TEXT`] and the help topics may take 8 KB of the Uno's flash, and
+// 32 KB of the Mega's, so that they leave the rest to the code.
+const _: () = assert!(
+    HELP.len() + FEATURE_HELP.len() + help::TOPICS_LEN
+        <= (if cfg!(feature = "mega2560") { 32 } else { 8 }) * 1024,
+    "the help texts take too much flash, build without help-details"
+);
+
+/// The summary of the commands.
+fn help() -> progmem::Text {
+    // SAFETY: The summary is in flash, and ASCII.
+    unsafe { progmem::Text::new(core::ptr::addr_of!(HELP_TEXT).cast(), HELP_TEXT.len()) }
+}
+
+/// Maximum length of a stored command line, such as a macro body or a rule's
+/// action, in bytes.
+const LINE_LENGTH: usize = 32;
+
+/// Maximum length of an input line, in bytes. The `long-lines` feature
+/// doubles it, at the cost of RAM for the line and each line of history.
+#[cfg(not(feature = "long-lines"))]
+const INPUT_LENGTH: usize = 64;
+#[cfg(feature = "long-lines")]
+const INPUT_LENGTH: usize = 128;
+
+/// How long to wait for an ultrasonic echo, enough for about 5 m.
+const SONAR_TIMEOUT_US: u32 = 30_000;
+
+/// The speed of sound at 20 °C, in metres per second.
+const SPEED_OF_SOUND: u32 = 343;
+/// How long `icp` waits for all of its edges.
+#[cfg(feature = "uno")]
+const ICP_TIMEOUT_MS: u32 = 5_000;
+/// How long `duty` waits for all of its edges.
+const DUTY_TIMEOUT_MS: u32 = 1_000;
+
+/// I2C bus clock, in hertz.
+const I2C_SPEED: u32 = 100_000;
+
+/// The range of 7-bit I2C addresses not reserved by the specification.
+const I2C_ADDRESSES: core::ops::RangeInclusive<u8> = 0x08..=0x77;
+
+/// Nominal voltage of the internal bandgap reference, in millivolts.
+const BANDGAP_MILLIVOLTS: u32 = 1100;
+
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let pins = arduino_hal::pins!(dp);
+
+    let timer0 = Timer0Pwm::new(dp.TC0, Prescaler::Prescale1024);
+    let mut adc = arduino_hal::Adc::new(dp.ADC, Default::default());
+    #[cfg(not(feature = "leonardo"))]
+    let board = board::Board::new(pins, dp.USART0, dp.TWI, &mut adc, &timer0);
+    #[cfg(feature = "leonardo")]
+    let board = board::Board::new(pins, (dp.USB_DEVICE, dp.PLL), dp.TWI, &mut adc, &timer0);
+    let reset_cause = ResetCause::take(&dp.CPU.mcusr);
+    let wdt = wdt::Wdt::new(dp.WDT, &dp.CPU.mcusr);
+    #[cfg(not(feature = "leonardo"))]
+    millis::init(dp.TC2);
+    #[cfg(feature = "leonardo")]
+    millis::init(dp.TC3);
+
+    let mut crab = Crab {
+        serial: Console::new(Serial::from(board.serial)),
+        led: board.led,
+        heartbeat: heartbeat::Heartbeat::new(),
+        pwm: board.pwm,
+        eeprom: arduino_hal::Eeprom::new(dp.EEPROM),
+        wdt,
+        reset_cause,
+        macros: Macros::new(),
+        groups: Groups::new(),
+        macro_depth: 0,
+        background: false,
+        rules: rules::Rules::new(),
+        alerts: [None; board::ANALOG_INPUTS],
+        datalog: None,
+        buttons: button::Buttons::new(),
+        keypad: None,
+        scroll: None,
+        firmata: firmata::Reporting::new(),
+        modbus_address: 1,
+        safe_mode: false,
+        safe_mode_latched: false,
+
+        adc,
+        i2c: board.i2c,
+        spi: spi::Config::new(),
+        neopixel: None,
+        lcd: None,
+        imu: None,
+        scale: None,
+        stepper: None,
+        melody: None,
+        gps: None,
+        sweeps: [None, None],
+        pulses: [None, None, None, None],
+        fades: [None, None, None, None],
+        watches: [None, None, None, None],
+        schedule: schedule::Schedule::new(),
+        pid: None,
+        random: random::Pool::new(),
+        stats: stats::Stats::new(),
+        powermon: None,
+        batch: None,
+        uart: uart::DEFAULT,
+        #[cfg(feature = "sd-log")]
+        logger: None,
+        #[cfg(feature = "radio")]
+        radio: None,
+        #[cfg(feature = "can")]
+        can: None,
+        #[cfg(feature = "can")]
+        can_receive: false,
+
+        digital: board.digital,
+        analog: board.analog,
+    };
+
+    // SAFETY: All state shared with interrupt handlers is behind a `Mutex`.
+    unsafe { avr_device::interrupt::enable() };
+
+    // Safe mode from the jumper also keeps the default serial settings, in
+    // case the stored ones don't work with the terminal.
+    crab.check_safemode_jumper();
+    #[cfg(not(feature = "leonardo"))]
+    if let Some(settings) = uart::load(&crab.eeprom).filter(|_| !crab.safe_mode) {
+        uart::apply(settings);
+        crab.uart = settings;
+    }
+
+    let _ = fwriteln!(
+        &mut crab.serial,
+        "mechanical crab ready, last reset: {}",
+        crab.reset_cause.name()
+    );
+
+    if !crab.safe_mode {
+        let _ = crab.load_pins();
+    }
+    crab.datalog = datalog::resume(&mut crab.eeprom);
+    crab.schedule = schedule::Schedule::load(&crab.eeprom);
+    let startup: String<INPUT_LENGTH> = storage::read_str(&crab.eeprom, storage::STARTUP_SCRIPT);
+    let _ = crab.run_line(&startup);
+
+    let mut editor = editor::Editor::new(COMMANDS, KEYWORDS);
+    let mut decoder = binary::Decoder::new();
+    let mut firmata_decoder = firmata::Decoder::new();
+    let mut modbus_receiver = modbus::Receiver::new();
+    crab.serial.prompt().unwrap();
+    loop {
+        crab.poll();
+        match crab.serial.mode {
+            console::Mode::Binary => {
+                if let Ok(byte) = crab.serial.read() {
+                    match decoder.push(byte) {
+                        Some(Ok(frame)) => crab.run_frame(&frame),
+                        Some(Err(())) => crab.serial.send_frame(binary::NAK, 0, &[]),
+                        None => {}
+                    }
+                }
+                continue;
+            }
+            console::Mode::Firmata => {
+                if let Ok(byte) = crab.serial.read() {
+                    if let Some(message) = firmata_decoder.push(byte) {
+                        crab.run_firmata(message);
+                    }
+                }
+                crab.report_firmata();
+                continue;
+            }
+            console::Mode::Modbus => {
+                if let Ok(byte) = crab.serial.read() {
+                    modbus_receiver.push(byte);
+                }
+                if let Some(frame) = modbus_receiver.take_frame() {
+                    crab.run_modbus(&frame);
+                }
+                continue;
+            }
+            console::Mode::Human | console::Mode::Machine | console::Mode::Json => {}
+        }
+        match editor.read(&mut crab.serial) {
+            Ok(input) if crab.batch.is_some() => crab.collect_batch(&input),
+            Ok(input) => {
+                let _ = crab.run_line(&input);
+            }
+            Err(nb::Error::WouldBlock) => continue,
+            Err(nb::Error::Other(editor::Error::TooLong)) => {
+                stats::count(&mut crab.stats.line_overflows, 1);
+                if let Some(batch) = &mut crab.batch {
+                    batch.overflowed = true;
+                    continue;
+                }
+                crab.serial.begin_response("");
+                let _ = fwriteln!(
+                    &mut crab.serial,
+                    "line too long, maximum is {} characters",
+                    INPUT_LENGTH
+                );
+                let _ = crab.serial.end_response(Some(Error::Overflow.code()));
+            }
+            Err(nb::Error::Other(editor::Error::Serial)) => {}
+        }
+        if crab.batch.is_none() {
+            crab.serial.prompt().unwrap();
+        }
+    }
+}
+
+/// The console's port, the hardware UART or, on the 32U4, its own USB.
+#[cfg(not(feature = "leonardo"))]
+type Usart = arduino_hal::hal::usart::Usart0<arduino_hal::DefaultClock>;
+#[cfg(feature = "leonardo")]
+type Usart = usb::Serial;
+/// Where the console reads and writes, the serial port and, with the
+/// `ethernet` feature, a network client.
+#[cfg(not(feature = "ethernet"))]
+type Serial = Usart;
+#[cfg(feature = "ethernet")]
+type Serial = ethernet::Mirror<Usart>;
+
+/// The board state and peripherals the command interpreter operates on.
+struct Crab {
+    serial: Console<Serial>,
+    led: board::LedPin,
+    heartbeat: heartbeat::Heartbeat,
+    pwm: board::PwmPin,
+    adc: arduino_hal::Adc,
+    i2c: board::I2cBus,
+    spi: spi::Config,
+    neopixel: Option<neopixel::Strip>,
+    lcd: Option<lcd::Lcd>,
+    /// Address of the MPU6050, once woken up.
+    imu: Option<u8>,
+    scale: Option<hx711::Hx711>,
+    stepper: Option<stepper::Stepper>,
+    melody: Option<melody::Melody>,
+    /// Sentences from the software serial port, once `gps start` ran.
+    gps: Option<nmea::Parser>,
+    /// Servo sweeps in progress, indexed like [`servo::PINS`].
+    sweeps: [Option<servo::Sweep>; 2],
+    pulses: [Option<pulse::Pulse>; pulse::MAX_PULSES],
+    fades: [Option<fade::Fade>; fade::MAX_FADES],
+    watches: [Option<watch::Watch>; watch::MAX_WATCHES],
+    schedule: schedule::Schedule,
+    pid: Option<pid::Pid>,
+    random: random::Pool,
+    stats: stats::Stats,
+    powermon: Option<powermon::Monitor>,
+    /// Lines pasted in batch mode, which run at its end.
+    batch: Option<batch::Batch>,
+    /// The console's serial settings.
+    uart: uart::Settings,
+    #[cfg(feature = "sd-log")]
+    logger: Option<sdlog::Logger>,
+    #[cfg(feature = "radio")]
+    radio: Option<radio::Radio>,
+    #[cfg(feature = "can")]
+    can: Option<can::Mcp2515>,
+    /// Whether frames received are printed.
+    #[cfg(feature = "can")]
+    can_receive: bool,
+    eeprom: arduino_hal::Eeprom,
+    wdt: wdt::Wdt,
+    reset_cause: ResetCause,
+    macros: Macros,
+    groups: Groups,
+    /// How many macro invocations deep the current command is.
+    macro_depth: u8,
+    /// The commands running are those of a rule's action or a schedule
+    /// entry, whose responses go out as events.
+    background: bool,
+    rules: rules::Rules,
+    /// Threshold alerts, one per analog pin.
+    alerts: [Option<alert::Alert>; board::ANALOG_INPUTS],
+    datalog: Option<datalog::Datalog>,
+    buttons: button::Buttons,
+    keypad: Option<keypad::Keypad>,
+    /// Text scrolling through the LED matrix.
+    scroll: Option<matrix::Scroll>,
+    /// Inputs reported in Firmata mode.
+    firmata: firmata::Reporting,
+    /// Slave address in Modbus mode.
+    modbus_address: u8,
+    /// Only allow commands that read.
+    safe_mode: bool,
+    /// Safe mode was turned on by the jumper at boot, and stays on.
+    safe_mode_latched: bool,
+
+    /// Indexed like [`board::DIGITAL_PINS`].
+    digital: [AnyPin; board::DIGITAL_PINS.len()],
+    analog: [adc::Channel; board::ANALOG_CHANNELS],
+}
+
+impl Crab {
+    /// Runs the background tasks, between reading input bytes.
+    fn poll(&mut self) {
+        #[cfg(not(feature = "leonardo"))]
+        self.stats.check_uart();
+        while let Some(event) = notify::take_event() {
+            stats::count(&mut self.stats.events, 1);
+            let level = if event.high { "high" } else { "low" };
+            let _ = fwriteln!(
+                &mut self.serial,
+                "event d{} {} {}",
+                event.pin,
+                level,
+                event.time
+            );
+        }
+        #[cfg(feature = "uno")]
+        while let Some(event) = comparator::take_event() {
+            stats::count(&mut self.stats.events, 1);
+            let level = if event.high { "high" } else { "low" };
+            let _ = fwriteln!(
+                &mut self.serial,
+                "event comparator {} {}",
+                level,
+                event.time
+            );
+        }
+        let serial = &mut self.serial;
+        let events = &mut self.stats.events;
+        self.buttons.poll(|pin, pressed| {
+            stats::count(events, 1);
+            let state = if pressed { "pressed" } else { "released" };
+            let _ = fwriteln!(serial, "event d{} {} {}", pin, state, millis::millis());
+        });
+        if let Some(ref mut melody) = self.melody {
+            if !melody.poll() {
+                self.melody = None;
+            }
+        }
+        if let Some(ref mut scroll) = self.scroll {
+            if !scroll.poll() {
+                self.scroll = None;
+            }
+        }
+        if let Some(key) = self.keypad.as_mut().and_then(keypad::Keypad::poll) {
+            stats::count(&mut self.stats.events, 1);
+            let _ = fwriteln!(&mut self.serial, "event key {} {}", key, millis::millis());
+        }
+        let dropped = notify::take_dropped();
+        if dropped > 0 {
+            stats::count(&mut self.stats.events_dropped, u32::from(dropped));
+            let _ = fwriteln!(&mut self.serial, "events dropped: {}", dropped);
+        }
+        while let Some(byte) = sserial::take_byte() {
+            match self.gps {
+                Some(ref mut gps) => {
+                    gps.feed(byte);
+                }
+                None => {
+                    let _ = uwrite!(&mut self.serial, "{}", byte as char);
+                }
+            }
+        }
+        match ir::take_frame() {
+            Some(ir::Frame::Key { address, command }) => {
+                let _ = uwriteln!(
+                    &mut self.serial,
+                    "ir: address 0x{:04X} command 0x{:02X}",
+                    address,
+                    command
+                );
+            }
+            Some(ir::Frame::Repeat) => {
+                let _ = fwriteln!(&mut self.serial, "ir: repeat");
+            }
+            None => {}
+        }
+        if let Some(ref mut motor) = self.stepper {
+            if motor.poll() {
+                let _ = fwriteln!(&mut self.serial, "stepper: done");
+            }
+        }
+        #[cfg(feature = "radio")]
+        while let Some(data) = self.radio.as_mut().and_then(radio::Radio::receive) {
+            stats::count(&mut self.stats.events, 1);
+            let _ = fwrite!(&mut self.serial, "radio:");
+            for byte in &data {
+                let _ = uwrite!(&mut self.serial, " {:02X}", *byte);
+            }
+            let _ = uwriteln!(&mut self.serial, "");
+        }
+        #[cfg(feature = "can")]
+        while let Some(frame) = self
+            .can
+            .as_ref()
+            .filter(|_| self.can_receive)
+            .and_then(can::Mcp2515::receive)
+        {
+            stats::count(&mut self.stats.events, 1);
+            if frame.extended {
+                let _ = uwrite!(&mut self.serial, "can: {:08X}", frame.id);
+            } else {
+                let _ = uwrite!(&mut self.serial, "can: {:03X}", frame.id);
+            }
+            for byte in &frame.data {
+                let _ = uwrite!(&mut self.serial, " {:02X}", *byte);
+            }
+            let _ = uwriteln!(&mut self.serial, "");
+        }
+        #[cfg(feature = "sd-log")]
+        if self.logger.as_mut().map_or(false, |logger| logger.is_due()) {
+            let result = self.log_sample();
+            if let Err(error) = result {
+                let _ = fwriteln!(&mut self.serial, "log error: {}", sd_error_name(error));
+                self.logger = None;
+            }
+        }
+        if let Some(ref mut log) = self.datalog {
+            if log.is_due() {
+                let pin = log.pin();
+                let value = self.analog_read(pin).unwrap_or(0);
+                if let Some(ref mut log) = self.datalog {
+                    log.record(&mut self.eeprom, value);
+                }
+            }
+        }
+        for pin in 0..self.alerts.len() as u8 {
+            if self.alerts[usize::from(pin)].is_none() {
+                continue;
+            }
+            let value = self.analog_read(pin).unwrap_or(0);
+            let Some(alert) = self.alerts[usize::from(pin)].as_mut() else {
+                continue;
+            };
+            let direction = alert.direction.name();
+            let threshold = alert.threshold;
+            match alert.update(value) {
+                Some(alert::Event::Triggered) => {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "alert a{}: {} {} {}",
+                        pin,
+                        value,
+                        direction,
+                        threshold
+                    );
+                }
+                Some(alert::Event::Cleared) => {
+                    let _ = fwriteln!(&mut self.serial, "alert a{}: {} cleared", pin, value);
+                }
+                None => {}
+            }
+        }
+        for index in 0..self.rules.len() {
+            // An action can clear the rules, which ends the loop early.
+            let Some(input) = self.rules.get(index).map(|rule| rule.input) else {
+                break;
+            };
+            let value = self.read_input(input).unwrap_or(0);
+            if let Some(action) = self.rules.update(index, value).cloned() {
+                self.run_action(&action);
+            }
+        }
+        for slot in self.sweeps.iter_mut() {
+            if let Some(sweep) = slot {
+                if sweep.poll() {
+                    let _ = fwriteln!(&mut self.serial, "servo {}: done", sweep.pin());
+                    *slot = None;
+                }
+            }
+        }
+        for index in 0..schedule::MAX_ENTRIES {
+            if let Some(action) = self.schedule.due(index).cloned() {
+                self.run_action(&action);
+            }
+        }
+        self.check_power();
+        if self.pid.as_mut().map_or(false, pid::Pid::is_due) {
+            self.step_pid();
+        }
+        for slot in self.pulses.iter_mut() {
+            if slot.as_ref().map_or(false, pulse::Pulse::poll) {
+                *slot = None;
+            }
+        }
+        for index in 0..watch::MAX_WATCHES {
+            let Some(ref mut watch) = self.watches[index] else {
+                continue;
+            };
+            if !watch.is_due() {
+                continue;
+            }
+            let input = watch.input;
+            let Some(value) = self.read_input(input) else {
+                continue;
+            };
+            let _ = fwrite!(&mut self.serial, "watch {} {} ", index, watch::Name(input));
+            let _ = match input {
+                rules::Input::Analog(_) => uwrite!(&mut self.serial, "{}", value),
+                rules::Input::Digital(_) => {
+                    uwrite!(
+                        &mut self.serial,
+                        "{}",
+                        if value != 0 { "high" } else { "low" }
+                    )
+                }
+            };
+            let _ = fwriteln!(&mut self.serial, " {}", millis::millis());
+        }
+        match self.heartbeat.level() {
+            Some(true) => self.led.set_high(),
+            Some(false) => self.led.set_low(),
+            None => {}
+        }
+        for index in 0..fade::MAX_FADES {
+            let Some(ref mut fade) = self.fades[index] else {
+                continue;
+            };
+            let pin = fade.pin;
+            let (duty, done) = fade.step();
+            if done {
+                self.fades[index] = None;
+            }
+            if let Some(duty) = duty {
+                let _ = self.set_duty(pin, duty);
+            }
+        }
+        if i2cslave::is_enabled() {
+            self.serve_i2c();
+        }
+    }
+
+    /// Runs a request of the binary protocol and sends the response.
+    fn run_frame(&mut self, frame: &binary::Frame) {
+        let mut data = [0; 2];
+        self.serial.begin_response("");
+        let result = match (frame.opcode, &frame.payload[..]) {
+            (binary::PING, []) => Ok(0),
+            (binary::TEXT, text) => match core::str::from_utf8(text) {
+                Ok(line) => self.run_line(line).map(|()| 0),
+                Err(_) => Err(Error::UnknownCommand),
+            },
+            (binary::GET, &[pin]) => match self.digital_pin(pin) {
+                Some(p) => {
+                    data[0] = u8::from(p.is_high());
+                    Ok(1)
+                }
+                None => Err(Error::BadPin),
+            },
+            (binary::SET, &[pin, level]) => self
+                .execute(Command::SetPin {
+                    pin,
+                    value: level != 0,
+                })
+                .map(|()| 0),
+            (binary::ADC, &[pin]) => match self.analog_read(pin) {
+                Some(value) => {
+                    data = value.to_le_bytes();
+                    Ok(2)
+                }
+                None => Err(Error::BadPin),
+            },
+            (binary::PWM, &[duty_cycle]) => self.execute(Command::Pwm { duty_cycle }).map(|()| 0),
+            (binary::TEXT_MODE, []) => {
+                self.serial.mode = console::Mode::Human;
+                Ok(0)
+            }
+            _ => Err(Error::UnknownCommand),
+        };
+        // Only text commands respond with their output, the messages of the
+        // others are left out for the status.
+        let response = self.serial.take_response();
+        let _ = self.serial.end_response(None);
+        let status = result.map_or_else(Error::number, |_| 0);
+        let data = match (frame.opcode, result) {
+            (binary::TEXT, _) => response.as_bytes(),
+            (_, Ok(len)) => &data[..len],
+            (_, Err(_)) => &[],
+        };
+        self.serial.send_frame(frame.opcode, status, data);
+        if frame.opcode == binary::TEXT_MODE {
+            let _ = self.serial.prompt();
+        }
+    }
+
+    /// Handles a Firmata message.
+    fn run_firmata(&mut self, message: firmata::Message) {
+        let locked = self.locked_pins();
+        match message {
+            firmata::Message::SetPinMode { .. } | firmata::Message::AnalogWrite { .. }
+                if self.safe_mode => {}
+            firmata::Message::SetPinMode { pin, .. } if locked & pin_mask(pin) != 0 => {}
+            firmata::Message::AnalogWrite {
+                pin: board::PWM_PIN,
+                ..
+            } if locked & pin_mask(board::PWM_PIN) != 0 => {}
+            firmata::Message::ReportVersion => firmata::write_version(self.serial.raw()),
+            firmata::Message::ReportFirmware => firmata::write_firmware(self.serial.raw()),
+            firmata::Message::CapabilityQuery => firmata::write_capabilities(self.serial.raw()),
+            firmata::Message::AnalogMappingQuery => {
+                firmata::write_analog_mapping(self.serial.raw())
+            }
+            firmata::Message::SetPinMode { pin, mode } => {
+                if let Some(p) = self.digital_pin(pin) {
+                    match mode {
+                        firmata::MODE_INPUT => p.as_input(),
+                        firmata::MODE_OUTPUT => p.as_output(false),
+                        _ => {}
+                    }
+                }
+            }
+            firmata::Message::DigitalPort { port, levels } => {
+                for bit in 0..8 {
+                    self.write_firmata_pin(port * 8 + bit, levels & (1 << bit) != 0);
+                }
+            }
+            firmata::Message::SetDigitalPin { pin, high } => self.write_firmata_pin(pin, high),
+            firmata::Message::ReportDigital { port, enabled } => {
+                if port < firmata::PORTS {
+                    let mask = 1 << port;
+                    self.firmata.ports = if enabled {
+                        self.firmata.ports | mask
+                    } else {
+                        self.firmata.ports & !mask
+                    };
+                    // Reporting starts with the current levels.
+                    let levels = self.port_levels(port);
+                    self.firmata.levels[usize::from(port)] = levels;
+                    if enabled {
+                        firmata::write_digital_port(self.serial.raw(), port, levels);
+                    }
+                }
+            }
+            firmata::Message::ReportAnalog { pin, enabled } => {
+                if pin < firmata::ANALOG_PINS {
+                    let mask = 1 << pin;
+                    self.firmata.analog = if enabled {
+                        self.firmata.analog | mask
+                    } else {
+                        self.firmata.analog & !mask
+                    };
+                }
+            }
+            firmata::Message::AnalogWrite {
+                pin: board::PWM_PIN,
+                value,
+            } => {
+                self.pwm.set_duty(value.min(255) as u8);
+                self.pwm.enable();
+            }
+            firmata::Message::AnalogWrite { .. } => {}
+            firmata::Message::SamplingInterval { ms } => self.firmata.interval_ms = ms.max(1),
+            firmata::Message::Reset => self.firmata = firmata::Reporting::new(),
+        }
+    }
+
+    /// Drives a digital output, or the LED, from Firmata. Inputs and locked
+    /// pins are left alone.
+    fn write_firmata_pin(&mut self, pin: u8, high: bool) {
+        if self.safe_mode || self.locked_pins() & pin_mask(pin) != 0 {
+            return;
+        }
+        if pin == LED_PIN {
+            if high {
+                self.led.set_high();
+            } else {
+                self.led.set_low();
+            }
+            return;
+        }
+        if let Some(p) = self.digital_pin(pin) {
+            if !p.is_output() {
+                return;
+            }
+            if high {
+                p.set_high();
+            } else {
+                p.set_low();
+            }
+        }
+    }
+
+    /// Handles a Modbus request addressed to the crab, and responds unless it
+    /// was broadcast. Frames with a bad CRC are dropped, as Modbus expects.
+    fn run_modbus(&mut self, bytes: &[u8]) {
+        let Some(frame) = modbus::Frame::new(bytes) else {
+            return;
+        };
+        if frame.address != self.modbus_address && frame.address != modbus::BROADCAST {
+            return;
+        }
+        let mut data = Vec::<u8, { modbus::MAX_RESPONSE }>::new();
+        let result = frame
+            .request()
+            .and_then(|request| self.modbus_request(request, &frame, &mut data));
+        if frame.address != modbus::BROADCAST {
+            modbus::respond(self.serial.raw(), &frame, result.map(|()| &data[..]));
+        }
+    }
+
+    fn modbus_request(
+        &mut self,
+        request: modbus::Request,
+        frame: &modbus::Frame,
+        data: &mut Vec<u8, { modbus::MAX_RESPONSE }>,
+    ) -> Result<(), modbus::Exception> {
+        let check = |start: u16, count: u16, limit: u16| {
+            if count == 0 {
+                Err(modbus::Exception::BadValue)
+            } else if u32::from(start) + u32::from(count) > u32::from(limit) {
+                Err(modbus::Exception::BadAddress)
+            } else {
+                Ok(start as u8..(start + count) as u8)
+            }
+        };
+        match request {
+            modbus::Request::ReadCoils { start, count }
+            | modbus::Request::ReadDiscreteInputs { start, count } => {
+                let coils = matches!(request, modbus::Request::ReadCoils { .. });
+                let pins = check(start, count, modbus::BITS)?;
+                let _ = data.push(0);
+                for (i, pin) in pins.enumerate() {
+                    if i % 8 == 0 {
+                        let _ = data.push(0);
+                    }
+                    let on = if coils {
+                        self.coil(pin).unwrap_or(false)
+                    } else {
+                        self.discrete_input(pin)
+                    };
+                    if on {
+                        if let Some(byte) = data.last_mut() {
+                            *byte |= 1 << (i % 8);
+                        }
+                    }
+                }
+                data[0] = (data.len() - 1) as u8;
+            }
+            modbus::Request::ReadInputRegisters { start, count } => {
+                let pins = check(start, count, modbus::REGISTERS)?;
+                let _ = data.push((count * 2) as u8);
+                for pin in pins {
+                    let value = self.analog_read(pin).unwrap_or(0);
+                    let _ = data.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            modbus::Request::WriteCoil { address, on } => {
+                let pin = check(address, 1, modbus::BITS)?.start;
+                if self.coil(pin).is_none() {
+                    return Err(modbus::Exception::BadAddress);
+                }
+                self.write_firmata_pin(pin, on);
+                let _ = data.extend_from_slice(frame.data());
+            }
+            modbus::Request::WriteCoils {
+                start,
+                count,
+                values,
+            } => {
+                let pins = check(start, count, modbus::BITS)?;
+                // Nothing is written unless all the coils are outputs.
+                if pins.clone().any(|pin| self.coil(pin).is_none()) {
+                    return Err(modbus::Exception::BadAddress);
+                }
+                for (i, pin) in pins.enumerate() {
+                    self.write_firmata_pin(pin, values[i / 8] & (1 << (i % 8)) != 0);
+                }
+                let _ = data.extend_from_slice(&frame.data()[..4]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the level of a digital output or the LED, which are the Modbus
+    /// coils, or `None` for other pins.
+    fn coil(&mut self, pin: u8) -> Option<bool> {
+        if pin == LED_PIN {
+            return Some(self.led.is_set_high());
+        }
+        let p = self.digital_pin(pin)?;
+        p.is_output().then(|| p.is_high())
+    }
+
+    /// Returns the level of a digital pin or the LED for Modbus. Pins that
+    /// are not available read as low.
+    fn discrete_input(&mut self, pin: u8) -> bool {
+        if pin == LED_PIN {
+            return self.led.is_set_high();
+        }
+        self.digital_pin(pin).map_or(false, |p| p.is_high())
+    }
+
+    /// Applies the registers the I2C controller wrote in slave mode, and
+    /// brings the others up to date.
+    fn serve_i2c(&mut self) {
+        let (mut written, registers) = i2cslave::take_written();
+        if self.safe_mode {
+            written = 0;
+        }
+        let word =
+            |register: usize| u16::from_le_bytes([registers[register], registers[register + 1]]);
+        let locked = self.locked_pins();
+        if written & (0b11 << i2cslave::DIRECTIONS) != 0 {
+            let outputs = word(i2cslave::DIRECTIONS);
+            for pin in board::DIGITAL_PINS
+                .into_iter()
+                .filter(|&pin| locked & pin_mask(pin) == 0)
+            {
+                let Some(p) = self.digital_pin(pin) else {
+                    continue;
+                };
+                match (outputs & pin_mask(pin) != 0, p.is_output()) {
+                    (true, false) => p.as_output(false),
+                    (false, true) => p.as_input(),
+                    _ => {}
+                }
+            }
+        }
+        if written & (0b11 << i2cslave::OUTPUTS) != 0 {
+            let levels = word(i2cslave::OUTPUTS);
+            for pin in 0..=LED_PIN {
+                self.write_firmata_pin(pin, levels & pin_mask(pin) != 0);
+            }
+        }
+        if written & (1 << i2cslave::PWM) != 0 && locked & pin_mask(board::PWM_PIN) == 0 {
+            self.pwm.set_duty(registers[i2cslave::PWM]);
+            self.pwm.enable();
+        }
+
+        let mut registers = [0; i2cslave::REGISTERS];
+        let (mut levels, mut outputs, mut output_levels) = (0u16, 0u16, 0u16);
+        for pin in 0..=LED_PIN {
+            if self.discrete_input(pin) {
+                levels |= 1 << pin;
+            }
+            if let Some(level) = self.coil(pin) {
+                outputs |= 1 << pin;
+                output_levels |= u16::from(level) << pin;
+            }
+        }
+        registers[i2cslave::INPUTS..][..2].copy_from_slice(&levels.to_le_bytes());
+        registers[i2cslave::DIRECTIONS..][..2].copy_from_slice(&outputs.to_le_bytes());
+        registers[i2cslave::OUTPUTS..][..2].copy_from_slice(&output_levels.to_le_bytes());
+        for pin in 0..4 {
+            let value = self.analog_read(pin).unwrap_or(0);
+            registers[i2cslave::ANALOG + usize::from(pin) * 2..][..2]
+                .copy_from_slice(&value.to_le_bytes());
+        }
+        registers[i2cslave::PWM] = self.pwm.get_duty();
+        i2cslave::publish(&registers);
+    }
+
+    /// Returns the levels of the digital inputs in a Firmata port.
+    fn port_levels(&mut self, port: u8) -> u8 {
+        let mut levels = 0;
+        for bit in 0..8 {
+            if let Some(p) = self.digital_pin(port * 8 + bit) {
+                if !p.is_output() && p.is_high() {
+                    levels |= 1 << bit;
+                }
+            }
+        }
+        levels
+    }
+
+    /// Reports digital inputs that changed, and analog inputs once every
+    /// sampling interval, as far as the Firmata host asked for them.
+    fn report_firmata(&mut self) {
+        for port in 0..firmata::PORTS {
+            if self.firmata.ports & (1 << port) == 0 {
+                continue;
+            }
+            let levels = self.port_levels(port);
+            if levels != self.firmata.levels[usize::from(port)] {
+                self.firmata.levels[usize::from(port)] = levels;
+                firmata::write_digital_port(self.serial.raw(), port, levels);
+            }
+        }
+        let now = millis::millis();
+        if self.firmata.analog == 0 || (now.wrapping_sub(self.firmata.next_sample) as i32) < 0 {
+            return;
+        }
+        self.firmata.next_sample = now.wrapping_add(u32::from(self.firmata.interval_ms));
+        for channel in 0..firmata::ANALOG_PINS {
+            if self.firmata.analog & (1 << channel) == 0 {
+                continue;
+            }
+            if let Some(value) = self.analog_read(channel) {
+                firmata::write_analog(self.serial.raw(), channel, value);
+            }
+        }
+    }
+
+    /// Runs a line of `;`-separated commands in order.
+    ///
+    /// Execution stops at the first command that fails, so that a typo in the
+    /// middle of a sequence does not leave the remaining steps running without
+    /// it.
+    fn run_line(&mut self, line: &str) -> Result<(), Error> {
+        io::run_line::<INPUT_LENGTH, _>(self, line)
+    }
+
+    /// Runs the commands of a rule's action or a schedule entry, whose
+    /// responses go out as events.
+    fn run_action(&mut self, line: &str) {
+        let background = core::mem::replace(&mut self.background, true);
+        let _ = self.run_line(line);
+        self.background = background;
+    }
+
+    /// Acknowledges new serial settings with the old ones, and switches.
+    #[cfg(not(feature = "leonardo"))]
+    fn set_uart(&mut self, settings: uart::Settings) {
+        let _ = fwriteln!(&mut self.serial, "serial: switching to {}", settings);
+        self.serial.flush();
+        uart::apply(settings);
+        self.uart = settings;
+    }
+
+    /// Adds a line to the batch being collected, or at the end marker runs the
+    /// batch's lines in order, up to the first that fails.
+    fn collect_batch(&mut self, line: &str) {
+        let Some(batch) = &mut self.batch else {
+            return;
+        };
+        if !line.trim().eq_ignore_ascii_case(batch::END) {
+            batch.push(line);
+            return;
+        }
+        let Some(batch) = self.batch.take() else {
+            return;
+        };
+        self.serial.echo_input = batch.echo;
+        if batch.overflowed {
+            self.serial.begin_response("batch");
+            let _ = fwriteln!(
+                &mut self.serial,
+                "batch too long, maximum is {} bytes, nothing run",
+                batch::CAPACITY
+            );
+            let _ = self.serial.end_response(Some(Error::Overflow.code()));
+            return;
+        }
+        for (number, line) in batch.lines().enumerate() {
+            if self.run_line(line).is_err() {
+                let _ = fwriteln!(&mut self.serial, "batch stopped at line {}", number + 1);
+                return;
+            }
+        }
+    }
+
+    /// Replaces the names of pins in the arguments of commands that take
+    /// digital pins with their numbers, and groups with lists of them.
+    /// Returns `None` if there are none.
+    fn resolve_names(&self, input: &str) -> Option<String<{ 2 * INPUT_LENGTH }>> {
+        let (keyword, arguments) = input.split_once(' ')?;
+        // Scrolling text is not made of pins.
+        if !PIN_COMMANDS.contains(&keyword) || input.starts_with("matrix text ") {
+            return None;
+        }
+        let mut resolved = String::new();
+        resolved.push_str(keyword).ok()?;
+        let mut found = false;
+        resolved.push(' ').ok()?;
+        let mut rest = arguments;
+        loop {
+            let (word, tail) = rest.split_at(rest.find([' ', ',']).unwrap_or(rest.len()));
+            let pin = names::is_valid(word)
+                .then(|| names::find(&self.eeprom, word))
+                .flatten();
+            let group = self.groups.get(word);
+            match (pin, group) {
+                (Some(pin), _) => {
+                    found = true;
+                    push_pin(&mut resolved, pin)?;
+                }
+                (None, Some(pins)) => {
+                    found = true;
+                    for (i, &pin) in pins.iter().enumerate() {
+                        if i > 0 {
+                            resolved.push(',').ok()?;
+                        }
+                        push_pin(&mut resolved, pin)?;
+                    }
+                }
+                (None, None) => resolved.push_str(word).ok()?,
+            }
+            // Keeps the separator, a space or the comma of a pin list.
+            let Some(separator) = tail.chars().next() else {
+                break;
+            };
+            resolved.push(separator).ok()?;
+            rest = &tail[1..];
+        }
+        found.then_some(resolved)
+    }
+
+    /// Drives a list of pins to the same level at once, and ends any pulses
+    /// on them.
+    fn set_pins(&mut self, pins: &Pins, value: bool) {
+        self.cancel_pulses(pins);
+        // Inputs become outputs at the level they read, so that the only
+        // change is the one all pins make together.
+        for &pin in pins {
+            if let Some(p) = self.digital_pin(pin) {
+                let level = p.is_high();
+                p.as_output(level);
+            }
+        }
+        gpio::set_levels(pins, value);
+    }
+
+    /// Sets the duty cycle of the PWM output, or of software PWM on any
+    /// other pin, which fails if all its channels are in use.
+    fn set_duty(&mut self, pin: u8, duty: u8) -> Result<(), ()> {
+        if pin == board::PWM_PIN {
+            self.pwm.set_duty(duty);
+            self.pwm.enable();
+            Ok(())
+        } else {
+            softpwm::set(pin, duty)
+        }
+    }
+
+    /// Stops a fade on a pin, leaving it at its current duty cycle.
+    fn cancel_fade(&mut self, pin: u8) {
+        for slot in self.fades.iter_mut() {
+            if slot.as_ref().map_or(false, |fade| fade.pin == pin) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Drops the pulses on any of the pins, leaving them at their current
+    /// level.
+    fn cancel_pulses(&mut self, pins: &[u8]) {
+        for slot in self.pulses.iter_mut() {
+            if slot.as_ref().map_or(false, |pulse| {
+                pulse.pins().iter().any(|pin| pins.contains(pin))
+            }) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Checks that all pins of a list are digital pins or the LED.
+    fn check_pins(&mut self, pins: &Pins) -> Result<(), Error> {
+        if let Some(&pin) = pins
+            .iter()
+            .find(|&&pin| pin != LED_PIN && !board::DIGITAL_PINS.contains(&pin))
+        {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "unknown pin: {}, valid pins are {}",
+                pin,
+                board::OUTPUT_PIN_NAMES
+            );
+            return Err(Error::BadPin);
+        }
+        Ok(())
+    }
+
+    /// Checks that a pin or group name can be told apart from pin numbers,
+    /// keywords, and the names of other pins.
+    fn check_name(&mut self, name: &str, max_length: usize) -> Result<(), Error> {
+        let reserved = COMMANDS
+            .split(' ')
+            .chain(KEYWORDS.split(' '))
+            .chain(RESERVED_WORDS)
+            .any(|word| word == name);
+        if name.len() > max_length || !names::is_valid(name) || reserved {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "invalid name: {}, names start with a letter, have up to {} letters, digits, or _, and are not keywords",
+                name,
+                max_length
+            );
+            return Err(Error::OutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Runs a parsed command, printing its response.
+    fn execute(&mut self, command: Command<'_>) -> Result<(), Error> {
+        if self.safe_mode && !command.is_read_only() {
+            let _ = fwriteln!(&mut self.serial, "safe mode is on, only reads are allowed");
+            return Err(Error::ReadOnly);
+        }
+        let locked = self.locked_pins();
+        if let Some(pin) =
+            (0..=LED_PIN).find(|&pin| locked & (1 << pin) != 0 && command.drives(pin))
+        {
+            let _ = fwriteln!(&mut self.serial, "d{} is locked", pin);
+            return Err(Error::Locked);
+        }
+        match command {
+            Command::Help => {
+                let _ = uwriteln!(&mut self.serial, "{}", help());
+            }
+            Command::HelpTopic { command } => {
+                let Some(topic) = help::topic(command) else {
+                    let _ = fwriteln!(&mut self.serial, "no help for: {}", command);
+                    return Err(Error::NotFound);
+                };
+                let _ = uwrite!(&mut self.serial, "{}", topic);
+            }
+            Command::Led(value) => {
+                let _ = self.digital_write(LED_PIN, value);
+            }
+            Command::Heartbeat(mode) => self.heartbeat.set_mode(mode),
+            Command::GetPin { pin } => io::get_pin(self, pin)?,
+            Command::SetPin { pin, value } => io::set_pin(self, pin, value)?,
+            Command::GetPins { ref pins } => {
+                self.check_pins(pins)?;
+                let mut bits = 0u16;
+                for (i, &pin) in pins.iter().enumerate() {
+                    bits |= u16::from(self.discrete_input(pin)) << i;
+                }
+                let bits = self.serial.value(bits);
+                let _ = fwriteln!(&mut self.serial, "bits: {}", bits);
+            }
+            Command::Capture {
+                ref pins,
+                rate_hz,
+                samples,
+                format,
+            } => {
+                self.check_pins(pins)?;
+                if pins.len() > capture::MAX_PINS {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "too many pins, at most {} can be captured",
+                        capture::MAX_PINS
+                    );
+                    return Err(Error::Overflow);
+                }
+                if rate_hz == 0 || rate_hz > capture::MAX_RATE {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "rate out of range, valid rates are 1-{}Hz",
+                        capture::MAX_RATE
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                if samples == 0 || samples > capture::MAX_SAMPLES {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "samples out of range, valid counts are 1-{}",
+                        capture::MAX_SAMPLES
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.capture(pins, rate_hz, usize::from(samples), format);
+            }
+            Command::GroupDefine { name, ref pins } => {
+                self.check_name(name, groups::MAX_NAME_LENGTH)?;
+                self.check_pins(pins)?;
+                if self.groups.define(name, pins).is_err() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "cannot define group: table size of {} exceeded",
+                        groups::MAX_GROUPS
+                    );
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::GroupList => {
+                for (name, pins) in self.groups.iter() {
+                    let _ = fwrite!(&mut self.serial, "{}:", name);
+                    for &pin in pins {
+                        let _ = fwrite!(&mut self.serial, " d{}", pin);
+                    }
+                    let _ = uwriteln!(&mut self.serial, "");
+                }
+            }
+            Command::SetPins { ref pins, value } => {
+                self.check_pins(pins)?;
+                self.set_pins(pins, value);
+            }
+            Command::Pulse {
+                pins,
+                value,
+                duration_ms,
+            } => {
+                self.check_pins(&pins)?;
+                self.cancel_pulses(&pins);
+                let Some(index) = self.pulses.iter().position(Option::is_none) else {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "cannot start pulse: {} pulses running",
+                        pulse::MAX_PULSES
+                    );
+                    return Err(Error::Busy);
+                };
+                self.set_pins(&pins, value);
+                self.pulses[index] = Some(pulse::Pulse::new(pins, value, duration_ms));
+            }
+            Command::PortRead { port } => {
+                let name = port_name(port);
+                let value = self.serial.value(u16::from(gpio::read_port(port)));
+                let _ = fwriteln!(&mut self.serial, "pin{}: {}", name, value);
+            }
+            // The writable bits and their pins are the Uno's, which on the 32U4
+            // are other pins.
+            #[cfg(feature = "leonardo")]
+            Command::PortWrite { .. } => return self.report_unavailable(),
+            #[cfg(not(feature = "leonardo"))]
+            Command::PortWrite { port, mask, value } => {
+                if mask & !port.writable() != 0 {
+                    let _ = uwriteln!(
+                        &mut self.serial,
+                        "mask out of range, writable pins of port {} are 0x{:02X}",
+                        port_name(port),
+                        port.writable()
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                // Keeps the pins' own idea of their direction in step.
+                for bit in (0..8).filter(|bit| mask & (1 << bit) != 0) {
+                    if let Some(p) = port.digital_pin(bit).and_then(|pin| self.digital_pin(pin)) {
+                        let level = p.is_high();
+                        p.as_output(level);
+                    }
+                }
+                gpio::write_port(port, mask, value);
+            }
+            Command::Pwm { duty_cycle } => {
+                self.cancel_fade(board::PWM_PIN);
+                self.pwm.set_duty(duty_cycle);
+                self.pwm.enable();
+            }
+            Command::Pwm16 { pin, duty_cycle } => {
+                if !pwm16::PINS.contains(&pin) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are {}",
+                        pin,
+                        board::TIMER1_PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                }
+                if servo::is_active() || self.sweeps.iter().any(Option::is_some) {
+                    let _ = fwriteln!(&mut self.serial, "timer1 is busy with servos");
+                    return Err(Error::Busy);
+                }
+                if wave::is_active() {
+                    let _ = fwriteln!(&mut self.serial, "timer1 is busy with a waveform");
+                    return Err(Error::Busy);
+                }
+                self.check_tone_idle()?;
+                self.raw_output_pin(pin)?;
+                pwm16::write(pin, duty_cycle);
+            }
+            Command::Wave {
+                pin,
+                shape,
+                frequency,
+            } => {
+                if !wave::PINS.contains(&pin) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are {}",
+                        pin,
+                        board::TIMER1_PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                }
+                if frequency == 0 || frequency > wave::MAX_FREQUENCY {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "frequency out of range, valid frequencies are 1-{} Hz",
+                        wave::MAX_FREQUENCY
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                if servo::is_active() || self.sweeps.iter().any(Option::is_some) {
+                    let _ = fwriteln!(&mut self.serial, "timer1 is busy with servos");
+                    return Err(Error::Busy);
+                }
+                if pwm16::is_active() {
+                    let _ = fwriteln!(&mut self.serial, "timer1 is busy with pwm16");
+                    return Err(Error::Busy);
+                }
+                self.check_tone_idle()?;
+                self.raw_output_pin(pin)?;
+                wave::start(pin, shape, frequency);
+            }
+            Command::WaveOff => wave::stop(),
+            Command::Tone { pin, frequency } => {
+                self.check_tone_pin(pin)?;
+                if !(tone::MIN_FREQUENCY..=tone::MAX_FREQUENCY).contains(&frequency) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "frequency out of range, valid frequencies are {}-{} Hz",
+                        tone::MIN_FREQUENCY,
+                        tone::MAX_FREQUENCY
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.melody = None;
+                tone::start(pin, frequency);
+            }
+            Command::ToneOff => tone::stop(),
+            Command::Melody { pin, song } => {
+                self.check_tone_pin(pin)?;
+                let song = rtttl::jingle(song).unwrap_or(song);
+                self.melody = None;
+                tone::stop();
+                match melody::Melody::start(pin, song) {
+                    Some(melody) => self.melody = Some(melody),
+                    None => {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "unknown melody: {}, give RTTTL or one of pass, fail, ready",
+                            song
+                        );
+                        return Err(Error::NotFound);
+                    }
+                }
+            }
+            Command::MelodyOff => {
+                self.melody = None;
+                tone::stop();
+            }
+            Command::Dac { value, millivolts } => {
+                let value = if millivolts {
+                    let vcc = self.read_vcc();
+                    if u32::from(value) > vcc {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "voltage out of range: {}mV, the supply is {}mV",
+                            value,
+                            vcc
+                        );
+                        return Err(Error::OutOfRange);
+                    }
+                    dac::from_millivolts(u32::from(value), vcc)
+                } else if value > dac::MAX_VALUE {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "value out of range: {}, maximum is {}",
+                        value,
+                        dac::MAX_VALUE
+                    );
+                    return Err(Error::OutOfRange);
+                } else {
+                    value
+                };
+                if let Err(error) = dac::write(self.i2c.claim(&mut self.adc), value) {
+                    return self.report_i2c_error(error);
+                }
+                if millivolts {
+                    let _ = fwriteln!(&mut self.serial, "dac: {}", value);
+                }
+            }
+            Command::SoftPwm { pin, duty_cycle } => {
+                self.raw_output_pin(pin)?;
+                self.cancel_fade(pin);
+                if softpwm::set(pin, duty_cycle).is_err() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "too many pins, at most {} run software PWM",
+                        softpwm::MAX_CHANNELS
+                    );
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::Fade {
+                pin,
+                from,
+                to,
+                duration_ms,
+            } => {
+                if duration_ms > fade::MAX_DURATION_MS {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "duration out of range, at most {} ms",
+                        fade::MAX_DURATION_MS
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                if pin != board::PWM_PIN {
+                    self.raw_output_pin(pin)?;
+                }
+                self.cancel_fade(pin);
+                let Some(index) = self.fades.iter().position(Option::is_none) else {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "too many fades, at most {} at a time",
+                        fade::MAX_FADES
+                    );
+                    return Err(Error::Overflow);
+                };
+                if self.set_duty(pin, from).is_err() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "too many pins, at most {} run software PWM",
+                        softpwm::MAX_CHANNELS
+                    );
+                    return Err(Error::Overflow);
+                }
+                self.fades[index] = Some(fade::Fade::new(pin, from, to, duration_ms));
+            }
+            Command::Adc { pin } => io::adc(self, pin)?,
+            Command::AdcFreeRun(true) => freerun::start(),
+            Command::AdcFreeRun(false) => freerun::stop(),
+            #[cfg(feature = "uno")]
+            Command::Temp { celsius: false } => {
+                let value = self.with_adc(read_temperature);
+                let _ = uwriteln!(&mut self.serial, "temp: 0x{:04X}", value);
+            }
+            #[cfg(feature = "uno")]
+            Command::Temp { celsius: true } => {
+                let value = self.with_adc(read_temperature);
+                let offset = !self.eeprom.read_byte(storage::TEMP_OFFSET) as i8;
+                // Typical characteristic from the datasheet: 324 LSB at 0 °C,
+                // rising by about 1.22 LSB per degree.
+                let celsius = (value as i32 - 324) * 100 / 122 + offset as i32;
+                let _ = fwriteln!(&mut self.serial, "temp: {}C", celsius);
+            }
+            Command::TempCal { offset } => {
+                self.eeprom.write_byte(storage::TEMP_OFFSET, !offset as u8);
+            }
+            Command::Vcc => {
+                let millivolts = self.read_vcc();
+                let _ = fwriteln!(&mut self.serial, "vcc: {}mV", millivolts);
+            }
+            Command::Serial(None) => {
+                let _ = fwriteln!(&mut self.serial, "serial: {}", self.uart);
+            }
+            #[cfg(not(feature = "leonardo"))]
+            Command::Serial(Some(settings)) => {
+                if !uart::BAUD_RATES.contains(&settings.baud) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unsupported baud rate: {}, valid rates are 1200-115200",
+                        settings.baud
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.set_uart(settings);
+                uart::store(&mut self.eeprom, settings);
+            }
+            #[cfg(not(feature = "leonardo"))]
+            Command::SerialDefault => {
+                self.set_uart(uart::DEFAULT);
+                uart::clear(&mut self.eeprom);
+            }
+            Command::Powermon(None) => match self.powermon {
+                Some(ref monitor) => {
+                    let action = if monitor.safe { ", safe" } else { "" };
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "powermon: below {}mV{}",
+                        monitor.threshold_mv(),
+                        action
+                    );
+                }
+                None => {
+                    let _ = fwriteln!(&mut self.serial, "powermon: off");
+                }
+            },
+            Command::Powermon(Some((threshold_mv, safe))) => {
+                self.powermon = Some(powermon::Monitor::new(threshold_mv, safe));
+            }
+            Command::PowermonOff => self.powermon = None,
+            Command::Random { max } => {
+                // Cycles through the analog pins, whose noise differs.
+                for i in 0..random::SAMPLES {
+                    let sample = self.analog_read((i % 4) as u8).unwrap_or(0);
+                    self.random.add(sample);
+                }
+                let number = self.random.number(max);
+                let number = self.serial.value(number);
+                let _ = fwriteln!(&mut self.serial, "random: {}", number);
+            }
+            Command::Uptime => {
+                let _ = fwriteln!(&mut self.serial, "uptime: {}ms", millis::millis());
+            }
+            Command::Timestamps(enabled) => self.serial.timestamps = enabled,
+            Command::Mode(mode) => self.serial.mode = mode,
+            Command::Modbus { address } => {
+                if !(1..=modbus::MAX_ADDRESS).contains(&address) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "address must be 1-{}",
+                        modbus::MAX_ADDRESS
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.modbus_address = address;
+                self.serial.mode = console::Mode::Modbus;
+            }
+            Command::Prompt(enabled) => self.serial.show_prompt = enabled,
+            Command::Base(base) => self.serial.base = base,
+            Command::Echo(enabled) => self.serial.echo_input = enabled,
+            Command::Delay { ms } => arduino_hal::delay_ms(u32::from(ms)),
+            Command::MacroDefine { name, body } => {
+                if self.macros.define(name, body).is_err() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "cannot define macro: name, body, or table size exceeded"
+                    );
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::MacroRun { name } => {
+                let Some(body) = self.macros.get(name).cloned() else {
+                    let _ = fwriteln!(&mut self.serial, "unknown macro: {}", name);
+                    return Err(Error::NotFound);
+                };
+                if self.macro_depth >= macros::MAX_DEPTH {
+                    let _ = fwriteln!(&mut self.serial, "macros nested too deeply");
+                    return Err(Error::Overflow);
+                }
+                self.macro_depth += 1;
+                let result = self.run_line(&body);
+                self.macro_depth -= 1;
+                return result;
+            }
+            Command::Batch => {
+                let _ = fwriteln!(
+                    &mut self.serial,
+                    "batch: paste up to {} bytes of lines, then `{}` to run them",
+                    batch::CAPACITY,
+                    batch::END
+                );
+                self.batch = Some(batch::Batch::new(self.serial.echo_input));
+                self.serial.echo_input = false;
+            }
+            Command::MacroList => {
+                for (name, body) in self.macros.iter() {
+                    let _ = fwriteln!(&mut self.serial, "{}: {}", name, body);
+                }
+            }
+            Command::WhenAdd {
+                input,
+                condition,
+                action,
+            } => {
+                let Some(value) = self.read_input(input) else {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown input, valid inputs are analog pins {} and digital pins {}",
+                        board::ANALOG_PIN_NAMES,
+                        board::PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                };
+                if self.rules.add(input, condition, action, value).is_err() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "cannot add rule: command or table size exceeded"
+                    );
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::WhenList => {
+                for rule in self.rules.iter() {
+                    let _ = fwriteln!(&mut self.serial, "when {}", rule);
+                }
+            }
+            Command::WhenClear => self.rules.clear(),
+            Command::WatchAdd { input, interval_ms } => {
+                if self.read_input(input).is_none() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown input, valid inputs are analog pins {} and digital pins {}",
+                        board::ANALOG_PIN_NAMES,
+                        board::PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                }
+                if interval_ms < watch::MIN_INTERVAL_MS {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "interval out of range, at least {} ms",
+                        watch::MIN_INTERVAL_MS
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                let Some(index) = self.watches.iter().position(Option::is_none) else {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "too many watches, at most {} at a time",
+                        watch::MAX_WATCHES
+                    );
+                    return Err(Error::Overflow);
+                };
+                self.watches[index] = Some(watch::Watch::new(input, interval_ms));
+                let _ = fwriteln!(&mut self.serial, "watch: {}", index);
+            }
+            Command::WatchList => {
+                for (index, slot) in self.watches.iter().enumerate() {
+                    if let Some(watch) = slot {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "{}: {} every {}ms",
+                            index,
+                            watch::Name(watch.input),
+                            watch.interval_ms
+                        );
+                    }
+                }
+            }
+            Command::WatchStop { index } => {
+                let Some(slot @ Some(_)) = self.watches.get_mut(index) else {
+                    let _ = fwriteln!(&mut self.serial, "no watch {}", index);
+                    return Err(Error::NotFound);
+                };
+                *slot = None;
+            }
+            Command::ScheduleAdd {
+                interval_ms,
+                action,
+            } => {
+                if interval_ms == 0 || interval_ms == u32::MAX {
+                    let _ = fwriteln!(&mut self.serial, "interval out of range");
+                    return Err(Error::OutOfRange);
+                }
+                match self.schedule.add(&mut self.eeprom, interval_ms, action) {
+                    Ok(index) => {
+                        let _ = fwriteln!(&mut self.serial, "entry: {}", index);
+                    }
+                    Err(()) => {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "cannot add entry: command longer than {} or schedule full",
+                            schedule::MAX_ACTION
+                        );
+                        return Err(Error::Overflow);
+                    }
+                }
+            }
+            Command::ScheduleList => {
+                for index in 0..schedule::MAX_ENTRIES {
+                    if let Some(entry) = self.schedule.get(index) {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "{}: every {}ms: {}",
+                            index,
+                            entry.interval_ms,
+                            entry.action.as_str()
+                        );
+                    }
+                }
+            }
+            Command::ScheduleDelete { index } => {
+                if self.schedule.remove(&mut self.eeprom, index).is_err() {
+                    let _ = fwriteln!(&mut self.serial, "no entry {}", index);
+                    return Err(Error::NotFound);
+                }
+            }
+            Command::PidSetup {
+                input,
+                output,
+                gains,
+                setpoint,
+            } => {
+                if self.analog_read(input).is_none() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are {}",
+                        input,
+                        board::ANALOG_PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                }
+                if self.pid.as_ref().map_or(false, |pid| pid.running) {
+                    let _ = fwriteln!(&mut self.serial, "pid is running, stop it first");
+                    return Err(Error::Busy);
+                }
+                if output != board::PWM_PIN {
+                    self.raw_output_pin(output)?;
+                }
+                let interval_ms = self
+                    .pid
+                    .as_ref()
+                    .map_or(pid::DEFAULT_INTERVAL_MS, |pid| pid.interval_ms);
+                let mut pid = pid::Pid::new(input, output, gains, setpoint);
+                pid.interval_ms = interval_ms;
+                self.pid = Some(pid);
+            }
+            Command::PidInterval { interval_ms } => {
+                let Some(ref mut pid) = self.pid else {
+                    let _ = fwriteln!(&mut self.serial, "pid not set up");
+                    return Err(Error::NotSetUp);
+                };
+                if interval_ms == 0 {
+                    let _ = fwriteln!(&mut self.serial, "interval out of range");
+                    return Err(Error::OutOfRange);
+                }
+                pid.interval_ms = interval_ms;
+            }
+            Command::PidStart => {
+                let Some(ref mut pid) = self.pid else {
+                    let _ = fwriteln!(&mut self.serial, "pid not set up");
+                    return Err(Error::NotSetUp);
+                };
+                pid.start();
+            }
+            Command::PidStop => {
+                let Some(ref mut pid) = self.pid else {
+                    let _ = fwriteln!(&mut self.serial, "pid not set up");
+                    return Err(Error::NotSetUp);
+                };
+                pid.running = false;
+                let output = pid.output;
+                let _ = self.write_pid_output(output, 0);
+            }
+            Command::PidStatus => {
+                let Some(ref pid) = self.pid else {
+                    let _ = fwriteln!(&mut self.serial, "pid not set up");
+                    return Err(Error::NotSetUp);
+                };
+                let state = if pid.running { "running" } else { "stopped" };
+                let (input, output, duty) = (pid.input, pid.output, pid.duty);
+                let _ = fwriteln!(
+                    &mut self.serial,
+                    "pid: {}, every {}ms",
+                    state,
+                    pid.interval_ms
+                );
+                let _ = fwriteln!(
+                    &mut self.serial,
+                    "gains: kp {} ki {} kd {}",
+                    pid.gains.kp,
+                    pid.gains.ki,
+                    pid.gains.kd
+                );
+                let _ = fwriteln!(&mut self.serial, "setpoint: {}", pid.setpoint);
+                let value = self.analog_read(input).unwrap_or(0);
+                let _ = fwriteln!(&mut self.serial, "a{}: {}", input, value);
+                let _ = fwriteln!(&mut self.serial, "d{}: {}", output, duty);
+            }
+            Command::Alert { pin, alert } => {
+                let Some(slot) = self.alerts.get_mut(usize::from(pin)) else {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are {}",
+                        pin,
+                        board::ANALOG_PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                };
+                *slot = alert;
+            }
+            Command::DatalogStart { pin, interval_s } => {
+                if self.analog_read(pin).is_none() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are {}",
+                        pin,
+                        board::ANALOG_PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                }
+                if interval_s == 0 {
+                    let _ = fwriteln!(&mut self.serial, "interval must be at least 1s");
+                    return Err(Error::OutOfRange);
+                }
+                self.datalog = Some(datalog::start(&mut self.eeprom, pin, interval_s));
+            }
+            Command::DatalogStop => {
+                self.datalog = None;
+                datalog::stop(&mut self.eeprom);
+            }
+            Command::DatalogDump => {
+                let Some((pin, interval_s)) = datalog::settings(&self.eeprom) else {
+                    let _ = fwriteln!(&mut self.serial, "no data logged");
+                    return Err(Error::NotFound);
+                };
+                let _ = fwriteln!(&mut self.serial, "seconds,a{}", pin);
+                let serial = &mut self.serial;
+                let mut seconds = 0u32;
+                // Times start over at a restart, since the time that the
+                // reset took is unknown.
+                datalog::dump(&self.eeprom, |entry| match entry {
+                    datalog::Entry::Sample(value) => {
+                        let _ = fwriteln!(serial, "{},{}", seconds, value);
+                        seconds += u32::from(interval_s);
+                    }
+                    datalog::Entry::Restart => {
+                        let _ = fwriteln!(serial, "restart,");
+                        seconds = 0;
+                    }
+                });
+            }
+            Command::Notify { pin, enabled } => {
+                self.check_pin_change(pin)?;
+                notify::set(pin, enabled);
+            }
+            Command::Button { pin, debounce_ms } => {
+                let raw = self.raw_pin(pin)?;
+                raw.set_level(true);
+                match button::read(&raw, debounce_ms) {
+                    Some(pressed) => {
+                        let state = if pressed { "pressed" } else { "released" };
+                        let _ = fwriteln!(&mut self.serial, "d{}: {}", pin, state);
+                    }
+                    None => {
+                        let _ = fwriteln!(&mut self.serial, "d{}: still bouncing", pin);
+                        return Err(Error::Device);
+                    }
+                }
+            }
+            Command::ButtonNotify {
+                pin,
+                enabled: true,
+                debounce_ms,
+            } => {
+                let raw = self.raw_pin(pin)?;
+                raw.set_level(true);
+                if self.buttons.watch(pin, raw, debounce_ms).is_err() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "too many buttons, at most {} send events",
+                        button::MAX_BUTTONS
+                    );
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::ButtonNotify {
+                pin,
+                enabled: false,
+                ..
+            } => self.buttons.unwatch(pin),
+            Command::KeypadInit {
+                ref rows,
+                ref columns,
+            } => {
+                self.keypad = None;
+                if rows.len() > keypad::MAX_LINES || columns.len() > keypad::MAX_LINES {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "too many pins, at most {} rows and {} columns",
+                        keypad::MAX_LINES,
+                        keypad::MAX_LINES
+                    );
+                    return Err(Error::Overflow);
+                }
+                let all = || rows.iter().chain(columns.iter());
+                if all()
+                    .enumerate()
+                    .any(|(i, pin)| all().skip(i + 1).any(|p| p == pin))
+                {
+                    let _ = fwriteln!(&mut self.serial, "row and column pins must differ");
+                    return Err(Error::BadPin);
+                }
+                let mut row_pins = heapless::Vec::new();
+                for &pin in rows {
+                    let raw = self.raw_pin(pin)?;
+                    raw.set_level(false);
+                    let _ = row_pins.push(raw);
+                }
+                let mut column_pins = heapless::Vec::new();
+                for &pin in columns {
+                    let raw = self.raw_pin(pin)?;
+                    raw.set_level(true);
+                    let _ = column_pins.push(raw);
+                }
+                self.keypad = Some(keypad::Keypad::new(row_pins, column_pins));
+            }
+            Command::KeypadOff => self.keypad = None,
+            Command::MatrixInit {
+                ref rows,
+                ref columns,
+            } => {
+                if rows.len() > matrix::MAX_LINES || columns.len() > matrix::MAX_LINES {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "too many pins, at most {} rows and {} columns",
+                        matrix::MAX_LINES,
+                        matrix::MAX_LINES
+                    );
+                    return Err(Error::Overflow);
+                }
+                self.matrix_init(rows, columns)?;
+            }
+            Command::MatrixCharlieplex { ref pins } => {
+                if !(2..=matrix::MAX_LINES).contains(&pins.len()) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "a charlieplexed matrix has 2-{} pins",
+                        matrix::MAX_LINES
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.matrix_init(pins, &Pins::new())?;
+            }
+            Command::MatrixSet { x, y, on } => {
+                let (width, height) = self.matrix_size()?;
+                if usize::from(x) >= width || usize::from(y) >= height {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "pixel out of range, the matrix is {}x{}",
+                        width,
+                        height
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.scroll = None;
+                matrix::set_pixel(usize::from(x), usize::from(y), on);
+            }
+            Command::MatrixClear => {
+                self.matrix_size()?;
+                self.scroll = None;
+                matrix::show([0; matrix::MAX_LINES]);
+            }
+            Command::MatrixText { text } => {
+                self.matrix_size()?;
+                let Some(scroll) = matrix::Scroll::new(text) else {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "text too long, at most {} characters",
+                        matrix::MAX_TEXT
+                    );
+                    return Err(Error::Overflow);
+                };
+                self.scroll = Some(scroll);
+            }
+            Command::MatrixOff => {
+                self.scroll = None;
+                matrix::stop();
+            }
+            Command::Touch { send, sense } => {
+                if send == sense {
+                    let _ = fwriteln!(&mut self.serial, "send and sense pins must differ");
+                    return Err(Error::BadPin);
+                }
+                let send = self.raw_output_pin(send)?;
+                let sense = self.raw_pin(sense)?;
+                match touch::read(&send, &sense) {
+                    Some(count) => {
+                        let _ = fwriteln!(&mut self.serial, "touch: {}", count);
+                    }
+                    None => {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "touch error: timeout, check the resistor between the pins"
+                        );
+                        return Err(Error::Device);
+                    }
+                }
+            }
+            #[cfg(feature = "uno")]
+            Command::ComparatorOn { reference } => {
+                self.raw_pin(7)?;
+                if let comparator::Reference::Pin = reference {
+                    self.raw_pin(6)?;
+                }
+                comparator::enable(reference);
+                let level = if comparator::is_high() { "high" } else { "low" };
+                let _ = fwriteln!(&mut self.serial, "comparator: {}", level);
+            }
+            #[cfg(feature = "uno")]
+            Command::ComparatorOff => comparator::disable(),
+            #[cfg(feature = "uno")]
+            Command::Icp { count, edges } => {
+                if count == 0 {
+                    let _ = fwriteln!(&mut self.serial, "count must be 1-255");
+                    return Err(Error::OutOfRange);
+                }
+                self.raw_pin(icp::PIN)?;
+                match icp::measure(edges, count, ICP_TIMEOUT_MS) {
+                    Ok(stats) => {
+                        let average = stats.average();
+                        let _ = fwrite!(
+                            &mut self.serial,
+                            "icp: min {} max {} avg {} counts",
+                            stats.min,
+                            stats.max,
+                            average
+                        );
+                        if !matches!(edges, icp::Edges::Both) {
+                            let hz = icp::COUNTS_PER_US * 1_000_000 / average.max(1);
+                            let _ = fwrite!(&mut self.serial, ", {}Hz", hz);
+                        }
+                        let _ = uwriteln!(&mut self.serial, "");
+                    }
+                    Err(icp::Error::Busy) => {
+                        let _ = fwriteln!(&mut self.serial, "timer1 is busy with servos or pwm16");
+                        return Err(Error::Busy);
+                    }
+                    Err(icp::Error::Timeout) => {
+                        let _ = fwriteln!(&mut self.serial, "icp error: timeout");
+                        return Err(Error::Device);
+                    }
+                }
+            }
+            Command::Duty { pin } => {
+                let raw = self.raw_pin(pin)?;
+                match duty::measure(pin, raw, DUTY_TIMEOUT_MS) {
+                    Ok(measurement) => {
+                        let duty = measurement.duty_permille();
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "d{}: {}Hz, duty {}.{}%, high {}us, period {}us",
+                            pin,
+                            measurement.frequency_hz(),
+                            duty / 10,
+                            duty % 10,
+                            measurement.high_us,
+                            measurement.period_us
+                        );
+                    }
+                    Err(duty::Timeout { high }) => {
+                        let level = if high { "high" } else { "low" };
+                        let _ = fwriteln!(&mut self.serial, "no signal, d{} stays {}", pin, level);
+                        return Err(Error::Device);
+                    }
+                }
+            }
+            Command::StartupSet { script } => {
+                // It runs as an input line, so it has to fit into one.
+                if script.len() > INPUT_LENGTH
+                    || storage::write_str(
+                        &mut self.eeprom,
+                        storage::STARTUP_SCRIPT,
+                        storage::STARTUP_SCRIPT_SIZE,
+                        script,
+                    )
+                    .is_err()
+                {
+                    let _ = fwriteln!(&mut self.serial, "startup script too long");
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::StartupShow => {
+                let script: String<INPUT_LENGTH> =
+                    storage::read_str(&self.eeprom, storage::STARTUP_SCRIPT);
+                let _ = fwriteln!(&mut self.serial, "startup: {}", script.as_str());
+            }
+            Command::EepromRead { address, len } => {
+                if u32::from(address) + u32::from(len) > u32::from(self.eeprom.capacity()) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "address out of range, EEPROM has {} bytes",
+                        self.eeprom.capacity()
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                for offset in 0..len {
+                    if offset % 16 == 0 {
+                        if offset != 0 {
+                            let _ = uwriteln!(&mut self.serial, "");
+                        }
+                        let _ = uwrite!(&mut self.serial, "0x{:04X}:", address + offset);
+                    }
+                    let byte = self.eeprom.read_byte(address + offset);
+                    let _ = uwrite!(&mut self.serial, " {:02X}", byte);
+                }
+                let _ = uwriteln!(&mut self.serial, "");
+            }
+            Command::EepromWrite { address, bytes } => {
+                let end = address.saturating_add(bytes.len() as u16);
+                if address < storage::LOCKS.end && storage::LOCKS.start < end {
+                    let _ = uwriteln!(
+                        &mut self.serial,
+                        "0x{:04X}-0x{:04X} hold the locks, use lock and unlock",
+                        storage::LOCKS.start,
+                        storage::LOCKS.end - 1
+                    );
+                    return Err(Error::Locked);
+                }
+                if self.eeprom.write(address, &bytes).is_err() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "address out of range, EEPROM has {} bytes",
+                        self.eeprom.capacity()
+                    );
+                    return Err(Error::OutOfRange);
+                }
+            }
+            Command::Save => self.save_pins(),
+            Command::Load => {
+                if self.load_pins().is_err() {
+                    let _ = fwriteln!(&mut self.serial, "no saved pin configuration");
+                    return Err(Error::NotFound);
+                }
+            }
+            Command::Reset => {
+                self.serial.flush();
+                let _ = self.wdt.start(wdt::Timeout::Ms16);
+                loop {
+                    avr_device::asm::nop();
+                }
+            }
+            #[cfg(not(feature = "uno"))]
+            Command::Temp { .. }
+            | Command::ComparatorOn { .. }
+            | Command::ComparatorOff
+            | Command::Icp { .. } => return self.report_unavailable(),
+            #[cfg(feature = "uno")]
+            Command::Bootload => {
+                let _ = fwriteln!(&mut self.serial, "bootloader: starting");
+                self.serial.flush();
+                self.wdt.stop();
+                bootloader::enter();
+            }
+            #[cfg(not(feature = "uno"))]
+            Command::Bootload => return self.report_unavailable(),
+            // The USB console has no baud rate.
+            #[cfg(feature = "leonardo")]
+            Command::Serial(Some(_)) | Command::SerialDefault => return self.report_unavailable(),
+            Command::WdtOn { timeout_ms } => {
+                let timeout = match timeout_ms {
+                    16 => wdt::Timeout::Ms16,
+                    32 => wdt::Timeout::Ms32,
+                    64 => wdt::Timeout::Ms64,
+                    125 => wdt::Timeout::Ms125,
+                    250 => wdt::Timeout::Ms250,
+                    500 => wdt::Timeout::Ms500,
+                    1000 => wdt::Timeout::Ms1000,
+                    2000 => wdt::Timeout::Ms2000,
+                    4000 => wdt::Timeout::Ms4000,
+                    8000 => wdt::Timeout::Ms8000,
+                    _ => {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "unsupported timeout: {}, valid are 16, 32, 64, 125, 250, 500, 1000, 2000, 4000, 8000",
+                            timeout_ms
+                        );
+                        return Err(Error::OutOfRange);
+                    }
+                };
+                let _ = self.wdt.start(timeout);
+            }
+            Command::WdtOff => self.wdt.stop(),
+            Command::WdtKick => self.wdt.feed(),
+            Command::Sleep { mode, wake_pin } => {
+                // Powering down stops the USB clock, and a pin change doesn't
+                // wake the console up again.
+                #[cfg(feature = "leonardo")]
+                if let sleep::SleepMode::PowerDown = mode {
+                    return self.report_unavailable();
+                }
+                if let Some(pin) = wake_pin {
+                    self.check_pin_change(pin)?;
+                }
+                self.serial.flush();
+                // Free-running conversions would wake the crab straight away.
+                self.with_adc(|_| sleep::sleep(mode, wake_pin));
+                let _ = fwriteln!(&mut self.serial, "woke up");
+            }
+            Command::Stats => {
+                let stats = &self.stats;
+                let counters = [
+                    ("commands", stats.commands),
+                    ("parse errors", stats.parse_errors),
+                    ("framing errors", stats.framing_errors),
+                    ("overruns", stats.overruns),
+                    ("line overflows", stats.line_overflows),
+                    ("events", stats.events),
+                    ("events dropped", stats.events_dropped),
+                    ("expect failures", stats.expect_failures),
+                    ("pin change interrupts", pcint::interrupts()),
+                ];
+                for (name, count) in counters {
+                    let _ = fwriteln!(&mut self.serial, "{}: {}", name, count);
+                }
+            }
+            Command::Expect { input, expectation } => {
+                let is_analog = matches!(input, rules::Input::Analog(_));
+                if is_analog == matches!(expectation, Expectation::Level(_)) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "high and low are for digital inputs, ranges for analog ones"
+                    );
+                    return Err(Error::BadPin);
+                }
+                let Some(value) = self.read_input(input) else {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown input, valid inputs are analog pins {} and digital pins {}",
+                        board::ANALOG_PIN_NAMES,
+                        board::PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                };
+                let pass = expectation.holds(value);
+                if !pass {
+                    stats::count(&mut self.stats.expect_failures, 1);
+                }
+                let verdict = if pass { "PASS" } else { "FAIL" };
+                let _ = match input {
+                    rules::Input::Analog(pin) => fwrite!(&mut self.serial, "{} a{}", verdict, pin),
+                    rules::Input::Digital(pin) => fwrite!(&mut self.serial, "{} d{}", verdict, pin),
+                };
+                let _ = match expectation {
+                    Expectation::Level(high) => {
+                        fwrite!(&mut self.serial, " {}", if high { "high" } else { "low" })
+                    }
+                    Expectation::Between(low, high) => {
+                        fwrite!(&mut self.serial, " between {} {}", low, high)
+                    }
+                };
+                let _ = fwriteln!(&mut self.serial, ": {}", value);
+            }
+            Command::Selftest { ref pins } => {
+                if pins.len() % 2 != 0 || pins.iter().any(|&pin| self.digital_pin(pin).is_none()) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "pins have to come in jumpered pairs of {}",
+                        board::PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                }
+                let mut pass = true;
+                for pair in pins.chunks(2) {
+                    let ok = self.loopback(pair[0], pair[1]) && self.loopback(pair[1], pair[0]);
+                    pass &= ok;
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "pins d{}-d{}: {}",
+                        pair[0],
+                        pair[1],
+                        outcome(ok)
+                    );
+                }
+                let adc = self.with_adc(selftest::adc);
+                pass &= adc.is_ok();
+                let (Ok(millivolts) | Err(millivolts)) = adc;
+                let _ = fwriteln!(
+                    &mut self.serial,
+                    "adc: {}, vcc {}mV",
+                    outcome(adc.is_ok()),
+                    millivolts
+                );
+                let eeprom = selftest::eeprom(&mut self.eeprom);
+                pass &= eeprom;
+                let _ = fwriteln!(&mut self.serial, "eeprom: {}", outcome(eeprom));
+                let timers = selftest::timers();
+                pass &= timers;
+                let _ = fwriteln!(&mut self.serial, "timers: {}", outcome(timers));
+                let _ = fwriteln!(&mut self.serial, "selftest: {}", outcome(pass));
+                if !pass {
+                    return Err(Error::Device);
+                }
+            }
+            Command::Free => {
+                let _ = fwriteln!(&mut self.serial, "free: {} bytes", free_ram());
+            }
+            Command::LastReset => {
+                let _ = fwriteln!(&mut self.serial, "last reset: {}", self.reset_cause.name());
+            }
+            Command::Version => {
+                let _ = fwriteln!(
+                    &mut self.serial,
+                    "version: {} git: {} features: {}",
+                    env!("CARGO_PKG_VERSION"),
+                    env!("CRAB_GIT_HASH"),
+                    env!("CRAB_FEATURES")
+                );
+            }
+            Command::Lock { pin: None } => {
+                let _ = fwrite!(&mut self.serial, "locked:");
+                if locked == 0 {
+                    let _ = fwrite!(&mut self.serial, " none");
+                }
+                for pin in (0..=LED_PIN).filter(|&pin| locked & (1 << pin) != 0) {
+                    let _ = fwrite!(&mut self.serial, " d{}", pin);
+                }
+                let _ = uwriteln!(&mut self.serial, "");
+            }
+            Command::Lock { pin: Some(pin) } => {
+                if pin > LED_PIN {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are 0-13",
+                        pin
+                    );
+                    return Err(Error::BadPin);
+                }
+                self.set_locked_pins(locked | 1 << pin);
+            }
+            Command::Unlock { pin, code } => {
+                if pin > LED_PIN {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are 0-13",
+                        pin
+                    );
+                    return Err(Error::BadPin);
+                }
+                self.check_unlock_code(code.unwrap_or_default())?;
+                self.set_locked_pins(locked & !(1 << pin));
+            }
+            Command::LockCode { code } => {
+                let stored: String<LINE_LENGTH> =
+                    storage::read_str(&self.eeprom, storage::UNLOCK_CODE);
+                if !stored.is_empty() {
+                    let _ = fwriteln!(&mut self.serial, "a code is already set, remove it first");
+                    return Err(Error::Locked);
+                }
+                if storage::write_str(
+                    &mut self.eeprom,
+                    storage::UNLOCK_CODE,
+                    storage::UNLOCK_CODE_SIZE,
+                    code,
+                )
+                .is_err()
+                {
+                    let _ = fwriteln!(&mut self.serial, "code too long, maximum is 15 characters");
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::UnlockCode { code } => {
+                self.check_unlock_code(code)?;
+                let _ = storage::write_str(
+                    &mut self.eeprom,
+                    storage::UNLOCK_CODE,
+                    storage::UNLOCK_CODE_SIZE,
+                    "",
+                );
+            }
+            Command::NameList => {
+                let mut found = false;
+                names::for_each(&self.eeprom, |pin, name| {
+                    let _ = fwriteln!(&mut self.serial, "d{}: {}", pin, name);
+                    found = true;
+                });
+                if !found {
+                    let _ = fwriteln!(&mut self.serial, "no names");
+                }
+            }
+            Command::NameSet { pin, name } => {
+                if pin > LED_PIN {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are 0-13",
+                        pin
+                    );
+                    return Err(Error::BadPin);
+                }
+                if let Some(name) = name {
+                    self.check_name(name, names::MAX_LENGTH)?;
+                }
+                if names::set(&mut self.eeprom, pin, name).is_err() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "cannot name pin: all {} names taken",
+                        names::MAX_NAMES
+                    );
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::Safemode(None) => {
+                let state = match (self.safe_mode, self.safe_mode_latched) {
+                    (true, true) => "on (jumper)",
+                    (true, false) => "on",
+                    (false, _) => "off",
+                };
+                let _ = fwriteln!(&mut self.serial, "safemode: {}", state);
+            }
+            Command::Safemode(Some(enabled)) => {
+                if self.safe_mode_latched && !enabled {
+                    let _ = fwriteln!(&mut self.serial, "safe mode is on by jumper");
+                    return Err(Error::ReadOnly);
+                }
+                self.safe_mode = enabled;
+            }
+            Command::SafemodeJumper { pin } => {
+                if let Some(pin) = pin {
+                    if !board::DIGITAL_PINS.contains(&pin) {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "unknown pin: {}, valid pins are {}",
+                            pin,
+                            board::PIN_NAMES
+                        );
+                        return Err(Error::BadPin);
+                    }
+                }
+                self.eeprom
+                    .write_byte(storage::SAFEMODE_JUMPER, pin.unwrap_or(0xFF));
+            }
+            Command::IdGet => {
+                let name: String<LINE_LENGTH> = storage::read_str(&self.eeprom, storage::DEVICE_ID);
+                let _ = fwriteln!(&mut self.serial, "id: {}", name.as_str());
+            }
+            Command::IdSet { name } => {
+                if storage::write_str(
+                    &mut self.eeprom,
+                    storage::DEVICE_ID,
+                    storage::DEVICE_ID_SIZE,
+                    name,
+                )
+                .is_err()
+                {
+                    let _ = fwriteln!(&mut self.serial, "name too long, maximum is 15 characters");
+                    return Err(Error::Overflow);
+                }
+            }
+            Command::I2cScan => {
+                let bus = self.i2c.claim(&mut self.adc);
+                let mut found = false;
+                for address in I2C_ADDRESSES {
+                    if let Ok(true) = bus.ping_device(address, i2c::Direction::Write) {
+                        let _ = uwrite!(&mut self.serial, "0x{:02X} ", address);
+                        found = true;
+                    }
+                }
+                if found {
+                    let _ = uwriteln!(&mut self.serial, "");
+                } else {
+                    let _ = fwriteln!(&mut self.serial, "no devices found");
+                }
+            }
+            Command::I2cWrite { address, ref bytes } => {
+                check_i2c_address(&mut self.serial, address)?;
+                if let Err(error) = self.i2c.claim(&mut self.adc).write(address, bytes) {
+                    return self.report_i2c_error(error);
+                }
+            }
+            Command::I2cRead { address, len } => {
+                check_i2c_address(&mut self.serial, address)?;
+                let len = check_length(&mut self.serial, len)?;
+                let mut buffer = [0; MAX_BYTES];
+                if let Err(error) = self
+                    .i2c
+                    .claim(&mut self.adc)
+                    .read(address, &mut buffer[..len])
+                {
+                    return self.report_i2c_error(error);
+                }
+                let _ = uwrite!(&mut self.serial, "0x{:02X}:", address);
+                for byte in &buffer[..len] {
+                    let _ = uwrite!(&mut self.serial, " {:02X}", *byte);
+                }
+                let _ = uwriteln!(&mut self.serial, "");
+            }
+            Command::I2cReadRegister {
+                address,
+                register,
+                len,
+            } => {
+                check_i2c_address(&mut self.serial, address)?;
+                let len = check_length(&mut self.serial, len)?;
+                let mut buffer = [0; MAX_BYTES];
+                // The register pointer is written and the data read back in a
+                // single transaction, joined by a repeated start.
+                if let Err(error) = self.i2c.claim(&mut self.adc).write_read(
+                    address,
+                    &[register],
+                    &mut buffer[..len],
+                ) {
+                    return self.report_i2c_error(error);
+                }
+                let _ = uwrite!(&mut self.serial, "0x{:02X}:", register);
+                for byte in &buffer[..len] {
+                    let _ = uwrite!(&mut self.serial, " {:02X}", *byte);
+                }
+                let _ = uwriteln!(&mut self.serial, "");
+            }
+            Command::I2cWriteRegister {
+                address,
+                register,
+                ref bytes,
+            } => {
+                check_i2c_address(&mut self.serial, address)?;
+                let mut buffer = heapless::Vec::<u8, { MAX_BYTES + 1 }>::new();
+                let _ = buffer.push(register);
+                let _ = buffer.extend_from_slice(bytes);
+                if let Err(error) = self.i2c.claim(&mut self.adc).write(address, &buffer) {
+                    return self.report_i2c_error(error);
+                }
+            }
+            Command::I2cSlave { address: None } => i2cslave::disable(),
+            Command::I2cSlave {
+                address: Some(address),
+            } => {
+                if !i2cslave::ADDRESSES.contains(&address) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "address out of range, slave addresses are 8-119"
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.i2c.claim(&mut self.adc);
+                self.serve_i2c();
+                i2cslave::enable(address);
+            }
+            Command::SpiTransfer { mut bytes } => {
+                let cs = self.spi_chip_select();
+                cs.set_level(false);
+                spi::transfer(&self.spi, &mut bytes);
+                cs.set_level(true);
+                for byte in &bytes {
+                    let _ = uwrite!(&mut self.serial, "{:02X} ", *byte);
+                }
+                let _ = uwriteln!(&mut self.serial, "");
+            }
+            Command::SpiMode { mode } => {
+                if mode > 3 {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown mode: {}, valid modes are 0-3",
+                        mode
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.spi.mode = mode;
+            }
+            Command::SpiDivider { divider } => {
+                if !spi::DIVIDERS.contains(&divider) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown divider: {}, valid dividers are 2, 4, 8, 16, 32, 64, 128",
+                        divider
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.spi.divider = divider;
+            }
+            Command::SpiCs { pin } => {
+                if spi::BUS_PINS.contains(&pin) || self.digital_pin(pin).is_none() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are {}",
+                        pin,
+                        board::SPI_CS_PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                }
+                self.spi.cs = pin;
+            }
+            Command::OneWireSearch { pin } => {
+                let bus = self.onewire_bus(pin)?;
+                let mut search = onewire::Search::new();
+                let mut found = false;
+                loop {
+                    match search.next(&bus) {
+                        Ok(Some(rom)) => {
+                            write_rom(&mut self.serial, &rom);
+                            let _ = uwriteln!(&mut self.serial, "");
+                            found = true;
+                        }
+                        Ok(None) => break,
+                        Err(onewire::Error::NoPresence) if !found => {
+                            let _ = fwriteln!(&mut self.serial, "no devices found");
+                            break;
+                        }
+                        Err(error) => {
+                            let _ = fwriteln!(
+                                &mut self.serial,
+                                "onewire error: {}",
+                                onewire_error_name(error)
+                            );
+                            return Err(Error::Device);
+                        }
+                    }
+                }
+            }
+            Command::OneWireReset { pin } => {
+                let bus = self.onewire_bus(pin)?;
+                if bus.reset() {
+                    let _ = fwriteln!(&mut self.serial, "presence");
+                } else {
+                    let _ = fwriteln!(&mut self.serial, "no presence");
+                }
+            }
+            Command::OneWireWrite { pin, ref bytes } => {
+                let bus = self.onewire_bus(pin)?;
+                for byte in bytes {
+                    bus.write_byte(*byte);
+                }
+            }
+            Command::OneWireRead { pin, len } => {
+                let len = check_length(&mut self.serial, len)?;
+                let bus = self.onewire_bus(pin)?;
+                for _ in 0..len {
+                    let _ = uwrite!(&mut self.serial, "{:02X} ", bus.read_byte());
+                }
+                let _ = uwriteln!(&mut self.serial, "");
+            }
+            Command::Ds18b20 { pin, rom } => {
+                let bus = self.onewire_bus(pin)?;
+                if let Err(error) = self.read_ds18b20(&bus, rom) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "onewire error: {}",
+                        onewire_error_name(error)
+                    );
+                    return Err(Error::Device);
+                }
+            }
+            Command::Dht { pin, model } => {
+                let raw = self.raw_pin(pin)?;
+                match dht::read(raw, model) {
+                    Ok(reading) => {
+                        let _ = fwrite!(&mut self.serial, "temp: ");
+                        write_tenths(&mut self.serial, reading.temperature.into());
+                        let _ = fwrite!(&mut self.serial, "C humidity: ");
+                        write_tenths(&mut self.serial, reading.humidity.into());
+                        let _ = fwriteln!(&mut self.serial, "%");
+                    }
+                    Err(error) => {
+                        let message = match error {
+                            dht::Error::NoResponse => "no response",
+                            dht::Error::Timeout => "timeout",
+                            dht::Error::Checksum => "bad checksum",
+                        };
+                        let _ = fwriteln!(&mut self.serial, "dht error: {}", message);
+                        return Err(Error::Device);
+                    }
+                }
+            }
+            Command::Sonar { trig, echo } => {
+                if trig == echo {
+                    let _ = fwriteln!(&mut self.serial, "trigger and echo pins must differ");
+                    return Err(Error::BadPin);
+                }
+                let trig = self.raw_output_pin(trig)?;
+                let echo = self.raw_pin(echo)?;
+                let Some(duration) = measure_echo(trig, echo) else {
+                    let _ = fwriteln!(&mut self.serial, "no echo");
+                    return Err(Error::Device);
+                };
+                // The pulse covers the distance twice.
+                let millimetres = duration * SPEED_OF_SOUND / 2000;
+                let _ = fwriteln!(&mut self.serial, "distance: {}mm", millimetres);
+            }
+            Command::NeopixelInit { pin, count } => {
+                let count = usize::from(count);
+                if count == 0 || count > neopixel::MAX_PIXELS {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "count out of range, valid counts are 1-{}",
+                        neopixel::MAX_PIXELS
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                let pin = self.raw_output_pin(pin)?;
+                let strip = neopixel::Strip::new(pin, count);
+                strip.show();
+                self.neopixel = Some(strip);
+            }
+            Command::NeopixelSet {
+                index,
+                red,
+                green,
+                blue,
+            } => {
+                let strip = self.neopixel_strip()?;
+                let len = strip.len();
+                if !strip.set(usize::from(index), red, green, blue) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "index out of range, the strip has {} LEDs",
+                        len
+                    );
+                    return Err(Error::OutOfRange);
+                }
+            }
+            Command::NeopixelFill { red, green, blue } => {
+                self.neopixel_strip()?.fill(red, green, blue);
+            }
+            Command::NeopixelShow => {
+                self.neopixel_strip()?.show();
+            }
+            Command::LcdInitParallel { rs, en, data } => {
+                let rs = self.raw_output_pin(rs)?;
+                let en = self.raw_output_pin(en)?;
+                let mut pins = [rs; 4];
+                for (pin, number) in pins.iter_mut().zip(data) {
+                    *pin = self.raw_output_pin(number)?;
+                }
+                let bus = lcd::Bus::Parallel { rs, en, data: pins };
+                self.lcd = None;
+                match lcd::Lcd::new(bus, self.i2c.claim(&mut self.adc)) {
+                    Ok(display) => self.lcd = Some(display),
+                    Err(error) => return self.report_i2c_error(error),
+                }
+            }
+            Command::LcdInitI2c { address } => {
+                check_i2c_address(&mut self.serial, address)?;
+                self.lcd = None;
+                match lcd::Lcd::new(lcd::Bus::I2c { address }, self.i2c.claim(&mut self.adc)) {
+                    Ok(display) => self.lcd = Some(display),
+                    Err(error) => return self.report_i2c_error(error),
+                }
+            }
+            Command::LcdPrint { text } => {
+                let Some(display) = self.lcd.as_mut() else {
+                    return self.report_no_lcd();
+                };
+                if let Err(error) = display.print(self.i2c.claim(&mut self.adc), text) {
+                    return self.report_i2c_error(error);
+                }
+            }
+            Command::LcdClear => {
+                let Some(display) = self.lcd.as_mut() else {
+                    return self.report_no_lcd();
+                };
+                if let Err(error) = display.clear(self.i2c.claim(&mut self.adc)) {
+                    return self.report_i2c_error(error);
+                }
+            }
+            Command::LcdPos { row, column } => {
+                let Some(display) = self.lcd.as_mut() else {
+                    return self.report_no_lcd();
+                };
+                if let Err(error) = display.set_position(self.i2c.claim(&mut self.adc), row, column)
+                {
+                    return self.report_i2c_error(error);
+                }
+            }
+            Command::EncoderAttach { a, b } => {
+                if a == b {
+                    let _ = fwriteln!(&mut self.serial, "encoder pins must differ");
+                    return Err(Error::BadPin);
+                }
+                self.check_pin_change(a)?;
+                self.check_pin_change(b)?;
+                let pin_a = self.raw_pin(a)?;
+                let pin_b = self.raw_pin(b)?;
+                pin_a.set_level(true);
+                pin_b.set_level(true);
+                encoder::attach(a, b);
+            }
+            Command::EncoderRead => match encoder::position() {
+                Some(position) => {
+                    let _ = fwriteln!(&mut self.serial, "encoder: {}", position);
+                }
+                None => {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "no encoder attached, use encoder attach <a> <b>"
+                    );
+                    return Err(Error::NotSetUp);
+                }
+            },
+            Command::IrListen { pin } => {
+                self.check_pin_change(pin)?;
+                self.raw_pin(pin)?;
+                ir::listen(pin);
+            }
+            Command::IrStop => ir::stop(),
+            Command::StepperInit { ref pins } => {
+                if pins.len() != 2 && pins.len() != 4 {
+                    let _ = fwriteln!(&mut self.serial, "stepper needs 2 or 4 pins");
+                    return Err(Error::OutOfRange);
+                }
+                self.stepper = None;
+                let mut raw = heapless::Vec::<gpio::RawPin, 4>::new();
+                for &pin in pins {
+                    let _ = raw.push(self.raw_output_pin(pin)?);
+                }
+                let driver = match raw[..] {
+                    [step, dir] => stepper::Driver::StepDir { step, dir },
+                    [a, b, c, d] => stepper::Driver::FourWire([a, b, c, d]),
+                    _ => unreachable!("pin count is checked above"),
+                };
+                self.stepper = Some(stepper::Stepper::new(driver));
+            }
+            Command::StepperMove { steps, speed } => {
+                let Some(ref mut motor) = self.stepper else {
+                    return self.report_no_stepper();
+                };
+                motor.start(steps, speed);
+            }
+            Command::StepperStop => {
+                let Some(ref mut motor) = self.stepper else {
+                    return self.report_no_stepper();
+                };
+                motor.stop();
+            }
+            Command::Motor {
+                motor,
+                drive,
+                speed,
+            } => {
+                // Timer0's other output, which enables motor B on the Uno, is
+                // the LED on the Mega and the I2C clock on the Leonardo.
+                #[cfg(not(feature = "uno"))]
+                if let motor::Motor::B = motor {
+                    return self.report_unavailable();
+                }
+                let (in1, in2) = motor.direction_pins();
+                let (level1, level2) = drive.levels();
+                for (pin, high) in [(in1, level1), (in2, level2)] {
+                    let Some(p) = self.digital_pin(pin) else {
+                        unreachable!("motor pins are digital pins");
+                    };
+                    if high {
+                        p.set_high();
+                    } else {
+                        p.set_low();
+                    }
+                }
+                match motor {
+                    motor::Motor::A => {
+                        self.pwm.set_duty(speed);
+                        self.pwm.enable();
+                    }
+                    #[cfg(feature = "uno")]
+                    motor::Motor::B => {
+                        if let Some(p) = self.digital_pin(6) {
+                            p.set_low();
+                        }
+                        motor::set_d6_duty(speed);
+                    }
+                    #[cfg(not(feature = "uno"))]
+                    motor::Motor::B => {}
+                }
+            }
+            Command::Servo { pin, angle } => {
+                let index = self.servo_pin(pin, angle)?;
+                self.sweeps[index] = None;
+                servo::write(pin, angle);
+            }
+            Command::ServoSweep {
+                pin,
+                from,
+                to,
+                duration_ms,
+            } => {
+                let index = self.servo_pin(pin, from.max(to))?;
+                self.sweeps[index] = Some(servo::Sweep::new(pin, from, to, duration_ms));
+            }
+            Command::RtcGet => match rtc::read(self.i2c.claim(&mut self.adc)) {
+                Ok(time) => {
+                    let _ = fwriteln!(&mut self.serial, "rtc: {}", time);
+                }
+                Err(error) => return self.report_i2c_error(error),
+            },
+            Command::RtcSet { time } => {
+                if !time.is_valid() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "invalid date or time, the clock covers 2000-2099"
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                if let Err(error) = rtc::write(self.i2c.claim(&mut self.adc), &time) {
+                    return self.report_i2c_error(error);
+                }
+            }
+            Command::ImuInit { address } => {
+                check_i2c_address(&mut self.serial, address)?;
+                self.imu = None;
+                match imu::init(self.i2c.claim(&mut self.adc), address) {
+                    Ok(()) => self.imu = Some(address),
+                    Err(error) => return self.report_imu_error(error),
+                }
+            }
+            Command::ImuRead => {
+                let Some(address) = self.imu else {
+                    let _ = fwriteln!(&mut self.serial, "no imu set up, use imu init");
+                    return Err(Error::NotSetUp);
+                };
+                match imu::read(self.i2c.claim(&mut self.adc), address) {
+                    Ok(reading) => {
+                        let [x, y, z] = reading.accel;
+                        let _ = fwrite!(&mut self.serial, "accel: {} {} {} mg gyro:", x, y, z);
+                        for rate in reading.gyro {
+                            let _ = fwrite!(&mut self.serial, " ");
+                            write_tenths(&mut self.serial, i32::from(rate));
+                        }
+                        let _ = fwriteln!(&mut self.serial, " dps");
+                    }
+                    Err(error) => return self.report_imu_error(error),
+                }
+            }
+            Command::BaroRead { interface } => {
+                let bus = match interface {
+                    bme280::Interface::I2c { address } => {
+                        check_i2c_address(&mut self.serial, address)?;
+                        bme280::Bus::I2c { address }
+                    }
+                    bme280::Interface::Spi => bme280::Bus::Spi {
+                        cs: self.spi_chip_select(),
+                        config: spi::Config {
+                            mode: 0,
+                            divider: 4,
+                            cs: self.spi.cs,
+                        },
+                    },
+                };
+                match bme280::read(bus, self.i2c.claim(&mut self.adc)) {
+                    Ok(reading) => {
+                        let _ = fwrite!(&mut self.serial, "temp: ");
+                        write_tenths(&mut self.serial, reading.temperature.into());
+                        let _ = fwrite!(&mut self.serial, "C pressure: {}Pa", reading.pressure);
+                        if let Some(humidity) = reading.humidity {
+                            let _ = fwrite!(&mut self.serial, " humidity: ");
+                            write_tenths(&mut self.serial, humidity.into());
+                            let _ = fwrite!(&mut self.serial, "%");
+                        }
+                        let _ = uwriteln!(&mut self.serial, "");
+                    }
+                    Err(bme280::Error::I2c(error)) => return self.report_i2c_error(error),
+                    Err(bme280::Error::UnknownDevice(id)) => {
+                        let _ =
+                            uwriteln!(&mut self.serial, "not a bmp280 or bme280, id 0x{:02X}", id);
+                        return Err(Error::Device);
+                    }
+                    Err(bme280::Error::Timeout) => {
+                        let _ = fwriteln!(&mut self.serial, "baro error: timeout");
+                        return Err(Error::Device);
+                    }
+                }
+            }
+            Command::ScaleInit { dout, sck } => {
+                if dout == sck {
+                    let _ = fwriteln!(&mut self.serial, "data and clock pins must differ");
+                    return Err(Error::BadPin);
+                }
+                self.scale = None;
+                let dout = self.raw_pin(dout)?;
+                let sck = self.raw_output_pin(sck)?;
+                self.scale = Some(hx711::Hx711::new(dout, sck));
+            }
+            Command::ScaleRead { samples } => {
+                let value = self.read_scale(samples)?;
+                let mut tare = [0; 4];
+                let _ = self.eeprom.read(storage::SCALE_TARE, &mut tare);
+                let tare = !i32::from_le_bytes(tare);
+                let _ = fwriteln!(&mut self.serial, "scale: {}", value - tare);
+            }
+            Command::ScaleTare => {
+                let value = self.read_scale(10)?;
+                let _ = self
+                    .eeprom
+                    .write(storage::SCALE_TARE, &(!value).to_le_bytes());
+            }
+            Command::ShiftOut {
+                data,
+                clock,
+                latch,
+                ref bytes,
+                order,
+            } => {
+                if data == clock || data == latch || clock == latch {
+                    let _ = fwriteln!(&mut self.serial, "data, clock, and latch pins must differ");
+                    return Err(Error::BadPin);
+                }
+                let data = self.raw_output_pin(data)?;
+                let clock = self.raw_output_pin(clock)?;
+                let latch = self.raw_output_pin(latch)?;
+                // The outputs update on the rising edge of the latch.
+                for &byte in bytes {
+                    shift::shift_out(&data, &clock, order, byte);
+                }
+                latch.set_level(true);
+                latch.set_level(false);
+            }
+            Command::ShiftIn {
+                data,
+                clock,
+                load,
+                len,
+                order,
+            } => {
+                if data == clock || data == load || clock == load {
+                    let _ = fwriteln!(&mut self.serial, "data, clock, and load pins must differ");
+                    return Err(Error::BadPin);
+                }
+                let len = check_length(&mut self.serial, len)?;
+                let data = self.raw_pin(data)?;
+                let clock = self.raw_output_pin(clock)?;
+                let load = self.raw_output_pin(load)?;
+                // The inputs are captured while the load line is low, and
+                // shifted out once it goes high again.
+                load.set_level(true);
+                let mut buffer = [0; MAX_BYTES];
+                for byte in &mut buffer[..len] {
+                    *byte = shift::shift_in(&data, &clock, order);
+                }
+                let _ = fwrite!(&mut self.serial, "shiftin:");
+                for byte in &buffer[..len] {
+                    let _ = uwrite!(&mut self.serial, " {:02X}", *byte);
+                }
+                let _ = uwriteln!(&mut self.serial, "");
+            }
+            Command::SoftSerialOpen { rx, tx, baud } => {
+                self.gps = None;
+                self.open_soft_serial(rx, tx, baud)?;
+            }
+            Command::SoftSerialSend { text } => {
+                if !sserial::is_open() {
+                    let _ = fwriteln!(&mut self.serial, "no port open, use sserial open");
+                    return Err(Error::NotSetUp);
+                }
+                sserial::send(text.as_bytes());
+                sserial::send(b"\r\n");
+            }
+            Command::SoftSerialClose => {
+                self.gps = None;
+                sserial::close();
+            }
+            Command::GpsStart { rx, tx, baud } => {
+                self.gps = None;
+                self.open_soft_serial(rx, tx, baud)?;
+                self.gps = Some(nmea::Parser::new());
+            }
+            Command::GpsRead => {
+                let Some(ref gps) = self.gps else {
+                    let _ = fwriteln!(&mut self.serial, "no receiver set up, use gps start");
+                    return Err(Error::NotSetUp);
+                };
+                let fix = gps.fix;
+                if gps.sentences == 0 {
+                    let _ = fwriteln!(&mut self.serial, "gps: no data");
+                    return Ok(());
+                }
+                let state = if fix.valid { "fix" } else { "no fix" };
+                let _ = fwriteln!(&mut self.serial, "gps: {}", state);
+                if let (Some(latitude), Some(longitude)) = (fix.latitude, fix.longitude) {
+                    let _ = fwriteln!(&mut self.serial, "lat: {}", latitude);
+                    let _ = fwriteln!(&mut self.serial, "lon: {}", longitude);
+                }
+                match (fix.date_time(), fix.time) {
+                    (Some(time), _) => {
+                        let _ = fwriteln!(&mut self.serial, "utc: {}Z", time);
+                    }
+                    (None, Some(time)) => {
+                        let _ = fwriteln!(&mut self.serial, "utc: {}Z", time);
+                    }
+                    (None, None) => {}
+                }
+                if let Some(satellites) = fix.satellites {
+                    let _ = fwriteln!(&mut self.serial, "satellites: {}", satellites);
+                }
+            }
+            Command::GpsStop => {
+                if self.gps.take().is_some() {
+                    sserial::close();
+                }
+            }
+            #[cfg(feature = "sd-log")]
+            Command::LogStart {
+                interval_ms,
+                ref channels,
+            } => {
+                for channel in channels {
+                    let valid = match *channel {
+                        sdlog::Channel::Analog(pin) => self.analog_read(pin).is_some(),
+                        sdlog::Channel::Digital(pin) => self.digital_pin(pin).is_some(),
+                    };
+                    if !valid {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "unknown channel, valid channels are analog pins {} and digital pins {}",
+                            board::ANALOG_PIN_NAMES,
+                            board::PIN_NAMES
+                        );
+                        return Err(Error::BadPin);
+                    }
+                }
+                self.logger = None;
+                let result = self
+                    .sd_card()
+                    .and_then(|card| sdlog::Logger::new(card, channels.clone(), interval_ms));
+                match result {
+                    Ok(logger) => self.logger = Some(logger),
+                    Err(error) => return self.report_sd_error(error),
+                }
+            }
+            #[cfg(feature = "sd-log")]
+            Command::LogStop => {
+                if let Some(mut logger) = self.logger.take() {
+                    if let Err(error) = logger.flush() {
+                        return self.report_sd_error(error);
+                    }
+                }
+            }
+            #[cfg(feature = "sd-log")]
+            Command::LogDump => {
+                let result = match self.logger {
+                    Some(ref logger) => {
+                        let serial = &mut self.serial;
+                        sdlog::dump(logger.card(), |byte| {
+                            let _ = uwrite!(serial, "{}", byte as char);
+                        })
+                    }
+                    None => self.sd_card().and_then(|card| {
+                        let serial = &mut self.serial;
+                        sdlog::dump(&card, |byte| {
+                            let _ = uwrite!(serial, "{}", byte as char);
+                        })
+                    }),
+                };
+                if let Err(error) = result {
+                    return self.report_sd_error(error);
+                }
+            }
+            #[cfg(feature = "radio")]
+            Command::RadioInit { ce, csn } => {
+                if let Some(radio) = self.radio.take() {
+                    radio.off();
+                }
+                for pin in [ce, csn] {
+                    if spi::BUS_PINS.contains(&pin) || self.digital_pin(pin).is_none() {
+                        let _ = fwriteln!(
+                            &mut self.serial,
+                            "unknown pin: {}, valid pins are {}",
+                            pin,
+                            board::SPI_CS_PIN_NAMES
+                        );
+                        return Err(Error::BadPin);
+                    }
+                }
+                if ce == csn {
+                    let _ = fwriteln!(&mut self.serial, "ce and csn pins must differ");
+                    return Err(Error::BadPin);
+                }
+                self.claim_spi_ss();
+                let ce_pin = self.raw_output_pin(ce)?;
+                let csn_pin = self.raw_output_pin(csn)?;
+                csn_pin.set_level(true);
+                match radio::Radio::init(ce_pin, csn_pin, csn) {
+                    Ok(radio) => self.radio = Some(radio),
+                    Err(error) => return self.report_radio_error(error),
+                }
+            }
+            #[cfg(feature = "radio")]
+            Command::RadioChannel { channel } => {
+                if channel > radio::MAX_CHANNEL {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "channel out of range: {}, maximum is {}",
+                        channel,
+                        radio::MAX_CHANNEL
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.radio()?.set_channel(channel);
+            }
+            #[cfg(feature = "radio")]
+            Command::RadioListen { address } => {
+                self.radio()?.listen(radio::address(address));
+            }
+            #[cfg(feature = "radio")]
+            Command::RadioSend { address, ref data } => {
+                let result = self.radio()?.send(radio::address(address), data);
+                if let Err(error) = result {
+                    return self.report_radio_error(error);
+                }
+            }
+            #[cfg(feature = "radio")]
+            Command::RadioOff => {
+                if let Some(radio) = self.radio.take() {
+                    radio.off();
+                }
+            }
+            #[cfg(feature = "can")]
+            Command::CanInit {
+                bitrate,
+                crystal_mhz,
+            } => {
+                if !can::is_supported(bitrate, crystal_mhz) {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unsupported bit rate: {}, valid rates are 125000, 250000, 500000, and 1000000 with an 8 or 16 MHz crystal",
+                        bitrate
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                self.can = None;
+                let cs = self.spi_chip_select();
+                match can::Mcp2515::init(cs, self.spi.cs, bitrate, crystal_mhz) {
+                    Ok(controller) => self.can = Some(controller),
+                    Err(error) => return self.report_can_error(error),
+                }
+            }
+            #[cfg(feature = "can")]
+            Command::CanSend { id, ref data } => {
+                if id > can::MAX_EXTENDED_ID {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "identifier out of range, maximum is 0x1FFFFFFF"
+                    );
+                    return Err(Error::OutOfRange);
+                }
+                if data.len() > can::MAX_DATA {
+                    let _ = fwriteln!(&mut self.serial, "too many bytes, maximum is 8");
+                    return Err(Error::Overflow);
+                }
+                let result = self.can()?.send(id, data);
+                if let Err(error) = result {
+                    return self.report_can_error(error);
+                }
+            }
+            #[cfg(feature = "can")]
+            Command::CanReceive(enabled) => {
+                self.can()?;
+                self.can_receive = enabled;
+            }
+            #[cfg(feature = "ethernet")]
+            Command::NetInit { cs, ip, port } => {
+                ethernet::stop();
+                if spi::BUS_PINS.contains(&cs) || self.digital_pin(cs).is_none() {
+                    let _ = fwriteln!(
+                        &mut self.serial,
+                        "unknown pin: {}, valid pins are {}",
+                        cs,
+                        board::SPI_CS_PIN_NAMES
+                    );
+                    return Err(Error::BadPin);
+                }
+                self.claim_spi_ss();
+                let cs_pin = self.raw_output_pin(cs)?;
+                cs_pin.set_level(true);
+                if let Err(error) = ethernet::start(cs_pin, cs, ip, port) {
+                    let message = match error {
+                        ethernet::Error::NotFound => "no controller found",
+                        ethernet::Error::Socket => "socket didn't open",
+                    };
+                    let _ = fwriteln!(&mut self.serial, "net error: {}", message);
+                    return Err(Error::Device);
+                }
+            }
+            #[cfg(feature = "ethernet")]
+            Command::NetStatus => {
+                let Some(status) = ethernet::status() else {
+                    let _ = fwriteln!(&mut self.serial, "no controller set up, use net init");
+                    return Err(Error::NotSetUp);
+                };
+                let [a, b, c, d] = status.ip;
+                let _ = fwriteln!(
+                    &mut self.serial,
+                    "net: {}.{}.{}.{}:{}",
+                    a,
+                    b,
+                    c,
+                    d,
+                    status.port
+                );
+                let link = if status.link { "up" } else { "down" };
+                let _ = fwriteln!(&mut self.serial, "link: {}", link);
+                let client = if status.connected {
+                    "connected"
+                } else {
+                    "none"
+                };
+                let _ = fwriteln!(&mut self.serial, "client: {}", client);
+            }
+            #[cfg(feature = "ethernet")]
+            Command::NetOff => ethernet::stop(),
+            Command::StartupClear => {
+                let _ = storage::write_str(
+                    &mut self.eeprom,
+                    storage::STARTUP_SCRIPT,
+                    storage::STARTUP_SCRIPT_SIZE,
+                    "",
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Crab {
+    /// Returns the general purpose digital pin with the given number.
+    fn digital_pin(&mut self, pin: u8) -> Option<&mut AnyPin> {
+        let index = board::DIGITAL_PINS.iter().position(|&p| p == pin)?;
+        Some(&mut self.digital[index])
+    }
+
+    fn analog_read(&mut self, pin: u8) -> Option<u16> {
+        if usize::from(pin) >= board::ANALOG_INPUTS || self.i2c.holds(pin) {
+            return None;
+        }
+        if freerun::is_running() {
+            return freerun::read(pin);
+        }
+        match self.analog.get(usize::from(pin)) {
+            Some(channel) => Some(self.adc.read_blocking(channel)),
+            None => self.i2c.analog_read(&mut self.adc, pin),
+        }
+    }
+
+    /// Runs conversions of the ADC driver, with free-running conversions
+    /// paused meanwhile.
+    fn with_adc<T>(&mut self, f: impl FnOnce(&mut arduino_hal::Adc) -> T) -> T {
+        let free_running = freerun::is_running();
+        if free_running {
+            freerun::stop();
+        }
+        let result = f(&mut self.adc);
+        if free_running {
+            freerun::start();
+        }
+        result
+    }
+
+    /// Reads an analog input, or a digital one as 0 or 1.
+    fn read_input(&mut self, input: rules::Input) -> Option<u16> {
+        match input {
+            rules::Input::Analog(pin) => self.analog_read(pin),
+            rules::Input::Digital(pin) => self.digital_pin(pin).map(|p| u16::from(p.is_high())),
+        }
+    }
+
+    /// Readies the configured SPI chip select as an output driven high.
+    fn spi_chip_select(&mut self) -> gpio::RawPin {
+        self.claim_spi_ss();
+        let cs = self.spi.cs;
+        let Some(pin) = self.digital_pin(cs) else {
+            unreachable!("chip select is validated by spi cs");
+        };
+        pin.set_high();
+        let Some(raw) = gpio::RawPin::new(cs) else {
+            unreachable!("digital pins are on a port");
+        };
+        raw
+    }
+
+    /// Makes the SPI slave select an output. The hardware falls back to slave
+    /// mode if its slave select is an input that gets pulled low, even when
+    /// it is not the chip select.
+    fn claim_spi_ss(&mut self) {
+        if let Some(ss) = self.digital_pin(board::SPI_SS_PIN) {
+            if !ss.is_output() {
+                ss.as_output(true);
+            }
+        }
+    }
+
+    #[cfg(feature = "radio")]
+    fn radio(&mut self) -> Result<&mut radio::Radio, Error> {
+        match self.radio {
+            Some(ref mut radio) => Ok(radio),
+            None => {
+                let _ = fwriteln!(&mut self.serial, "no radio set up, use radio init");
+                Err(Error::NotSetUp)
+            }
+        }
+    }
+
+    #[cfg(feature = "radio")]
+    fn report_radio_error(&mut self, error: radio::Error) -> Result<(), Error> {
+        let message = match error {
+            radio::Error::NotFound => "no module found",
+            radio::Error::NoAck => "not acknowledged",
+        };
+        let _ = fwriteln!(&mut self.serial, "radio error: {}", message);
+        Err(Error::Device)
+    }
+
+    #[cfg(feature = "can")]
+    fn can(&mut self) -> Result<&can::Mcp2515, Error> {
+        match self.can {
+            Some(ref controller) => Ok(controller),
+            None => {
+                let _ = fwriteln!(&mut self.serial, "no controller set up, use can init");
+                Err(Error::NotSetUp)
+            }
+        }
+    }
+
+    #[cfg(feature = "can")]
+    fn report_can_error(&mut self, error: can::Error) -> Result<(), Error> {
+        let message = match error {
+            can::Error::NotFound => "no controller found",
+            can::Error::Mode => "controller didn't start",
+            can::Error::NotSent => "not acknowledged",
+        };
+        let _ = fwriteln!(&mut self.serial, "can error: {}", message);
+        Err(Error::Device)
+    }
+
+    #[cfg(feature = "sd-log")]
+    fn sd_card(&mut self) -> Result<sd::SdCard, sd::Error> {
+        let cs = self.spi_chip_select();
+        sd::SdCard::init(cs, self.spi.cs)
+    }
+
+    /// Appends a line with the uptime and the value of each logged channel.
+    #[cfg(feature = "sd-log")]
+    fn log_sample(&mut self) -> Result<(), sd::Error> {
+        let Some(ref logger) = self.logger else {
+            return Ok(());
+        };
+        let mut values = heapless::Vec::<u16, { sdlog::MAX_CHANNELS }>::new();
+        let channels = sdlog::Channels::from_slice(logger.channels()).unwrap_or_default();
+        for channel in channels {
+            let value = match channel {
+                sdlog::Channel::Analog(pin) => self.analog_read(pin).unwrap_or(0),
+                sdlog::Channel::Digital(pin) => {
+                    self.digital_pin(pin).map_or(0, |p| u16::from(p.is_high()))
+                }
+            };
+            let _ = values.push(value);
+        }
+        let Some(ref mut logger) = self.logger else {
+            return Ok(());
+        };
+        uwrite!(logger, "{}", millis::millis())?;
+        for value in values {
+            fwrite!(logger, ",{}", value)?;
+        }
+        fwrite!(logger, "\n")
+    }
+
+    #[cfg(feature = "sd-log")]
+    fn report_sd_error(&mut self, error: sd::Error) -> Result<(), Error> {
+        let _ = fwriteln!(&mut self.serial, "sd error: {}", sd_error_name(error));
+        Err(Error::Device)
+    }
+
+    /// Releases a digital pin to act as a 1-Wire bus.
+    fn onewire_bus(&mut self, pin: u8) -> Result<OneWire, Error> {
+        Ok(OneWire::new(self.raw_pin(pin)?))
+    }
+
+    /// Checks that an input follows an output jumpered to it, both high and
+    /// low, and leaves both pins as inputs.
+    fn loopback(&mut self, output: u8, input: u8) -> bool {
+        let mut pass = true;
+        for high in [true, false] {
+            if let Some(p) = self.digital_pin(output) {
+                p.as_output(high);
+            }
+            arduino_hal::delay_us(10);
+            pass &= self.digital_pin(input).map_or(false, |p| {
+                p.as_input();
+                p.is_high() == high
+            });
+        }
+        if let Some(p) = self.digital_pin(output) {
+            p.as_input();
+        }
+        pass
+    }
+
+    /// Measures the supply voltage in millivolts against the bandgap.
+    fn read_vcc(&mut self) -> u32 {
+        // The bandgap needs a moment to settle after switching the
+        // multiplexer over, so the first conversion is discarded.
+        let value = self.with_adc(|adc| {
+            let _ = adc.read_blocking(&adc::channel::Vbg);
+            adc.read_blocking(&adc::channel::Vbg).max(1)
+        });
+        BANDGAP_MILLIVOLTS * 1024 / value as u32
+    }
+
+    /// Checks the supply voltage, if it is due.
+    fn check_power(&mut self) {
+        if !self
+            .powermon
+            .as_mut()
+            .map_or(false, powermon::Monitor::is_due)
+        {
+            return;
+        }
+        let millivolts = self.read_vcc().min(u32::from(u16::MAX)) as u16;
+        let Some(ref mut monitor) = self.powermon else {
+            return;
+        };
+        let (threshold, safe) = (monitor.threshold_mv(), monitor.safe);
+        match monitor.update(millivolts) {
+            Some(alert::Event::Triggered) => {
+                let _ = fwriteln!(
+                    &mut self.serial,
+                    "powermon: vcc {}mV below {}mV",
+                    millivolts,
+                    threshold
+                );
+                if safe {
+                    self.enter_safe_state();
+                }
+            }
+            Some(alert::Event::Cleared) => {
+                let _ = fwriteln!(&mut self.serial, "powermon: vcc {}mV recovered", millivolts);
+            }
+            None => {}
+        }
+    }
+
+    /// Drives every output low, stops everything that toggles outputs on
+    /// its own, and turns on safe mode so that only commands that read run
+    /// until it is turned off.
+    fn enter_safe_state(&mut self) {
+        if let Some(ref mut pid) = self.pid {
+            pid.running = false;
+        }
+        self.pulses = [None, None, None, None];
+        self.fades = [None, None, None, None];
+        wave::stop();
+        self.scroll = None;
+        matrix::stop();
+        self.melody = None;
+        tone::stop();
+        self.pwm.disable();
+        self.led.set_low();
+        for pin in board::DIGITAL_PINS {
+            let _ = softpwm::set(pin, 0);
+            if let Some(p) = self.digital_pin(pin) {
+                if p.is_output() {
+                    p.set_low();
+                }
+            }
+        }
+        self.safe_mode = true;
+    }
+
+    /// Captures and prints samples of pins that have been checked. The
+    /// buffer only takes up the stack while the capture runs.
+    #[inline(never)]
+    fn capture(&mut self, pins: &Pins, rate_hz: u32, samples: usize, format: capture::Format) {
+        let mut raw_pins = Vec::<gpio::RawPin, { capture::MAX_PINS }>::new();
+        for &pin in pins {
+            if let Some(raw) = gpio::RawPin::new(pin) {
+                let _ = raw_pins.push(raw);
+            }
+        }
+        let mut buffer = [0; capture::MAX_SAMPLES as usize];
+        let buffer = &mut buffer[..samples];
+        capture::sample(&raw_pins, rate_hz, buffer);
+        match format {
+            capture::Format::Hex => {
+                for line in buffer.chunks(32) {
+                    for &sample in line {
+                        let _ = uwrite!(&mut self.serial, "{:02X}", sample);
+                    }
+                    let _ = uwriteln!(&mut self.serial, "");
+                }
+            }
+            capture::Format::Rle => {
+                let serial = &mut self.serial;
+                capture::runs(buffer, |sample, length| {
+                    let sample = serial.value(u16::from(sample));
+                    let _ = fwriteln!(serial, "{} {}", sample, length);
+                });
+            }
+            capture::Format::Sump => {
+                capture::write_sump(self.serial.raw(), buffer);
+                self.serial.flush();
+            }
+        }
+    }
+
+    /// Takes a step of the PID loop. A loop whose output cannot be driven is
+    /// stopped, and safe mode or a lock on the output pin holds its output.
+    fn step_pid(&mut self) {
+        let Some(ref pid) = self.pid else {
+            return;
+        };
+        let (input, output) = (pid.input, pid.output);
+        if self.safe_mode || self.locked_pins() & pin_mask(output) != 0 {
+            return;
+        }
+        let value = self.analog_read(input).unwrap_or(0);
+        let Some(ref mut pid) = self.pid else {
+            return;
+        };
+        let duty = pid.step(value);
+        if self.write_pid_output(output, duty).is_err() {
+            if let Some(ref mut pid) = self.pid {
+                pid.running = false;
+            }
+            let _ = fwriteln!(
+                &mut self.serial,
+                "pid: stopped, too many pins, at most {} run software PWM",
+                softpwm::MAX_CHANNELS
+            );
+        }
+    }
+
+    /// Drives the PID output, with hardware PWM on the PWM output and
+    /// software PWM on the other pins.
+    fn write_pid_output(&mut self, pin: u8, duty: u8) -> Result<(), ()> {
+        if pin == board::PWM_PIN {
+            self.pwm.set_duty(duty);
+            self.pwm.enable();
+            Ok(())
+        } else {
+            softpwm::set(pin, duty)
+        }
+    }
+
+    /// Checks that a pin can play tones, and that nothing else uses Timer1,
+    /// for `tone` and `melody`.
+    fn check_tone_pin(&mut self, pin: u8) -> Result<(), Error> {
+        if !tone::PINS.contains(&pin) {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "unknown pin: {}, valid pins are {}",
+                pin,
+                board::TIMER1_PIN_NAMES
+            );
+            return Err(Error::BadPin);
+        }
+        if servo::is_active() || self.sweeps.iter().any(Option::is_some) {
+            let _ = fwriteln!(&mut self.serial, "timer1 is busy with servos");
+            return Err(Error::Busy);
+        }
+        if pwm16::is_active() {
+            let _ = fwriteln!(&mut self.serial, "timer1 is busy with pwm16");
+            return Err(Error::Busy);
+        }
+        if wave::is_active() {
+            let _ = fwriteln!(&mut self.serial, "timer1 is busy with a waveform");
+            return Err(Error::Busy);
+        }
+        self.raw_output_pin(pin)?;
+        Ok(())
+    }
+
+    /// Fails if Timer1 plays a tone or a melody, for its other uses.
+    fn check_tone_idle(&mut self) -> Result<(), Error> {
+        if tone::is_active() || self.melody.is_some() {
+            let _ = fwriteln!(&mut self.serial, "timer1 is busy with a tone");
+            return Err(Error::Busy);
+        }
+        Ok(())
+    }
+
+    /// Sets up the pins of an LED matrix and starts refreshing it, without
+    /// column pins for a charlieplexed one.
+    fn matrix_init(&mut self, rows: &Pins, columns: &Pins) -> Result<(), Error> {
+        self.scroll = None;
+        matrix::stop();
+        let all = || rows.iter().chain(columns.iter());
+        if all()
+            .enumerate()
+            .any(|(i, pin)| all().skip(i + 1).any(|p| p == pin))
+        {
+            let _ = fwriteln!(&mut self.serial, "matrix pins must differ");
+            return Err(Error::BadPin);
+        }
+        for &pin in rows {
+            if columns.is_empty() {
+                self.raw_pin(pin)?.set_level(false);
+            } else {
+                self.raw_output_pin(pin)?;
+            }
+        }
+        for &pin in columns {
+            self.raw_output_pin(pin)?.set_level(true);
+        }
+        matrix::start(rows, columns);
+        Ok(())
+    }
+
+    fn matrix_size(&mut self) -> Result<(usize, usize), Error> {
+        matrix::size().ok_or_else(|| {
+            let _ = fwriteln!(&mut self.serial, "no matrix set up, use matrix init");
+            Error::NotSetUp
+        })
+    }
+
+    /// Turns a digital pin into an output driven low and hands out its
+    /// registers for bit-banging.
+    fn raw_output_pin(&mut self, pin: u8) -> Result<gpio::RawPin, Error> {
+        let Some(p) = self.digital_pin(pin) else {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "unknown pin: {}, valid pins are {}",
+                pin,
+                board::PIN_NAMES
+            );
+            return Err(Error::BadPin);
+        };
+        p.set_low();
+        let Some(raw) = gpio::RawPin::new(pin) else {
+            unreachable!("digital pins are on a port");
+        };
+        Ok(raw)
+    }
+
+    fn report_i2c_error(&mut self, error: i2c::Error) -> Result<(), Error> {
+        let _ = fwriteln!(&mut self.serial, "i2c error: {}", i2c_error_name(error));
+        Err(Error::Device)
+    }
+
+    fn report_imu_error(&mut self, error: imu::Error) -> Result<(), Error> {
+        match error {
+            imu::Error::I2c(error) => self.report_i2c_error(error),
+            imu::Error::UnknownDevice(id) => {
+                let _ = uwriteln!(&mut self.serial, "not an mpu6050, id 0x{:02X}", id);
+                Err(Error::Device)
+            }
+        }
+    }
+
+    /// Averages readings from the load cell amplifier, before taring.
+    fn read_scale(&mut self, samples: u8) -> Result<i32, Error> {
+        let Some(scale) = self.scale.as_ref() else {
+            let _ = fwriteln!(&mut self.serial, "no scale set up, use scale init");
+            return Err(Error::NotSetUp);
+        };
+        if samples == 0 {
+            let _ = fwriteln!(&mut self.serial, "samples must be 1-255");
+            return Err(Error::OutOfRange);
+        }
+        let Some(value) = scale.average(samples) else {
+            let _ = fwriteln!(&mut self.serial, "scale error: timeout");
+            return Err(Error::Device);
+        };
+        Ok(value)
+    }
+
+    /// Checks a servo pin and angle, and makes the pin an output. Returns the
+    /// pin's index into [`servo::PINS`].
+    fn servo_pin(&mut self, pin: u8, angle: u8) -> Result<usize, Error> {
+        let Some(index) = servo::PINS.iter().position(|&p| p == pin) else {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "unknown pin: {}, valid pins are {}",
+                pin,
+                board::TIMER1_PIN_NAMES
+            );
+            return Err(Error::BadPin);
+        };
+        if pwm16::is_active() {
+            let _ = fwriteln!(&mut self.serial, "timer1 is busy with pwm16");
+            return Err(Error::Busy);
+        }
+        if wave::is_active() {
+            let _ = fwriteln!(&mut self.serial, "timer1 is busy with a waveform");
+            return Err(Error::Busy);
+        }
+        self.check_tone_idle()?;
+        if angle > servo::MAX_ANGLE {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "angle out of range, valid angles are 0-{}",
+                servo::MAX_ANGLE
+            );
+            return Err(Error::OutOfRange);
+        }
+        if let Some(p) = self.digital_pin(pin) {
+            p.set_low();
+        }
+        Ok(index)
+    }
+
+    /// Fails a command that needs hardware this board doesn't have.
+    #[cfg(not(feature = "uno"))]
+    fn report_unavailable(&mut self) -> Result<(), Error> {
+        let _ = fwriteln!(&mut self.serial, "not available on this board");
+        Err(Error::UnknownCommand)
+    }
+
+    fn report_no_stepper(&mut self) -> Result<(), Error> {
+        let _ = uwriteln!(
+            &mut self.serial,
+            "no stepper set up, use stepper init <pins…>"
+        );
+        Err(Error::NotSetUp)
+    }
+
+    fn report_no_lcd(&mut self) -> Result<(), Error> {
+        let _ = uwriteln!(
+            &mut self.serial,
+            "no display set up, use lcd init <pins…> or lcd init i2c"
+        );
+        Err(Error::NotSetUp)
+    }
+
+    fn neopixel_strip(&mut self) -> Result<&mut neopixel::Strip, Error> {
+        match self.neopixel {
+            Some(ref mut strip) => Ok(strip),
+            None => {
+                let _ = fwriteln!(
+                    &mut self.serial,
+                    "no strip set up, use neopixel init <pin> <count>"
+                );
+                Err(Error::NotSetUp)
+            }
+        }
+    }
+
+    /// Opens the software serial port, for `sserial open` and `gps start`.
+    fn open_soft_serial(&mut self, rx: u8, tx: u8, baud: u32) -> Result<(), Error> {
+        if rx == tx {
+            let _ = fwriteln!(&mut self.serial, "receive and transmit pins must differ");
+            return Err(Error::BadPin);
+        }
+        if !sserial::BAUD_RATES.contains(&baud) {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "unsupported baud rate: {}, valid rates are 1200-19200",
+                baud
+            );
+            return Err(Error::OutOfRange);
+        }
+        self.check_pin_change(rx)?;
+        let rx_pin = self.raw_pin(rx)?;
+        let tx_pin = self.raw_output_pin(tx)?;
+        // Both lines idle high, and the pull-up keeps an unconnected
+        // receive pin from reading noise as start bits.
+        rx_pin.set_level(true);
+        tx_pin.set_level(true);
+        sserial::open(rx, tx, baud);
+        Ok(())
+    }
+
+    /// Turns a digital pin into a floating input and hands out its registers
+    /// for bit-banging.
+    fn raw_pin(&mut self, pin: u8) -> Result<gpio::RawPin, Error> {
+        let Some(p) = self.digital_pin(pin) else {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "unknown pin: {}, valid pins are {}",
+                pin,
+                board::PIN_NAMES
+            );
+            return Err(Error::BadPin);
+        };
+        p.as_input();
+        let Some(raw) = gpio::RawPin::new(pin) else {
+            unreachable!("digital pins are on a port");
+        };
+        Ok(raw)
+    }
+
+    /// Checks that a digital pin has a pin change interrupt, which every one
+    /// on the Uno does but only some on the Mega.
+    fn check_pin_change(&mut self, pin: u8) -> Result<(), Error> {
+        if self.digital_pin(pin).is_none() || board::pin_change_bit(pin).is_none() {
+            let _ = fwriteln!(
+                &mut self.serial,
+                "unknown pin: {}, valid pins are {}",
+                pin,
+                board::PIN_CHANGE_PIN_NAMES
+            );
+            return Err(Error::BadPin);
+        }
+        Ok(())
+    }
+
+    /// Converts and prints the temperature of one DS18B20, or of every one on
+    /// the bus.
+    fn read_ds18b20(&mut self, bus: &OneWire, rom: Option<Rom>) -> Result<(), onewire::Error> {
+        // All sensors convert at once, then are read out one at a time.
+        ds18b20::convert(bus, rom.as_ref())?;
+        if let Some(rom) = rom {
+            let value = ds18b20::read_centi_celsius(bus, Some(&rom))?;
+            write_rom(&mut self.serial, &rom);
+            let _ = fwriteln!(&mut self.serial, ": {}cC", value);
+            return Ok(());
+        }
+        let mut search = onewire::Search::new();
+        let mut found = false;
+        while let Some(rom) = search.next(bus)? {
+            if rom[0] != ds18b20::FAMILY_CODE {
+                continue;
+            }
+            let value = ds18b20::read_centi_celsius(bus, Some(&rom))?;
+            write_rom(&mut self.serial, &rom);
+            let _ = fwriteln!(&mut self.serial, ": {}cC", value);
+            found = true;
+        }
+        if !found {
+            let _ = fwriteln!(&mut self.serial, "no devices found");
+        }
+        Ok(())
+    }
+
+    /// Turns on safe mode for good if the jumper pin is pulled to ground. All
+    /// pins are still inputs at this point.
+    fn check_safemode_jumper(&mut self) {
+        let pin = self.eeprom.read_byte(storage::SAFEMODE_JUMPER);
+        let Some(raw) = gpio::RawPin::new(pin).filter(|_| board::DIGITAL_PINS.contains(&pin))
+        else {
+            return;
+        };
+        raw.set_level(true);
+        arduino_hal::delay_us(10);
+        let jumpered = !raw.is_high();
+        raw.set_level(false);
+        self.safe_mode = jumpered;
+        self.safe_mode_latched = jumpered;
+    }
+
+    /// Returns the pins locked with `lock`, as a bitmask.
+    fn locked_pins(&self) -> u16 {
+        let mut locked = [0; 2];
+        let _ = self.eeprom.read(storage::LOCKED_PINS, &mut locked);
+        !u16::from_le_bytes(locked)
+    }
+
+    fn set_locked_pins(&mut self, locked: u16) {
+        let _ = self
+            .eeprom
+            .write(storage::LOCKED_PINS, &(!locked).to_le_bytes());
+    }
+
+    /// Checks a code against the one stored with `lock code`, if any.
+    fn check_unlock_code(&mut self, code: &str) -> Result<(), Error> {
+        let stored: String<LINE_LENGTH> = storage::read_str(&self.eeprom, storage::UNLOCK_CODE);
+        if stored != code {
+            let _ = fwriteln!(&mut self.serial, "wrong code");
+            return Err(Error::Locked);
+        }
+        Ok(())
+    }
+
+    /// Stores the current pin directions, output levels, and PWM duty cycle.
+    ///
+    /// Directions and levels are saved as bitmasks indexed by pin number, so
+    /// pins above d15 are not saved and come back as inputs.
+    fn save_pins(&mut self) {
+        let mut outputs = 1u16 << LED_PIN;
+        let mut levels = u16::from(self.led.is_set_high()) << LED_PIN;
+        for pin in board::DIGITAL_PINS {
+            let Some(p) = self.digital_pin(pin) else {
+                continue;
+            };
+            if p.is_output() {
+                outputs |= pin_mask(pin);
+                if p.is_high() {
+                    levels |= pin_mask(pin);
+                }
+            }
+        }
+        let [outputs_low, outputs_high] = outputs.to_le_bytes();
+        let [levels_low, levels_high] = levels.to_le_bytes();
+        let config = [
+            storage::PIN_CONFIG_MAGIC,
+            outputs_low,
+            outputs_high,
+            levels_low,
+            levels_high,
+            self.pwm.get_duty(),
+        ];
+        let _ = self.eeprom.write(storage::PIN_CONFIG, &config);
+    }
+
+    /// Restores the pin configuration stored by [`Crab::save_pins`].
+    ///
+    /// Outputs are switched to their saved level directly, without passing
+    /// through the opposite level.
+    fn load_pins(&mut self) -> Result<(), ()> {
+        let mut config = [0; 6];
+        self.eeprom
+            .read(storage::PIN_CONFIG, &mut config)
+            .map_err(|_| ())?;
+        if config[0] != storage::PIN_CONFIG_MAGIC {
+            return Err(());
+        }
+        let outputs = u16::from_le_bytes([config[1], config[2]]);
+        let levels = u16::from_le_bytes([config[3], config[4]]);
+        for pin in board::DIGITAL_PINS {
+            let Some(p) = self.digital_pin(pin) else {
+                continue;
+            };
+            match (outputs & pin_mask(pin) != 0, levels & pin_mask(pin) != 0) {
+                (true, true) => p.set_high(),
+                (true, false) => p.set_low(),
+                (false, _) => p.as_input(),
+            }
+        }
+        if levels & (1 << LED_PIN) != 0 {
+            self.led.set_high();
+        } else {
+            self.led.set_low();
+        }
+        let duty_cycle = config[5];
+        self.pwm.set_duty(duty_cycle);
+        if duty_cycle > 0 {
+            self.pwm.enable();
+        } else {
+            self.pwm.disable();
+        }
+        Ok(())
+    }
+}
+
+impl io::Io for Crab {
+    type Output = Console<Serial>;
+
+    const DIGITAL_PINS: &'static str = board::OUTPUT_PIN_NAMES;
+    const ANALOG_PINS: &'static str = board::ANALOG_PIN_NAMES;
+
+    fn output(&mut self) -> &mut Console<Serial> {
+        &mut self.serial
+    }
+
+    fn base(&self) -> console::Base {
+        self.serial.base
+    }
+
+    fn digital_read(&mut self, pin: u8) -> Option<bool> {
+        if pin == LED_PIN {
+            return Some(self.led.is_set_high());
+        }
+        self.digital_pin(pin).map(|p| p.is_high())
+    }
+
+    fn digital_write(&mut self, pin: u8, high: bool) -> Option<()> {
+        self.cancel_pulses(&[pin]);
+        if pin == LED_PIN {
+            if high {
+                self.led.set_high();
+            } else {
+                self.led.set_low();
+            }
+            return Some(());
+        }
+        let p = self.digital_pin(pin)?;
+        if high {
+            p.set_high();
+        } else {
+            p.set_low();
+        }
+        Some(())
+    }
+
+    fn analog_read(&mut self, pin: u8) -> Option<u16> {
+        Crab::analog_read(self, pin)
+    }
+
+    fn describe_pin(&mut self, pin: u8) {
+        if let Some(name) = names::get(&self.eeprom, pin) {
+            let _ = fwriteln!(&mut self.serial, "name: {}", name.as_str());
+        }
+    }
+
+    fn run(&mut self, _input: &str, command: Command<'_>) -> Result<(), Error> {
+        self.execute(command)
+    }
+
+    fn begin_command(&mut self, input: &str) {
+        stats::count(&mut self.stats.commands, 1);
+        self.serial.begin_response(input);
+    }
+
+    fn end_command(&mut self, result: Result<(), Error>) {
+        self.heartbeat.command_ran(result.err().map(Error::number));
+        let error = result.err().map(Error::code);
+        let _ = if self.background {
+            self.serial.end_event(error)
+        } else {
+            self.serial.end_response(error)
+        };
+    }
+
+    fn with_resolved<R>(&mut self, input: &str, parse: impl FnOnce(&mut Self, &str) -> R) -> R {
+        let resolved = self.resolve_names(input);
+        parse(self, resolved.as_deref().unwrap_or(input))
+    }
+
+    fn invalid_command(&mut self, input: &str) -> Error {
+        stats::count(&mut self.stats.parse_errors, 1);
+        let _ = fwriteln!(&mut self.serial, "invalid command: {}", input);
+        if self.serial.mode == console::Mode::Human {
+            let _ = uwriteln!(&mut self.serial, "{}", help());
+        }
+        Error::UnknownCommand
+    }
+}
+
+enum AnyPin {
+    DigitalIn(Pin<Input<Floating>, Dynamic>),
+    DigitalOut(Pin<Output, Dynamic>),
+}
+
+impl AnyPin {
+    fn as_input(&mut self) {
+        *self = match self {
+            AnyPin::DigitalIn(_) => return,
+            AnyPin::DigitalOut(pin) => {
+                let fake_pin = unsafe { core::mem::zeroed() };
+                AnyPin::DigitalIn(
+                    core::mem::replace(pin, fake_pin)
+                        .into_floating_input()
+                        .downgrade(),
+                )
+            }
+        }
+    }
+
+    /// Turns the pin into an output, starting out at the given level if it was
+    /// an input before.
+    fn as_output(&mut self, high: bool) {
+        *self = match self {
+            AnyPin::DigitalIn(ref mut pin) => {
+                let fake_pin = unsafe { core::mem::zeroed() };
+                let pin = core::mem::replace(pin, fake_pin);
+                let pin = if high {
+                    pin.into_output_high()
+                } else {
+                    pin.into_output()
+                };
+                AnyPin::DigitalOut(pin.downgrade())
+            }
+            AnyPin::DigitalOut(_) => return,
+        };
+    }
+
+    fn is_output(&self) -> bool {
+        matches!(self, AnyPin::DigitalOut(_))
+    }
+
+    fn is_high(&self) -> bool {
+        match self {
+            AnyPin::DigitalIn(pin) => pin.is_high(),
+            AnyPin::DigitalOut(pin) => pin.is_set_high(),
+        }
+    }
+
+    fn set_high(&mut self) {
+        self.as_output(true);
+        if let AnyPin::DigitalOut(pin) = self {
+            pin.set_high();
+        } else {
+            unreachable!("pin is not an output");
+        }
+    }
+
+    fn set_low(&mut self) {
+        self.as_output(false);
+        if let AnyPin::DigitalOut(pin) = self {
+            pin.set_low();
+        } else {
+            unreachable!("pin is not an output");
+        }
+    }
+}
+
+/// Appends a pin number to a command being put together.
+fn push_pin<const N: usize>(line: &mut String<N>, pin: u8) -> Option<()> {
+    if pin >= 10 {
+        line.push(char::from(b'0' + pin / 10)).ok()?;
+    }
+    line.push(char::from(b'0' + pin % 10)).ok()
+}
+
+/// The bit of a pin in the bitmasks of pins, such as the locked ones, which
+/// only cover d0-d15.
+fn pin_mask(pin: u8) -> u16 {
+    1u16.checked_shl(pin.into()).unwrap_or(0)
+}
+
+fn outcome(pass: bool) -> &'static str {
+    if pass {
+        "pass"
+    } else {
+        "fail"
+    }
+}
+
+fn port_name(port: gpio::Port) -> &'static str {
+    match port {
+        gpio::Port::B => "b",
+        gpio::Port::C => "c",
+        gpio::Port::D => "d",
+    }
+}
+
+/// Rejects I2C addresses outside the 7-bit range, which the TWI peripheral
+/// would otherwise silently truncate.
+fn check_i2c_address<W: ufmt::uWrite>(serial: &mut W, address: u8) -> Result<(), Error> {
+    if address > 0x7F {
+        let _ = fwriteln!(serial, "address out of range, I2C addresses are 0-127");
+        return Err(Error::OutOfRange);
+    }
+    Ok(())
+}
+
+/// Checks that a bus transfer length fits in a read buffer of `MAX_BYTES`.
+fn check_length<W: ufmt::uWrite>(serial: &mut W, len: u8) -> Result<usize, Error> {
+    let len = usize::from(len);
+    if len == 0 || len > MAX_BYTES {
+        let _ = fwriteln!(
+            serial,
+            "length out of range, valid lengths are 1-{}",
+            MAX_BYTES
+        );
+        return Err(Error::OutOfRange);
+    }
+    Ok(len)
+}
+
+/// Fires an ultrasonic ranging pulse and returns the length of the echo pulse
+/// in microseconds, if one arrives.
+fn measure_echo(trig: gpio::RawPin, echo: gpio::RawPin) -> Option<u32> {
+    trig.set_level(true);
+    arduino_hal::delay_us(10);
+    trig.set_level(false);
+
+    let start = millis::micros();
+    while !echo.is_high() {
+        if millis::micros().wrapping_sub(start) > SONAR_TIMEOUT_US {
+            return None;
+        }
+    }
+    let rise = millis::micros();
+    while echo.is_high() {
+        if millis::micros().wrapping_sub(rise) > SONAR_TIMEOUT_US {
+            return None;
+        }
+    }
+    Some(millis::micros().wrapping_sub(rise))
+}
+
+/// Writes a value given in tenths with one decimal place.
+fn write_tenths<W: ufmt::uWrite>(serial: &mut W, value: i32) {
+    let sign = if value < 0 { "-" } else { "" };
+    let value = value.unsigned_abs();
+    let _ = fwrite!(serial, "{}{}.{}", sign, value / 10, value % 10);
+}
+
+#[cfg(feature = "sd-log")]
+fn sd_error_name(error: sd::Error) -> &'static str {
+    match error {
+        sd::Error::NoCard => "no card",
+        sd::Error::Init => "card not supported",
+        sd::Error::Command(_) => "command rejected",
+        sd::Error::Data => "data error",
+        sd::Error::Timeout => "timeout",
+    }
+}
+
+fn write_rom<W: ufmt::uWrite>(serial: &mut W, rom: &Rom) {
+    for byte in rom {
+        let _ = uwrite!(serial, "{:02X}", *byte);
+    }
+}
+
+fn onewire_error_name(error: onewire::Error) -> &'static str {
+    match error {
+        onewire::Error::NoPresence => "no device present",
+        onewire::Error::Crc => "bad checksum",
+        onewire::Error::Timeout => "timeout",
+    }
+}
+
+fn i2c_error_name(error: i2c::Error) -> &'static str {
+    match error {
+        i2c::Error::ArbitrationLost => "arbitration lost",
+        i2c::Error::AddressNack => "no acknowledge for address",
+        i2c::Error::DataNack => "no acknowledge for data",
+        i2c::Error::BusError => "bus error",
+        i2c::Error::Unknown => "unknown",
+    }
+}
+
+/// Reads the internal temperature sensor, which the ATmega2560 lacks.
+///
+/// The sensor is only meaningful against the internal 1.1 V reference, so the
+/// reference is switched over for the conversion and restored to AVcc after.
+#[cfg(feature = "uno")]
+fn read_temperature(adc: &mut arduino_hal::Adc) -> u16 {
+    // SAFETY: The ADC driver only ever modifies the MUX bits of ADMUX after
+    // initialisation, so changing the reference bits underneath it is fine.
+    let admux = unsafe { &(*arduino_hal::pac::ADC::ptr()).admux };
+    admux.modify(|_, w| w.refs().internal());
+    // The reference capacitor needs to discharge to the new voltage, and the
+    // first conversion after switching is inaccurate either way.
+    arduino_hal::delay_ms(5);
+    let _ = adc.read_blocking(&adc::channel::Temperature);
+    let value = adc.read_blocking(&adc::channel::Temperature);
+    admux.modify(|_, w| w.refs().avcc());
+    value
+}
+
+/// Returns the number of bytes between the end of the static data and the
+/// current top of the stack.
+///
+/// Nothing is allocated on a heap, so this is all the memory left for the stack
+/// to grow into.
+fn free_ram() -> u16 {
+    extern "C" {
+        /// End of `.data` and `.bss`, defined by the avr-libc linker script.
+        static __heap_start: u8;
+    }
+    let marker = 0u8;
+    let stack = core::ptr::addr_of!(marker) as u16;
+    // SAFETY: Only the address of the symbol is taken, it is never read.
+    let statics_end = unsafe { core::ptr::addr_of!(__heap_start) } as u16;
+    stack.saturating_sub(statics_end)
+}
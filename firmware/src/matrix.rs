@@ -0,0 +1,286 @@
+//! LED matrices of up to 8x8, refreshed from the millisecond tick.
+//!
+//! Each tick turns off the row that is lit and lights the next one, so an
+//! 8x8 matrix is refreshed at 125 Hz with each row lit an eighth of the time.
+//!
+//! A multiplexed matrix has a pin per row, wired to the anodes and driven
+//! high to light the row, and a pin per column, wired to the cathodes
+//! through resistors and driven low to light a pixel of the lit row.
+//!
+//! A charlieplexed matrix of n pins has n rows of n - 1 LEDs. Row r has its
+//! anodes on pin r, and the cathodes of its LEDs on the other pins in order.
+//! While a row is lit, the cathodes to light are driven low and every other
+//! pin but the anode floats.
+
+use core::cell::RefCell;
+
+use avr_device::interrupt::{self, CriticalSection, Mutex};
+use heapless::{String, Vec};
+
+use crate::gpio::RawPin;
+use crate::{millis, progmem};
+
+/// The most rows, and the most columns.
+pub const MAX_LINES: usize = 8;
+
+/// The longest text that can scroll.
+pub const MAX_TEXT: usize = 32;
+
+/// How long the text stays put before it moves on by a column.
+const SCROLL_MS: u32 = 100;
+
+const GLYPH_WIDTH: usize = 5;
+/// A glyph and the blank column after it.
+const GLYPH_STEP: usize = GLYPH_WIDTH + 1;
+
+/// The characters of [`FONT`], in order. Anything else is shown as `?`.
+const CHARACTERS: &str = " !-.:?0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// A 5x7 font, a byte per column from the left with the top row in bit 0.
+#[link_section = ".progmem.data"]
+static FONT: [[u8; GLYPH_WIDTH]; 42] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x5F, 0x00, 0x00],
+    [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x60, 0x60, 0x00, 0x00],
+    [0x00, 0x36, 0x36, 0x00, 0x00],
+    [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x3E, 0x51, 0x49, 0x45, 0x3E],
+    [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46],
+    [0x21, 0x41, 0x45, 0x4B, 0x31],
+    [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3C, 0x4A, 0x49, 0x49, 0x30],
+    [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36],
+    [0x06, 0x49, 0x49, 0x29, 0x1E],
+    [0x7E, 0x11, 0x11, 0x11, 0x7E],
+    [0x7F, 0x49, 0x49, 0x49, 0x36],
+    [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x22, 0x1C],
+    [0x7F, 0x49, 0x49, 0x49, 0x41],
+    [0x7F, 0x09, 0x09, 0x09, 0x01],
+    [0x3E, 0x41, 0x49, 0x49, 0x7A],
+    [0x7F, 0x08, 0x08, 0x08, 0x7F],
+    [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01],
+    [0x7F, 0x08, 0x14, 0x22, 0x41],
+    [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F],
+    [0x7F, 0x04, 0x08, 0x10, 0x7F],
+    [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06],
+    [0x3E, 0x41, 0x51, 0x21, 0x5E],
+    [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7F, 0x01, 0x01],
+    [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F],
+    [0x3F, 0x40, 0x38, 0x40, 0x3F],
+    [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x07, 0x08, 0x70, 0x08, 0x07],
+    [0x61, 0x51, 0x49, 0x45, 0x43],
+];
+
+struct Matrix {
+    /// The row pins, which are all the pins of a charlieplexed matrix.
+    rows: Vec<u8, MAX_LINES>,
+    /// The column pins, empty for a charlieplexed matrix.
+    columns: Vec<u8, MAX_LINES>,
+    /// A byte per row, with the leftmost column in bit 0.
+    pixels: [u8; MAX_LINES],
+    /// The row that is lit.
+    row: usize,
+}
+
+static MATRIX: Mutex<RefCell<Option<Matrix>>> = Mutex::new(RefCell::new(None));
+
+impl Matrix {
+    fn is_charlieplexed(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    fn width(&self) -> usize {
+        if self.is_charlieplexed() {
+            self.rows.len() - 1
+        } else {
+            self.columns.len()
+        }
+    }
+
+    /// The pin of a column's cathodes in a row.
+    fn cathode(&self, row: usize, column: usize) -> u8 {
+        if self.is_charlieplexed() {
+            self.rows[if column < row { column } else { column + 1 }]
+        } else {
+            self.columns[column]
+        }
+    }
+
+    fn blank(&self, row: usize) {
+        let Some(anode) = RawPin::new(self.rows[row]) else {
+            return;
+        };
+        anode.set_level(false);
+        if self.is_charlieplexed() {
+            anode.set_output(false);
+            for column in 0..self.width() {
+                if let Some(cathode) = RawPin::new(self.cathode(row, column)) {
+                    cathode.set_output(false);
+                }
+            }
+        }
+    }
+
+    fn light(&self, row: usize) {
+        for column in 0..self.width() {
+            let lit = self.pixels[row] & (1 << column) != 0;
+            if let Some(cathode) = RawPin::new(self.cathode(row, column)) {
+                if self.is_charlieplexed() {
+                    cathode.set_output(lit);
+                } else {
+                    cathode.set_level(!lit);
+                }
+            }
+        }
+        if let Some(anode) = RawPin::new(self.rows[row]) {
+            anode.set_level(true);
+            anode.set_output(true);
+        }
+    }
+}
+
+/// Starts refreshing a matrix, blank at first. The pins of a multiplexed
+/// matrix have to be outputs, and those of a charlieplexed one, for which
+/// `columns` is empty, floating inputs.
+pub fn start(rows: &[u8], columns: &[u8]) {
+    stop();
+    let matrix = Matrix {
+        rows: Vec::from_slice(rows).unwrap_or_default(),
+        columns: Vec::from_slice(columns).unwrap_or_default(),
+        pixels: [0; MAX_LINES],
+        row: 0,
+    };
+    interrupt::free(|cs| *MATRIX.borrow(cs).borrow_mut() = Some(matrix));
+}
+
+/// Stops refreshing, and turns the matrix off.
+pub fn stop() {
+    let matrix = interrupt::free(|cs| MATRIX.borrow(cs).borrow_mut().take());
+    if let Some(matrix) = matrix {
+        matrix.blank(matrix.row);
+    }
+}
+
+/// The columns and rows of the matrix, if one is set up.
+pub fn size() -> Option<(usize, usize)> {
+    interrupt::free(|cs| {
+        let matrix = MATRIX.borrow(cs).borrow();
+        matrix.as_ref().map(|m| (m.width(), m.rows.len()))
+    })
+}
+
+/// Turns a pixel on or off, counting from the top left.
+pub fn set_pixel(x: usize, y: usize, on: bool) {
+    interrupt::free(|cs| {
+        if let Some(matrix) = MATRIX.borrow(cs).borrow_mut().as_mut() {
+            if let Some(row) = matrix.pixels.get_mut(y) {
+                if on {
+                    *row |= 1 << x;
+                } else {
+                    *row &= !(1 << x);
+                }
+            }
+        }
+    })
+}
+
+/// Shows a whole picture, a byte per row.
+pub fn show(pixels: [u8; MAX_LINES]) {
+    interrupt::free(|cs| {
+        if let Some(matrix) = MATRIX.borrow(cs).borrow_mut().as_mut() {
+            matrix.pixels = pixels;
+        }
+    })
+}
+
+/// Lights the next row. Called from the millisecond tick.
+pub fn update(cs: CriticalSection) {
+    if let Some(matrix) = MATRIX.borrow(cs).borrow_mut().as_mut() {
+        matrix.blank(matrix.row);
+        matrix.row = (matrix.row + 1) % matrix.rows.len();
+        matrix.light(matrix.row);
+    }
+}
+
+/// A column of a glyph.
+fn glyph_column(c: char, column: usize) -> u8 {
+    let index = CHARACTERS
+        .find(c.to_ascii_lowercase())
+        .or_else(|| CHARACTERS.find('?'))
+        .unwrap_or(0);
+    // SAFETY: The index is within the font, which is in flash. The font is
+    // only ever addressed through raw pointers.
+    unsafe {
+        progmem::read_byte(
+            core::ptr::addr_of!(FONT)
+                .cast::<u8>()
+                .add(index * GLYPH_WIDTH + column),
+        )
+    }
+}
+
+/// Text scrolling through the matrix from the right, over and over.
+pub struct Scroll {
+    text: String<MAX_TEXT>,
+    /// How many columns the text has moved on.
+    offset: usize,
+    last_step: u32,
+}
+
+impl Scroll {
+    /// Fails if the text is too long.
+    pub fn new(text: &str) -> Option<Self> {
+        Some(Scroll {
+            text: String::try_from(text).ok()?,
+            offset: 0,
+            last_step: millis::millis().wrapping_sub(SCROLL_MS),
+        })
+    }
+
+    /// Moves the text on if it is due. Returns `false` once the matrix is
+    /// gone.
+    pub fn poll(&mut self) -> bool {
+        let Some((width, height)) = size() else {
+            return false;
+        };
+        let now = millis::millis();
+        if now.wrapping_sub(self.last_step) < SCROLL_MS {
+            return true;
+        }
+        self.last_step = now;
+        let mut pixels = [0; MAX_LINES];
+        for x in 0..width {
+            // The text starts off just right of the matrix.
+            let Some(column) = (self.offset + x).checked_sub(width) else {
+                continue;
+            };
+            let Some(c) = self.text.chars().nth(column / GLYPH_STEP) else {
+                continue;
+            };
+            if column % GLYPH_STEP == GLYPH_WIDTH {
+                continue;
+            }
+            let bits = glyph_column(c, column % GLYPH_STEP);
+            for (y, row) in pixels.iter_mut().enumerate().take(height) {
+                if bits & (1 << y) != 0 {
+                    *row |= 1 << x;
+                }
+            }
+        }
+        show(pixels);
+        self.offset = (self.offset + 1) % (width + self.text.len() * GLYPH_STEP);
+        true
+    }
+}
@@ -0,0 +1,65 @@
+//! RTTTL melodies played on [`crate::tone`] in the background.
+//!
+//! Each note sounds for seven eighths of its duration and is silent for the
+//! rest, so that repeated notes can be told apart.
+
+use heapless::String;
+
+use protocol::rtttl::{self, Defaults};
+
+use crate::{millis, tone, INPUT_LENGTH};
+
+pub struct Melody {
+    pin: u8,
+    song: String<INPUT_LENGTH>,
+    defaults: Defaults,
+    /// Where the next note starts in `song`.
+    position: usize,
+    /// When the current note started, and how long it lasts.
+    started: u32,
+    duration_ms: u32,
+}
+
+impl Melody {
+    /// Starts playing a melody on one of [`tone::PINS`], which has to be an
+    /// output driven low. Returns `None` if the melody doesn't parse.
+    pub fn start(pin: u8, song: &str) -> Option<Self> {
+        let (defaults, position) = rtttl::parse(song)?;
+        let now = millis::millis();
+        Some(Melody {
+            pin,
+            song: String::try_from(song).ok()?,
+            defaults,
+            position,
+            started: now,
+            duration_ms: 0,
+        })
+    }
+
+    /// Moves on to the next note when it is due, and returns whether the
+    /// melody is still playing.
+    pub fn poll(&mut self) -> bool {
+        let now = millis::millis();
+        let elapsed = now.wrapping_sub(self.started);
+        if elapsed < self.duration_ms {
+            if elapsed >= self.duration_ms * 7 / 8 {
+                tone::stop();
+            }
+            return true;
+        }
+        let Some((note, len)) = rtttl::next_note(&self.song[self.position..], &self.defaults)
+        else {
+            tone::stop();
+            return false;
+        };
+        self.position += len;
+        if note.frequency == 0 {
+            tone::stop();
+        } else {
+            tone::start(self.pin, note.frequency);
+        }
+        self.started = now;
+        self.duration_ms = u32::from(note.duration_ms);
+        true
+    }
+}
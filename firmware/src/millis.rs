@@ -0,0 +1,117 @@
+//! Millisecond tick counter.
+//!
+//! Timer0 is already taken by the PWM output, so Timer2 runs in CTC mode and
+//! fires a compare-match interrupt once every millisecond. The 32U4 has no
+//! Timer2, so there it is Timer3, counting the same way. The tick also
+//! starts each frame of [`crate::softpwm`], steps [`crate::wave`], and refreshes
+//! [`crate::matrix`].
+
+use core::cell::Cell;
+
+use avr_device::interrupt::Mutex;
+
+use crate::{matrix, softpwm, wave};
+
+const PRESCALER: u32 = 64;
+const TIMER_COUNTS: u32 = 250;
+
+const MILLIS_INCREMENT: u32 = PRESCALER * TIMER_COUNTS / 16_000;
+
+/// Microseconds per timer count, at 16 MHz.
+const MICROS_PER_COUNT: u32 = PRESCALER / 16;
+
+static MILLIS_COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// Starts the millisecond tick, taking ownership of Timer2.
+///
+/// Interrupts have to be enabled globally for the counter to advance.
+#[cfg(not(feature = "leonardo"))]
+pub fn init(tc2: arduino_hal::pac::TC2) {
+    tc2.tccr2a.write(|w| w.wgm2().ctc());
+    tc2.ocr2a.write(|w| w.bits((TIMER_COUNTS - 1) as u8));
+    tc2.tccr2b.write(|w| w.cs2().prescale_64());
+    tc2.timsk2.write(|w| w.ocie2a().set_bit());
+
+    avr_device::interrupt::free(|cs| MILLIS_COUNTER.borrow(cs).set(0));
+}
+
+/// Starts the millisecond tick, taking ownership of Timer3.
+///
+/// Interrupts have to be enabled globally for the counter to advance.
+#[cfg(feature = "leonardo")]
+pub fn init(tc3: arduino_hal::pac::TC3) {
+    // CTC mode with the top in OCR3A is WGM3 0b0100, split over both
+    // control registers.
+    tc3.tccr3a.write(|w| w.wgm3().bits(0));
+    tc3.ocr3a.write(|w| w.bits((TIMER_COUNTS - 1) as u16));
+    tc3.tccr3b
+        .write(|w| w.wgm3().bits(0b01).cs3().prescale_64());
+    tc3.timsk3.write(|w| w.ocie3a().set_bit());
+
+    avr_device::interrupt::free(|cs| MILLIS_COUNTER.borrow(cs).set(0));
+}
+
+/// Returns the timer count, and whether a compare match is pending.
+#[cfg(not(feature = "leonardo"))]
+fn counts() -> (u32, bool) {
+    // SAFETY: The timer registers are only read here, after `init` set them up.
+    let tc2 = unsafe { &*arduino_hal::pac::TC2::ptr() };
+    let counts = tc2.tcnt2.read().bits();
+    (u32::from(counts), tc2.tifr2.read().ocf2a().bit_is_set())
+}
+
+#[cfg(feature = "leonardo")]
+fn counts() -> (u32, bool) {
+    // SAFETY: The timer registers are only read here, after `init` set them up.
+    let tc3 = unsafe { &*arduino_hal::pac::TC3::ptr() };
+    let counts = tc3.tcnt3.read().bits();
+    (u32::from(counts), tc3.tifr3.read().ocf3a().bit_is_set())
+}
+
+/// Returns the number of milliseconds since [`init`] was called.
+pub fn millis() -> u32 {
+    avr_device::interrupt::free(|cs| MILLIS_COUNTER.borrow(cs).get())
+}
+
+/// Returns the number of microseconds since [`init`] was called, with a
+/// resolution of 4 µs.
+///
+/// This wraps around after about 71 minutes, so it is only meant for
+/// measuring short intervals.
+pub fn micros() -> u32 {
+    avr_device::interrupt::free(|cs| {
+        let mut millis = MILLIS_COUNTER.borrow(cs).get();
+        let (counts, pending) = counts();
+        // A compare match may be pending since interrupts were disabled, in
+        // which case the count has already started over.
+        if pending && counts < TIMER_COUNTS - 1 {
+            millis = millis.wrapping_add(MILLIS_INCREMENT);
+        }
+        millis
+            .wrapping_mul(1000)
+            .wrapping_add(counts * MICROS_PER_COUNT)
+    })
+}
+
+#[cfg_attr(feature = "uno", avr_device::interrupt(atmega328p))]
+#[cfg_attr(feature = "mega2560", avr_device::interrupt(atmega2560))]
+#[cfg(not(feature = "leonardo"))]
+fn TIMER2_COMPA() {
+    tick();
+}
+
+#[cfg(feature = "leonardo")]
+#[avr_device::interrupt(atmega32u4)]
+fn TIMER3_COMPA() {
+    tick();
+}
+
+fn tick() {
+    avr_device::interrupt::free(|cs| {
+        let counter = MILLIS_COUNTER.borrow(cs);
+        counter.set(counter.get().wrapping_add(MILLIS_INCREMENT));
+        softpwm::start_frame(cs);
+        wave::update(cs);
+        matrix::update(cs);
+    })
+}
@@ -0,0 +1,228 @@
+//! Modbus RTU slave.
+//!
+//! Coils and discrete inputs 0-13 are the digital pins d0-d13, where the
+//! coils read back the levels of the outputs and the discrete inputs the
+//! levels of all pins. Input registers 0-3 hold the analog readings of
+//! a0-a3.
+//!
+//! Frames end with a pause of at least 1.75 ms, as recommended for fast baud
+//! rates. The millisecond tick only tells that a pause has begun within the
+//! last 2 ms, so the crab takes up to 3 ms to respond.
+
+use embedded_hal::serial::Write;
+use heapless::Vec;
+use nb::block;
+
+use crate::millis;
+
+/// Requests to this address are carried out by every slave, which don't
+/// respond to them.
+pub const BROADCAST: u8 = 0;
+/// Highest address a slave can have.
+pub const MAX_ADDRESS: u8 = 247;
+
+/// Number of coils and discrete inputs.
+pub const BITS: u16 = 14;
+/// Number of input registers.
+pub const REGISTERS: u16 = 4;
+
+/// Longest request accepted.
+const MAX_FRAME: usize = 32;
+/// Longest response sent, before the CRC.
+pub const MAX_RESPONSE: usize = 16;
+
+/// Milliseconds of silence that end a frame, see the module documentation.
+const FRAME_GAP_MS: u32 = 2;
+
+const READ_COILS: u8 = 0x01;
+const READ_DISCRETE_INPUTS: u8 = 0x02;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_COIL: u8 = 0x05;
+const WRITE_MULTIPLE_COILS: u8 = 0x0F;
+
+/// Set in the function code of an exception response.
+const EXCEPTION: u8 = 0x80;
+
+#[derive(Clone, Copy)]
+/// Exception codes, named illegal function, data address, and data value in
+/// the specification.
+pub enum Exception {
+    UnknownFunction = 1,
+    BadAddress = 2,
+    BadValue = 3,
+}
+
+pub enum Request<'a> {
+    ReadCoils {
+        start: u16,
+        count: u16,
+    },
+    ReadDiscreteInputs {
+        start: u16,
+        count: u16,
+    },
+    ReadInputRegisters {
+        start: u16,
+        count: u16,
+    },
+    WriteCoil {
+        address: u16,
+        on: bool,
+    },
+    /// Coil values packed eight to a byte, least significant bit first.
+    WriteCoils {
+        start: u16,
+        count: u16,
+        values: &'a [u8],
+    },
+}
+
+/// A received frame, with the CRC checked.
+pub struct Frame<'a> {
+    pub address: u8,
+    pub function: u8,
+    data: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    /// Splits a frame into its parts, if the CRC matches.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        let [ref content @ .., low, high] = *bytes else {
+            return None;
+        };
+        let [address, function, ref data @ ..] = *content else {
+            return None;
+        };
+        if crc16(content) != u16::from_le_bytes([low, high]) {
+            return None;
+        }
+        Some(Frame {
+            address,
+            function,
+            data,
+        })
+    }
+
+    pub fn request(&self) -> Result<Request<'a>, Exception> {
+        let word = |i: usize| u16::from_be_bytes([self.data[i], self.data[i + 1]]);
+        match (self.function, self.data.len()) {
+            (READ_COILS, 4) => Ok(Request::ReadCoils {
+                start: word(0),
+                count: word(2),
+            }),
+            (READ_DISCRETE_INPUTS, 4) => Ok(Request::ReadDiscreteInputs {
+                start: word(0),
+                count: word(2),
+            }),
+            (READ_INPUT_REGISTERS, 4) => Ok(Request::ReadInputRegisters {
+                start: word(0),
+                count: word(2),
+            }),
+            (WRITE_SINGLE_COIL, 4) => match word(2) {
+                0xFF00 => Ok(Request::WriteCoil {
+                    address: word(0),
+                    on: true,
+                }),
+                0x0000 => Ok(Request::WriteCoil {
+                    address: word(0),
+                    on: false,
+                }),
+                _ => Err(Exception::BadValue),
+            },
+            (WRITE_MULTIPLE_COILS, len) if len >= 5 => {
+                let count = word(2);
+                let values = &self.data[5..];
+                if usize::from(self.data[4]) != values.len()
+                    || values.len() != usize::from(count).div_ceil(8)
+                {
+                    return Err(Exception::BadValue);
+                }
+                Ok(Request::WriteCoils {
+                    start: word(0),
+                    count,
+                    values,
+                })
+            }
+            (READ_COILS | READ_DISCRETE_INPUTS | READ_INPUT_REGISTERS, _)
+            | (WRITE_SINGLE_COIL | WRITE_MULTIPLE_COILS, _) => Err(Exception::BadValue),
+            _ => Err(Exception::UnknownFunction),
+        }
+    }
+
+    /// The request data, which some responses repeat.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+/// Collects bytes into frames, which end with a pause.
+pub struct Receiver {
+    bytes: Vec<u8, MAX_FRAME>,
+    overlong: bool,
+    last_byte: u32,
+}
+
+impl Receiver {
+    pub const fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            overlong: false,
+            last_byte: 0,
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) {
+        self.overlong |= self.bytes.push(byte).is_err();
+        self.last_byte = millis::millis();
+    }
+
+    /// Returns the bytes of a frame that has ended. Overlong frames are
+    /// dropped.
+    pub fn take_frame(&mut self) -> Option<Vec<u8, MAX_FRAME>> {
+        if self.bytes.is_empty() || millis::millis().wrapping_sub(self.last_byte) < FRAME_GAP_MS {
+            return None;
+        }
+        let bytes = core::mem::take(&mut self.bytes);
+        let overlong = core::mem::take(&mut self.overlong);
+        (!overlong).then_some(bytes)
+    }
+}
+
+/// CRC-16 as used by Modbus, which is reflected with an initial value of
+/// 0xFFFF.
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    for &byte in bytes {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Sends a response, or an exception response if the request failed.
+pub fn respond<W: Write<u8>>(serial: &mut W, frame: &Frame<'_>, result: Result<&[u8], Exception>) {
+    let mut content = Vec::<u8, { MAX_RESPONSE + 2 }>::new();
+    match result {
+        Ok(data) => {
+            let _ = content.extend_from_slice(&[frame.address, frame.function]);
+            let _ = content.extend_from_slice(data);
+        }
+        Err(exception) => {
+            let _ = content.extend_from_slice(&[
+                frame.address,
+                frame.function | EXCEPTION,
+                exception as u8,
+            ]);
+        }
+    }
+    let crc = crc16(&content);
+    for &byte in content.iter().chain(&crc.to_le_bytes()) {
+        let _ = block!(serial.write(byte));
+    }
+}
@@ -0,0 +1,23 @@
+//! DC motors behind an L298N-style H-bridge.
+//!
+//! Each motor has two direction inputs and a PWM enable input. Motor A is
+//! enabled by the PWM output, and on the Uno motor B by the other Timer0
+//! output on d6, which the HAL does not expose while d6 is a general purpose
+//! pin, so it is switched over here directly. The Mega only has motor A.
+
+pub use protocol::args::motor::Motor;
+
+/// Sets the duty cycle of the PWM output on d6, which has to be an output
+/// driven low. Zero disconnects the timer from the pin.
+#[cfg(feature = "uno")]
+pub fn set_d6_duty(duty: u8) {
+    // SAFETY: Only the channel A settings are touched, which the HAL's PWM
+    // output on d5 does not use.
+    let tc0 = unsafe { &*arduino_hal::pac::TC0::ptr() };
+    tc0.ocr0a.write(|w| w.bits(duty));
+    if duty == 0 {
+        tc0.tccr0a.modify(|_, w| w.com0a().disconnected());
+    } else {
+        tc0.tccr0a.modify(|_, w| w.com0a().match_clear());
+    }
+}
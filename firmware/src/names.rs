@@ -0,0 +1,92 @@
+//! Names for digital pins, which commands that take a pin accept in place of
+//! its number.
+//!
+//! Each name takes a slot in EEPROM, holding the pin number, or 0xFF for a
+//! free slot, followed by the name as a length-prefixed string.
+
+use heapless::String;
+
+use crate::storage;
+
+/// How many pins can have a name.
+pub const MAX_NAMES: u16 = storage::PIN_NAMES_SIZE / SLOT_SIZE;
+
+/// Longest name.
+pub const MAX_LENGTH: usize = 14;
+
+pub type Name = String<MAX_LENGTH>;
+
+const SLOT_SIZE: u16 = 16;
+const FREE: u8 = 0xFF;
+
+/// Whether a name can be told apart from a pin number, including ones such
+/// as `d7` and `a3`, and the other words of a command, which the caller
+/// still has to check for.
+pub fn is_valid(name: &str) -> bool {
+    let pin_like = name
+        .strip_prefix(['a', 'd'])
+        .map_or(false, |number| number.bytes().all(|b| b.is_ascii_digit()));
+    name.len() <= MAX_LENGTH
+        && !pin_like
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn slot_address(slot: u16) -> u16 {
+    storage::PIN_NAMES + slot * SLOT_SIZE
+}
+
+fn read_slot(eeprom: &arduino_hal::Eeprom, slot: u16) -> Option<(u8, Name)> {
+    let address = slot_address(slot);
+    let pin = eeprom.read_byte(address);
+    (pin != FREE).then(|| (pin, storage::read_str(eeprom, address + 1)))
+}
+
+/// Returns the name of a pin.
+pub fn get(eeprom: &arduino_hal::Eeprom, pin: u8) -> Option<Name> {
+    (0..MAX_NAMES)
+        .filter_map(|slot| read_slot(eeprom, slot))
+        .find(|(p, _)| *p == pin)
+        .map(|(_, name)| name)
+}
+
+/// Returns the pin with a name.
+pub fn find(eeprom: &arduino_hal::Eeprom, name: &str) -> Option<u8> {
+    (0..MAX_NAMES)
+        .filter_map(|slot| read_slot(eeprom, slot))
+        .find(|(_, n)| n == name)
+        .map(|(pin, _)| pin)
+}
+
+/// Names a pin, replacing its previous name and taking the name from any
+/// other pin, or removes its name.
+///
+/// Fails if all slots are taken.
+pub fn set(eeprom: &mut arduino_hal::Eeprom, pin: u8, name: Option<&str>) -> Result<(), ()> {
+    let mut free = None;
+    for slot in 0..MAX_NAMES {
+        match read_slot(eeprom, slot) {
+            Some((p, n)) if p == pin || Some(n.as_str()) == name => {
+                eeprom.write_byte(slot_address(slot), FREE);
+                free = free.or(Some(slot));
+            }
+            Some(_) => {}
+            None => free = free.or(Some(slot)),
+        }
+    }
+    let Some(name) = name else {
+        return Ok(());
+    };
+    let slot = free.ok_or(())?;
+    let address = slot_address(slot);
+    storage::write_str(eeprom, address + 1, SLOT_SIZE - 1, name)?;
+    eeprom.write_byte(address, pin);
+    Ok(())
+}
+
+/// Calls `f` with each named pin and its name.
+pub fn for_each(eeprom: &arduino_hal::Eeprom, mut f: impl FnMut(u8, &str)) {
+    for (pin, name) in (0..MAX_NAMES).filter_map(|slot| read_slot(eeprom, slot)) {
+        f(pin, &name);
+    }
+}
@@ -0,0 +1,114 @@
+//! WS2812 addressable LED strips.
+//!
+//! The LEDs take a continuous 800 kHz bit stream where each bit is told apart
+//! by the length of its high pulse, which at 16 MHz leaves 20 cycles per bit.
+//! This is only achievable with hand-counted assembly and interrupts off.
+
+use core::arch::asm;
+
+use arduino_hal::delay_us;
+use avr_device::interrupt;
+use heapless::Vec;
+
+use crate::gpio::RawPin;
+
+/// Each pixel takes three bytes of SRAM, which there is not much of.
+pub const MAX_PIXELS: usize = 32;
+
+pub struct Strip {
+    pin: RawPin,
+    /// Colours in the order the LEDs expect them: green, red, blue.
+    pixels: Vec<[u8; 3], MAX_PIXELS>,
+}
+
+impl Strip {
+    /// Sets up a strip of `count` pixels, all off, on a pin that is already an
+    /// output driven low.
+    pub fn new(pin: RawPin, count: usize) -> Self {
+        let mut pixels = Vec::new();
+        let _ = pixels.resize(count.min(MAX_PIXELS), [0; 3]);
+        Strip { pin, pixels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    /// Sets a pixel's colour, to be sent with the next [`show`](Self::show).
+    /// Returns `false` if the index is past the end of the strip.
+    pub fn set(&mut self, index: usize, red: u8, green: u8, blue: u8) -> bool {
+        match self.pixels.get_mut(index) {
+            Some(pixel) => {
+                *pixel = [green, red, blue];
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn fill(&mut self, red: u8, green: u8, blue: u8) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = [green, red, blue];
+        }
+    }
+
+    /// Sends the colours out to the strip.
+    pub fn show(&self) {
+        let Some(first) = self.pixels.first() else {
+            return;
+        };
+        let port = self.pin.port_register();
+        let ptr = first.as_ptr();
+        let count = (self.pixels.len() * 3) as u16;
+
+        interrupt::free(|_| {
+            // SAFETY: The port register belongs to the strip's pin, and all
+            // other pins on it are written back unchanged. The loop reads one
+            // byte past the end of the pixels, which is never used.
+            unsafe {
+                let hi = *port | self.pin.mask();
+                let lo = *port & !self.pin.mask();
+                // 20 cycles per bit: high from cycle 0, low from cycle 5 for
+                // a zero or from cycle 13 for a one. The port address is in Z
+                // and the next byte is loaded through X.
+                asm!(
+                    "1:",
+                    "st Z, {hi}",
+                    "sbrc {byte}, 7",
+                    "mov {next}, {hi}",
+                    "dec {bit}",
+                    "st Z, {next}",
+                    "mov {next}, {lo}",
+                    "breq 2f",
+                    "rol {byte}",
+                    "nop",
+                    "nop",
+                    "nop",
+                    "st Z, {lo}",
+                    "nop",
+                    "nop",
+                    "nop",
+                    "rjmp 1b",
+                    "2:",
+                    "ldi {bit}, 8",
+                    "ld {byte}, X+",
+                    "st Z, {lo}",
+                    "nop",
+                    "sbiw {count}, 1",
+                    "brne 1b",
+                    in("Z") port,
+                    inout("X") ptr.add(1) => _,
+                    byte = inout(reg) *ptr => _,
+                    bit = inout(reg_upper) 8u8 => _,
+                    next = inout(reg) lo => _,
+                    count = inout(reg_iw) count => _,
+                    hi = in(reg) hi,
+                    lo = in(reg) lo,
+                    options(nostack),
+                );
+            }
+        });
+        // The strip latches the colours once the line has been low for a while.
+        delay_us(300);
+    }
+}
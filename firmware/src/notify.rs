@@ -0,0 +1,114 @@
+//! Pin change notifications.
+//!
+//! The pin change interrupt compares the watched pins against their last
+//! levels and queues an event for each one that changed, so that short
+//! pulses are caught even while the main loop is busy.
+
+use core::cell::{Cell, RefCell};
+
+use avr_device::interrupt::{self, CriticalSection, Mutex};
+use heapless::Deque;
+
+use crate::gpio::RawPin;
+use crate::{board, millis, pcint};
+
+/// Events not yet printed. Further changes are dropped until there is room.
+const QUEUE_SIZE: usize = 16;
+
+#[derive(Clone, Copy)]
+pub struct Event {
+    pub pin: u8,
+    pub high: bool,
+    /// Uptime in milliseconds.
+    pub time: u32,
+}
+
+#[derive(Clone, Copy)]
+struct Watched {
+    /// Digital pins to notify about, one bit per pin number, which goes up
+    /// to 53 on the Mega.
+    pins: u64,
+    /// The last levels of the watched pins.
+    levels: u64,
+    /// Changes dropped because the queue was full.
+    dropped: u16,
+}
+
+static WATCHED: Mutex<Cell<Watched>> = Mutex::new(Cell::new(Watched {
+    pins: 0,
+    levels: 0,
+    dropped: 0,
+}));
+static EVENTS: Mutex<RefCell<Deque<Event, QUEUE_SIZE>>> = Mutex::new(RefCell::new(Deque::new()));
+
+fn is_high(pin: u8) -> bool {
+    RawPin::new(pin).map_or(false, |pin| pin.is_high())
+}
+
+/// Starts or stops notifying about changes of a digital pin with a pin change
+/// interrupt.
+pub fn set(pin: u8, enabled: bool) {
+    interrupt::free(|cs| {
+        let cell = WATCHED.borrow(cs);
+        let mut watched = cell.get();
+        let bit = 1 << pin;
+        let was_enabled = watched.pins & bit != 0;
+        if enabled {
+            watched.pins |= bit;
+            watched.levels = watched.levels & !bit | u64::from(is_high(pin)) << pin;
+            if !was_enabled {
+                pcint::enable(pin);
+            }
+        } else {
+            watched.pins &= !bit;
+            if was_enabled {
+                pcint::disable(pin);
+            }
+        }
+        cell.set(watched);
+    })
+}
+
+/// Returns the oldest queued event, if any.
+pub fn take_event() -> Option<Event> {
+    interrupt::free(|cs| EVENTS.borrow(cs).borrow_mut().pop_front())
+}
+
+/// Returns how many changes were dropped since the last call.
+pub fn take_dropped() -> u16 {
+    interrupt::free(|cs| {
+        let cell = WATCHED.borrow(cs);
+        let mut watched = cell.get();
+        let dropped = watched.dropped;
+        watched.dropped = 0;
+        cell.set(watched);
+        dropped
+    })
+}
+
+/// Queues events for the watched pins that changed. Called from the pin
+/// change interrupt handlers.
+pub fn update(cs: CriticalSection) {
+    let cell = WATCHED.borrow(cs);
+    let mut watched = cell.get();
+    if watched.pins == 0 {
+        return;
+    }
+    let time = millis::millis();
+    let mut events = EVENTS.borrow(cs).borrow_mut();
+    for pin in board::DIGITAL_PINS {
+        let bit = 1 << pin;
+        if watched.pins & bit == 0 {
+            continue;
+        }
+        let high = is_high(pin);
+        if (watched.levels & bit != 0) == high {
+            continue;
+        }
+        watched.levels ^= bit;
+        if events.push_back(Event { pin, high, time }).is_err() {
+            watched.dropped = watched.dropped.saturating_add(1);
+        }
+    }
+    cell.set(watched);
+}
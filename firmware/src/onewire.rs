@@ -0,0 +1,198 @@
+//! Bit-banged 1-Wire bus master.
+//!
+//! The bus pin is driven open-drain: it is only ever pulled low or released,
+//! and needs an external pull-up resistor, typically 4.7 kΩ.
+
+use arduino_hal::delay_us;
+use avr_device::interrupt;
+
+use crate::gpio::RawPin;
+
+pub use protocol::args::onewire::Rom;
+
+/// ROM command starting a search for device IDs.
+const SEARCH_ROM: u8 = 0xF0;
+/// ROM command addressing the device with the ROM ID that follows.
+const MATCH_ROM: u8 = 0x55;
+/// ROM command addressing all devices at once.
+const SKIP_ROM: u8 = 0xCC;
+
+pub enum Error {
+    /// No device answered the reset pulse.
+    NoPresence,
+    /// Data was received with a bad checksum.
+    Crc,
+    /// A device did not finish an operation in time.
+    Timeout,
+}
+
+pub struct OneWire {
+    pin: RawPin,
+}
+
+impl OneWire {
+    /// Takes over a pin that is currently configured as a floating input.
+    pub fn new(pin: RawPin) -> Self {
+        pin.set_level(false);
+        pin.release();
+        OneWire { pin }
+    }
+
+    /// Sends a reset pulse and returns whether any device answered it.
+    pub fn reset(&self) -> bool {
+        self.pin.pull_low();
+        delay_us(480);
+        let present = interrupt::free(|_| {
+            self.pin.release();
+            delay_us(70);
+            !self.pin.is_high()
+        });
+        delay_us(410);
+        present
+    }
+
+    /// Resets the bus and addresses a single device, or all of them.
+    pub fn select(&self, rom: Option<&Rom>) -> Result<(), Error> {
+        if !self.reset() {
+            return Err(Error::NoPresence);
+        }
+        match rom {
+            Some(rom) => {
+                self.write_byte(MATCH_ROM);
+                for &byte in rom {
+                    self.write_byte(byte);
+                }
+            }
+            None => self.write_byte(SKIP_ROM),
+        }
+        Ok(())
+    }
+
+    pub fn write_bit(&self, bit: bool) {
+        // The line must return high within the 60 µs slot for a one, so the
+        // millisecond tick is held off until the slot is over.
+        interrupt::free(|_| {
+            self.pin.pull_low();
+            if bit {
+                delay_us(6);
+                self.pin.release();
+                delay_us(64);
+            } else {
+                delay_us(60);
+                self.pin.release();
+                delay_us(10);
+            }
+        });
+    }
+
+    pub fn read_bit(&self) -> bool {
+        interrupt::free(|_| {
+            self.pin.pull_low();
+            delay_us(3);
+            self.pin.release();
+            delay_us(10);
+            let bit = self.pin.is_high();
+            delay_us(53);
+            bit
+        })
+    }
+
+    /// Writes a byte, least significant bit first.
+    pub fn write_byte(&self, byte: u8) {
+        for i in 0..8 {
+            self.write_bit(byte & (1 << i) != 0);
+        }
+    }
+
+    pub fn read_byte(&self) -> u8 {
+        (0..8).fold(0, |byte, i| byte | u8::from(self.read_bit()) << i)
+    }
+}
+
+/// Walks the binary tree of device IDs on the bus, one device per call, as
+/// described in Maxim application note 187.
+pub struct Search {
+    rom: Rom,
+    /// The bit position where the last pass took the zero branch at a
+    /// conflict, 1-based. Zero when there is no branch left to explore.
+    last_discrepancy: u8,
+    done: bool,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Search {
+            rom: [0; 8],
+            last_discrepancy: 0,
+            done: false,
+        }
+    }
+
+    /// Finds the next device, or returns `None` once all have been listed.
+    pub fn next(&mut self, bus: &OneWire) -> Result<Option<Rom>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+        if !bus.reset() {
+            return Err(Error::NoPresence);
+        }
+        bus.write_byte(SEARCH_ROM);
+
+        let mut last_zero = 0;
+        for bit_number in 1..=64u8 {
+            let byte = usize::from((bit_number - 1) / 8);
+            let mask = 1 << ((bit_number - 1) % 8);
+            let bit = bus.read_bit();
+            let complement = bus.read_bit();
+            let direction = match (bit, complement) {
+                // Every device left has put its ID bit on the bus: they agree.
+                (true, false) => true,
+                (false, true) => false,
+                (false, false) => {
+                    let direction = if bit_number < self.last_discrepancy {
+                        self.rom[byte] & mask != 0
+                    } else {
+                        bit_number == self.last_discrepancy
+                    };
+                    if !direction {
+                        last_zero = bit_number;
+                    }
+                    direction
+                }
+                // The devices all dropped out, e.g. because one was removed.
+                (true, true) => return Err(Error::NoPresence),
+            };
+            if direction {
+                self.rom[byte] |= mask;
+            } else {
+                self.rom[byte] &= !mask;
+            }
+            bus.write_bit(direction);
+        }
+
+        self.last_discrepancy = last_zero;
+        self.done = last_zero == 0;
+        if crc8(&self.rom) != 0 {
+            return Err(Error::Crc);
+        }
+        Ok(Some(self.rom))
+    }
+}
+
+/// The Dallas/Maxim CRC-8, which comes out as zero over data followed by its
+/// own checksum.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0;
+    for &byte in data {
+        let mut byte = byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 1;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
@@ -0,0 +1,142 @@
+//! Pin change interrupts.
+//!
+//! Pins are addressed by their Arduino digital pin number. Which pins have an
+//! interrupt, and in which bank, depends on the board. On the Uno the analog
+//! pins' bank is not used, on the Mega only some digital pins have one, and
+//! the 32U4 only has the bank of PORTB.
+//!
+//! Several modules can want the same pin's interrupt, such as `notify` and
+//! the wake-up pin of `sleep`, so each pin counts how often it was enabled and
+//! stays enabled until it was disabled as often.
+
+use core::cell::{Cell, RefCell};
+
+use avr_device::interrupt::{CriticalSection, Mutex};
+
+use crate::{board, encoder, ir, notify, sserial};
+
+static TRIGGERED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+static INTERRUPTS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+/// How often each pin's interrupt is enabled, indexed by bank and bit.
+static USERS: Mutex<RefCell<[u8; 3 * 8]>> = Mutex::new(RefCell::new([0; 3 * 8]));
+
+fn exint() -> &'static arduino_hal::pac::exint::RegisterBlock {
+    // SAFETY: The pin change registers are not owned by any driver and only
+    // modified from this module, inside critical sections.
+    unsafe { &*arduino_hal::pac::EXINT::ptr() }
+}
+
+/// Enables the pin change interrupt for a digital pin. Pins without one are
+/// ignored.
+pub fn enable(pin: u8) {
+    set(pin, true);
+}
+
+/// Disables the pin change interrupt for a digital pin, once every enable of
+/// it has been matched by a disable.
+pub fn disable(pin: u8) {
+    set(pin, false);
+}
+
+fn set(pin: u8, enabled: bool) {
+    let Some((bank, bit)) = board::pin_change_bit(pin) else {
+        return;
+    };
+    let update = |bits: u8| {
+        if enabled {
+            bits | 1 << bit
+        } else {
+            bits & !(1 << bit)
+        }
+    };
+    avr_device::interrupt::free(|cs| {
+        let mut users = USERS.borrow(cs).borrow_mut();
+        let count = &mut users[usize::from(bank * 8 + bit)];
+        if enabled {
+            *count = count.saturating_add(1);
+            if *count > 1 {
+                return;
+            }
+        } else {
+            if *count == 0 {
+                return;
+            }
+            *count -= 1;
+            if *count > 0 {
+                return;
+            }
+        }
+        let exint = exint();
+        let mask = match bank {
+            0 => {
+                exint.pcmsk0.modify(|r, w| w.bits(update(r.bits())));
+                exint.pcmsk0.read().bits()
+            }
+            // The Uno's bank 1 is the analog pins.
+            #[cfg(feature = "mega2560")]
+            1 => {
+                exint.pcmsk1.modify(|r, w| w.bits(update(r.bits())));
+                exint.pcmsk1.read().bits()
+            }
+            #[cfg(not(feature = "leonardo"))]
+            _ => {
+                exint.pcmsk2.modify(|r, w| w.bits(update(r.bits())));
+                exint.pcmsk2.read().bits()
+            }
+            #[cfg(feature = "leonardo")]
+            _ => unreachable!("the 32U4 only has bank 0"),
+        };
+        // The bank stays enabled while any of its pins are.
+        #[cfg(feature = "leonardo")]
+        exint.pcicr.write(|w| w.pcie0().bit(mask != 0));
+        #[cfg(not(feature = "leonardo"))]
+        exint.pcicr.modify(|r, w| {
+            let banks = if mask != 0 {
+                r.pcie().bits() | 1 << bank
+            } else {
+                r.pcie().bits() & !(1 << bank)
+            };
+            w.pcie().bits(banks)
+        });
+    })
+}
+
+/// Returns whether any enabled pin has changed since the last call.
+pub fn take_triggered() -> bool {
+    avr_device::interrupt::free(|cs| TRIGGERED.borrow(cs).replace(false))
+}
+
+/// Returns how many pin change interrupts there have been since reset.
+pub fn interrupts() -> u32 {
+    avr_device::interrupt::free(|cs| INTERRUPTS.borrow(cs).get())
+}
+
+fn on_change(cs: CriticalSection) {
+    TRIGGERED.borrow(cs).set(true);
+    let interrupts = INTERRUPTS.borrow(cs);
+    interrupts.set(interrupts.get().saturating_add(1));
+    encoder::update(cs);
+    ir::update(cs);
+    notify::update(cs);
+    sserial::update(cs);
+}
+
+#[cfg_attr(feature = "uno", avr_device::interrupt(atmega328p))]
+#[cfg_attr(feature = "mega2560", avr_device::interrupt(atmega2560))]
+#[cfg_attr(feature = "leonardo", avr_device::interrupt(atmega32u4))]
+fn PCINT0() {
+    avr_device::interrupt::free(on_change);
+}
+
+#[cfg(feature = "mega2560")]
+#[avr_device::interrupt(atmega2560)]
+fn PCINT1() {
+    avr_device::interrupt::free(on_change);
+}
+
+#[cfg(not(feature = "leonardo"))]
+#[cfg_attr(feature = "uno", avr_device::interrupt(atmega328p))]
+#[cfg_attr(feature = "mega2560", avr_device::interrupt(atmega2560))]
+fn PCINT2() {
+    avr_device::interrupt::free(on_change);
+}
@@ -0,0 +1,81 @@
+//! A PID control loop from an analog input to a PWM output.
+//!
+//! Gains are fixed-point numbers with 8 fraction bits, with the integral and
+//! derivative gains per second so that changing the interval keeps the loop
+//! tuned. The derivative acts on the input rather than on the error, so a
+//! new setpoint does not kick the output. The integral stops growing once it
+//! alone would drive the output past its range.
+
+use crate::millis;
+
+pub use protocol::args::pid::{Gains, FRACTION_BITS};
+
+pub const DEFAULT_INTERVAL_MS: u16 = 100;
+
+const OUTPUT_MAX: i64 = 255 << FRACTION_BITS;
+
+pub struct Pid {
+    /// The analog pin read.
+    pub input: u8,
+    /// The digital pin driven.
+    pub output: u8,
+    pub gains: Gains,
+    pub setpoint: u16,
+    pub interval_ms: u16,
+    pub running: bool,
+    /// The last output duty cycle.
+    pub duty: u8,
+    /// The integral term in output units, times 1000 for the milliseconds.
+    integral: i64,
+    last_input: Option<u16>,
+    next: u32,
+}
+
+impl Pid {
+    pub fn new(input: u8, output: u8, gains: Gains, setpoint: u16) -> Self {
+        Pid {
+            input,
+            output,
+            gains,
+            setpoint,
+            interval_ms: DEFAULT_INTERVAL_MS,
+            running: false,
+            duty: 0,
+            integral: 0,
+            last_input: None,
+            next: 0,
+        }
+    }
+
+    /// Starts the loop from scratch, with the first step right away.
+    pub fn start(&mut self) {
+        self.running = true;
+        self.integral = 0;
+        self.last_input = None;
+        self.next = millis::millis();
+    }
+
+    /// Returns whether the next step is due, and schedules the one after.
+    pub fn is_due(&mut self) -> bool {
+        let now = millis::millis();
+        if !self.running || (now.wrapping_sub(self.next) as i32) < 0 {
+            return false;
+        }
+        self.next = self.next.wrapping_add(u32::from(self.interval_ms));
+        true
+    }
+
+    /// Takes a reading of the input and returns the new duty cycle.
+    pub fn step(&mut self, value: u16) -> u8 {
+        let dt = i64::from(self.interval_ms);
+        let error = i64::from(self.setpoint) - i64::from(value);
+        self.integral =
+            (self.integral + i64::from(self.gains.ki.0) * error * dt).clamp(0, OUTPUT_MAX * 1000);
+        let change = i64::from(value) - i64::from(self.last_input.unwrap_or(value));
+        self.last_input = Some(value);
+        let output = i64::from(self.gains.kp.0) * error + self.integral / 1000
+            - i64::from(self.gains.kd.0) * change * 1000 / dt;
+        self.duty = (output.clamp(0, OUTPUT_MAX) >> FRACTION_BITS) as u8;
+        self.duty
+    }
+}
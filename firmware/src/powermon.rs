@@ -0,0 +1,49 @@
+//! Supply voltage monitoring, for boards running off batteries.
+//!
+//! The main loop measures Vcc against the bandgap once a second and warns
+//! once when it sags below a threshold, and again once it has recovered past
+//! the threshold by [`HYSTERESIS_MV`].
+
+use protocol::alert;
+
+use crate::millis;
+
+pub const INTERVAL_MS: u32 = 1000;
+
+pub const HYSTERESIS_MV: u16 = 100;
+
+pub struct Monitor {
+    alert: alert::Alert,
+    /// Whether to drive the outputs low and turn on safe mode on a sag.
+    pub safe: bool,
+    next: u32,
+}
+
+impl Monitor {
+    pub fn new(threshold_mv: u16, safe: bool) -> Self {
+        Monitor {
+            alert: alert::Alert::new(alert::Direction::Below, threshold_mv, HYSTERESIS_MV),
+            safe,
+            next: millis::millis(),
+        }
+    }
+
+    pub fn threshold_mv(&self) -> u16 {
+        self.alert.threshold
+    }
+
+    /// Returns whether the next measurement should be taken, and schedules
+    /// the one after.
+    pub fn is_due(&mut self) -> bool {
+        let now = millis::millis();
+        if (now.wrapping_sub(self.next) as i32) < 0 {
+            return false;
+        }
+        self.next = now.wrapping_add(INTERVAL_MS);
+        true
+    }
+
+    pub fn update(&mut self, millivolts: u16) -> Option<alert::Event> {
+        self.alert.update(millivolts)
+    }
+}
@@ -0,0 +1,183 @@
+//! Reading data kept in flash.
+//!
+//! Statics normally get copied into RAM at startup, which the 2 KiB of the
+//! ATmega328P cannot spare for long texts. Statics in the `.progmem.data`
+//! section stay in flash instead, where the CPU can only read them with the
+//! `lpm` instruction, so they must never be dereferenced like normal data.
+
+use core::arch::asm;
+
+use ufmt::{uDisplay, uWrite, Formatter};
+
+/// Reads a byte from flash.
+///
+/// # Safety
+///
+/// The address has to be within a static in the `.progmem.data` section.
+pub unsafe fn read_byte(address: *const u8) -> u8 {
+    let byte;
+    asm!("lpm {}, Z", out(reg) byte, in("Z") address, options(pure, readonly, nostack));
+    byte
+}
+
+/// Copies texts one after the other into an array, to be put into flash as
+/// a static.
+pub const fn concat<const N: usize>(texts: &[&str]) -> [u8; N] {
+    let mut bytes = [0; N];
+    let mut len = 0;
+    let mut i = 0;
+    while i < texts.len() {
+        let text = texts[i].as_bytes();
+        let mut j = 0;
+        while j < text.len() {
+            bytes[len] = text[j];
+            len += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+    bytes
+}
+
+/// A text in flash, printed a few bytes at a time.
+pub struct Text {
+    start: *const u8,
+    len: usize,
+}
+
+impl Text {
+    /// # Safety
+    ///
+    /// The text has to be ASCII, and within a static in the `.progmem.data`
+    /// section.
+    pub unsafe fn new(start: *const u8, len: usize) -> Self {
+        Text { start, len }
+    }
+}
+
+impl uDisplay for Text {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        let mut buf = [0; 16];
+        for offset in (0..self.len).step_by(buf.len()) {
+            let len = buf.len().min(self.len - offset);
+            for (i, byte) in buf[..len].iter_mut().enumerate() {
+                // SAFETY: The address is within the text, which is in flash.
+                *byte = unsafe { read_byte(self.start.add(offset + i)) };
+            }
+            f.write_str(core::str::from_utf8(&buf[..len]).unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
+
+/// A format string in flash, written a piece at a time between its `{}`
+/// placeholders by [`fwrite!`].
+pub struct Format {
+    start: *const u8,
+    len: usize,
+    offset: usize,
+}
+
+impl Format {
+    /// # Safety
+    ///
+    /// The format string has to be ASCII, and within a static in the
+    /// `.progmem.data` section.
+    pub unsafe fn new(start: *const u8, len: usize) -> Self {
+        Format {
+            start,
+            len,
+            offset: 0,
+        }
+    }
+
+    /// Writes the text up to the next placeholder, or to the end, and skips
+    /// the placeholder.
+    pub fn piece<W: uWrite + ?Sized>(&mut self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        // SAFETY: Only addresses within the format string are read, which is
+        // in flash.
+        let byte = |offset: usize| unsafe { read_byte(self.start.add(offset)) };
+        let mut buf = [0; 16];
+        let mut len = 0;
+        while self.offset < self.len {
+            let next = byte(self.offset);
+            self.offset += 1;
+            if next == b'{' && self.offset < self.len && byte(self.offset) == b'}' {
+                self.offset += 1;
+                break;
+            }
+            buf[len] = next;
+            len += 1;
+            if len == buf.len() {
+                f.write_str(core::str::from_utf8(&buf).unwrap_or_default())?;
+                len = 0;
+            }
+        }
+        f.write_str(core::str::from_utf8(&buf[..len]).unwrap_or_default())
+    }
+}
+
+/// The arguments of [`fwrite!`], a tuple of references to each.
+pub trait Args {
+    fn write<W: uWrite + ?Sized>(
+        self,
+        format: &mut Format,
+        f: &mut Formatter<'_, W>,
+    ) -> Result<(), W::Error>;
+}
+
+macro_rules! impl_args {
+    ($($arg:ident: $type:ident),*) => {
+        impl<$($type: uDisplay + ?Sized),*> Args for ($(&$type,)*) {
+            fn write<W: uWrite + ?Sized>(
+                self,
+                format: &mut Format,
+                f: &mut Formatter<'_, W>,
+            ) -> Result<(), W::Error> {
+                let ($($arg,)*) = self;
+                $(
+                    format.piece(f)?;
+                    $arg.fmt(f)?;
+                )*
+                format.piece(f)
+            }
+        }
+    };
+}
+
+impl_args!();
+impl_args!(a: A);
+impl_args!(a: A, b: B);
+impl_args!(a: A, b: B, c: C);
+impl_args!(a: A, b: B, c: C, d: D);
+impl_args!(a: A, b: B, c: C, d: D, e: E);
+impl_args!(a: A, b: B, c: C, d: D, e: E, g: G);
+
+/// Like `uwrite!`, but with the format string kept in flash rather than
+/// copied into RAM. It takes only `{}` placeholders, and ASCII text.
+macro_rules! fwrite {
+    ($w:expr, $format:expr $(, $arg:expr)* $(,)?) => {{
+        const FORMAT: &str = $format;
+        const _: () = assert!(FORMAT.is_ascii());
+        #[link_section = ".progmem.data"]
+        static TEXT: [u8; FORMAT.len()] = $crate::progmem::concat(&[FORMAT]);
+        // SAFETY: The format string is ASCII, and in flash.
+        let mut format =
+            unsafe { $crate::progmem::Format::new(core::ptr::addr_of!(TEXT).cast(), TEXT.len()) };
+        match ($(&($arg),)*) {
+            args => {
+                use ufmt::UnstableDoAsFormatter as _;
+                ($w).do_as_formatter(|f| $crate::progmem::Args::write(args, &mut format, f))
+            }
+        }
+    }};
+}
+
+/// Like `uwriteln!`, with the format string in flash, see [`fwrite!`].
+macro_rules! fwriteln {
+    ($w:expr, $format:literal $(, $arg:expr)* $(,)?) => {
+        $crate::progmem::fwrite!($w, concat!($format, "\n") $(, $arg)*)
+    };
+}
+
+pub(crate) use {fwrite, fwriteln};
@@ -0,0 +1,41 @@
+//! Timed outputs, which `set … for <ms>` drives to a level and the main loop
+//! drives back once the time is up.
+//!
+//! The pulse ends even if the host never sends another command, but a command
+//! that blocks the main loop, such as a long `delay`, stretches it.
+
+use crate::{gpio, millis, Pins};
+
+/// How many pulses can run at the same time.
+pub const MAX_PULSES: usize = 4;
+
+pub struct Pulse {
+    pins: Pins,
+    /// Level of the pins during the pulse.
+    level: bool,
+    end: u32,
+}
+
+impl Pulse {
+    /// Starts a pulse on pins that have just been driven to `level`.
+    pub fn new(pins: Pins, level: bool, duration_ms: u32) -> Self {
+        Pulse {
+            pins,
+            level,
+            end: millis::millis().wrapping_add(duration_ms),
+        }
+    }
+
+    pub fn pins(&self) -> &Pins {
+        &self.pins
+    }
+
+    /// Ends the pulse if its time is up. Returns `true` once it has ended.
+    pub fn poll(&self) -> bool {
+        if (millis::millis().wrapping_sub(self.end) as i32) < 0 {
+            return false;
+        }
+        gpio::set_levels(&self.pins, !self.level);
+        true
+    }
+}
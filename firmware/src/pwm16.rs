@@ -0,0 +1,57 @@
+//! 16-bit PWM on the Timer1 outputs, d9 and d10 on the Uno.
+//!
+//! Timer1 counts the full 16 bits at 16 MHz, for a PWM frequency of 244 Hz.
+//! It cannot drive servos at the same time.
+
+use crate::board;
+
+/// The pins with a Timer1 output, OC1A and OC1B.
+pub const PINS: [u8; 2] = board::TIMER1_PINS;
+
+const TOP: u16 = 0xFFFF;
+
+fn tc1() -> &'static arduino_hal::pac::tc1::RegisterBlock {
+    // SAFETY: Timer1 is only used for 16-bit PWM while no servos are
+    // attached.
+    unsafe { &*arduino_hal::pac::TC1::ptr() }
+}
+
+/// Whether Timer1 is running for 16-bit PWM.
+pub fn is_active() -> bool {
+    let tc1 = tc1();
+    !tc1.tccr1b.read().cs1().is_no_clock() && tc1.icr1.read().bits() == TOP
+}
+
+/// Sets the duty cycle on one of [`PINS`], which has to be an output driven
+/// low.
+pub fn write(pin: u8, duty: u16) {
+    let tc1 = tc1();
+    if tc1.tccr1b.read().cs1().is_no_clock() {
+        // Fast PWM with the top in ICR1.
+        tc1.icr1.write(|w| w.bits(TOP));
+        tc1.tccr1a.write(|w| w.wgm1().bits(0b10));
+        tc1.tccr1b.write(|w| w.wgm1().bits(0b11).cs1().direct());
+    }
+
+    // A compare value of zero still pulses for one count, so a duty cycle of
+    // zero disconnects the output instead, leaving the pin low.
+    if pin == PINS[0] {
+        tc1.ocr1a.write(|w| w.bits(duty));
+        tc1.tccr1a.modify(|_, w| {
+            if duty == 0 {
+                w.com1a().disconnected()
+            } else {
+                w.com1a().match_clear()
+            }
+        });
+    } else {
+        tc1.ocr1b.write(|w| w.bits(duty));
+        tc1.tccr1a.modify(|_, w| {
+            if duty == 0 {
+                w.com1b().disconnected()
+            } else {
+                w.com1b().match_clear()
+            }
+        });
+    }
+}
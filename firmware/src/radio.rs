@@ -0,0 +1,226 @@
+//! nRF24L01 2.4 GHz radio modules on the SPI bus.
+//!
+//! Payloads are sent with auto acknowledgement and up to 15 retries, so a
+//! send only succeeds once the other end received it. Each payload is 32
+//! bytes on air, the first of them the length of the data that follows,
+//! which works with the modules that lack dynamic payload lengths too.
+//!
+//! A listening radio stays in receive mode except while it sends, and
+//! [`Radio::receive`] takes the payloads that arrived in the meantime.
+
+use arduino_hal::{delay_ms, delay_us};
+use heapless::Vec;
+
+use crate::gpio::RawPin;
+use crate::{millis, spi};
+
+const R_REGISTER: u8 = 0x00;
+const W_REGISTER: u8 = 0x20;
+const R_RX_PAYLOAD: u8 = 0x61;
+const W_TX_PAYLOAD: u8 = 0xA0;
+const FLUSH_TX: u8 = 0xE1;
+const FLUSH_RX: u8 = 0xE2;
+
+const CONFIG: u8 = 0x00;
+const EN_AA: u8 = 0x01;
+const EN_RXADDR: u8 = 0x02;
+const SETUP_AW: u8 = 0x03;
+const SETUP_RETR: u8 = 0x04;
+const RF_CH: u8 = 0x05;
+const RF_SETUP: u8 = 0x06;
+const STATUS: u8 = 0x07;
+const RX_ADDR_P0: u8 = 0x0A;
+const RX_ADDR_P1: u8 = 0x0B;
+const TX_ADDR: u8 = 0x10;
+const RX_PW_P0: u8 = 0x11;
+const RX_PW_P1: u8 = 0x12;
+const FIFO_STATUS: u8 = 0x17;
+
+/// `CONFIG`: powered up with a 16-bit CRC, in transmit mode.
+const POWER_UP: u8 = 0b0000_1110;
+const PRIM_RX: u8 = 1 << 0;
+/// `STATUS`: a payload arrived, was acknowledged, or ran out of retries.
+const RX_DR: u8 = 1 << 6;
+const TX_DS: u8 = 1 << 5;
+const MAX_RT: u8 = 1 << 4;
+/// `FIFO_STATUS`: nothing received.
+const RX_EMPTY: u8 = 1 << 0;
+/// `SETUP_AW`: five byte addresses.
+const ADDRESS_WIDTH: u8 = 0b11;
+/// `SETUP_RETR`: 15 retries, 1.5 ms apart.
+const RETRIES: u8 = 0x5F;
+/// `RF_SETUP`: 1 Mbit/s at full power.
+const RATE_AND_POWER: u8 = 0b0000_0110;
+
+pub const ADDRESS_LENGTH: usize = 5;
+pub const PAYLOAD_SIZE: usize = 32;
+/// The most data a payload holds, after its length byte.
+pub const MAX_DATA: usize = PAYLOAD_SIZE - 1;
+/// The highest channel, 2.525 GHz.
+pub const MAX_CHANNEL: u8 = 125;
+const DEFAULT_CHANNEL: u8 = 76;
+
+/// How long a send gets, for all retries.
+const SEND_TIMEOUT_MS: u32 = 100;
+
+pub enum Error {
+    /// No module answered on the bus.
+    NotFound,
+    /// The other end didn't acknowledge the payload.
+    NoAck,
+}
+
+pub struct Radio {
+    ce: RawPin,
+    csn: RawPin,
+    spi: spi::Config,
+    /// The address listened on, if any.
+    listening: Option<[u8; ADDRESS_LENGTH]>,
+}
+
+/// Pads an address of up to five characters with zeroes.
+pub fn address(name: &str) -> [u8; ADDRESS_LENGTH] {
+    let mut address = [0; ADDRESS_LENGTH];
+    for (byte, &c) in address.iter_mut().zip(name.as_bytes()) {
+        *byte = c;
+    }
+    address
+}
+
+impl Radio {
+    /// Sets up the module behind chip enable and chip select pins, which
+    /// have to be outputs, CE low and CSN high.
+    pub fn init(ce: RawPin, csn: RawPin, csn_pin: u8) -> Result<Self, Error> {
+        let radio = Radio {
+            ce,
+            csn,
+            spi: spi::Config {
+                mode: 0,
+                divider: 4,
+                cs: csn_pin,
+            },
+            listening: None,
+        };
+        // The module takes up to 100 ms to start after power on.
+        delay_ms(5);
+        radio.write_register(SETUP_AW, ADDRESS_WIDTH);
+        if radio.read_register(SETUP_AW) != ADDRESS_WIDTH {
+            return Err(Error::NotFound);
+        }
+        radio.write_register(SETUP_RETR, RETRIES);
+        radio.write_register(RF_SETUP, RATE_AND_POWER);
+        radio.write_register(RF_CH, DEFAULT_CHANNEL);
+        radio.write_register(EN_AA, 0b11);
+        radio.write_register(EN_RXADDR, 0b11);
+        radio.write_register(RX_PW_P0, PAYLOAD_SIZE as u8);
+        radio.write_register(RX_PW_P1, PAYLOAD_SIZE as u8);
+        radio.command(FLUSH_TX, &mut []);
+        radio.command(FLUSH_RX, &mut []);
+        radio.write_register(STATUS, RX_DR | TX_DS | MAX_RT);
+        radio.write_register(CONFIG, POWER_UP);
+        // The oscillator needs 1.5 ms to start up.
+        delay_ms(2);
+        Ok(radio)
+    }
+
+    pub fn set_channel(&mut self, channel: u8) {
+        self.write_register(RF_CH, channel);
+    }
+
+    /// Starts receiving payloads sent to an address.
+    pub fn listen(&mut self, address: [u8; ADDRESS_LENGTH]) {
+        self.listening = Some(address);
+        self.start_listening();
+    }
+
+    fn start_listening(&mut self) {
+        let Some(mut address) = self.listening else {
+            return;
+        };
+        self.command(W_REGISTER | RX_ADDR_P1, &mut address);
+        self.write_register(CONFIG, POWER_UP | PRIM_RX);
+        self.ce.set_level(true);
+    }
+
+    /// Sends data of up to [`MAX_DATA`] bytes to an address, and waits for
+    /// the acknowledgement.
+    pub fn send(&mut self, address: [u8; ADDRESS_LENGTH], data: &[u8]) -> Result<(), Error> {
+        self.ce.set_level(false);
+        self.write_register(CONFIG, POWER_UP);
+        // Acknowledgements come back on pipe 0, from the address sent to.
+        let mut tx = address;
+        self.command(W_REGISTER | TX_ADDR, &mut tx);
+        let mut rx = address;
+        self.command(W_REGISTER | RX_ADDR_P0, &mut rx);
+        self.command(FLUSH_TX, &mut []);
+        let len = data.len().min(MAX_DATA);
+        let mut payload = [0; PAYLOAD_SIZE];
+        payload[0] = len as u8;
+        payload[1..=len].copy_from_slice(&data[..len]);
+        self.command(W_TX_PAYLOAD, &mut payload);
+        // A pulse of at least 10 µs on CE sends the payload.
+        self.ce.set_level(true);
+        delay_us(15);
+        self.ce.set_level(false);
+
+        let start = millis::millis();
+        let status = loop {
+            let status = self.read_register(STATUS);
+            if status & (TX_DS | MAX_RT) != 0 {
+                break status;
+            }
+            if millis::millis().wrapping_sub(start) > SEND_TIMEOUT_MS {
+                break MAX_RT;
+            }
+        };
+        self.write_register(STATUS, TX_DS | MAX_RT);
+        if status & TX_DS == 0 {
+            self.command(FLUSH_TX, &mut []);
+        }
+        self.start_listening();
+        if status & TX_DS == 0 {
+            return Err(Error::NoAck);
+        }
+        Ok(())
+    }
+
+    /// Takes the data of a payload that arrived, if any.
+    pub fn receive(&mut self) -> Option<Vec<u8, MAX_DATA>> {
+        self.listening?;
+        if self.read_register(FIFO_STATUS) & RX_EMPTY != 0 {
+            return None;
+        }
+        let mut payload = [0; PAYLOAD_SIZE];
+        self.command(R_RX_PAYLOAD, &mut payload);
+        self.write_register(STATUS, RX_DR);
+        let len = usize::from(payload[0]).min(MAX_DATA);
+        Vec::from_slice(&payload[1..=len]).ok()
+    }
+
+    /// Powers the module down.
+    pub fn off(self) {
+        self.ce.set_level(false);
+        self.write_register(CONFIG, 0);
+    }
+
+    fn read_register(&self, register: u8) -> u8 {
+        let mut value = [0];
+        self.command(R_REGISTER | register, &mut value);
+        value[0]
+    }
+
+    fn write_register(&self, register: u8, value: u8) {
+        self.command(W_REGISTER | register, &mut [value]);
+    }
+
+    /// Sends a command followed by `data`, which is replaced with the bytes
+    /// clocked back in.
+    fn command(&self, command: u8, data: &mut [u8]) {
+        let bus = spi::Bus::begin(&self.spi);
+        self.csn.set_level(false);
+        bus.exchange(command);
+        bus.transfer(data);
+        self.csn.set_level(true);
+        bus.end();
+    }
+}
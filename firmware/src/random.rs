@@ -0,0 +1,35 @@
+//! Random numbers from ADC noise.
+//!
+//! Only the lowest bit of each conversion is noisy enough to use, and even
+//! that is biased, so the bits are stirred into a pool with a multiply and a
+//! rotation, which spreads every input bit over the whole word. The pool is
+//! kept between numbers, so entropy that one number did not need carries
+//! over to the next.
+
+/// Conversions stirred in per number.
+pub const SAMPLES: usize = 64;
+
+pub struct Pool {
+    state: u32,
+}
+
+impl Pool {
+    pub const fn new() -> Self {
+        Pool { state: 0x2545_F491 }
+    }
+
+    /// Stirs the lowest bit of a conversion into the pool.
+    pub fn add(&mut self, sample: u16) {
+        self.state = (self.state ^ u32::from(sample & 1))
+            .wrapping_mul(0x9E37_79B1)
+            .rotate_left(7);
+    }
+
+    /// Returns a number from 0 up to and including `max`, from the pool's
+    /// current state.
+    pub fn number(&mut self, max: u16) -> u16 {
+        // A final round, so that numbers taken back to back still differ.
+        self.add(0);
+        ((u64::from(self.state) * (u64::from(max) + 1)) >> 32) as u16
+    }
+}
@@ -0,0 +1,49 @@
+//! Reset cause reporting.
+
+use arduino_hal::pac::cpu::MCUSR;
+
+/// What caused the most recent reset, as recorded in `MCUSR`.
+#[derive(Clone, Copy)]
+pub enum ResetCause {
+    PowerOn,
+    BrownOut,
+    Watchdog,
+    External,
+    /// No flag was set, either because a bootloader cleared them or because
+    /// the firmware jumped to the reset vector.
+    Unknown,
+}
+
+impl ResetCause {
+    /// Reads the reset flags and clears them, so that the next reset reports
+    /// only its own cause.
+    ///
+    /// This has to run before the watchdog is set up, which clears its flag.
+    pub fn take(mcusr: &MCUSR) -> Self {
+        let flags = mcusr.read();
+        // A power-on reset can set the other flags too, so it is checked first.
+        let cause = if flags.porf().bit_is_set() {
+            ResetCause::PowerOn
+        } else if flags.borf().bit_is_set() {
+            ResetCause::BrownOut
+        } else if flags.wdrf().bit_is_set() {
+            ResetCause::Watchdog
+        } else if flags.extrf().bit_is_set() {
+            ResetCause::External
+        } else {
+            ResetCause::Unknown
+        };
+        mcusr.reset();
+        cause
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ResetCause::PowerOn => "power-on",
+            ResetCause::BrownOut => "brown-out",
+            ResetCause::Watchdog => "watchdog",
+            ResetCause::External => "external",
+            ResetCause::Unknown => "unknown",
+        }
+    }
+}
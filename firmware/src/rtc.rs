@@ -0,0 +1,51 @@
+//! DS3231 and DS1307 real-time clocks.
+//!
+//! Both chips sit at the same I2C address and keep the time in the same BCD
+//! registers, starting at register 0. Only the years 2000-2099 are supported.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use protocol::args::rtc::to_bcd;
+
+pub use protocol::args::rtc::DateTime;
+
+pub const ADDRESS: u8 = 0x68;
+
+/// Clock halt flag in the DS1307's seconds register. Always clear on the
+/// DS3231.
+const CLOCK_HALT: u8 = 0x80;
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+pub fn read(i2c: &mut arduino_hal::I2c) -> Result<DateTime, arduino_hal::i2c::Error> {
+    let mut registers = [0; 7];
+    i2c.write_read(ADDRESS, &[0], &mut registers)?;
+    Ok(DateTime {
+        year: 2000 + u16::from(from_bcd(registers[6])),
+        // The DS3231 keeps a century flag in the top bit.
+        month: from_bcd(registers[5] & 0x1F),
+        day: from_bcd(registers[4] & 0x3F),
+        // Always set in 24-hour mode, so the 12-hour flags are not checked.
+        hour: from_bcd(registers[2] & 0x3F),
+        minute: from_bcd(registers[1] & 0x7F),
+        second: from_bcd(registers[0] & !CLOCK_HALT),
+    })
+}
+
+/// Sets the clock, which also starts a halted DS1307.
+pub fn write(i2c: &mut arduino_hal::I2c, time: &DateTime) -> Result<(), arduino_hal::i2c::Error> {
+    i2c.write(
+        ADDRESS,
+        &[
+            0,
+            to_bcd(time.second),
+            to_bcd(time.minute),
+            to_bcd(time.hour),
+            time.weekday(),
+            to_bcd(time.day),
+            to_bcd(time.month),
+            to_bcd((time.year - 2000) as u8),
+        ],
+    )
+}
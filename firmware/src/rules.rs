@@ -0,0 +1,98 @@
+//! Trigger rules, which run a command when an input meets a condition.
+//!
+//! A rule fires when its condition becomes true, rather than for as long as
+//! it stays true. Like macros, rules are kept in RAM and lost on reset.
+
+use heapless::{String, Vec};
+use ufmt::{uDisplay, uWrite, Formatter};
+
+use crate::progmem::fwrite;
+use crate::LINE_LENGTH;
+
+pub use protocol::args::rules::{Condition, Input};
+
+/// How many rules can be active at the same time.
+pub const MAX_RULES: usize = 4;
+
+pub type Action = String<LINE_LENGTH>;
+
+pub struct Rule {
+    pub input: Input,
+    pub condition: Condition,
+    pub action: Action,
+    /// Whether the condition held when last checked.
+    held: bool,
+}
+
+impl uDisplay for Rule {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        match self.input {
+            Input::Analog(pin) => fwrite!(f, "a{}", pin)?,
+            Input::Digital(pin) => fwrite!(f, "d{}", pin)?,
+        }
+        match self.condition {
+            Condition::Above(threshold) => fwrite!(f, " > {}", threshold)?,
+            Condition::Below(threshold) => fwrite!(f, " < {}", threshold)?,
+            Condition::Rises => fwrite!(f, " rises")?,
+            Condition::Falls => fwrite!(f, " falls")?,
+        }
+        fwrite!(f, " {}", self.action.as_str())
+    }
+}
+
+pub struct Rules {
+    entries: Vec<Rule, MAX_RULES>,
+}
+
+impl Rules {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds a rule, given the input's current value.
+    ///
+    /// Fails if the action is too long, or if the table is full.
+    pub fn add(
+        &mut self,
+        input: Input,
+        condition: Condition,
+        action: &str,
+        value: u16,
+    ) -> Result<(), ()> {
+        let rule = Rule {
+            input,
+            condition,
+            action: Action::try_from(action)?,
+            held: condition.is_edge() && condition.holds(value),
+        };
+        self.entries.push(rule).map_err(|_| ())
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Rule> {
+        self.entries.get(index)
+    }
+
+    /// Records the input's current value for a rule, and returns its action
+    /// if the condition has just become true.
+    pub fn update(&mut self, index: usize, value: u16) -> Option<&Action> {
+        let rule = self.entries.get_mut(index)?;
+        let held = rule.condition.holds(value);
+        let fired = held && !rule.held;
+        rule.held = held;
+        fired.then_some(&rule.action)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> {
+        self.entries.iter()
+    }
+}
@@ -0,0 +1,121 @@
+//! Commands that run periodically, kept in EEPROM so that they carry on
+//! after a reset without a host attached.
+//!
+//! Each entry takes a slot holding its interval as a little-endian word, or
+//! all ones for a free slot, followed by its command as a length-prefixed
+//! string. The first run of an entry is one interval after it was added or
+//! after the reset.
+
+use heapless::String;
+
+use crate::{millis, storage};
+
+/// How many entries the schedule holds.
+pub const MAX_ENTRIES: usize = (storage::SCHEDULE_SIZE / SLOT_SIZE) as usize;
+
+/// Longest command of an entry.
+pub const MAX_ACTION: usize = (SLOT_SIZE - 5) as usize;
+
+pub type Action = String<MAX_ACTION>;
+
+const SLOT_SIZE: u16 = 32;
+const FREE: u32 = u32::MAX;
+
+pub struct Entry {
+    pub interval_ms: u32,
+    pub action: Action,
+    next: u32,
+}
+
+pub struct Schedule {
+    entries: [Option<Entry>; MAX_ENTRIES],
+}
+
+fn slot_address(index: usize) -> u16 {
+    storage::SCHEDULE + index as u16 * SLOT_SIZE
+}
+
+impl Schedule {
+    pub const fn new() -> Self {
+        Schedule {
+            entries: [None, None, None, None],
+        }
+    }
+
+    /// Reads the schedule from EEPROM.
+    pub fn load(eeprom: &arduino_hal::Eeprom) -> Self {
+        let mut schedule = Schedule::new();
+        for (index, slot) in schedule.entries.iter_mut().enumerate() {
+            let address = slot_address(index);
+            let mut interval = [0; 4];
+            let _ = eeprom.read(address, &mut interval);
+            let interval_ms = u32::from_le_bytes(interval);
+            if interval_ms != FREE && interval_ms != 0 {
+                *slot = Some(Entry::new(
+                    interval_ms,
+                    storage::read_str(eeprom, address + 4),
+                ));
+            }
+        }
+        schedule
+    }
+
+    /// Adds an entry in the first free slot and returns its index.
+    ///
+    /// Fails if the command is too long, or if the schedule is full.
+    pub fn add(
+        &mut self,
+        eeprom: &mut arduino_hal::Eeprom,
+        interval_ms: u32,
+        action: &str,
+    ) -> Result<usize, ()> {
+        let action = Action::try_from(action)?;
+        let index = self.entries.iter().position(Option::is_none).ok_or(())?;
+        let address = slot_address(index);
+        storage::write_str(eeprom, address + 4, SLOT_SIZE - 4, &action)?;
+        let _ = eeprom.write(address, &interval_ms.to_le_bytes());
+        self.entries[index] = Some(Entry::new(interval_ms, action));
+        Ok(index)
+    }
+
+    /// Removes an entry. Fails if there is none at the index.
+    pub fn remove(&mut self, eeprom: &mut arduino_hal::Eeprom, index: usize) -> Result<(), ()> {
+        self.entries
+            .get_mut(index)
+            .and_then(Option::take)
+            .ok_or(())?;
+        let _ = eeprom.write(slot_address(index), &FREE.to_le_bytes());
+        Ok(())
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)?.as_ref()
+    }
+
+    /// Returns an entry's command if it is due, and schedules its next run.
+    /// Runs missed while the main loop was busy are skipped.
+    pub fn due(&mut self, index: usize) -> Option<&Action> {
+        let entry = self.entries.get_mut(index)?.as_mut()?;
+        let now = millis::millis();
+        let late = now.wrapping_sub(entry.next);
+        if (late as i32) < 0 {
+            return None;
+        }
+        entry.next = if late >= entry.interval_ms {
+            now.wrapping_add(entry.interval_ms)
+        } else {
+            entry.next.wrapping_add(entry.interval_ms)
+        };
+        Some(&entry.action)
+    }
+}
+
+impl Entry {
+    fn new(interval_ms: u32, action: Action) -> Self {
+        Entry {
+            interval_ms,
+            action,
+            next: millis::millis().wrapping_add(interval_ms),
+        }
+    }
+}
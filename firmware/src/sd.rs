@@ -0,0 +1,214 @@
+//! SD cards in SPI mode, read and written raw in 512-byte blocks.
+
+use crate::gpio::RawPin;
+use crate::{millis, spi};
+
+pub const BLOCK_SIZE: usize = 512;
+
+const GO_IDLE_STATE: u8 = 0;
+const SEND_IF_COND: u8 = 8;
+const SET_BLOCKLEN: u8 = 16;
+const READ_SINGLE_BLOCK: u8 = 17;
+const WRITE_BLOCK: u8 = 24;
+const APP_CMD: u8 = 55;
+const READ_OCR: u8 = 58;
+/// Application command, has to follow `APP_CMD`.
+const SD_SEND_OP_COND: u8 = 41;
+
+/// R1 response while the card is still initialising.
+const R1_IDLE: u8 = 0x01;
+const DATA_START: u8 = 0xFE;
+/// Data response token for an accepted block.
+const DATA_ACCEPTED: u8 = 0x05;
+/// Host capacity support in `SD_SEND_OP_COND`, and card capacity status in
+/// the OCR.
+const HIGH_CAPACITY: u32 = 1 << 30;
+
+/// How long the card gets to initialise, or to finish writing a block.
+const TIMEOUT_MS: u32 = 1000;
+
+pub enum Error {
+    /// No card answered the reset command.
+    NoCard,
+    /// The card did not finish initialising, or is not supported.
+    Init,
+    /// A read or write command was rejected with the given R1 response.
+    Command(u8),
+    /// A block was not accepted or not delivered.
+    Data,
+    Timeout,
+}
+
+pub struct SdCard {
+    cs: RawPin,
+    spi: spi::Config,
+    /// SDHC and SDXC cards are addressed by block rather than by byte.
+    high_capacity: bool,
+}
+
+impl SdCard {
+    /// Initialises the card behind a chip select pin, which has to be an
+    /// output driven high.
+    pub fn init(cs: RawPin, cs_pin: u8) -> Result<Self, Error> {
+        // Cards start up in open-drain mode and have to be clocked at no more
+        // than 400 kHz until initialised.
+        let mut card = SdCard {
+            cs,
+            spi: spi::Config {
+                mode: 0,
+                divider: 128,
+                cs: cs_pin,
+            },
+            high_capacity: false,
+        };
+        let bus = spi::Bus::begin(&card.spi);
+        let result = card.start(&bus);
+        card.cs.set_level(true);
+        bus.exchange(0xFF);
+        bus.end();
+        result?;
+        card.spi.divider = 4;
+        Ok(card)
+    }
+
+    fn start(&mut self, bus: &spi::Bus) -> Result<(), Error> {
+        // At least 74 clocks with the card deselected switch it to SPI mode.
+        for _ in 0..10 {
+            bus.exchange(0xFF);
+        }
+        self.cs.set_level(false);
+        if self.command(bus, GO_IDLE_STATE, 0) != R1_IDLE {
+            return Err(Error::NoCard);
+        }
+
+        // Only version 2 cards know this command, and echo the check pattern.
+        let version_2 = self.command(bus, SEND_IF_COND, 0x1AA) == R1_IDLE;
+        if version_2 {
+            let mut echo = [0xFF; 4];
+            bus.transfer(&mut echo);
+            if echo[3] != 0xAA {
+                return Err(Error::Init);
+            }
+        }
+
+        let start = millis::millis();
+        loop {
+            self.command(bus, APP_CMD, 0);
+            let argument = if version_2 { HIGH_CAPACITY } else { 0 };
+            if self.command(bus, SD_SEND_OP_COND, argument) == 0 {
+                break;
+            }
+            if millis::millis().wrapping_sub(start) > TIMEOUT_MS {
+                return Err(Error::Init);
+            }
+        }
+
+        if version_2 {
+            if self.command(bus, READ_OCR, 0) != 0 {
+                return Err(Error::Init);
+            }
+            let mut ocr = [0xFF; 4];
+            bus.transfer(&mut ocr);
+            self.high_capacity = u32::from_be_bytes(ocr) & HIGH_CAPACITY != 0;
+        }
+        if !self.high_capacity && self.command(bus, SET_BLOCKLEN, BLOCK_SIZE as u32) != 0 {
+            return Err(Error::Init);
+        }
+        Ok(())
+    }
+
+    /// Writes a block, clobbering `data` with whatever the card sends back
+    /// meanwhile.
+    pub fn write_block(&self, block: u32, data: &mut [u8; BLOCK_SIZE]) -> Result<(), Error> {
+        self.transaction(|bus| {
+            let response = self.command(bus, WRITE_BLOCK, self.address(block));
+            if response != 0 {
+                return Err(Error::Command(response));
+            }
+            bus.exchange(DATA_START);
+            bus.transfer(data);
+            // The CRC is not checked in SPI mode.
+            bus.exchange(0xFF);
+            bus.exchange(0xFF);
+            if bus.exchange(0xFF) & 0x1F != DATA_ACCEPTED {
+                return Err(Error::Data);
+            }
+            // The card holds its output low while it is programming.
+            let start = millis::millis();
+            while bus.exchange(0xFF) != 0xFF {
+                if millis::millis().wrapping_sub(start) > TIMEOUT_MS {
+                    return Err(Error::Timeout);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads a block, handing each byte to `f` as it arrives.
+    pub fn read_block(&self, block: u32, mut f: impl FnMut(u8)) -> Result<(), Error> {
+        self.transaction(|bus| {
+            let response = self.command(bus, READ_SINGLE_BLOCK, self.address(block));
+            if response != 0 {
+                return Err(Error::Command(response));
+            }
+            let start = millis::millis();
+            loop {
+                match bus.exchange(0xFF) {
+                    DATA_START => break,
+                    0xFF if millis::millis().wrapping_sub(start) <= TIMEOUT_MS => {}
+                    0xFF => return Err(Error::Timeout),
+                    _ => return Err(Error::Data),
+                }
+            }
+            for _ in 0..BLOCK_SIZE {
+                f(bus.exchange(0xFF));
+            }
+            bus.exchange(0xFF);
+            bus.exchange(0xFF);
+            Ok(())
+        })
+    }
+
+    fn transaction<T>(&self, f: impl FnOnce(&spi::Bus) -> Result<T, Error>) -> Result<T, Error> {
+        let bus = spi::Bus::begin(&self.spi);
+        self.cs.set_level(false);
+        let result = f(&bus);
+        self.cs.set_level(true);
+        // The card only releases its output on the clock after deselection.
+        bus.exchange(0xFF);
+        bus.end();
+        result
+    }
+
+    fn address(&self, block: u32) -> u32 {
+        if self.high_capacity {
+            block
+        } else {
+            block * BLOCK_SIZE as u32
+        }
+    }
+
+    /// Sends a command and returns its R1 response, or `0xFF` if there was
+    /// none.
+    fn command(&self, bus: &spi::Bus, index: u8, argument: u32) -> u8 {
+        // Only the first two commands need a valid CRC in SPI mode.
+        let crc = match index {
+            GO_IDLE_STATE => 0x95,
+            SEND_IF_COND => 0x87,
+            _ => 0x01,
+        };
+        bus.exchange(0xFF);
+        bus.exchange(0x40 | index);
+        for byte in argument.to_be_bytes() {
+            bus.exchange(byte);
+        }
+        bus.exchange(crc);
+        for _ in 0..10 {
+            let response = bus.exchange(0xFF);
+            if response & 0x80 == 0 {
+                return response;
+            }
+        }
+        0xFF
+    }
+}
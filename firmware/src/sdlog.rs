@@ -0,0 +1,110 @@
+//! Logging samples to an SD card.
+//!
+//! The log is plain text, one line of comma-separated values per sample,
+//! written raw from the first block of the card on and ended by a block of
+//! zeros. Any filesystem on the card is overwritten.
+
+use ufmt::uWrite;
+
+use crate::millis;
+use crate::sd::{self, SdCard, BLOCK_SIZE};
+
+pub use protocol::args::sdlog::{Channel, Channels, MAX_CHANNELS};
+
+pub struct Logger {
+    card: SdCard,
+    channels: Channels,
+    interval_ms: u32,
+    next_sample: u32,
+    /// The block being filled.
+    block: u32,
+    buffer: [u8; BLOCK_SIZE],
+    len: usize,
+}
+
+impl Logger {
+    /// Starts a new log, replacing the one on the card.
+    pub fn new(card: SdCard, channels: Channels, interval_ms: u32) -> Result<Self, sd::Error> {
+        let mut logger = Logger {
+            card,
+            channels,
+            interval_ms,
+            next_sample: millis::millis(),
+            block: 0,
+            buffer: [0; BLOCK_SIZE],
+            len: 0,
+        };
+        logger.card.write_block(0, &mut logger.buffer)?;
+        logger.buffer = [0; BLOCK_SIZE];
+        Ok(logger)
+    }
+
+    pub fn card(&self) -> &SdCard {
+        &self.card
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    /// Returns whether the next sample is due, and schedules the one after.
+    pub fn is_due(&mut self) -> bool {
+        if (millis::millis().wrapping_sub(self.next_sample) as i32) < 0 {
+            return false;
+        }
+        self.next_sample = self.next_sample.wrapping_add(self.interval_ms);
+        true
+    }
+
+    /// Writes out the partially filled block, along with the end marker after
+    /// it.
+    pub fn flush(&mut self) -> Result<(), sd::Error> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        self.card.write_block(self.block, &mut self.buffer)?;
+        self.buffer = [0; BLOCK_SIZE];
+        self.card.write_block(self.block + 1, &mut self.buffer)?;
+        self.buffer = [0; BLOCK_SIZE];
+        self.len = 0;
+        self.block += 1;
+        Ok(())
+    }
+}
+
+impl uWrite for Logger {
+    type Error = sd::Error;
+
+    /// Appends to the log. Full blocks are written out right away, so little
+    /// is lost if power fails.
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        for &byte in s.as_bytes() {
+            self.buffer[self.len] = byte;
+            self.len += 1;
+            if self.len == BLOCK_SIZE {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints the log on a card, calling `f` with each character of it.
+pub fn dump(card: &SdCard, mut f: impl FnMut(u8)) -> Result<(), sd::Error> {
+    let mut block = 0;
+    loop {
+        let mut ended = false;
+        card.read_block(block, |byte| {
+            if byte == 0 {
+                ended = true;
+            }
+            if !ended {
+                f(byte);
+            }
+        })?;
+        if ended {
+            return Ok(());
+        }
+        block += 1;
+    }
+}
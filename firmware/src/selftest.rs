@@ -0,0 +1,55 @@
+//! Checks for `selftest`, a quick go/no-go test of a freshly built board.
+//!
+//! Each check leaves the hardware as it found it. Checking pins needs pairs
+//! of them jumpered together, which the main loop drives, as it owns them.
+
+use arduino_hal::hal::adc;
+
+use crate::{millis, storage, BANDGAP_MILLIVOLTS};
+
+/// The range of supply voltages a working board runs at, in millivolts.
+pub const VCC_RANGE: core::ops::RangeInclusive<u32> = 2700..=5500;
+
+/// The highest reading of the ground channel, which should be zero.
+const GROUND_NOISE: u16 = 8;
+
+/// Measures the supply voltage against the bandgap, and checks that the
+/// ground channel reads zero. Returns the voltage in millivolts, if it is
+/// within [`VCC_RANGE`].
+pub fn adc(adc: &mut arduino_hal::Adc) -> Result<u32, u32> {
+    let _ = adc.read_blocking(&adc::channel::Gnd);
+    let ground = adc.read_blocking(&adc::channel::Gnd);
+    // The bandgap needs a moment to settle after switching the multiplexer
+    // over, so the first conversion is discarded.
+    let _ = adc.read_blocking(&adc::channel::Vbg);
+    let bandgap = adc.read_blocking(&adc::channel::Vbg).max(1);
+    let millivolts = BANDGAP_MILLIVOLTS * 1024 / u32::from(bandgap);
+    if ground <= GROUND_NOISE && VCC_RANGE.contains(&millivolts) {
+        Ok(millivolts)
+    } else {
+        Err(millivolts)
+    }
+}
+
+/// Writes two patterns to a scratch cell and reads them back, then puts
+/// back what was there.
+pub fn eeprom(eeprom: &mut arduino_hal::Eeprom) -> bool {
+    let original = eeprom.read_byte(storage::SELFTEST);
+    let pass = [0x55, 0xAA].into_iter().all(|pattern| {
+        eeprom.write_byte(storage::SELFTEST, pattern);
+        eeprom.read_byte(storage::SELFTEST) == pattern
+    });
+    eeprom.write_byte(storage::SELFTEST, original);
+    pass
+}
+
+/// Checks that the millisecond tick and the microsecond clock agree with a
+/// busy wait of 20 ms.
+pub fn timers() -> bool {
+    let start_ms = millis::millis();
+    let start_us = millis::micros();
+    arduino_hal::delay_ms(20);
+    let elapsed_ms = millis::millis().wrapping_sub(start_ms);
+    let elapsed_us = millis::micros().wrapping_sub(start_us);
+    (19..=22).contains(&elapsed_ms) && (19_000..=22_000).contains(&elapsed_us)
+}
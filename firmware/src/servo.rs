@@ -0,0 +1,93 @@
+//! Hobby servos on the Timer1 outputs, d9 and d10 on the Uno.
+//!
+//! Timer1 runs a 20 ms frame with 0.5 µs resolution once the first servo is
+//! set, and each output pulses for 544-2400 µs depending on the angle, the
+//! same range the Arduino servo library uses.
+
+use crate::{board, millis};
+
+/// The pins with a Timer1 output, OC1A and OC1B.
+pub const PINS: [u8; 2] = board::TIMER1_PINS;
+
+pub const MAX_ANGLE: u8 = 180;
+
+/// 20 ms frame at 0.5 µs per count.
+const FRAME_COUNTS: u16 = 40_000;
+const MIN_PULSE_US: u32 = 544;
+const MAX_PULSE_US: u32 = 2400;
+
+/// Whether Timer1 is running the servo frame.
+pub fn is_active() -> bool {
+    // SAFETY: Only reads the timer's configuration.
+    let tc1 = unsafe { &*arduino_hal::pac::TC1::ptr() };
+    !tc1.tccr1b.read().cs1().is_no_clock() && tc1.icr1.read().bits() == FRAME_COUNTS - 1
+}
+
+/// Moves the servo on one of [`PINS`], which has to be an output driven low.
+pub fn write(pin: u8, angle: u8) {
+    // SAFETY: Timer1 is not used by anything else while servos are attached.
+    let tc1 = unsafe { &*arduino_hal::pac::TC1::ptr() };
+    if tc1.tccr1b.read().cs1().is_no_clock() {
+        // Fast PWM with the frame length in ICR1.
+        tc1.icr1.write(|w| w.bits(FRAME_COUNTS - 1));
+        tc1.tccr1a.write(|w| w.wgm1().bits(0b10));
+        tc1.tccr1b.write(|w| w.wgm1().bits(0b11).cs1().prescale_8());
+    }
+
+    let angle = u32::from(angle.min(MAX_ANGLE));
+    let pulse_us = MIN_PULSE_US + (MAX_PULSE_US - MIN_PULSE_US) * angle / u32::from(MAX_ANGLE);
+    let counts = (pulse_us * 2) as u16;
+    if pin == PINS[0] {
+        tc1.ocr1a.write(|w| w.bits(counts));
+        tc1.tccr1a.modify(|_, w| w.com1a().match_clear());
+    } else {
+        tc1.ocr1b.write(|w| w.bits(counts));
+        tc1.tccr1a.modify(|_, w| w.com1b().match_clear());
+    }
+}
+
+/// A servo moving from one angle to another at a steady rate.
+pub struct Sweep {
+    pin: u8,
+    from: u8,
+    to: u8,
+    start: u32,
+    duration_ms: u16,
+    angle: u8,
+}
+
+impl Sweep {
+    pub fn new(pin: u8, from: u8, to: u8, duration_ms: u16) -> Self {
+        write(pin, from);
+        Sweep {
+            pin,
+            from: from.min(MAX_ANGLE),
+            to: to.min(MAX_ANGLE),
+            start: millis::millis(),
+            duration_ms,
+            angle: from,
+        }
+    }
+
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Moves the servo along. Returns `true` once it has reached the end.
+    pub fn poll(&mut self) -> bool {
+        let elapsed = millis::millis().wrapping_sub(self.start);
+        let duration = u32::from(self.duration_ms);
+        let angle = if elapsed >= duration {
+            self.to
+        } else {
+            let from = i32::from(self.from);
+            let distance = i32::from(self.to) - from;
+            (from + distance * elapsed as i32 / duration as i32) as u8
+        };
+        if angle != self.angle {
+            write(self.pin, angle);
+            self.angle = angle;
+        }
+        elapsed >= duration
+    }
+}
@@ -0,0 +1,36 @@
+//! Shift registers clocked by hand, such as the 74HC595 and 74HC165.
+
+use crate::gpio::RawPin;
+
+pub use protocol::args::shift::BitOrder;
+
+/// Clocks out one byte on the rising edges of the clock, which has to be an
+/// output driven low.
+pub fn shift_out(data: &RawPin, clock: &RawPin, order: BitOrder, byte: u8) {
+    for i in 0..8 {
+        let bit = match order {
+            BitOrder::MsbFirst => byte & (0x80 >> i),
+            BitOrder::LsbFirst => byte & (1 << i),
+        };
+        data.set_level(bit != 0);
+        clock.set_level(true);
+        clock.set_level(false);
+    }
+}
+
+/// Clocks in one byte from a register that presents its first bit before the
+/// first rising edge of the clock, as the 74HC165 does.
+pub fn shift_in(data: &RawPin, clock: &RawPin, order: BitOrder) -> u8 {
+    let mut byte = 0;
+    for i in 0..8 {
+        if data.is_high() {
+            byte |= match order {
+                BitOrder::MsbFirst => 0x80 >> i,
+                BitOrder::LsbFirst => 1 << i,
+            };
+        }
+        clock.set_level(true);
+        clock.set_level(false);
+    }
+    byte
+}
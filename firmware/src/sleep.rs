@@ -0,0 +1,58 @@
+//! Low-power sleep.
+
+use crate::pcint;
+
+pub use protocol::args::sleep::SleepMode;
+
+/// Arduino pin number of the serial receive line.
+const RX_PIN: u8 = 0;
+
+/// Sleeps until a byte arrives on the serial port or `wake_pin` changes level.
+///
+/// In power-down mode the UART is stopped along with all clocks, so it is
+/// woken by a pin change on the receive line instead, and the byte that caused
+/// it is lost. The millisecond tick also pauses while powered down.
+pub fn sleep(mode: SleepMode, wake_pin: Option<u8>) {
+    // SAFETY: Sleep mode is only configured from here.
+    let cpu = unsafe { &*arduino_hal::pac::CPU::ptr() };
+
+    match mode {
+        SleepMode::Idle => cpu.smcr.write(|w| w.sm().idle()),
+        SleepMode::PowerDown => {
+            cpu.smcr.write(|w| w.sm().pdown());
+            pcint::enable(RX_PIN);
+        }
+    }
+    if let Some(pin) = wake_pin {
+        pcint::enable(pin);
+    }
+
+    pcint::take_triggered();
+    // In idle mode the millisecond tick wakes the CPU regularly to check
+    // whether a byte has arrived.
+    while !has_input() && !pcint::take_triggered() {
+        cpu.smcr.modify(|_, w| w.se().set_bit());
+        avr_device::asm::sleep();
+        cpu.smcr.modify(|_, w| w.se().clear_bit());
+    }
+
+    if matches!(mode, SleepMode::PowerDown) {
+        pcint::disable(RX_PIN);
+    }
+    if let Some(pin) = wake_pin {
+        pcint::disable(pin);
+    }
+}
+
+#[cfg(not(feature = "leonardo"))]
+fn has_input() -> bool {
+    // SAFETY: The UART status register is only read.
+    let usart = unsafe { &*arduino_hal::pac::USART0::ptr() };
+    usart.ucsr0a.read().rxc0().bit_is_set()
+}
+
+/// On the 32U4, the USB interrupts wake the CPU as well as the tick.
+#[cfg(feature = "leonardo")]
+fn has_input() -> bool {
+    crate::usb::has_input()
+}
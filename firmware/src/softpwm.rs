@@ -0,0 +1,188 @@
+//! Software PWM on any digital pin, at 1 kHz.
+//!
+//! Each millisecond tick of Timer2 starts a frame and drives every channel
+//! high. Timer2's second compare unit then interrupts at each channel's duty
+//! cycle in turn to drive it low again, so the interrupts cost a few
+//! microseconds per channel and frame rather than one per PWM step. On the
+//! 32U4 the tick and the compare unit are Timer3's.
+
+use core::cell::Cell;
+
+use avr_device::interrupt::{self, CriticalSection, Mutex};
+
+use crate::gpio::RawPin;
+
+/// How many pins can run software PWM at the same time.
+pub const MAX_CHANNELS: usize = 4;
+
+/// Timer2 counts per frame, as set up by [`crate::millis`].
+const FRAME_COUNTS: u16 = 250;
+
+#[derive(Clone, Copy)]
+struct Channel {
+    pin: u8,
+    /// The timer count at which the pin goes low, or the whole frame for a
+    /// pin that stays high.
+    end: u8,
+}
+
+static CHANNELS: Mutex<Cell<[Option<Channel>; MAX_CHANNELS]>> =
+    Mutex::new(Cell::new([None; MAX_CHANNELS]));
+
+#[cfg(not(feature = "leonardo"))]
+mod timer {
+    fn tc2() -> &'static arduino_hal::pac::tc2::RegisterBlock {
+        // SAFETY: Only the second compare unit is used, which `millis` leaves
+        // alone.
+        unsafe { &*arduino_hal::pac::TC2::ptr() }
+    }
+
+    pub fn count() -> u8 {
+        tc2().tcnt2.read().bits()
+    }
+
+    /// The count the compare unit interrupts at.
+    pub fn scheduled() -> u8 {
+        tc2().ocr2b.read().bits()
+    }
+
+    pub fn schedule(end: u8) {
+        let tc2 = tc2();
+        tc2.ocr2b.write(|w| w.bits(end));
+        tc2.tifr2.write(|w| w.ocf2b().set_bit());
+        tc2.timsk2.modify(|_, w| w.ocie2b().set_bit());
+    }
+
+    pub fn unschedule() {
+        tc2().timsk2.modify(|_, w| w.ocie2b().clear_bit());
+    }
+}
+
+#[cfg(feature = "leonardo")]
+mod timer {
+    fn tc3() -> &'static arduino_hal::pac::tc3::RegisterBlock {
+        // SAFETY: Only the second compare unit is used, which `millis` leaves
+        // alone.
+        unsafe { &*arduino_hal::pac::TC3::ptr() }
+    }
+
+    /// The count, which stays below a frame's 250 and so fits a byte.
+    pub fn count() -> u8 {
+        tc3().tcnt3.read().bits() as u8
+    }
+
+    /// The count the compare unit interrupts at.
+    pub fn scheduled() -> u8 {
+        tc3().ocr3b.read().bits() as u8
+    }
+
+    pub fn schedule(end: u8) {
+        let tc3 = tc3();
+        tc3.ocr3b.write(|w| w.bits(u16::from(end)));
+        tc3.tifr3.write(|w| w.ocf3b().set_bit());
+        tc3.timsk3.modify(|_, w| w.ocie3b().set_bit());
+    }
+
+    pub fn unschedule() {
+        tc3().timsk3.modify(|_, w| w.ocie3b().clear_bit());
+    }
+}
+
+fn set_level(pin: u8, high: bool) {
+    if let Some(pin) = RawPin::new(pin) {
+        pin.set_level(high);
+    }
+}
+
+/// Sets the duty cycle of a digital pin (0-13), which has to be an output.
+/// A duty cycle of zero stops the pin's PWM and leaves it low.
+///
+/// Fails if all channels are in use by other pins.
+pub fn set(pin: u8, duty: u8) -> Result<(), ()> {
+    interrupt::free(|cs| {
+        let cell = CHANNELS.borrow(cs);
+        let mut channels = cell.get();
+        let existing = channels
+            .iter()
+            .position(|c| c.map_or(false, |c| c.pin == pin));
+        if duty == 0 {
+            if let Some(index) = existing {
+                channels[index] = None;
+            }
+            set_level(pin, false);
+            cell.set(channels);
+            return Ok(());
+        }
+        let Some(index) = existing.or_else(|| channels.iter().position(Option::is_none)) else {
+            return Err(());
+        };
+        let end = (u16::from(duty) * FRAME_COUNTS / 255) as u8;
+        channels[index] = Some(Channel { pin, end });
+        cell.set(channels);
+        Ok(())
+    })
+}
+
+/// Returns the earliest end of a channel after the given timer count.
+fn next_end(channels: &[Option<Channel>], after: Option<u8>) -> Option<u8> {
+    channels
+        .iter()
+        .flatten()
+        .map(|c| c.end)
+        .filter(|&end| after.map_or(true, |after| end > after) && u16::from(end) < FRAME_COUNTS)
+        .min()
+}
+
+/// Starts a frame. Called from the millisecond tick.
+pub fn start_frame(cs: CriticalSection) {
+    let channels = CHANNELS.borrow(cs).get();
+    for channel in channels.iter().flatten() {
+        set_level(channel.pin, true);
+    }
+    end_from(&channels, next_end(&channels, None));
+}
+
+/// Drives low the channels whose end has already come, starting with `next`,
+/// and schedules an interrupt for the first one still to come.
+///
+/// An end that passed while the interrupts were being handled would not
+/// raise another one until the next frame.
+fn end_from(channels: &[Option<Channel>], mut next: Option<u8>) {
+    while let Some(end) = next {
+        if end > timer::count() {
+            timer::schedule(end);
+            return;
+        }
+        end_channels(channels, end);
+        next = next_end(channels, Some(end));
+    }
+    timer::unschedule();
+}
+
+fn end_channels(channels: &[Option<Channel>], end: u8) {
+    for channel in channels.iter().flatten().filter(|c| c.end == end) {
+        set_level(channel.pin, false);
+    }
+}
+
+#[cfg_attr(feature = "uno", avr_device::interrupt(atmega328p))]
+#[cfg_attr(feature = "mega2560", avr_device::interrupt(atmega2560))]
+#[cfg(not(feature = "leonardo"))]
+fn TIMER2_COMPB() {
+    end_scheduled();
+}
+
+#[cfg(feature = "leonardo")]
+#[avr_device::interrupt(atmega32u4)]
+fn TIMER3_COMPB() {
+    end_scheduled();
+}
+
+fn end_scheduled() {
+    interrupt::free(|cs| {
+        let channels = CHANNELS.borrow(cs).get();
+        let end = timer::scheduled();
+        end_channels(&channels, end);
+        end_from(&channels, next_end(&channels, Some(end)));
+    })
+}
@@ -0,0 +1,139 @@
+//! Hardware SPI master on d11 (MOSI), d12 (MISO), and d13 (SCK) on the Uno,
+//! and d51, d50, and d52 on the Mega.
+//!
+//! The peripheral is only enabled for the duration of a transfer, so the pins
+//! go back to their usual roles in between. On the Uno the built-in LED
+//! shares its pin with SCK and flickers along with the clock.
+
+#[cfg(feature = "ethernet")]
+use core::cell::Cell;
+
+#[cfg(feature = "ethernet")]
+use avr_device::interrupt::{self, Mutex};
+
+use crate::board;
+
+/// Clock dividers supported by the hardware, relative to the 16 MHz CPU clock.
+pub const DIVIDERS: [u8; 7] = [2, 4, 8, 16, 32, 64, 128];
+
+/// Arduino pins used by the peripheral itself, which cannot be chip selects.
+pub const BUS_PINS: [u8; 3] = board::SPI_BUS_PINS;
+
+pub struct Config {
+    /// SPI mode 0-3, combining clock polarity and phase.
+    pub mode: u8,
+    /// One of [`DIVIDERS`].
+    pub divider: u8,
+    /// Arduino pin number of the chip select line, driven low during a
+    /// transfer.
+    pub cs: u8,
+}
+
+impl Config {
+    pub const fn new() -> Self {
+        Config {
+            mode: 0,
+            divider: 4,
+            cs: board::SPI_SS_PIN,
+        }
+    }
+}
+
+/// Clocks out `data` and replaces it with the bytes clocked back in.
+///
+/// The chip select is left to the caller. Note that the slave select, d10 on
+/// the Uno, has to be an output for the duration, otherwise the peripheral
+/// drops out of master mode when it is pulled low.
+pub fn transfer(config: &Config, data: &mut [u8]) {
+    let bus = Bus::begin(config);
+    bus.transfer(data);
+    bus.end();
+}
+
+/// Whether a [`Bus`] is in the middle of a transaction.
+#[cfg(feature = "ethernet")]
+static BUSY: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Whether a transaction is under way, which output to another device on the
+/// bus has to wait for.
+#[cfg(feature = "ethernet")]
+pub fn is_busy() -> bool {
+    interrupt::free(|cs| BUSY.borrow(cs).get())
+}
+
+/// The peripheral enabled for a transaction made up of several transfers.
+///
+/// Between [`transfer`] calls the clock line goes back to following the LED,
+/// which a device whose chip select is still low could take for clock edges.
+pub struct Bus {
+    /// Port B directions from before the transaction.
+    directions: u8,
+}
+
+impl Bus {
+    pub fn begin(config: &Config) -> Self {
+        let (spr, double_speed) = match config.divider {
+            2 => (0, true),
+            4 => (0, false),
+            8 => (1, true),
+            16 => (1, false),
+            32 => (2, true),
+            64 => (2, false),
+            _ => (3, false),
+        };
+
+        let directions = portb().ddrb.read().bits();
+        // SAFETY: Only MOSI and SCK are added to the directions.
+        portb()
+            .ddrb
+            .modify(|r, w| unsafe { w.bits(r.bits() | board::SPI_OUTPUTS) });
+        spi().spsr.write(|w| w.spi2x().bit(double_speed));
+        spi().spcr.write(|w| {
+            w.spe()
+                .set_bit()
+                .mstr()
+                .set_bit()
+                .cpol()
+                .bit(config.mode & 0b10 != 0)
+                .cpha()
+                .bit(config.mode & 0b01 != 0)
+                .spr()
+                .bits(spr)
+        });
+        #[cfg(feature = "ethernet")]
+        interrupt::free(|cs| BUSY.borrow(cs).set(true));
+        Bus { directions }
+    }
+
+    pub fn transfer(&self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            *byte = self.exchange(*byte);
+        }
+    }
+
+    /// Clocks out one byte and returns the one clocked back in.
+    pub fn exchange(&self, byte: u8) -> u8 {
+        spi().spdr.write(|w| w.bits(byte));
+        while spi().spsr.read().spif().bit_is_clear() {}
+        spi().spdr.read().bits()
+    }
+
+    pub fn end(self) {
+        spi().spcr.reset();
+        // SAFETY: Restores the directions read when the transaction began.
+        portb().ddrb.write(|w| unsafe { w.bits(self.directions) });
+        #[cfg(feature = "ethernet")]
+        interrupt::free(|cs| BUSY.borrow(cs).set(false));
+    }
+}
+
+fn spi() -> &'static arduino_hal::pac::spi::RegisterBlock {
+    // SAFETY: The SPI peripheral is only used from here.
+    unsafe { &*arduino_hal::pac::SPI::ptr() }
+}
+
+fn portb() -> &'static arduino_hal::pac::portb::RegisterBlock {
+    // SAFETY: Only the directions of the bus pins are changed, and they are
+    // restored at the end of each transaction.
+    unsafe { &*arduino_hal::pac::PORTB::ptr() }
+}
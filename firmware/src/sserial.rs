@@ -0,0 +1,193 @@
+//! Software serial port on any two digital pins.
+//!
+//! Bytes are received from the pin change interrupt, which only notes the
+//! time of each edge on the receive pin and works out the bits since the one
+//! before, so interrupts stay off for a few microseconds per edge rather
+//! than for a whole byte. The bits after the last edge of a byte are filled
+//! in when the next start bit comes, or once the byte's time is up.
+//!
+//! Bytes are sent with interrupts on, each bit timed against the microsecond
+//! clock so that interrupts in between make it late by a few microseconds
+//! but don't delay the bits after it.
+
+use core::cell::{Cell, RefCell};
+
+use avr_device::interrupt::{self, CriticalSection, Mutex};
+use heapless::Deque;
+
+use crate::gpio::RawPin;
+use crate::{millis, pcint};
+
+pub use protocol::args::sserial::BAUD_RATES;
+
+/// Received bytes not yet forwarded to the console.
+const BUFFER_SIZE: usize = 32;
+
+/// A start bit, eight data bits, and a stop bit.
+const FRAME_BITS: u8 = 10;
+
+#[derive(Clone, Copy)]
+struct Port {
+    rx: u8,
+    tx: u8,
+    bit_us: u32,
+}
+
+/// The byte being received.
+struct Frame {
+    /// The time of the falling edge of the start bit.
+    start_us: u32,
+    /// The bits up to here have been taken into `byte`.
+    bit: u8,
+    byte: u8,
+}
+
+struct Receiver {
+    /// The level of the receive pin after the last edge.
+    high: bool,
+    frame: Option<Frame>,
+    bytes: Deque<u8, BUFFER_SIZE>,
+}
+
+static PORT: Mutex<Cell<Option<Port>>> = Mutex::new(Cell::new(None));
+static RECEIVER: Mutex<RefCell<Receiver>> = Mutex::new(RefCell::new(Receiver {
+    high: true,
+    frame: None,
+    bytes: Deque::new(),
+}));
+
+/// Opens the port. The receive pin has to be an input and the transmit pin
+/// an output driven high, the idle level.
+pub fn open(rx: u8, tx: u8, baud: u32) {
+    interrupt::free(|cs| {
+        if let Some(old) = PORT.borrow(cs).get() {
+            pcint::disable(old.rx);
+        }
+        PORT.borrow(cs).set(Some(Port {
+            rx,
+            tx,
+            bit_us: 1_000_000 / baud,
+        }));
+        let mut receiver = RECEIVER.borrow(cs).borrow_mut();
+        receiver.high = true;
+        receiver.frame = None;
+        receiver.bytes.clear();
+        pcint::enable(rx);
+    })
+}
+
+pub fn close() {
+    interrupt::free(|cs| {
+        if let Some(old) = PORT.borrow(cs).take() {
+            pcint::disable(old.rx);
+        }
+    })
+}
+
+pub fn is_open() -> bool {
+    interrupt::free(|cs| PORT.borrow(cs).get().is_some())
+}
+
+/// Sends bytes, eight data bits and one stop bit each. Does nothing if the
+/// port is closed.
+pub fn send(bytes: &[u8]) {
+    let Some(port) = interrupt::free(|cs| PORT.borrow(cs).get()) else {
+        return;
+    };
+    let Some(tx) = RawPin::new(port.tx) else {
+        return;
+    };
+    for &byte in bytes {
+        // The start bit, the data bits least significant first, and the stop
+        // bit.
+        let frame = u16::from(byte) << 1 | 1 << 9;
+        let start = millis::micros();
+        for bit in 0..FRAME_BITS {
+            tx.set_level(frame & (1 << bit) != 0);
+            let end = u32::from(bit + 1) * port.bit_us;
+            while millis::micros().wrapping_sub(start) < end {}
+        }
+    }
+}
+
+/// Returns the oldest received byte, if any.
+pub fn take_byte() -> Option<u8> {
+    interrupt::free(|cs| {
+        let port = PORT.borrow(cs).get()?;
+        let mut receiver = RECEIVER.borrow(cs).borrow_mut();
+        // A byte ending in ones has no edge after its last data bit.
+        let now = millis::micros();
+        if let Some(frame) = &receiver.frame {
+            if now.wrapping_sub(frame.start_us) >= u32::from(FRAME_BITS) * port.bit_us {
+                let high = receiver.high;
+                receiver.finish(high);
+            }
+        }
+        receiver.bytes.pop_front()
+    })
+}
+
+/// Takes the bits since the last edge if the receive pin changed. Called
+/// from the pin change interrupt handlers.
+pub fn update(cs: CriticalSection) {
+    let Some(port) = PORT.borrow(cs).get() else {
+        return;
+    };
+    let Some(rx) = RawPin::new(port.rx) else {
+        return;
+    };
+    let high = rx.is_high();
+    let mut receiver = RECEIVER.borrow(cs).borrow_mut();
+    if high == receiver.high {
+        return;
+    }
+    let now = millis::micros();
+    let level = receiver.high;
+    receiver.high = high;
+    if let Some(frame) = &mut receiver.frame {
+        // The edge is on the boundary of the bit it starts, whose number
+        // comes from rounding the time since the start bit.
+        let elapsed = now.wrapping_sub(frame.start_us);
+        let mut bit = 0;
+        let mut boundary = port.bit_us / 2;
+        while elapsed >= boundary && bit < FRAME_BITS {
+            bit += 1;
+            boundary += port.bit_us;
+        }
+        if bit < FRAME_BITS {
+            frame.take(bit, level);
+            return;
+        }
+        receiver.finish(level);
+    }
+    if !high {
+        receiver.frame = Some(Frame {
+            start_us: now,
+            bit: 1,
+            byte: 0,
+        });
+    }
+}
+
+impl Frame {
+    /// Takes the bits up to `end` as all at one level.
+    fn take(&mut self, end: u8, high: bool) {
+        if high {
+            for bit in self.bit.max(1)..end.min(9) {
+                self.byte |= 1 << (bit - 1);
+            }
+        }
+        self.bit = self.bit.max(end);
+    }
+}
+
+impl Receiver {
+    /// Takes the rest of the byte as all at one level, and queues it. A full
+    /// buffer drops the newest bytes.
+    fn finish(&mut self, high: bool) {
+        if let Some(mut frame) = self.frame.take() {
+            frame.take(FRAME_BITS, high);
+            let _ = self.bytes.push_back(frame.byte);
+        }
+    }
+}
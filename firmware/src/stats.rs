@@ -0,0 +1,67 @@
+//! Counters for debugging flaky links and lost events.
+//!
+//! The counters are kept in RAM and start from zero after each reset. They
+//! saturate rather than wrap around.
+
+pub struct Stats {
+    /// Commands run, including the ones that failed.
+    pub commands: u32,
+    /// Commands that did not parse.
+    pub parse_errors: u32,
+    /// Received bytes with a missing stop bit, usually a baud rate mismatch.
+    pub framing_errors: u32,
+    /// Received bytes lost because the main loop was too slow to read them.
+    pub overruns: u32,
+    /// Input lines dropped for not fitting into the line buffer.
+    pub line_overflows: u32,
+    /// Pin change, comparator, and button events printed.
+    pub events: u32,
+    /// Pin change events dropped because their queue was full.
+    pub events_dropped: u32,
+    /// `expect` commands that found an input other than expected.
+    pub expect_failures: u32,
+}
+
+#[cfg(not(feature = "leonardo"))]
+fn usart0() -> &'static arduino_hal::pac::usart0::RegisterBlock {
+    // SAFETY: Only reads the status register, which the serial driver does
+    // not depend on.
+    unsafe { &*arduino_hal::pac::USART0::ptr() }
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Stats {
+            commands: 0,
+            parse_errors: 0,
+            framing_errors: 0,
+            overruns: 0,
+            line_overflows: 0,
+            events: 0,
+            events_dropped: 0,
+            expect_failures: 0,
+        }
+    }
+
+    /// Counts the errors of the byte waiting in the UART, which the serial
+    /// driver does not report. Has to be called before the byte is read, as
+    /// reading it clears its errors.
+    #[cfg(not(feature = "leonardo"))]
+    pub fn check_uart(&mut self) {
+        let status = usart0().ucsr0a.read();
+        if status.rxc0().bit_is_clear() {
+            return;
+        }
+        if status.fe0().bit_is_set() {
+            count(&mut self.framing_errors, 1);
+        }
+        if status.dor0().bit_is_set() {
+            count(&mut self.overruns, 1);
+        }
+    }
+}
+
+/// Adds to a counter.
+pub fn count(counter: &mut u32, n: u32) {
+    *counter = counter.saturating_add(n);
+}
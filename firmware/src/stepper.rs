@@ -0,0 +1,129 @@
+//! Stepper motors, stepped from the main loop.
+//!
+//! Moves ramp up to their target speed and back down before the end, since a
+//! motor started at full speed tends to stall and lose steps.
+
+use crate::gpio::RawPin;
+use crate::millis;
+
+/// Acceleration of the speed ramps, in steps per second squared.
+const ACCELERATION: u32 = 500;
+/// The speed moves start and end at, in steps per second.
+const MIN_SPEED: u32 = 50;
+
+/// Coil patterns for the four wires, in order, energising two coils at a time.
+const FULL_STEPS: [u8; 4] = [0b1001, 0b0011, 0b0110, 0b1100];
+
+pub enum Driver {
+    /// A unipolar motor behind a ULN2003 or similar, one pin per coil.
+    FourWire([RawPin; 4]),
+    /// A driver board such as the A4988, moving one step per pulse.
+    StepDir { step: RawPin, dir: RawPin },
+}
+
+pub struct Stepper {
+    driver: Driver,
+    /// Index into [`FULL_STEPS`] for four-wire motors.
+    phase: u8,
+    remaining: u32,
+    forward: bool,
+    /// Current and target speed, in steps per second.
+    speed: u32,
+    target: u32,
+    /// When the next step is due, in microseconds.
+    next_step: u32,
+}
+
+impl Stepper {
+    /// Takes over the driver's pins, which have to be outputs driven low.
+    pub fn new(driver: Driver) -> Self {
+        Stepper {
+            driver,
+            phase: 0,
+            remaining: 0,
+            forward: true,
+            speed: 0,
+            target: 0,
+            next_step: 0,
+        }
+    }
+
+    /// Starts moving by a number of steps, negative for reverse, at up to
+    /// `speed` steps per second. Replaces any move in progress.
+    pub fn start(&mut self, steps: i32, speed: u16) {
+        self.remaining = steps.unsigned_abs();
+        self.forward = steps >= 0;
+        self.target = u32::from(speed).max(MIN_SPEED);
+        self.speed = MIN_SPEED;
+        self.next_step = millis::micros();
+        if let Driver::StepDir { dir, .. } = self.driver {
+            dir.set_level(self.forward);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.remaining = 0;
+        self.release();
+    }
+
+    /// Takes the next step if it is due. Returns `true` when this finished the
+    /// move.
+    pub fn poll(&mut self) -> bool {
+        if self.remaining == 0 {
+            return false;
+        }
+        let now = millis::micros();
+        if (now.wrapping_sub(self.next_step) as i32) < 0 {
+            return false;
+        }
+        self.step();
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.release();
+            return true;
+        }
+
+        let interval = 1_000_000 / self.speed;
+        let change = (ACCELERATION * interval / 1_000_000).max(1);
+        // Slow down once the remaining steps are about what stopping takes.
+        let stopping_steps = self.speed * self.speed / (2 * ACCELERATION);
+        self.speed = if self.remaining <= stopping_steps {
+            self.speed.saturating_sub(change).max(MIN_SPEED)
+        } else {
+            (self.speed + change).min(self.target)
+        };
+        self.next_step = self.next_step.wrapping_add(interval);
+        false
+    }
+
+    fn step(&mut self) {
+        match self.driver {
+            Driver::FourWire(pins) => {
+                self.phase = if self.forward {
+                    (self.phase + 1) % 4
+                } else {
+                    (self.phase + 3) % 4
+                };
+                let pattern = FULL_STEPS[usize::from(self.phase)];
+                for (i, pin) in pins.iter().enumerate() {
+                    pin.set_level(pattern & (1 << i) != 0);
+                }
+            }
+            Driver::StepDir { step, .. } => {
+                step.set_level(true);
+                arduino_hal::delay_us(2);
+                step.set_level(false);
+            }
+        }
+    }
+
+    /// Switches the coils of four-wire motors off, so they do not heat up
+    /// while holding position.
+    fn release(&self) {
+        if let Driver::FourWire(pins) = self.driver {
+            for pin in pins {
+                pin.set_level(false);
+            }
+        }
+    }
+}
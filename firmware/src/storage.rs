@@ -0,0 +1,114 @@
+//! Layout of the persistent settings in EEPROM.
+//!
+//! | Address | Size | Contents |
+//! | ------- | ---- | -------- |
+//! | 0x000   | 1    | Temperature calibration offset |
+//! | 0x010   | 128  | Startup script |
+//! | 0x090   | 6    | Pin configuration |
+//! | 0x0A0   | 16   | Device name |
+//! | 0x0B0   | 4    | Load cell tare offset |
+//! | 0x0C0   | 4    | Data logger settings |
+//! | 0x0D0   | 2    | Locked pins |
+//! | 0x0D2   | 1    | Safe mode jumper pin |
+//! | 0x0D4   | 6    | Serial port settings |
+//! | 0x0E0   | 16   | Unlock code |
+//! | 0x0F0   | 1    | Self-test scratch cell |
+//! | 0x100   | 512  | Data logger samples |
+//! | 0x300   | 128  | Schedule |
+//! | 0x380   | 128  | Pin names |
+
+use heapless::{String, Vec};
+
+/// The temperature calibration offset.
+///
+/// The offset is stored inverted, so that an erased cell (0xFF) reads back as
+/// an offset of zero.
+pub const TEMP_OFFSET: u16 = 0x000;
+
+/// The commands run at boot, as a length-prefixed string.
+pub const STARTUP_SCRIPT: u16 = 0x010;
+pub const STARTUP_SCRIPT_SIZE: u16 = 0x80;
+
+/// The saved pin configuration: a marker byte, the output and level bitmasks
+/// as little-endian words, and the PWM duty cycle.
+pub const PIN_CONFIG: u16 = 0x090;
+pub const PIN_CONFIG_MAGIC: u8 = 0xC5;
+
+/// The device name set with `id set`, as a length-prefixed string.
+pub const DEVICE_ID: u16 = 0x0A0;
+pub const DEVICE_ID_SIZE: u16 = 0x10;
+
+/// The reading subtracted from load cell measurements, as a little-endian
+/// word stored inverted like [`TEMP_OFFSET`].
+pub const SCALE_TARE: u16 = 0x0B0;
+
+/// The data logger's state, its analog pin, and its interval in seconds as a
+/// little-endian word.
+pub const DATALOG: u16 = 0x0C0;
+
+/// The pins locked with `lock`, as a little-endian bitmask stored inverted
+/// like [`TEMP_OFFSET`].
+pub const LOCKED_PINS: u16 = 0x0D0;
+
+/// The digital pin that turns on safe mode at boot when jumpered to ground,
+/// or 0xFF for none.
+pub const SAFEMODE_JUMPER: u16 = 0x0D2;
+
+/// The console's serial settings: a marker byte, the baud rate as a
+/// little-endian long, and the frame format, see [`crate::uart`].
+pub const SERIAL: u16 = 0x0D4;
+
+/// The code that `unlock` asks for, as a length-prefixed string.
+pub const UNLOCK_CODE: u16 = 0x0E0;
+pub const UNLOCK_CODE_SIZE: u16 = 0x10;
+
+/// The cells from [`LOCKED_PINS`] to the end of [`UNLOCK_CODE`], which
+/// `eeprom write` leaves alone so that it can't get around a lock.
+pub const LOCKS: core::ops::Range<u16> = LOCKED_PINS..UNLOCK_CODE + UNLOCK_CODE_SIZE;
+
+/// A cell that `selftest` writes to and restores.
+pub const SELFTEST: u16 = 0x0F0;
+
+/// The data logger's ring buffer, see [`crate::datalog`].
+pub const DATALOG_SAMPLES: u16 = 0x100;
+pub const DATALOG_SAMPLES_SIZE: u16 = 0x200;
+
+/// The periodic commands added with `schedule`, see [`crate::schedule`].
+pub const SCHEDULE: u16 = 0x300;
+pub const SCHEDULE_SIZE: u16 = 0x80;
+
+/// The pin names set with `name`, see [`crate::names`].
+pub const PIN_NAMES: u16 = 0x380;
+pub const PIN_NAMES_SIZE: u16 = 0x80;
+
+/// Reads a length-prefixed string.
+///
+/// Returns an empty string if the stored one does not fit, which includes
+/// erased EEPROM.
+pub fn read_str<const N: usize>(eeprom: &arduino_hal::Eeprom, address: u16) -> String<N> {
+    let len = eeprom.read_byte(address) as usize;
+    if len > N {
+        return String::new();
+    }
+    let mut buf = Vec::<u8, N>::new();
+    let _ = buf.resize(len, 0);
+    if eeprom.read(address + 1, &mut buf).is_err() {
+        return String::new();
+    }
+    String::from_utf8(buf).unwrap_or_default()
+}
+
+/// Writes a length-prefixed string, which must fit into `size` bytes
+/// including the length.
+pub fn write_str(
+    eeprom: &mut arduino_hal::Eeprom,
+    address: u16,
+    size: u16,
+    value: &str,
+) -> Result<(), ()> {
+    if value.len() >= size as usize {
+        return Err(());
+    }
+    eeprom.write_byte(address, value.len() as u8);
+    eeprom.write(address + 1, value.as_bytes()).map_err(|_| ())
+}
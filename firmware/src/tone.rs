@@ -0,0 +1,65 @@
+//! Square wave tones on the Timer1 outputs, d9 and d10 on the Uno, for a
+//! piezo buzzer or a small speaker.
+//!
+//! Timer1 counts at 2 MHz in CTC mode and toggles the output each time it
+//! reaches the top, so the tone is exact to within a count. It cannot drive
+//! servos, 16-bit PWM, or waveforms at the same time.
+
+use crate::board;
+
+/// The pins with a Timer1 output, OC1A and OC1B.
+pub const PINS: [u8; 2] = board::TIMER1_PINS;
+
+/// The lowest frequency in Hz, whose top still fits into 16 bits, and the
+/// highest, above hearing.
+pub const MIN_FREQUENCY: u16 = 31;
+pub const MAX_FREQUENCY: u16 = 20_000;
+
+/// Timer counts per second, and per half period of the tone.
+const COUNT_HZ: u32 = 2_000_000;
+
+fn tc1() -> &'static arduino_hal::pac::tc1::RegisterBlock {
+    // SAFETY: Timer1 is only used for tones while no servos are attached,
+    // and neither 16-bit PWM nor a waveform runs.
+    unsafe { &*arduino_hal::pac::TC1::ptr() }
+}
+
+/// Whether Timer1 is running a tone.
+pub fn is_active() -> bool {
+    let tc1 = tc1();
+    !tc1.tccr1b.read().cs1().is_no_clock() && tc1.tccr1b.read().wgm1().bits() == 0b01
+}
+
+/// Plays a tone on one of [`PINS`], which has to be an output driven low.
+/// Playing one on the other pin stops the first.
+pub fn start(pin: u8, frequency: u16) {
+    let tc1 = tc1();
+    let top = COUNT_HZ / 2 / u32::from(frequency.max(MIN_FREQUENCY)) - 1;
+    // Both outputs toggle at the top, OC1B by matching a compare value of
+    // zero.
+    tc1.ocr1a.write(|w| w.bits(top as u16));
+    tc1.ocr1b.write(|w| w.bits(0));
+    if pin == PINS[0] {
+        tc1.tccr1a.write(|w| w.com1a().match_toggle());
+    } else {
+        tc1.tccr1a.write(|w| w.com1b().match_toggle());
+    }
+    if !is_active() {
+        tc1.tcnt1.write(|w| w.bits(0));
+        tc1.tccr1b.write(|w| w.wgm1().bits(0b01).cs1().prescale_8());
+    } else if tc1.tcnt1.read().bits() > top as u16 {
+        // A lower top than the count would only be reached after wrapping
+        // around.
+        tc1.tcnt1.write(|w| w.bits(0));
+    }
+}
+
+/// Stops the tone, leaving the pin low.
+pub fn stop() {
+    if is_active() {
+        let tc1 = tc1();
+        tc1.tccr1b.reset();
+        tc1.tccr1a.reset();
+        tc1.ocr1a.reset();
+    }
+}
@@ -0,0 +1,54 @@
+//! Capacitive touch sensing with a high-value resistor, around 1 MΩ, between
+//! a send and a sense pin, and the touch pad on the sense pin.
+//!
+//! Toggling the send pin charges and discharges the pad through the
+//! resistor. A finger near the pad adds capacitance, so the sense pin takes
+//! longer to follow.
+
+use avr_device::interrupt;
+
+use crate::gpio::RawPin;
+
+/// How many charge and discharge cycles are summed for a reading.
+pub const SAMPLES: u8 = 30;
+
+/// Gives up on a cycle after this many loop iterations, as happens without a
+/// resistor.
+const TIMEOUT: u32 = 20_000;
+
+/// Measures the pad, and returns the sum of the charge and discharge times in
+/// loop iterations. Takes the send pin as an output driven low, and the sense
+/// pin as a floating input.
+pub fn read(send: &RawPin, sense: &RawPin) -> Option<u32> {
+    let mut total = 0;
+    for _ in 0..SAMPLES {
+        total += interrupt::free(|_| {
+            // Starts from a discharged pad.
+            sense.set_level(false);
+            sense.pull_low();
+            sense.release();
+            send.set_level(true);
+            let charge = wait_for(sense, true)?;
+            // Briefly drives the pad high to finish charging it.
+            sense.set_level(true);
+            sense.set_output(true);
+            sense.set_output(false);
+            sense.set_level(false);
+            send.set_level(false);
+            let discharge = wait_for(sense, false)?;
+            Some(charge + discharge)
+        })?;
+    }
+    Some(total)
+}
+
+fn wait_for(pin: &RawPin, high: bool) -> Option<u32> {
+    let mut count = 0;
+    while pin.is_high() != high {
+        count += 1;
+        if count > TIMEOUT {
+            return None;
+        }
+    }
+    Some(count)
+}
@@ -0,0 +1,126 @@
+//! Settings of the console's serial port.
+//!
+//! `arduino-hal` opens the port as 8N1 at [`DEFAULT_BAUD`], and settings
+//! stored with `serial` are written over its registers at boot.
+//!
+//! The 32U4's console is its own USB, which has no baud rate or frame
+//! format, so only [`DEFAULT`] is used there.
+#![cfg_attr(feature = "leonardo", allow(dead_code))]
+
+use crate::storage;
+
+pub use protocol::args::uart::{Frame, Parity, Settings};
+
+#[cfg(all(feature = "baud-9600", feature = "baud-115200"))]
+compile_error!("only one of the baud-9600 and baud-115200 features can be enabled");
+
+/// The baud rate the port starts at, unless another one is stored.
+#[cfg(feature = "baud-9600")]
+pub const DEFAULT_BAUD: u32 = 9600;
+#[cfg(feature = "baud-115200")]
+pub const DEFAULT_BAUD: u32 = 115_200;
+#[cfg(not(any(feature = "baud-9600", feature = "baud-115200")))]
+pub const DEFAULT_BAUD: u32 = 57_600;
+
+/// Supported baud rates, all within 3.5% of the rate the 16 MHz clock gets.
+pub const BAUD_RATES: [u32; 8] = [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115_200];
+
+const CLOCK_HZ: u32 = 16_000_000;
+
+/// Marks stored settings, which erased EEPROM doesn't have.
+const MAGIC: u8 = 0x5E;
+
+/// The settings the port opens with.
+pub const DEFAULT: Settings = Settings {
+    baud: DEFAULT_BAUD,
+    frame: Frame::DEFAULT,
+};
+
+fn frame_to_byte(frame: Frame) -> u8 {
+    (frame.data_bits - 5) | (frame.parity as u8) << 2 | (frame.stop_bits - 1) << 4
+}
+
+fn frame_from_byte(byte: u8) -> Option<Frame> {
+    let parity = match byte >> 2 & 0x03 {
+        0 => Parity::None,
+        1 => Parity::Even,
+        2 => Parity::Odd,
+        _ => return None,
+    };
+    (byte >> 5 == 0).then_some(Frame {
+        data_bits: (byte & 0x03) + 5,
+        parity,
+        stop_bits: (byte >> 4 & 0x01) + 1,
+    })
+}
+
+/// Reads the stored settings, if any.
+pub fn load(eeprom: &arduino_hal::Eeprom) -> Option<Settings> {
+    let mut stored = [0; 6];
+    eeprom.read(storage::SERIAL, &mut stored).ok()?;
+    let [magic, b0, b1, b2, b3, frame] = stored;
+    let baud = u32::from_le_bytes([b0, b1, b2, b3]);
+    if magic != MAGIC || !BAUD_RATES.contains(&baud) {
+        return None;
+    }
+    Some(Settings {
+        baud,
+        frame: frame_from_byte(frame)?,
+    })
+}
+
+pub fn store(eeprom: &mut arduino_hal::Eeprom, settings: Settings) {
+    let [b0, b1, b2, b3] = settings.baud.to_le_bytes();
+    let _ = eeprom.write(
+        storage::SERIAL,
+        &[MAGIC, b0, b1, b2, b3, frame_to_byte(settings.frame)],
+    );
+}
+
+/// Forgets the stored settings, so the port starts with the defaults.
+pub fn clear(eeprom: &mut arduino_hal::Eeprom) {
+    eeprom.write_byte(storage::SERIAL, 0xFF);
+}
+
+#[cfg(not(feature = "leonardo"))]
+fn usart0() -> &'static arduino_hal::pac::usart0::RegisterBlock {
+    // SAFETY: Only the registers for the baud rate and frame format are
+    // written, which the console doesn't touch after opening the port.
+    unsafe { &*arduino_hal::pac::USART0::ptr() }
+}
+
+/// Switches the port to new settings. Anything still being sent should be
+/// flushed first, or it goes out garbled.
+#[cfg(not(feature = "leonardo"))]
+pub fn apply(settings: Settings) {
+    let usart = usart0();
+    // The same divisor that `arduino-hal` picks, at double speed unless the
+    // divisor gets too large for the register.
+    let mut ubrr = (CLOCK_HZ / 4 / settings.baud - 1) / 2;
+    let mut u2x = true;
+    if ubrr > 4095 {
+        ubrr = (CLOCK_HZ / 8 / settings.baud - 1) / 2;
+        u2x = false;
+    }
+    usart.ucsr0a.write(|w| w.u2x0().bit(u2x));
+    usart.ubrr0.write(|w| w.bits(ubrr as u16));
+    let frame = settings.frame;
+    usart.ucsr0c.write(|w| {
+        let w = w.umsel0().usart_async();
+        let w = match frame.data_bits {
+            5 => w.ucsz0().chr5(),
+            6 => w.ucsz0().chr6(),
+            7 => w.ucsz0().chr7(),
+            _ => w.ucsz0().chr8(),
+        };
+        let w = match frame.parity {
+            Parity::None => w.upm0().disabled(),
+            Parity::Even => w.upm0().parity_even(),
+            Parity::Odd => w.upm0().parity_odd(),
+        };
+        match frame.stop_bits {
+            2 => w.usbs0().stop2(),
+            _ => w.usbs0().stop1(),
+        }
+    });
+}
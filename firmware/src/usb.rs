@@ -0,0 +1,510 @@
+//! The console of the ATmega32U4 boards, a CDC ACM serial port over the
+//! chip's own USB.
+//!
+//! The host sees the same kind of port as behind a USB serial adapter, so any
+//! terminal works with it, and the baud rate the terminal sets is ignored.
+//! The USB interrupts answer the host's requests on the control endpoint,
+//! and the console reads and writes the two bulk endpoints directly. A byte
+//! that hasn't been read keeps the host waiting to send more, rather than
+//! being lost, and written bytes go out with the next start of frame, every
+//! millisecond, unless they fill a packet before.
+//!
+//! The interrupts wait for the host within a control transfer, which takes
+//! it well under a millisecond, so the millisecond tick can be a little late
+//! while the port is being set up.
+
+use core::cell::Cell;
+use core::convert::Infallible;
+
+use arduino_hal::pac;
+use avr_device::interrupt::{self, Mutex};
+use embedded_hal::serial::{Read, Write};
+use ufmt::uWrite;
+
+use crate::{millis, progmem, uart};
+
+/// The largest packet of the control endpoint, the most the chip allows.
+const CONTROL_SIZE: usize = 64;
+/// The largest packet of the bulk endpoints.
+const BULK_SIZE: u8 = 64;
+
+/// The endpoint for line state notifications, which the crab never sends
+/// but the class requires.
+const NOTIFY_ENDPOINT: u8 = 1;
+/// The data from the host.
+const RX_ENDPOINT: u8 = 2;
+/// The data to the host.
+const TX_ENDPOINT: u8 = 3;
+
+/// How long a write waits for the host to take the bytes before. Once one
+/// has given up, writes only go through while the host keeps up, so that a
+/// host not reading doesn't slow down every command.
+const TX_TIMEOUT_MS: u32 = 250;
+
+/// The flags of `UEINTX`.
+const TXINI: u8 = 1 << 0;
+const RXOUTI: u8 = 1 << 2;
+const RXSTPI: u8 = 1 << 3;
+const FIFOCON: u8 = 1 << 7;
+
+/// The flags of `UDINT`.
+const SOFI: u8 = 1 << 2;
+const EORSTI: u8 = 1 << 3;
+
+/// The standard and CDC class requests answered, by `bRequest`.
+const GET_STATUS: u8 = 0x00;
+const CLEAR_FEATURE: u8 = 0x01;
+const SET_ADDRESS: u8 = 0x05;
+const GET_DESCRIPTOR: u8 = 0x06;
+const GET_CONFIGURATION: u8 = 0x08;
+const SET_CONFIGURATION: u8 = 0x09;
+const SET_INTERFACE: u8 = 0x0B;
+const SET_LINE_CODING: u8 = 0x20;
+const GET_LINE_CODING: u8 = 0x21;
+const SET_CONTROL_LINE_STATE: u8 = 0x22;
+
+/// The Leonardo's own vendor and product IDs, which hosts know the board's
+/// port by.
+const VENDOR_ID: u16 = 0x2341;
+const PRODUCT_ID: u16 = 0x8036;
+
+const PRODUCT: &str = "mechanical crab";
+
+#[link_section = ".progmem.data"]
+static DEVICE_DESCRIPTOR: [u8; 18] = {
+    let [vendor_low, vendor_high] = VENDOR_ID.to_le_bytes();
+    let [product_low, product_high] = PRODUCT_ID.to_le_bytes();
+    [
+        18,
+        0x01,
+        // USB 2.0.
+        0x00,
+        0x02,
+        // The communications device class.
+        0x02,
+        0x00,
+        0x00,
+        CONTROL_SIZE as u8,
+        vendor_low,
+        vendor_high,
+        product_low,
+        product_high,
+        // Device release 1.00.
+        0x00,
+        0x01,
+        // No manufacturer string, the product string, and no serial number.
+        0,
+        1,
+        0,
+        // One configuration.
+        1,
+    ]
+};
+
+#[rustfmt::skip]
+#[link_section = ".progmem.data"]
+static CONFIGURATION_DESCRIPTOR: [u8; 67] = [
+    // The configuration: two interfaces, bus powered at up to 100 mA.
+    9, 0x02, 67, 0, 2, 1, 0, 0x80, 50,
+    // The communications interface, with the notification endpoint.
+    9, 0x04, 0, 0, 1, 0x02, 0x02, 0x00, 0,
+    // CDC 1.10, without call management, with line coding and line state
+    // requests, and the data interface as the subordinate one.
+    5, 0x24, 0x00, 0x10, 0x01,
+    5, 0x24, 0x01, 0x00, 1,
+    4, 0x24, 0x02, 0x02,
+    5, 0x24, 0x06, 0, 1,
+    // The notification endpoint, interrupt IN.
+    7, 0x05, 0x80 | NOTIFY_ENDPOINT, 0x03, 16, 0, 64,
+    // The data interface, with the bulk endpoints.
+    9, 0x04, 1, 0, 2, 0x0A, 0x00, 0x00, 0,
+    7, 0x05, RX_ENDPOINT, 0x02, BULK_SIZE, 0, 0,
+    7, 0x05, 0x80 | TX_ENDPOINT, 0x02, BULK_SIZE, 0, 0,
+];
+
+/// The languages of the strings, US English only.
+#[link_section = ".progmem.data"]
+static LANGUAGES_DESCRIPTOR: [u8; 4] = [4, 0x03, 0x09, 0x04];
+
+#[link_section = ".progmem.data"]
+static PRODUCT_DESCRIPTOR: [u8; 2 + 2 * PRODUCT.len()] = string_descriptor(PRODUCT);
+
+/// Encodes an ASCII string as a string descriptor, in UTF-16.
+const fn string_descriptor<const N: usize>(text: &str) -> [u8; N] {
+    let mut bytes = [0; N];
+    bytes[0] = N as u8;
+    bytes[1] = 0x03;
+    let mut i = 0;
+    while i < text.len() {
+        bytes[2 + 2 * i] = text.as_bytes()[i];
+        i += 1;
+    }
+    bytes
+}
+
+/// Whether the host has picked the configuration, and the bulk endpoints
+/// exist.
+static CONFIGURED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+/// Whether a write gave up waiting for the host.
+static STALLED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+/// Whether the last packet sent was a full one, which the host takes as
+/// more to come until a shorter one follows, if only an empty one.
+static ENDS_FULL: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+/// The line coding the host set last, to give back to it: the baud rate, one
+/// stop bit, no parity, and eight data bits.
+static LINE_CODING: Mutex<Cell<[u8; 7]>> = Mutex::new(Cell::new({
+    let [b0, b1, b2, b3] = uart::DEFAULT_BAUD.to_le_bytes();
+    [b0, b1, b2, b3, 0, 0, 8]
+}));
+
+fn usb() -> &'static pac::usb_device::RegisterBlock {
+    // SAFETY: The USB registers are only touched from this module, by the
+    // interrupts or inside critical sections, so the endpoint selected is
+    // never changed underneath anyone.
+    unsafe { &*pac::USB_DEVICE::ptr() }
+}
+
+/// Selects the endpoint that the endpoint registers refer to.
+fn select(endpoint: u8) {
+    usb().uenum.write(|w| w.bits(endpoint));
+}
+
+/// Clears flags of the selected endpoint.
+fn clear(flags: u8) {
+    // SAFETY: Writing a one leaves a flag as it is.
+    usb().ueintx.write(|w| unsafe { w.bits(!flags) });
+}
+
+/// Sets up an endpoint: its type and direction for `UECFG0X`, and its size
+/// and banks for `UECFG1X`.
+fn configure(endpoint: u8, kind: u8, in_direction: bool, size: u8, banks: u8) {
+    let usb = usb();
+    select(endpoint);
+    usb.ueconx.write(|w| w.epen().set_bit());
+    usb.uecfg0x
+        .write(|w| w.eptype().bits(kind).epdir().bit(in_direction));
+    usb.uecfg1x
+        .write(|w| w.epsize().bits(size).epbk().bits(banks).alloc().set_bit());
+}
+
+/// The serial port, once the chip is attached to the bus.
+pub struct Serial {
+    _usb: pac::USB_DEVICE,
+}
+
+impl Serial {
+    /// Starts the USB clock from the PLL and attaches to the bus. The host
+    /// enumerates the port once interrupts are enabled.
+    pub fn new(usb: pac::USB_DEVICE, pll: pac::PLL) -> Self {
+        usb.uhwcon.write(|w| w.uvrege().set_bit());
+        usb.usbcon.write(|w| w.usbe().set_bit().frzclk().set_bit());
+        // The PLL runs at 48 MHz from the 16 MHz crystal halved.
+        pll.pllcsr.write(|w| w.pindiv().set_bit().plle().set_bit());
+        while pll.pllcsr.read().plock().bit_is_clear() {}
+        usb.usbcon.write(|w| w.usbe().set_bit().otgpade().set_bit());
+        usb.udien.write(|w| w.eorste().set_bit().sofe().set_bit());
+        usb.udcon.write(|w| w.detach().clear_bit());
+        Serial { _usb: usb }
+    }
+}
+
+/// Whether a byte from the host is waiting to be read.
+pub fn has_input() -> bool {
+    interrupt::free(|cs| {
+        if !CONFIGURED.borrow(cs).get() {
+            return false;
+        }
+        select(RX_ENDPOINT);
+        usb().ueintx.read().rxouti().bit_is_set()
+    })
+}
+
+/// Sends the bank being filled, or an empty packet after a full one, if
+/// the other bank is free. The TX endpoint has to be selected.
+fn send_pending(cs: interrupt::CriticalSection) {
+    let usb = usb();
+    let ends_full = ENDS_FULL.borrow(cs);
+    if (usb.uebclx.read().bits() > 0 || ends_full.get()) && usb.ueintx.read().rwal().bit_is_set() {
+        clear(TXINI | FIFOCON);
+        ends_full.set(false);
+    }
+}
+
+/// Writes a byte into the bank being filled, waiting for the host to take
+/// one if both are full, up to [`TX_TIMEOUT_MS`].
+fn put(byte: u8) {
+    let usb = usb();
+    let start = millis::millis();
+    loop {
+        let done = interrupt::free(|cs| {
+            if !CONFIGURED.borrow(cs).get() {
+                return true;
+            }
+            select(TX_ENDPOINT);
+            if usb.ueintx.read().rwal().bit_is_clear() {
+                return STALLED.borrow(cs).get();
+            }
+            STALLED.borrow(cs).set(false);
+            usb.uedatx.write(|w| w.bits(byte));
+            if usb.ueintx.read().rwal().bit_is_clear() {
+                clear(TXINI | FIFOCON);
+                ENDS_FULL.borrow(cs).set(true);
+            }
+            true
+        });
+        if done {
+            return;
+        }
+        if millis::millis().wrapping_sub(start) > TX_TIMEOUT_MS {
+            interrupt::free(|cs| STALLED.borrow(cs).set(true));
+            return;
+        }
+    }
+}
+
+impl uWrite for Serial {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        s.bytes().for_each(put);
+        Ok(())
+    }
+}
+
+impl Write<u8> for Serial {
+    type Error = Infallible;
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        put(byte);
+        Ok(())
+    }
+
+    /// Sends the bank being filled, and waits for the host to take every
+    /// bank, up to [`TX_TIMEOUT_MS`].
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        let usb = usb();
+        let start = millis::millis();
+        loop {
+            let done = interrupt::free(|cs| {
+                if !CONFIGURED.borrow(cs).get() || STALLED.borrow(cs).get() {
+                    return true;
+                }
+                select(TX_ENDPOINT);
+                send_pending(cs);
+                usb.uesta0x.read().nbusybk().bits() == 0 && !ENDS_FULL.borrow(cs).get()
+            });
+            if done || millis::millis().wrapping_sub(start) > TX_TIMEOUT_MS {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Read<u8> for Serial {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        let usb = usb();
+        interrupt::free(|cs| {
+            if !CONFIGURED.borrow(cs).get() {
+                return Err(nb::Error::WouldBlock);
+            }
+            select(RX_ENDPOINT);
+            if usb.ueintx.read().rxouti().bit_is_clear() {
+                return Err(nb::Error::WouldBlock);
+            }
+            let byte = (usb.uebclx.read().bits() > 0).then(|| usb.uedatx.read().bits());
+            // The bank goes back to the host once it is empty.
+            if usb.uebclx.read().bits() == 0 {
+                clear(RXOUTI | FIFOCON);
+            }
+            byte.ok_or(nb::Error::WouldBlock)
+        })
+    }
+}
+
+/// Waits until the control endpoint can take a packet to send, or the host
+/// moves on to the status stage early, and returns which. A new request
+/// from the host gives up on the one before, which is answered as far as
+/// it got.
+fn wait_in() -> bool {
+    loop {
+        let flags = usb().ueintx.read();
+        if flags.rxouti().bit_is_set() || flags.rxstpi().bit_is_set() {
+            return false;
+        }
+        if flags.txini().bit_is_set() {
+            return true;
+        }
+    }
+}
+
+/// Sends the status stage of a control write, an empty packet.
+fn send_status() {
+    if wait_in() {
+        clear(TXINI);
+    }
+}
+
+/// Waits for a packet from the host on the control endpoint, and returns
+/// whether it came rather than a new request.
+fn wait_out() -> bool {
+    loop {
+        let flags = usb().ueintx.read();
+        if flags.rxstpi().bit_is_set() {
+            return false;
+        }
+        if flags.rxouti().bit_is_set() {
+            return true;
+        }
+    }
+}
+
+/// Sends the data stage of a control read, up to as many bytes as the host
+/// asked for, and waits for its status stage.
+fn send(len: usize, requested: u16, byte: impl Fn(usize) -> u8) {
+    let usb = usb();
+    let total = len.min(usize::from(requested));
+    let mut sent = 0;
+    while wait_in() {
+        let packet = (total - sent).min(CONTROL_SIZE);
+        for i in sent..sent + packet {
+            usb.uedatx.write(|w| w.bits(byte(i)));
+        }
+        sent += packet;
+        clear(TXINI);
+        // A short packet ends the transfer, and so does a full one that
+        // gives the host everything it asked for.
+        if packet < CONTROL_SIZE || sent == usize::from(requested) {
+            break;
+        }
+    }
+    if wait_out() {
+        clear(RXOUTI);
+    }
+}
+
+/// Sends a descriptor from flash.
+fn send_descriptor(descriptor: &'static [u8], requested: u16) {
+    let start = descriptor.as_ptr();
+    // SAFETY: The descriptors are statics in flash, and every index is
+    // within the one sent.
+    send(descriptor.len(), requested, |i| unsafe {
+        progmem::read_byte(start.add(i))
+    });
+}
+
+/// Finds a descriptor by the type and index in the high and low byte of
+/// `wValue`.
+fn descriptor(value: u16) -> Option<&'static [u8]> {
+    match value.to_be_bytes() {
+        [0x01, _] => Some(&DEVICE_DESCRIPTOR),
+        [0x02, 0] => Some(&CONFIGURATION_DESCRIPTOR),
+        [0x03, 0] => Some(&LANGUAGES_DESCRIPTOR),
+        [0x03, 1] => Some(&PRODUCT_DESCRIPTOR),
+        _ => None,
+    }
+}
+
+/// Answers a request on the control endpoint. Returns whether it was one
+/// of the requests the crab knows, which the others are stalled for.
+fn handle_setup(cs: interrupt::CriticalSection) -> bool {
+    let usb = usb();
+    let mut setup = [0; 8];
+    for byte in &mut setup {
+        *byte = usb.uedatx.read().bits();
+    }
+    clear(RXSTPI);
+    let [request_type, request, value_low, value_high, _, _, len_low, len_high] = setup;
+    let value = u16::from_le_bytes([value_low, value_high]);
+    let requested = u16::from_le_bytes([len_low, len_high]);
+    match (request_type, request) {
+        (0x80..=0x82, GET_STATUS) => send(2, requested, |_| 0),
+        (0x00..=0x02, CLEAR_FEATURE) | (0x01, SET_INTERFACE) => send_status(),
+        (0x00, SET_ADDRESS) => {
+            usb.udaddr.write(|w| w.uadd().bits(value_low & 0x7F));
+            // The new address only applies once the status stage is done.
+            send_status();
+            wait_in();
+            usb.udaddr.modify(|_, w| w.adden().set_bit());
+        }
+        (0x80, GET_DESCRIPTOR) => match descriptor(value) {
+            Some(descriptor) => send_descriptor(descriptor, requested),
+            None => return false,
+        },
+        (0x80, GET_CONFIGURATION) => {
+            let configured = CONFIGURED.borrow(cs).get();
+            send(1, requested, |_| u8::from(configured));
+        }
+        (0x00, SET_CONFIGURATION) => {
+            configure(NOTIFY_ENDPOINT, 0b11, true, 0b001, 0b00);
+            // Two banks each, so that the host can fill one while the other
+            // is read.
+            configure(RX_ENDPOINT, 0b10, false, 0b011, 0b01);
+            configure(TX_ENDPOINT, 0b10, true, 0b011, 0b01);
+            usb.uerst.write(|w| w.eprst().bits(0b1110));
+            usb.uerst.write(|w| w.eprst().bits(0));
+            select(0);
+            CONFIGURED.borrow(cs).set(value_low != 0);
+            STALLED.borrow(cs).set(false);
+            ENDS_FULL.borrow(cs).set(false);
+            send_status();
+        }
+        (0x21, SET_LINE_CODING) => {
+            if !wait_out() {
+                return true;
+            }
+            let mut coding = [0; 7];
+            for byte in &mut coding {
+                *byte = usb.uedatx.read().bits();
+            }
+            clear(RXOUTI);
+            LINE_CODING.borrow(cs).set(coding);
+            send_status();
+        }
+        (0xA1, GET_LINE_CODING) => {
+            let coding = LINE_CODING.borrow(cs).get();
+            send(coding.len(), requested, |i| coding[i]);
+        }
+        (0x21, SET_CONTROL_LINE_STATE) => send_status(),
+        _ => return false,
+    }
+    true
+}
+
+#[avr_device::interrupt(atmega32u4)]
+fn USB_GEN() {
+    interrupt::free(|cs| {
+        let usb = usb();
+        let flags = usb.udint.read();
+        if flags.eorsti().bit_is_set() {
+            // SAFETY: Writing a one leaves a flag as it is.
+            usb.udint.write(|w| unsafe { w.bits(!EORSTI) });
+            CONFIGURED.borrow(cs).set(false);
+            configure(0, 0b00, false, 0b011, 0b00);
+            usb.ueienx.write(|w| w.rxstpe().set_bit());
+        }
+        if flags.sofi().bit_is_set() {
+            // SAFETY: As above.
+            usb.udint.write(|w| unsafe { w.bits(!SOFI) });
+            if CONFIGURED.borrow(cs).get() {
+                select(TX_ENDPOINT);
+                send_pending(cs);
+            }
+        }
+    })
+}
+
+#[avr_device::interrupt(atmega32u4)]
+fn USB_COM() {
+    interrupt::free(|cs| {
+        let usb = usb();
+        select(0);
+        if usb.ueintx.read().rxstpi().bit_is_clear() {
+            return;
+        }
+        if !handle_setup(cs) {
+            usb.ueconx.modify(|_, w| w.stallrq().set_bit());
+        }
+    })
+}
@@ -0,0 +1,58 @@
+//! Inputs printed at regular intervals, several at a time.
+//!
+//! Samples are taken on multiples of the interval since reset rather than
+//! of the time a watch started, so that watches with the same interval are
+//! read in the same pass of the main loop and their lines come together.
+
+use ufmt::{uDisplay, uWrite, Formatter};
+
+use crate::millis;
+use crate::progmem::fwrite;
+
+pub use protocol::args::rules::Input;
+
+/// How many inputs can be watched at the same time.
+pub const MAX_WATCHES: usize = 4;
+
+/// The shortest interval, which keeps the lines from flooding the serial
+/// port.
+pub const MIN_INTERVAL_MS: u16 = 10;
+
+pub struct Watch {
+    pub input: Input,
+    pub interval_ms: u16,
+    /// The number of the interval last sampled in.
+    slot: u32,
+}
+
+impl Watch {
+    pub fn new(input: Input, interval_ms: u16) -> Self {
+        Watch {
+            input,
+            interval_ms,
+            slot: millis::millis() / u32::from(interval_ms),
+        }
+    }
+
+    /// Whether a new interval has begun since the last sample.
+    pub fn is_due(&mut self) -> bool {
+        let slot = millis::millis() / u32::from(self.interval_ms);
+        if slot == self.slot {
+            return false;
+        }
+        self.slot = slot;
+        true
+    }
+}
+
+/// The name of the input, such as `a0` or `d2`.
+pub struct Name(pub Input);
+
+impl uDisplay for Name {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        match self.0 {
+            Input::Analog(pin) => fwrite!(f, "a{}", pin),
+            Input::Digital(pin) => fwrite!(f, "d{}", pin),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Waveforms on the Timer1 outputs, d9 and d10 on the Uno, for an RC filter
+//! to smooth into an analog signal.
+//!
+//! Timer1 runs 8-bit PWM at 62.5 kHz, well above the waveform, and the
+//! millisecond tick of [`crate::millis`] steps a phase accumulator and sets
+//! the duty cycle from it. That gives 1000 samples per second, so the higher
+//! frequencies come out as coarse staircases. Timer1 cannot drive servos or
+//! 16-bit PWM at the same time.
+
+use core::cell::Cell;
+
+use avr_device::interrupt::{self, CriticalSection, Mutex};
+
+use crate::board;
+
+pub use protocol::args::wave::Shape;
+
+/// The pins with a Timer1 output, OC1A and OC1B.
+pub const PINS: [u8; 2] = board::TIMER1_PINS;
+
+/// The highest frequency in Hz, which leaves four samples per period.
+pub const MAX_FREQUENCY: u16 = 250;
+
+const TOP: u16 = 0xFF;
+const SAMPLE_RATE: u32 = 1000;
+
+#[derive(Clone, Copy)]
+struct Wave {
+    pin: u8,
+    shape: Shape,
+    phase: u16,
+    /// Phase increment per sample, with 65536 for a whole period.
+    step: u16,
+}
+
+static WAVE: Mutex<Cell<Option<Wave>>> = Mutex::new(Cell::new(None));
+
+/// The first quarter of a sine period, from the middle to the top.
+const QUARTER_SINE: [u8; 17] = [
+    0, 12, 25, 37, 49, 60, 71, 81, 90, 98, 106, 112, 117, 122, 125, 127, 127,
+];
+
+fn tc1() -> &'static arduino_hal::pac::tc1::RegisterBlock {
+    // SAFETY: Timer1 is only used for waveforms while no servos are attached
+    // and no 16-bit PWM runs.
+    unsafe { &*arduino_hal::pac::TC1::ptr() }
+}
+
+/// Whether Timer1 is running a waveform.
+pub fn is_active() -> bool {
+    let tc1 = tc1();
+    !tc1.tccr1b.read().cs1().is_no_clock() && tc1.icr1.read().bits() == TOP
+}
+
+/// Starts a waveform on one of [`PINS`], which has to be an output driven
+/// low. Starting one on the other pin stops the first.
+pub fn start(pin: u8, shape: Shape, frequency: u16) {
+    let tc1 = tc1();
+    interrupt::free(|cs| {
+        tc1.icr1.write(|w| w.bits(TOP));
+        tc1.tccr1a.write(|w| w.wgm1().bits(0b10));
+        if pin == PINS[0] {
+            tc1.tccr1a.modify(|_, w| w.com1a().match_clear());
+        } else {
+            tc1.tccr1a.modify(|_, w| w.com1b().match_clear());
+        }
+        tc1.tccr1b.write(|w| w.wgm1().bits(0b11).cs1().direct());
+        let step = (u32::from(frequency) << 16) / SAMPLE_RATE;
+        WAVE.borrow(cs).set(Some(Wave {
+            pin,
+            shape,
+            phase: 0,
+            step: step as u16,
+        }));
+    })
+}
+
+/// Stops the waveform, leaving the pin low.
+pub fn stop() {
+    interrupt::free(|cs| {
+        if WAVE.borrow(cs).take().is_some() {
+            let tc1 = tc1();
+            tc1.tccr1b.reset();
+            tc1.tccr1a.reset();
+            tc1.icr1.reset();
+        }
+    })
+}
+
+fn sample(shape: Shape, phase: u16) -> u8 {
+    match shape {
+        Shape::Sine => {
+            // Mirrors the quarter period into the other three, with 16
+            // table steps per quarter.
+            let index = usize::from(phase >> 10);
+            let offset = match index / 16 {
+                0 | 2 => QUARTER_SINE[index % 16],
+                _ => QUARTER_SINE[16 - index % 16],
+            };
+            if index < 32 {
+                128 + offset
+            } else {
+                128 - offset
+            }
+        }
+        Shape::Triangle => {
+            // Each half period covers the full range.
+            let level = (phase >> 7) as u8;
+            if phase < 0x8000 {
+                level
+            } else {
+                0xFF - level
+            }
+        }
+        Shape::Square => {
+            if phase < 0x8000 {
+                0xFF
+            } else {
+                0
+            }
+        }
+    }
+}
+
+/// Moves the waveform on by one sample. Called from the millisecond tick.
+pub fn update(cs: CriticalSection) {
+    let cell = WAVE.borrow(cs);
+    let Some(mut wave) = cell.get() else {
+        return;
+    };
+    wave.phase = wave.phase.wrapping_add(wave.step);
+    let duty = u16::from(sample(wave.shape, wave.phase));
+    if wave.pin == PINS[0] {
+        tc1().ocr1a.write(|w| w.bits(duty));
+    } else {
+        tc1().ocr1b.write(|w| w.bits(duty));
+    }
+    cell.set(Some(wave));
+}
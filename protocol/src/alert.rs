@@ -0,0 +1,92 @@
+//! Analog threshold alerts.
+//!
+//! An alert triggers once the reading crosses its threshold, and clears once
+//! the reading has come back past the threshold by the hysteresis, so noise
+//! around the threshold does not trigger it over and over.
+
+#[derive(Clone, Copy, Debug)]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+impl Direction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Above => "above",
+            Direction::Below => "below",
+        }
+    }
+}
+
+pub const DEFAULT_HYSTERESIS: u16 = 8;
+
+pub enum Event {
+    Triggered,
+    Cleared,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Alert {
+    pub direction: Direction,
+    pub threshold: u16,
+    pub hysteresis: u16,
+    triggered: bool,
+}
+
+impl Alert {
+    pub fn new(direction: Direction, threshold: u16, hysteresis: u16) -> Self {
+        Alert {
+            direction,
+            threshold,
+            hysteresis,
+            triggered: false,
+        }
+    }
+
+    pub fn update(&mut self, value: u16) -> Option<Event> {
+        let (crossed, recovered) = match self.direction {
+            Direction::Above => (
+                value > self.threshold,
+                value < self.threshold.saturating_sub(self.hysteresis),
+            ),
+            Direction::Below => (
+                value < self.threshold,
+                value > self.threshold.saturating_add(self.hysteresis),
+            ),
+        };
+        if !self.triggered && crossed {
+            self.triggered = true;
+            Some(Event::Triggered)
+        } else if self.triggered && recovered {
+            self.triggered = false;
+            Some(Event::Cleared)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers_once_and_clears_past_the_hysteresis() {
+        let mut alert = Alert::new(Direction::Above, 500, 10);
+        assert!(alert.update(400).is_none());
+        assert!(matches!(alert.update(501), Some(Event::Triggered)));
+        assert!(alert.update(600).is_none());
+        assert!(alert.update(495).is_none());
+        assert!(matches!(alert.update(489), Some(Event::Cleared)));
+        assert!(matches!(alert.update(501), Some(Event::Triggered)));
+    }
+
+    #[test]
+    fn below() {
+        let mut alert = Alert::new(Direction::Below, 100, 10);
+        assert!(matches!(alert.update(99), Some(Event::Triggered)));
+        assert!(alert.update(110).is_none());
+        assert!(matches!(alert.update(111), Some(Event::Cleared)));
+    }
+}
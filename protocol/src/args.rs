@@ -0,0 +1,574 @@
+//! The arguments that commands take, grouped by the firmware module that
+//! acts on them. The firmware's modules re-export them, so this is the one
+//! place they are defined.
+
+pub mod bme280 {
+    /// The address with SDO low, 0x77 with SDO high.
+    pub const DEFAULT_ADDRESS: u8 = 0x76;
+
+    #[derive(Clone, Copy, Debug)]
+    pub enum Interface {
+        I2c {
+            address: u8,
+        },
+        /// On the chip select configured with `spi cs`.
+        Spi,
+    }
+}
+
+pub mod button {
+    pub const DEFAULT_DEBOUNCE_MS: u16 = 20;
+}
+
+pub mod capture {
+    #[derive(Clone, Copy, Debug)]
+    pub enum Format {
+        /// Each sample as two hex digits, 32 to a line.
+        Hex,
+        /// Each run of equal samples as a line with the sample and its length.
+        Rle,
+        /// Raw sample bytes, newest first, the way a SUMP device answers a run
+        /// command to sigrok or the OLS client.
+        Sump,
+    }
+}
+
+pub mod comparator {
+    #[derive(Clone, Copy, Debug)]
+    pub enum Reference {
+        /// The voltage on d7.
+        Pin,
+        /// The internal 1.1 V reference, in place of d6. d7 is the input then.
+        Bandgap,
+    }
+}
+
+pub mod console {
+    use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Mode {
+        Human,
+        /// Respond to each command with a single `OK` or `ERR` line.
+        Machine,
+        /// Exchange frames of the binary protocol.
+        Binary,
+        /// Speak Firmata.
+        Firmata,
+        /// Respond to each command with a JSON object, see [`crate::json`].
+        Json,
+        /// Act as a Modbus RTU slave. Text output is dropped, as Modbus has
+        /// no way to send it.
+        Modbus,
+    }
+
+    /// Base of the values that commands print.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Base {
+        Decimal,
+        Hex,
+        Binary,
+    }
+
+    impl Base {
+        /// Wraps a value to print in this base.
+        pub fn value(self, value: u16) -> Value {
+            Value { value, base: self }
+        }
+    }
+
+    /// A value to print in the base it was wrapped with.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Value {
+        value: u16,
+        base: Base,
+    }
+
+    impl uDisplay for Value {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            match self.base {
+                Base::Decimal => uwrite!(f, "{}", self.value),
+                Base::Hex => uwrite!(f, "0x{:X}", self.value),
+                Base::Binary => {
+                    f.write_str("0b")?;
+                    let bits = (16 - self.value.leading_zeros()).max(1);
+                    for bit in (0..bits).rev() {
+                        f.write_char(if self.value & (1 << bit) != 0 {
+                            '1'
+                        } else {
+                            '0'
+                        })?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+pub mod dht {
+    #[derive(Clone, Copy, Debug)]
+    pub enum Model {
+        Dht11,
+        Dht22,
+    }
+}
+
+pub mod expect {
+    /// What `expect` checks an input for.
+    #[derive(Clone, Copy, Debug)]
+    pub enum Expectation {
+        /// A digital level.
+        Level(bool),
+        /// An analog reading within a range, both ends included.
+        Between(u16, u16),
+    }
+
+    impl Expectation {
+        /// Whether an analog reading, or a digital level of 0 or 1, is as
+        /// expected.
+        pub fn holds(&self, value: u16) -> bool {
+            match *self {
+                Expectation::Level(high) => (value != 0) == high,
+                Expectation::Between(low, high) => (low..=high).contains(&value),
+            }
+        }
+    }
+}
+
+pub mod gpio {
+    /// A whole I/O port, for reading or writing up to eight pins at once.
+    #[derive(Clone, Copy, Debug)]
+    pub enum Port {
+        /// d8-d13.
+        B,
+        /// a0-a5.
+        C,
+        /// d0-d7.
+        D,
+    }
+
+    impl Port {
+        /// The pins that commands may drive: not the crystal on PORTB, the
+        /// I2C bus and reset on PORTC, or the serial port on PORTD.
+        pub fn writable(self) -> u8 {
+            match self {
+                Port::B => 0x3F,
+                Port::C => 0x0F,
+                Port::D => 0xFC,
+            }
+        }
+
+        /// The Arduino number of a digital pin on the port, for locks.
+        pub fn digital_pin(self, bit: u8) -> Option<u8> {
+            match self {
+                Port::B => Some(8 + bit),
+                Port::C => None,
+                Port::D => Some(bit),
+            }
+        }
+    }
+}
+
+pub mod heartbeat {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Mode {
+        Off,
+        /// A heartbeat, a flicker for each command, and error codes.
+        On,
+        /// Only error codes.
+        Error,
+    }
+}
+
+pub mod icp {
+    #[derive(Clone, Copy, Debug)]
+    pub enum Edges {
+        /// Periods from one rising edge to the next.
+        Rising,
+        Falling,
+        /// Alternating high and low times.
+        Both,
+    }
+}
+
+pub mod imu {
+    /// The address with AD0 low. Pulling AD0 high moves the sensor to 0x69,
+    /// out of the way of a real-time clock.
+    pub const DEFAULT_ADDRESS: u8 = 0x68;
+}
+
+pub mod lcd {
+    /// The usual address of PCF8574 backpacks, with no address jumpers set.
+    pub const DEFAULT_I2C_ADDRESS: u8 = 0x27;
+}
+
+pub mod motor {
+    #[derive(Clone, Copy, Debug)]
+    pub enum Motor {
+        A,
+        B,
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub enum Drive {
+        Forward,
+        Reverse,
+        /// Shorts the motor's terminals, stopping it faster than letting it
+        /// coast.
+        Brake,
+    }
+
+    impl Motor {
+        /// The direction inputs, IN1 and IN2 for motor A or IN3 and IN4 for
+        /// motor B.
+        pub fn direction_pins(self) -> (u8, u8) {
+            match self {
+                Motor::A => (7, 8),
+                Motor::B => (3, 4),
+            }
+        }
+    }
+
+    impl Drive {
+        /// Levels of the two direction inputs.
+        pub fn levels(self) -> (bool, bool) {
+            match self {
+                Drive::Forward => (true, false),
+                Drive::Reverse => (false, true),
+                Drive::Brake => (true, true),
+            }
+        }
+    }
+}
+
+pub mod onewire {
+    pub type Rom = [u8; 8];
+}
+
+pub mod pid {
+    use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+    /// Fraction bits of the gains.
+    pub const FRACTION_BITS: u32 = 8;
+
+    /// A gain, printed with two decimals.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Gain(pub i32);
+
+    impl uDisplay for Gain {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            let hundredths =
+                (self.0.unsigned_abs() * 100 + (1 << (FRACTION_BITS - 1))) >> FRACTION_BITS;
+            let sign = if self.0 < 0 { "-" } else { "" };
+            uwrite!(f, "{}{}.", sign, hundredths / 100)?;
+            if hundredths % 100 < 10 {
+                uwrite!(f, "0")?;
+            }
+            uwrite!(f, "{}", hundredths % 100)
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Gains {
+        pub kp: Gain,
+        pub ki: Gain,
+        pub kd: Gain,
+    }
+}
+
+pub mod rtc {
+    use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct DateTime {
+        pub year: u16,
+        pub month: u8,
+        pub day: u8,
+        pub hour: u8,
+        pub minute: u8,
+        pub second: u8,
+    }
+
+    impl DateTime {
+        /// Whether the date exists and lies in 2000-2099, the years the
+        /// clocks support.
+        pub fn is_valid(&self) -> bool {
+            (2000..=2099).contains(&self.year)
+                && (1..=12).contains(&self.month)
+                && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+                && self.hour < 24
+                && self.minute < 60
+                && self.second < 60
+        }
+
+        /// Day of the week, from 1 for Monday to 7 for Sunday.
+        pub fn weekday(&self) -> u8 {
+            // Sakamoto's method, which numbers Sunday as 0.
+            const OFFSETS: [u16; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+            let year = if self.month < 3 {
+                self.year - 1
+            } else {
+                self.year
+            };
+            let day = (year + year / 4 - year / 100
+                + year / 400
+                + OFFSETS[usize::from(self.month - 1)]
+                + u16::from(self.day))
+                % 7;
+            if day == 0 {
+                7
+            } else {
+                day as u8
+            }
+        }
+    }
+
+    impl uDisplay for DateTime {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            // The two-digit fields go through the hex formatter as BCD, since
+            // it is the only one that pads with zeros.
+            uwrite!(
+                f,
+                "{}-{:02X}-{:02X}T{:02X}:{:02X}:{:02X}",
+                self.year,
+                to_bcd(self.month),
+                to_bcd(self.day),
+                to_bcd(self.hour),
+                to_bcd(self.minute),
+                to_bcd(self.second)
+            )
+        }
+    }
+
+    fn days_in_month(year: u16, month: u8) -> u8 {
+        match month {
+            2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    pub fn to_bcd(value: u8) -> u8 {
+        (value / 10) << 4 | (value % 10)
+    }
+}
+
+pub mod rules {
+    #[derive(Clone, Copy, Debug)]
+    pub enum Input {
+        Analog(u8),
+        Digital(u8),
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub enum Condition {
+        Above(u16),
+        Below(u16),
+        Rises,
+        Falls,
+    }
+
+    impl Condition {
+        /// Whether the condition holds for an analog reading, or a digital
+        /// level of 0 or 1.
+        pub fn holds(&self, value: u16) -> bool {
+            match *self {
+                Condition::Above(threshold) => value > threshold,
+                Condition::Below(threshold) => value < threshold,
+                Condition::Rises => value != 0,
+                Condition::Falls => value == 0,
+            }
+        }
+
+        /// Edges only fire on a change, conditions on thresholds also fire
+        /// when they already hold as the rule is added.
+        pub fn is_edge(&self) -> bool {
+            matches!(self, Condition::Rises | Condition::Falls)
+        }
+    }
+}
+
+pub mod sdlog {
+    use heapless::Vec;
+
+    pub const MAX_CHANNELS: usize = 8;
+
+    #[derive(Clone, Copy, Debug)]
+    pub enum Channel {
+        Analog(u8),
+        Digital(u8),
+    }
+
+    pub type Channels = Vec<Channel, MAX_CHANNELS>;
+}
+
+pub mod shift {
+    #[derive(Clone, Copy, Debug)]
+    pub enum BitOrder {
+        MsbFirst,
+        LsbFirst,
+    }
+}
+
+pub mod sserial {
+    /// The baud rates the software serial port runs at. Faster ones leave
+    /// too little time per bit for the overhead of timing the edges.
+    pub const BAUD_RATES: [u32; 5] = [1200, 2400, 4800, 9600, 19200];
+
+    /// The rate `gps start` opens the port at, which NMEA receivers start
+    /// at.
+    pub const GPS_BAUD: u32 = 9600;
+}
+
+pub mod sleep {
+    #[derive(Clone, Copy, Debug)]
+    pub enum SleepMode {
+        /// Only the CPU is stopped, the UART and timers keep running.
+        Idle,
+        /// Everything is stopped except for the pin change interrupts.
+        PowerDown,
+    }
+}
+
+pub mod uart {
+    use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Parity {
+        None,
+        Even,
+        Odd,
+    }
+
+    /// The format of a character, such as 8N1.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Frame {
+        /// 5 to 8.
+        pub data_bits: u8,
+        pub parity: Parity,
+        /// 1 or 2.
+        pub stop_bits: u8,
+    }
+
+    impl Frame {
+        /// 8N1, which is what `serial` picks when given only a baud rate.
+        pub const DEFAULT: Self = Self {
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: 1,
+        };
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Settings {
+        pub baud: u32,
+        pub frame: Frame,
+    }
+
+    impl uDisplay for Settings {
+        fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+            let parity = match self.frame.parity {
+                Parity::None => 'N',
+                Parity::Even => 'E',
+                Parity::Odd => 'O',
+            };
+            uwrite!(
+                f,
+                "{} {}{}{}",
+                self.baud,
+                self.frame.data_bits,
+                parity,
+                self.frame.stop_bits
+            )
+        }
+    }
+}
+
+pub mod wave {
+    #[derive(Clone, Copy, Debug)]
+    pub enum Shape {
+        Sine,
+        Triangle,
+        Square,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::String;
+    use ufmt::uwrite;
+
+    use super::{console, pid, rtc, uart};
+
+    fn date(year: u16, month: u8, day: u8) -> rtc::DateTime {
+        rtc::DateTime {
+            year,
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        }
+    }
+
+    #[test]
+    fn dates() {
+        assert!(date(2024, 2, 29).is_valid());
+        assert!(!date(2023, 2, 29).is_valid());
+        assert!(!date(2024, 4, 31).is_valid());
+        assert!(!date(1999, 12, 31).is_valid());
+        // 2024-01-01 was a Monday, 2000-01-01 a Saturday.
+        assert_eq!(date(2024, 1, 1).weekday(), 1);
+        assert_eq!(date(2000, 1, 1).weekday(), 6);
+        assert_eq!(date(2024, 3, 3).weekday(), 7);
+    }
+
+    #[test]
+    fn formatting() {
+        let mut out: String<64> = String::new();
+        let time = rtc::DateTime {
+            hour: 7,
+            minute: 5,
+            second: 30,
+            ..date(2024, 3, 9)
+        };
+        uwrite!(out, "{}", time).unwrap();
+        assert_eq!(out, "2024-03-09T07:05:30");
+
+        out.clear();
+        uwrite!(
+            out,
+            "{} {} {}",
+            pid::Gain(384),
+            pid::Gain(-64),
+            pid::Gain(2)
+        )
+        .unwrap();
+        assert_eq!(out, "1.50 -0.25 0.01");
+
+        out.clear();
+        let settings = uart::Settings {
+            baud: 9600,
+            frame: uart::Frame {
+                parity: uart::Parity::Odd,
+                ..uart::Frame::DEFAULT
+            },
+        };
+        uwrite!(out, "{}", settings).unwrap();
+        assert_eq!(out, "9600 8O1");
+
+        out.clear();
+        uwrite!(
+            out,
+            "{} {} {} {}",
+            console::Base::Decimal.value(10),
+            console::Base::Hex.value(255),
+            console::Base::Binary.value(5),
+            console::Base::Binary.value(0)
+        )
+        .unwrap();
+        assert_eq!(out, "10 0xFF 0b101 0b0");
+    }
+}
@@ -0,0 +1,835 @@
+//! The commands of the console, as parsed from a line.
+
+use crate::alert;
+#[cfg(feature = "sd-log")]
+use crate::args::sdlog;
+use crate::args::{
+    bme280, capture, comparator, console, dht, expect, gpio, heartbeat, icp, motor, onewire::Rom,
+    pid, rtc, rules, shift, sleep::SleepMode, uart, wave,
+};
+
+/// Arduino pin number of the built-in LED.
+pub const LED_PIN: u8 = 13;
+
+/// Maximum number of bytes passed to a single command.
+pub const MAX_BYTES: usize = 16;
+
+pub type Bytes = heapless::Vec<u8, MAX_BYTES>;
+
+/// Maximum number of pins in a pin list, one for each of the ten general
+/// purpose digital pins and the LED.
+pub const MAX_PINS: usize = 11;
+
+pub type Pins = heapless::Vec<u8, MAX_PINS>;
+
+#[derive(Clone, Debug)]
+pub enum Command<'a> {
+    Help,
+    HelpTopic {
+        command: &'a str,
+    },
+    Led(bool),
+    /// Shows the state of the crab on the built-in LED.
+    Heartbeat(heartbeat::Mode),
+    GetPin {
+        pin: u8,
+    },
+    SetPin {
+        pin: u8,
+        value: bool,
+    },
+    /// Reads several pins at once, as a bitfield with the first pin in the
+    /// lowest bit.
+    GetPins {
+        pins: Pins,
+    },
+    Capture {
+        pins: Pins,
+        rate_hz: u32,
+        samples: u16,
+        format: capture::Format,
+    },
+    GroupDefine {
+        name: &'a str,
+        pins: Pins,
+    },
+    GroupList,
+    /// Sets several pins at once.
+    SetPins {
+        pins: Pins,
+        value: bool,
+    },
+    /// Sets pins, and sets them back to the other level after a while.
+    Pulse {
+        pins: Pins,
+        value: bool,
+        duration_ms: u32,
+    },
+    PortRead {
+        port: gpio::Port,
+    },
+    PortWrite {
+        port: gpio::Port,
+        mask: u8,
+        value: u8,
+    },
+    Pwm {
+        duty_cycle: u8,
+    },
+    Pwm16 {
+        pin: u8,
+        duty_cycle: u16,
+    },
+    Wave {
+        pin: u8,
+        shape: wave::Shape,
+        frequency: u16,
+    },
+    WaveOff,
+    Tone {
+        pin: u8,
+        frequency: u16,
+    },
+    ToneOff,
+    /// Plays an RTTTL melody, or one of the built-in jingles by name.
+    Melody {
+        pin: u8,
+        song: &'a str,
+    },
+    MelodyOff,
+    /// Sets the output of an MCP4725, as a 12-bit value or in millivolts.
+    Dac {
+        value: u16,
+        millivolts: bool,
+    },
+    SoftPwm {
+        pin: u8,
+        duty_cycle: u8,
+    },
+    /// Ramps the duty cycle of the PWM output or a software PWM pin.
+    Fade {
+        pin: u8,
+        from: u8,
+        to: u8,
+        duration_ms: u32,
+    },
+    Adc {
+        pin: u8,
+    },
+    /// Samples the analog inputs continuously in the background.
+    AdcFreeRun(bool),
+    Temp {
+        celsius: bool,
+    },
+    TempCal {
+        offset: i8,
+    },
+    Vcc,
+    /// The threshold in millivolts, and whether to go to a safe state.
+    Powermon(Option<(u16, bool)>),
+    PowermonOff,
+    Random {
+        max: u16,
+    },
+    Uptime,
+    Timestamps(bool),
+    Mode(console::Mode),
+    Modbus {
+        address: u8,
+    },
+    Prompt(bool),
+    Echo(bool),
+    Base(console::Base),
+    Delay {
+        ms: u16,
+    },
+    MacroDefine {
+        name: &'a str,
+        body: &'a str,
+    },
+    MacroRun {
+        name: &'a str,
+    },
+    MacroList,
+    WhenAdd {
+        input: rules::Input,
+        condition: rules::Condition,
+        action: &'a str,
+    },
+    WhenList,
+    WhenClear,
+    /// Prints an input at regular intervals, alongside any others watched.
+    WatchAdd {
+        input: rules::Input,
+        interval_ms: u16,
+    },
+    WatchList,
+    WatchStop {
+        index: usize,
+    },
+    ScheduleAdd {
+        interval_ms: u32,
+        action: &'a str,
+    },
+    ScheduleList,
+    ScheduleDelete {
+        index: usize,
+    },
+    PidSetup {
+        input: u8,
+        output: u8,
+        gains: pid::Gains,
+        setpoint: u16,
+    },
+    PidInterval {
+        interval_ms: u16,
+    },
+    PidStart,
+    PidStop,
+    PidStatus,
+    Notify {
+        pin: u8,
+        enabled: bool,
+    },
+    Button {
+        pin: u8,
+        debounce_ms: u16,
+    },
+    Touch {
+        send: u8,
+        sense: u8,
+    },
+    ComparatorOn {
+        reference: comparator::Reference,
+    },
+    ComparatorOff,
+    Icp {
+        count: u8,
+        edges: icp::Edges,
+    },
+    Duty {
+        pin: u8,
+    },
+    ButtonNotify {
+        pin: u8,
+        enabled: bool,
+        debounce_ms: u16,
+    },
+    /// Scans a matrix keypad, with the pins of its rows and of its columns.
+    KeypadInit {
+        rows: Pins,
+        columns: Pins,
+    },
+    KeypadOff,
+    /// Refreshes a multiplexed LED matrix, with the pins of its rows and of
+    /// its columns.
+    MatrixInit {
+        rows: Pins,
+        columns: Pins,
+    },
+    /// Refreshes a charlieplexed LED matrix on the given pins.
+    MatrixCharlieplex {
+        pins: Pins,
+    },
+    /// Turns a pixel on or off, counting from the top left.
+    MatrixSet {
+        x: u8,
+        y: u8,
+        on: bool,
+    },
+    MatrixClear,
+    /// Scrolls text through the matrix.
+    MatrixText {
+        text: &'a str,
+    },
+    MatrixOff,
+    DatalogStart {
+        pin: u8,
+        interval_s: u16,
+    },
+    DatalogStop,
+    DatalogDump,
+    Alert {
+        pin: u8,
+        /// No alert turns the pin's alert off.
+        alert: Option<alert::Alert>,
+    },
+    StartupSet {
+        script: &'a str,
+    },
+    StartupShow,
+    StartupClear,
+    EepromRead {
+        address: u16,
+        len: u16,
+    },
+    EepromWrite {
+        address: u16,
+        bytes: Bytes,
+    },
+    Save,
+    Load,
+    Reset,
+    Bootload,
+    Batch,
+    /// Prints the serial settings, or stores and switches to new ones.
+    Serial(Option<uart::Settings>),
+    SerialDefault,
+    WdtOn {
+        timeout_ms: u16,
+    },
+    WdtOff,
+    WdtKick,
+    Sleep {
+        mode: SleepMode,
+        wake_pin: Option<u8>,
+    },
+    Stats,
+    /// Prints whether an input reads as expected, for test scripts.
+    Expect {
+        input: rules::Input,
+        expectation: expect::Expectation,
+    },
+    Selftest {
+        pins: Pins,
+    },
+    Free,
+    LastReset,
+    Version,
+    IdGet,
+    IdSet {
+        name: &'a str,
+    },
+    I2cScan,
+    I2cWrite {
+        address: u8,
+        bytes: Bytes,
+    },
+    I2cRead {
+        address: u8,
+        len: u8,
+    },
+    I2cReadRegister {
+        address: u8,
+        register: u8,
+        len: u8,
+    },
+    /// Turns I2C slave mode on at an address, or off.
+    I2cSlave {
+        address: Option<u8>,
+    },
+    I2cWriteRegister {
+        address: u8,
+        register: u8,
+        bytes: Bytes,
+    },
+    SpiTransfer {
+        bytes: Bytes,
+    },
+    SpiMode {
+        mode: u8,
+    },
+    SpiDivider {
+        divider: u8,
+    },
+    SpiCs {
+        pin: u8,
+    },
+    OneWireSearch {
+        pin: u8,
+    },
+    OneWireReset {
+        pin: u8,
+    },
+    OneWireWrite {
+        pin: u8,
+        bytes: Bytes,
+    },
+    OneWireRead {
+        pin: u8,
+        len: u8,
+    },
+    Ds18b20 {
+        pin: u8,
+        rom: Option<Rom>,
+    },
+    Dht {
+        pin: u8,
+        model: dht::Model,
+    },
+    Sonar {
+        trig: u8,
+        echo: u8,
+    },
+    NeopixelInit {
+        pin: u8,
+        count: u8,
+    },
+    NeopixelSet {
+        index: u8,
+        red: u8,
+        green: u8,
+        blue: u8,
+    },
+    NeopixelFill {
+        red: u8,
+        green: u8,
+        blue: u8,
+    },
+    NeopixelShow,
+    LcdInitParallel {
+        rs: u8,
+        en: u8,
+        data: [u8; 4],
+    },
+    LcdInitI2c {
+        address: u8,
+    },
+    LcdPrint {
+        text: &'a str,
+    },
+    LcdClear,
+    LcdPos {
+        row: u8,
+        column: u8,
+    },
+    EncoderAttach {
+        a: u8,
+        b: u8,
+    },
+    EncoderRead,
+    IrListen {
+        pin: u8,
+    },
+    IrStop,
+    StepperInit {
+        pins: heapless::Vec<u8, 4>,
+    },
+    StepperMove {
+        steps: i32,
+        speed: u16,
+    },
+    StepperStop,
+    Motor {
+        motor: motor::Motor,
+        drive: motor::Drive,
+        speed: u8,
+    },
+    /// Locks a pin, or lists the locked pins.
+    Lock {
+        pin: Option<u8>,
+    },
+    Unlock {
+        pin: u8,
+        code: Option<&'a str>,
+    },
+    LockCode {
+        code: &'a str,
+    },
+    NameList,
+    /// Names a pin, or removes its name.
+    NameSet {
+        pin: u8,
+        name: Option<&'a str>,
+    },
+    /// Turns safe mode on or off, or shows whether it is on.
+    Safemode(Option<bool>),
+    SafemodeJumper {
+        pin: Option<u8>,
+    },
+    UnlockCode {
+        code: &'a str,
+    },
+    Servo {
+        pin: u8,
+        angle: u8,
+    },
+    ServoSweep {
+        pin: u8,
+        from: u8,
+        to: u8,
+        duration_ms: u16,
+    },
+    RtcGet,
+    RtcSet {
+        time: rtc::DateTime,
+    },
+    ImuInit {
+        address: u8,
+    },
+    ImuRead,
+    BaroRead {
+        interface: bme280::Interface,
+    },
+    ScaleInit {
+        dout: u8,
+        sck: u8,
+    },
+    ScaleRead {
+        samples: u8,
+    },
+    ScaleTare,
+    ShiftOut {
+        data: u8,
+        clock: u8,
+        latch: u8,
+        bytes: Bytes,
+        order: shift::BitOrder,
+    },
+    ShiftIn {
+        data: u8,
+        clock: u8,
+        load: u8,
+        len: u8,
+        order: shift::BitOrder,
+    },
+    SoftSerialOpen {
+        rx: u8,
+        tx: u8,
+        baud: u32,
+    },
+    SoftSerialSend {
+        text: &'a str,
+    },
+    SoftSerialClose,
+    /// Reads NMEA sentences from a GPS receiver on a software serial port.
+    GpsStart {
+        rx: u8,
+        tx: u8,
+        baud: u32,
+    },
+    GpsRead,
+    GpsStop,
+    #[cfg(feature = "sd-log")]
+    LogStart {
+        interval_ms: u32,
+        channels: sdlog::Channels,
+    },
+    #[cfg(feature = "sd-log")]
+    LogStop,
+    #[cfg(feature = "sd-log")]
+    LogDump,
+    #[cfg(feature = "radio")]
+    RadioInit {
+        ce: u8,
+        csn: u8,
+    },
+    #[cfg(feature = "radio")]
+    RadioChannel {
+        channel: u8,
+    },
+    /// Receives payloads sent to an address of up to five characters.
+    #[cfg(feature = "radio")]
+    RadioListen {
+        address: &'a str,
+    },
+    #[cfg(feature = "radio")]
+    RadioSend {
+        address: &'a str,
+        data: Bytes,
+    },
+    #[cfg(feature = "radio")]
+    RadioOff,
+    #[cfg(feature = "can")]
+    CanInit {
+        bitrate: u32,
+        /// The frequency of the controller's crystal.
+        crystal_mhz: u8,
+    },
+    /// Sends a frame, with an extended identifier if it doesn't fit into 11
+    /// bits.
+    #[cfg(feature = "can")]
+    CanSend {
+        id: u32,
+        data: Bytes,
+    },
+    #[cfg(feature = "can")]
+    CanReceive(bool),
+    /// Starts serving the console on a TCP port.
+    #[cfg(feature = "ethernet")]
+    NetInit {
+        cs: u8,
+        ip: [u8; 4],
+        port: u16,
+    },
+    #[cfg(feature = "ethernet")]
+    NetStatus,
+    #[cfg(feature = "ethernet")]
+    NetOff,
+}
+
+impl Command<'_> {
+    /// Whether the command changes the level or function of a digital pin,
+    /// which a lock prevents. Pins set up as inputs count as well, as that
+    /// lets go of whatever an output was driving.
+    ///
+    /// Every command is listed, so that a new one has to be sorted in.
+    pub fn drives(&self, pin: u8) -> bool {
+        match *self {
+            Command::SetPin { pin: p, .. }
+            | Command::Pwm16 { pin: p, .. }
+            | Command::SoftPwm { pin: p, .. }
+            | Command::Fade { pin: p, .. }
+            | Command::Wave { pin: p, .. }
+            | Command::Tone { pin: p, .. }
+            | Command::Melody { pin: p, .. }
+            | Command::Servo { pin: p, .. }
+            | Command::ServoSweep { pin: p, .. }
+            | Command::PidSetup { output: p, .. }
+            | Command::Button { pin: p, .. }
+            | Command::ButtonNotify { pin: p, .. }
+            | Command::Duty { pin: p }
+            | Command::SpiCs { pin: p }
+            | Command::OneWireSearch { pin: p }
+            | Command::OneWireReset { pin: p }
+            | Command::OneWireWrite { pin: p, .. }
+            | Command::OneWireRead { pin: p, .. }
+            | Command::Ds18b20 { pin: p, .. }
+            | Command::Dht { pin: p, .. }
+            | Command::NeopixelInit { pin: p, .. }
+            | Command::IrListen { pin: p } => p == pin,
+            Command::Touch { send: a, sense: b }
+            | Command::Sonar { trig: a, echo: b }
+            | Command::EncoderAttach { a, b }
+            | Command::ScaleInit { dout: a, sck: b }
+            | Command::SoftSerialOpen { rx: a, tx: b, .. }
+            | Command::GpsStart { rx: a, tx: b, .. } => pin == a || pin == b,
+            Command::ShiftOut {
+                data, clock, latch, ..
+            } => pin == data || pin == clock || pin == latch,
+            Command::ShiftIn {
+                data, clock, load, ..
+            } => pin == data || pin == clock || pin == load,
+            Command::LcdInitParallel { rs, en, data } => {
+                pin == rs || pin == en || data.contains(&pin)
+            }
+            Command::SafemodeJumper { pin: p } => p == Some(pin),
+            #[cfg(feature = "radio")]
+            Command::RadioInit { ce, csn } => pin == ce || pin == csn,
+            #[cfg(feature = "ethernet")]
+            Command::NetInit { cs, .. } => pin == cs,
+            Command::KeypadInit {
+                ref rows,
+                ref columns,
+            }
+            | Command::MatrixInit {
+                ref rows,
+                ref columns,
+            } => rows.contains(&pin) || columns.contains(&pin),
+            Command::SetPins { ref pins, .. }
+            | Command::MatrixCharlieplex { ref pins }
+            | Command::Pulse { ref pins, .. }
+            | Command::Selftest { ref pins } => pins.contains(&pin),
+            Command::StepperInit { ref pins } => pins.contains(&pin),
+            Command::PortWrite { port, mask, .. } => {
+                (0..8).any(|bit| mask & (1 << bit) != 0 && port.digital_pin(bit) == Some(pin))
+            }
+            Command::Led(_) | Command::Heartbeat(_) => pin == LED_PIN,
+            Command::Pwm { .. } => pin == 5,
+            // The input capture pin.
+            Command::Icp { .. } => pin == 8,
+            // AIN1, and AIN0 unless the bandgap stands in for it.
+            Command::ComparatorOn { reference } => {
+                pin == 7 || (pin == 6 && matches!(reference, comparator::Reference::Pin))
+            }
+            Command::Motor { motor, .. } => {
+                let (in1, in2) = motor.direction_pins();
+                let enable = match motor {
+                    motor::Motor::A => 5,
+                    motor::Motor::B => 6,
+                };
+                pin == in1 || pin == in2 || pin == enable
+            }
+            // Restores every pin.
+            Command::Load => true,
+            // Only reads pins, or drives ones that were set up by a command
+            // above.
+            Command::Help
+            | Command::HelpTopic { .. }
+            | Command::GetPin { .. }
+            | Command::GetPins { .. }
+            | Command::Capture { .. }
+            | Command::GroupDefine { .. }
+            | Command::GroupList
+            | Command::PortRead { .. }
+            | Command::WaveOff
+            | Command::ToneOff
+            | Command::MelodyOff
+            | Command::Dac { .. }
+            | Command::Adc { .. }
+            | Command::AdcFreeRun(_)
+            | Command::Temp { .. }
+            | Command::TempCal { .. }
+            | Command::Vcc
+            | Command::Powermon(_)
+            | Command::PowermonOff
+            | Command::Random { .. }
+            | Command::Uptime
+            | Command::Timestamps(_)
+            | Command::Mode(_)
+            | Command::Modbus { .. }
+            | Command::Prompt(_)
+            | Command::Echo(_)
+            | Command::Base(_)
+            | Command::Delay { .. }
+            | Command::MacroDefine { .. }
+            | Command::MacroRun { .. }
+            | Command::MacroList
+            | Command::WhenAdd { .. }
+            | Command::WhenList
+            | Command::WhenClear
+            | Command::WatchAdd { .. }
+            | Command::WatchList
+            | Command::WatchStop { .. }
+            | Command::ScheduleAdd { .. }
+            | Command::ScheduleList
+            | Command::ScheduleDelete { .. }
+            | Command::PidInterval { .. }
+            | Command::PidStart
+            | Command::PidStop
+            | Command::PidStatus
+            | Command::Notify { .. }
+            | Command::ComparatorOff
+            | Command::KeypadOff
+            | Command::MatrixSet { .. }
+            | Command::MatrixClear
+            | Command::MatrixText { .. }
+            | Command::MatrixOff
+            | Command::DatalogStart { .. }
+            | Command::DatalogStop
+            | Command::DatalogDump
+            | Command::Alert { .. }
+            | Command::StartupSet { .. }
+            | Command::StartupShow
+            | Command::StartupClear
+            | Command::EepromRead { .. }
+            | Command::EepromWrite { .. }
+            | Command::Save
+            | Command::Reset
+            | Command::Bootload
+            | Command::Batch
+            | Command::Serial(_)
+            | Command::SerialDefault
+            | Command::WdtOn { .. }
+            | Command::WdtOff
+            | Command::WdtKick
+            | Command::Sleep { .. }
+            | Command::Stats
+            | Command::Expect { .. }
+            | Command::Free
+            | Command::LastReset
+            | Command::Version
+            | Command::IdGet
+            | Command::IdSet { .. }
+            | Command::I2cScan
+            | Command::I2cWrite { .. }
+            | Command::I2cRead { .. }
+            | Command::I2cReadRegister { .. }
+            | Command::I2cSlave { .. }
+            | Command::I2cWriteRegister { .. }
+            | Command::SpiTransfer { .. }
+            | Command::SpiMode { .. }
+            | Command::SpiDivider { .. }
+            | Command::NeopixelSet { .. }
+            | Command::NeopixelFill { .. }
+            | Command::NeopixelShow
+            | Command::LcdInitI2c { .. }
+            | Command::LcdPrint { .. }
+            | Command::LcdClear
+            | Command::LcdPos { .. }
+            | Command::EncoderRead
+            | Command::IrStop
+            | Command::StepperMove { .. }
+            | Command::StepperStop
+            | Command::Lock { .. }
+            | Command::Unlock { .. }
+            | Command::LockCode { .. }
+            | Command::NameList
+            | Command::NameSet { .. }
+            | Command::Safemode(_)
+            | Command::UnlockCode { .. }
+            | Command::RtcGet
+            | Command::RtcSet { .. }
+            | Command::ImuInit { .. }
+            | Command::ImuRead
+            | Command::BaroRead { .. }
+            | Command::ScaleRead { .. }
+            | Command::ScaleTare
+            | Command::SoftSerialSend { .. }
+            | Command::SoftSerialClose
+            | Command::GpsRead
+            | Command::GpsStop => false,
+            #[cfg(feature = "sd-log")]
+            Command::LogStart { .. } | Command::LogStop | Command::LogDump => false,
+            #[cfg(feature = "radio")]
+            Command::RadioChannel { .. }
+            | Command::RadioListen { .. }
+            | Command::RadioSend { .. }
+            | Command::RadioOff => false,
+            #[cfg(feature = "can")]
+            Command::CanInit { .. } | Command::CanSend { .. } | Command::CanReceive(_) => false,
+            #[cfg(feature = "ethernet")]
+            Command::NetStatus | Command::NetOff => false,
+        }
+    }
+
+    /// Whether the command only reads, which safe mode allows. Macros are
+    /// allowed as their commands are checked one by one.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::Help
+                | Command::HelpTopic { .. }
+                | Command::GetPin { .. }
+                | Command::PortRead { .. }
+                | Command::GetPins { .. }
+                | Command::Capture { .. }
+                | Command::GroupList
+                | Command::Adc { .. }
+                | Command::Temp { .. }
+                | Command::Vcc
+                | Command::Powermon(None)
+                | Command::Random { .. }
+                | Command::Uptime
+                | Command::Timestamps(_)
+                | Command::Prompt(_)
+                | Command::Base(_)
+                | Command::Echo(_)
+                | Command::Delay { .. }
+                | Command::Batch
+                | Command::Serial(None)
+                | Command::MacroRun { .. }
+                | Command::MacroList
+                | Command::WhenList
+                | Command::WatchList
+                | Command::ScheduleList
+                | Command::PidStatus
+                | Command::Icp { .. }
+                | Command::Duty { .. }
+                | Command::DatalogDump
+                | Command::StartupShow
+                | Command::EepromRead { .. }
+                | Command::WdtKick
+                | Command::Stats
+                | Command::Expect { .. }
+                | Command::Free
+                | Command::LastReset
+                | Command::Version
+                | Command::IdGet
+                | Command::I2cScan
+                | Command::I2cRead { .. }
+                | Command::I2cReadRegister { .. }
+                | Command::Ds18b20 { .. }
+                | Command::Dht { .. }
+                | Command::EncoderRead
+                | Command::RtcGet
+                | Command::ImuRead
+                | Command::BaroRead { .. }
+                | Command::ScaleRead { .. }
+                | Command::GpsRead
+                | Command::Lock { pin: None }
+                | Command::NameList
+                | Command::Safemode(_)
+        )
+    }
+}
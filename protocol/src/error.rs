@@ -0,0 +1,84 @@
+//! Error codes that commands fail with.
+//!
+//! Each failure prints a message meant for people first, and then the code,
+//! which stays the same when the wording changes so that programs can tell
+//! a typo apart from a broken device.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The input is not a known command, or its arguments don't parse.
+    UnknownCommand = 1,
+    /// Pins that don't exist or can't be used for the command.
+    BadPin,
+    /// A value outside the range the command accepts.
+    OutOfRange,
+    /// A line, buffer, or table is full.
+    Overflow,
+    /// The hardware is in use by something else.
+    Busy,
+    /// A device doesn't respond, or reports an error.
+    Device,
+    /// The device or feature has to be set up first.
+    NotSetUp,
+    /// No macro, saved configuration, or log by that name.
+    NotFound,
+    /// The pin is locked, or the unlock code is wrong.
+    Locked,
+    /// Safe mode only allows commands that read.
+    ReadOnly,
+}
+
+impl Error {
+    /// Every error, in the order of their numbers.
+    pub const ALL: [Error; 10] = [
+        Error::UnknownCommand,
+        Error::BadPin,
+        Error::OutOfRange,
+        Error::Overflow,
+        Error::Busy,
+        Error::Device,
+        Error::NotSetUp,
+        Error::NotFound,
+        Error::Locked,
+        Error::ReadOnly,
+    ];
+
+    /// Looks an error up by its code, as printed after a failed command.
+    pub fn from_code(code: &str) -> Option<Error> {
+        Error::ALL.into_iter().find(|error| error.code() == code)
+    }
+
+    /// The error as a number for the binary protocol, starting at 1.
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Error::UnknownCommand => "unknown_command",
+            Error::BadPin => "bad_pin",
+            Error::OutOfRange => "out_of_range",
+            Error::Overflow => "overflow",
+            Error::Busy => "busy",
+            Error::Device => "device",
+            Error::NotSetUp => "not_set_up",
+            Error::NotFound => "not_found",
+            Error::Locked => "locked",
+            Error::ReadOnly => "read_only",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_and_numbers_round_trip() {
+        for (index, error) in Error::ALL.into_iter().enumerate() {
+            assert_eq!(Error::from_code(error.code()), Some(error));
+            assert_eq!(usize::from(error.number()), index + 1);
+        }
+        assert_eq!(Error::from_code("everything"), None);
+    }
+}
@@ -0,0 +1,140 @@
+//! Commands written out as text, for programs on the host that send them.
+//!
+//! Only the commands a host library needs to drive the crab are covered.
+//! Each is written in the form [`crate::parse_command`] reads back into the
+//! same command, which the tests check.
+
+use ufmt::{uWrite, uwrite};
+
+use crate::args::console::{Base, Mode};
+use crate::Command;
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Writes a command as a line of input, without the line ending. Returns
+/// `None` for commands that aren't covered.
+pub fn write_command<W: uWrite + ?Sized>(
+    w: &mut W,
+    command: &Command<'_>,
+) -> Option<Result<(), W::Error>> {
+    Some(match *command {
+        Command::Led(on) => uwrite!(w, "led {}", on_off(on)),
+        Command::GetPin { pin } => uwrite!(w, "get {}", pin),
+        Command::SetPin { pin, value } => {
+            uwrite!(w, "set {} {}", pin, if value { "high" } else { "low" })
+        }
+        Command::Pwm { duty_cycle } => uwrite!(w, "pwm {}", duty_cycle),
+        Command::Adc { pin } => uwrite!(w, "adc {}", pin),
+        Command::Servo { pin, angle } => uwrite!(w, "servo {} {}", pin, angle),
+        Command::Delay { ms } => uwrite!(w, "delay {}", ms),
+        Command::Uptime => uwrite!(w, "uptime"),
+        Command::Version => uwrite!(w, "version"),
+        Command::IdGet => uwrite!(w, "id get"),
+        Command::IdSet { name } => uwrite!(w, "id set {}", name),
+        Command::Timestamps(on) => uwrite!(w, "timestamps {}", on_off(on)),
+        Command::Prompt(on) => uwrite!(w, "prompt {}", on_off(on)),
+        Command::Echo(on) => uwrite!(w, "echo {}", on_off(on)),
+        Command::Base(base) => uwrite!(
+            w,
+            "base {}",
+            match base {
+                Base::Decimal => "dec",
+                Base::Hex => "hex",
+                Base::Binary => "bin",
+            }
+        ),
+        Command::Mode(Mode::Json) => uwrite!(w, "format json"),
+        Command::Mode(mode) => uwrite!(
+            w,
+            "mode {}",
+            match mode {
+                Mode::Human => "human",
+                Mode::Machine => "machine",
+                Mode::Binary => "binary",
+                Mode::Firmata => "firmata",
+                Mode::Json | Mode::Modbus => return None,
+            }
+        ),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::String;
+
+    use super::*;
+    use crate::parse_command;
+
+    fn written(command: &Command<'_>) -> String<32> {
+        let mut line = String::new();
+        write_command(&mut line, command).unwrap().unwrap();
+        line
+    }
+
+    /// Writes a command and parses it back.
+    macro_rules! round_trip {
+        ($command:expr, $pattern:pat) => {{
+            let line = written(&$command);
+            let parsed = parse_command(&line);
+            assert!(matches!(parsed, Some($pattern)), "{} doesn't parse back", line);
+        }};
+    }
+
+    #[test]
+    fn pins() {
+        round_trip!(Command::Led(true), Command::Led(true));
+        round_trip!(Command::GetPin { pin: 7 }, Command::GetPin { pin: 7 });
+        round_trip!(
+            Command::SetPin {
+                pin: 12,
+                value: false
+            },
+            Command::SetPin {
+                pin: 12,
+                value: false
+            }
+        );
+        round_trip!(Command::Adc { pin: 3 }, Command::Adc { pin: 3 });
+        round_trip!(
+            Command::Pwm { duty_cycle: 200 },
+            Command::Pwm { duty_cycle: 200 }
+        );
+        round_trip!(
+            Command::Servo { pin: 9, angle: 90 },
+            Command::Servo { pin: 9, angle: 90 }
+        );
+        assert_eq!(written(&Command::SetPin { pin: 7, value: true }), "set 7 high");
+    }
+
+    #[test]
+    fn console() {
+        round_trip!(Command::Mode(Mode::Machine), Command::Mode(Mode::Machine));
+        round_trip!(Command::Mode(Mode::Json), Command::Mode(Mode::Json));
+        round_trip!(Command::Echo(false), Command::Echo(false));
+        round_trip!(Command::Prompt(false), Command::Prompt(false));
+        round_trip!(Command::Timestamps(false), Command::Timestamps(false));
+        round_trip!(Command::Base(Base::Hex), Command::Base(Base::Hex));
+        round_trip!(Command::Delay { ms: 250 }, Command::Delay { ms: 250 });
+        round_trip!(Command::IdGet, Command::IdGet);
+        round_trip!(
+            Command::IdSet { name: "crab1" },
+            Command::IdSet { name: "crab1" }
+        );
+        round_trip!(Command::Version, Command::Version);
+        round_trip!(Command::Uptime, Command::Uptime);
+    }
+
+    #[test]
+    fn others_are_not_covered() {
+        let mut line: String<32> = String::new();
+        assert!(write_command(&mut line, &Command::Reset).is_none());
+        assert!(write_command(&mut line, &Command::Mode(Mode::Modbus)).is_none());
+    }
+}
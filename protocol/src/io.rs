@@ -0,0 +1,146 @@
+//! The commands that only need pins and the ADC.
+//!
+//! They run against [`Io`], which the firmware implements on the board and
+//! the simulator in `sim` on fake peripherals, so that both run the same
+//! code and print the same responses. [`run_line`] is the loop around them,
+//! from a line of input to the responses of its commands.
+
+use ufmt::{uWrite, uwriteln};
+
+use crate::args::console::Base;
+use crate::{normalize, parse_command, split_command, Command, Error, LED_PIN};
+
+pub trait Io {
+    type Output: uWrite;
+
+    /// The digital pins that commands may use, for error messages.
+    const DIGITAL_PINS: &'static str;
+    /// The analog inputs, for error messages.
+    const ANALOG_PINS: &'static str;
+
+    /// Where responses go.
+    fn output(&mut self) -> &mut Self::Output;
+
+    /// The base that values print in.
+    fn base(&self) -> Base;
+
+    /// Reads the level of a digital pin, or the level it is driven to if it
+    /// is an output. Returns `None` if there is no such pin.
+    fn digital_read(&mut self, pin: u8) -> Option<bool>;
+
+    /// Makes a digital pin an output and drives it. Returns `None` if there
+    /// is no such pin.
+    fn digital_write(&mut self, pin: u8, high: bool) -> Option<()>;
+
+    /// Reads an analog input, or returns `None` if there is no such input.
+    fn analog_read(&mut self, pin: u8) -> Option<u16>;
+
+    /// Prints whatever else is known about a pin after its level.
+    fn describe_pin(&mut self, _pin: u8) {}
+
+    /// Runs a parsed command, of those [`execute`] runs or any other.
+    /// `input` is the command as it was parsed.
+    fn run(&mut self, input: &str, command: Command<'_>) -> Result<(), Error>;
+
+    /// Called before each command of a line, with its normalized input.
+    fn begin_command(&mut self, _input: &str) {}
+
+    /// Called after each command of a line, with its result.
+    fn end_command(&mut self, _result: Result<(), Error>) {}
+
+    /// Hands a command to `parse`, after rewriting it if need be, such as to
+    /// replace the names of pins with their numbers.
+    fn with_resolved<R>(&mut self, input: &str, parse: impl FnOnce(&mut Self, &str) -> R) -> R {
+        parse(self, input)
+    }
+
+    /// Prints why a command didn't parse, and returns the error.
+    fn invalid_command(&mut self, input: &str) -> Error {
+        let _ = uwriteln!(self.output(), "invalid command: {}", input);
+        Error::UnknownCommand
+    }
+}
+
+/// Runs a line of `;`-separated commands in order, stopping at the first
+/// that fails.
+///
+/// Each command is normalized to at most `N` bytes, parsed, and run by
+/// [`Io::run`], between [`Io::begin_command`] and [`Io::end_command`].
+pub fn run_line<const N: usize, I: Io + ?Sized>(io: &mut I, line: &str) -> Result<(), Error> {
+    let mut rest = line;
+    while !rest.is_empty() {
+        let (input, tail) = split_command(rest);
+        rest = tail;
+        if input.is_empty() {
+            continue;
+        }
+        let normal = normalize::<N>(input);
+        let input = normal.as_deref().unwrap_or(input);
+        io.begin_command(input);
+        let result = io.with_resolved(input, |io, input| match parse_command(input) {
+            Some(command) => io.run(input, command),
+            None => Err(io.invalid_command(input)),
+        });
+        io.end_command(result);
+        result?;
+    }
+    Ok(())
+}
+
+/// Runs a command if it only needs [`Io`], and returns `None` otherwise.
+pub fn execute<I: Io + ?Sized>(io: &mut I, command: &Command<'_>) -> Option<Result<(), Error>> {
+    Some(match *command {
+        Command::Led(value) => {
+            let _ = io.digital_write(LED_PIN, value);
+            Ok(())
+        }
+        Command::GetPin { pin } => get_pin(io, pin),
+        Command::SetPin { pin, value } => set_pin(io, pin, value),
+        Command::Adc { pin } => adc(io, pin),
+        _ => return None,
+    })
+}
+
+/// Prints the level of a digital pin, for `get`.
+pub fn get_pin<I: Io + ?Sized>(io: &mut I, pin: u8) -> Result<(), Error> {
+    let Some(value) = io.digital_read(pin) else {
+        return unknown_digital_pin(io, pin);
+    };
+    let _ = uwriteln!(io.output(), "d{}: {}", pin, value);
+    io.describe_pin(pin);
+    Ok(())
+}
+
+/// Drives a digital pin, for `set`.
+pub fn set_pin<I: Io + ?Sized>(io: &mut I, pin: u8, value: bool) -> Result<(), Error> {
+    match io.digital_write(pin, value) {
+        Some(()) => Ok(()),
+        None => unknown_digital_pin(io, pin),
+    }
+}
+
+/// Prints the reading of an analog input, for `adc`.
+pub fn adc<I: Io + ?Sized>(io: &mut I, pin: u8) -> Result<(), Error> {
+    let Some(value) = io.analog_read(pin) else {
+        let _ = uwriteln!(
+            io.output(),
+            "unknown pin: {}, valid pins are {}",
+            pin,
+            I::ANALOG_PINS
+        );
+        return Err(Error::BadPin);
+    };
+    let value = io.base().value(value);
+    let _ = uwriteln!(io.output(), "a{}: {}", pin, value);
+    Ok(())
+}
+
+fn unknown_digital_pin<I: Io + ?Sized>(io: &mut I, pin: u8) -> Result<(), Error> {
+    let _ = uwriteln!(
+        io.output(),
+        "unknown pin: {}, valid pins are {}",
+        pin,
+        I::DIGITAL_PINS
+    );
+    Err(Error::BadPin)
+}
@@ -0,0 +1,167 @@
+//! JSON output, one object per line.
+//!
+//! Responses are turned into objects from the usual text. Each `key: value`
+//! becomes a member, with numbers and booleans left unquoted, and text that
+//! isn't part of a pair is kept under `"text"`.
+
+use ufmt::{uWrite, uwrite};
+
+/// Writes a response, such as `{"cmd":"adc","ok":true,"a0":512}`. A failed
+/// command keeps its whole text under `"message"` instead.
+pub fn write_response<W: uWrite + ?Sized>(
+    w: &mut W,
+    command: &str,
+    error: Option<&str>,
+    text: &str,
+) -> Result<(), W::Error> {
+    w.write_str("{\"cmd\":")?;
+    write_string(w, command)?;
+    match error {
+        None => {
+            w.write_str(",\"ok\":true")?;
+            let mut loose = text.lines().filter(|line| !line.contains(": ")).peekable();
+            if loose.peek().is_some() {
+                w.write_str(",\"text\":\"")?;
+                for (i, line) in loose.enumerate() {
+                    if i > 0 {
+                        w.write_str(" ")?;
+                    }
+                    write_escaped(w, line)?;
+                }
+                w.write_str("\"")?;
+            }
+            for line in text.lines() {
+                write_pairs(w, line)?;
+            }
+        }
+        Some(code) => {
+            w.write_str(",\"ok\":false,\"error\":")?;
+            write_string(w, code)?;
+            w.write_str(",\"message\":\"")?;
+            for (i, line) in text.lines().enumerate() {
+                if i > 0 {
+                    w.write_str(" ")?;
+                }
+                write_escaped(w, line)?;
+            }
+            w.write_str("\"")?;
+        }
+    }
+    w.write_str("}\n")
+}
+
+/// Writes a line printed outside of a command, such as an event.
+pub fn write_event<W: uWrite + ?Sized>(w: &mut W, text: &str) -> Result<(), W::Error> {
+    w.write_str("{\"event\":")?;
+    write_string(w, text)?;
+    w.write_str("}\n")
+}
+
+/// Writes the `key: value` pairs of a line as members. Where a line holds
+/// several, each key is the last word before its colon.
+fn write_pairs<W: uWrite + ?Sized>(w: &mut W, line: &str) -> Result<(), W::Error> {
+    let mut parts = line.split(": ").peekable();
+    let Some(mut key) = parts.next() else {
+        return Ok(());
+    };
+    while let Some(part) = parts.next() {
+        let (value, next_key) = match parts.peek() {
+            Some(_) => part.rsplit_once(' ').unwrap_or((part, "")),
+            None => (part, ""),
+        };
+        w.write_str(",")?;
+        write_string(w, key)?;
+        w.write_str(":")?;
+        write_value(w, value)?;
+        key = next_key;
+    }
+    Ok(())
+}
+
+/// Writes a value as a number or boolean if it looks like one, or as a
+/// string otherwise.
+fn write_value<W: uWrite + ?Sized>(w: &mut W, value: &str) -> Result<(), W::Error> {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let number = !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (digits == "0" || !digits.starts_with('0'));
+    if number || value == "true" || value == "false" {
+        w.write_str(value)
+    } else {
+        write_string(w, value)
+    }
+}
+
+fn write_string<W: uWrite + ?Sized>(w: &mut W, s: &str) -> Result<(), W::Error> {
+    w.write_str("\"")?;
+    write_escaped(w, s)?;
+    w.write_str("\"")
+}
+
+fn write_escaped<W: uWrite + ?Sized>(w: &mut W, s: &str) -> Result<(), W::Error> {
+    for c in s.chars() {
+        match c {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            c if c < ' ' => uwrite!(w, "\\u{:04x}", c as u32)?,
+            c => w.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::String;
+
+    use super::*;
+
+    fn response(command: &str, error: Option<&str>, text: &str) -> String<256> {
+        let mut out = String::new();
+        write_response(&mut out, command, error, text).unwrap();
+        out
+    }
+
+    #[test]
+    fn pairs_become_members() {
+        assert_eq!(
+            response("adc", None, "a0: 512\n"),
+            "{\"cmd\":\"adc\",\"ok\":true,\"a0\":512}\n"
+        );
+        assert_eq!(
+            response("uptime", None, "uptime: 12ms days: 0\n"),
+            "{\"cmd\":\"uptime\",\"ok\":true,\"uptime\":\"12ms\",\"days\":0}\n"
+        );
+    }
+
+    #[test]
+    fn numbers_and_booleans_are_unquoted() {
+        assert_eq!(
+            response("x", None, "a: -3\nb: 007\nc: true\n"),
+            "{\"cmd\":\"x\",\"ok\":true,\"a\":-3,\"b\":\"007\",\"c\":true}\n"
+        );
+    }
+
+    #[test]
+    fn loose_text() {
+        assert_eq!(
+            response("help", None, "commands: \"quoted\"\nsee help <command>\n"),
+            "{\"cmd\":\"help\",\"ok\":true,\"text\":\"see help <command>\",\"commands\":\"\\\"quoted\\\"\"}\n"
+        );
+    }
+
+    #[test]
+    fn errors_keep_the_message() {
+        assert_eq!(
+            response("get 99", Some("bad_pin"), "invalid pin: 99\n"),
+            "{\"cmd\":\"get 99\",\"ok\":false,\"error\":\"bad_pin\",\"message\":\"invalid pin: 99\"}\n"
+        );
+    }
+
+    #[test]
+    fn events_are_escaped() {
+        let mut out: String<64> = String::new();
+        write_event(&mut out, "tab\there").unwrap();
+        assert_eq!(out, "{\"event\":\"tab\\u0009here\"}\n");
+    }
+}
@@ -0,0 +1,27 @@
+//! Commands and responses of the mechanical crab's serial console.
+//!
+//! The firmware parses each line it receives into [`Command`]s here, and
+//! runs most of them against the hardware itself, and the basic I/O ones
+//! through [`io`]. Keeping the grammar apart from the hardware lets it be
+//! tested on the host, and lets programs on the host share it with the
+//! firmware.
+
+#![no_std]
+
+pub mod alert;
+pub mod args;
+mod command;
+pub mod error;
+pub mod format;
+pub mod io;
+pub mod json;
+pub mod nmea;
+mod parse;
+pub mod response;
+pub mod rtttl;
+
+pub use command::{Bytes, Command, Pins, LED_PIN, MAX_BYTES, MAX_PINS};
+pub use error::Error;
+pub use parse::{
+    normalize, parse_command, split_command, COMMANDS, KEYWORDS, PIN_COMMANDS, RESERVED_WORDS,
+};
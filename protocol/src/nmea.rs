@@ -0,0 +1,323 @@
+//! NMEA 0183 sentences from GPS receivers.
+//!
+//! Only the RMC and GGA sentences are read, from any talker, which between
+//! them carry the fix, the position, the UTC date and time, and the number
+//! of satellites in use. Everything else the receiver sends is skipped, as
+//! are sentences with a wrong checksum.
+
+use heapless::String;
+use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+use crate::args::rtc::{to_bcd, DateTime};
+
+/// The longest sentence the standard allows, without the line ending.
+pub const MAX_SENTENCE: usize = 82;
+
+/// A latitude or longitude in millionths of a degree, negative to the south
+/// and west.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Coordinate(pub i32);
+
+impl uDisplay for Coordinate {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        if self.0 < 0 {
+            f.write_str("-")?;
+        }
+        let value = self.0.unsigned_abs();
+        uwrite!(f, "{}.", value / 1_000_000)?;
+        let fraction = value % 1_000_000;
+        let mut digit = 100_000;
+        while digit > 1 && fraction < digit {
+            f.write_str("0")?;
+            digit /= 10;
+        }
+        uwrite!(f, "{}", fraction)
+    }
+}
+
+/// A UTC time of day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl uDisplay for Time {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        uwrite!(
+            f,
+            "{:02X}:{:02X}:{:02X}",
+            to_bcd(self.hour),
+            to_bcd(self.minute),
+            to_bcd(self.second)
+        )
+    }
+}
+
+/// What the receiver reported last.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Fix {
+    /// Whether the position is current.
+    pub valid: bool,
+    pub latitude: Option<Coordinate>,
+    pub longitude: Option<Coordinate>,
+    pub time: Option<Time>,
+    /// The date, as day, month, and year within 2000-2099.
+    pub date: Option<(u8, u8, u16)>,
+    pub satellites: Option<u8>,
+}
+
+impl Fix {
+    /// The date and time, once the receiver sent both.
+    pub fn date_time(&self) -> Option<DateTime> {
+        let (day, month, year) = self.date?;
+        let time = self.time?;
+        Some(DateTime {
+            year,
+            month,
+            day,
+            hour: time.hour,
+            minute: time.minute,
+            second: time.second,
+        })
+    }
+}
+
+/// Collects sentences byte by byte, and keeps the fix they report.
+#[derive(Default)]
+pub struct Parser {
+    line: String<MAX_SENTENCE>,
+    /// Whether the line is too long to be a sentence, and skipped until its
+    /// end.
+    overflowed: bool,
+    pub fix: Fix,
+    /// How many sentences have been read, to tell a silent receiver apart
+    /// from one without a fix.
+    pub sentences: u32,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a byte, and returns whether it ended an RMC or GGA sentence.
+    pub fn feed(&mut self, byte: u8) -> bool {
+        match byte {
+            b'$' => {
+                self.line.clear();
+                self.overflowed = false;
+                let _ = self.line.push('$');
+                false
+            }
+            b'\r' | b'\n' => {
+                let read = !self.overflowed && self.read_sentence();
+                self.line.clear();
+                self.overflowed = false;
+                read
+            }
+            _ => {
+                if self.line.is_empty() || self.line.push(char::from(byte)).is_err() {
+                    self.overflowed = true;
+                }
+                false
+            }
+        }
+    }
+
+    fn read_sentence(&mut self) -> bool {
+        let Some(sentence) = checked(&self.line) else {
+            return false;
+        };
+        let mut fields = sentence.split(',');
+        let id = fields.next().unwrap_or_default();
+        let mut fix = self.fix;
+        let read = match id.get(2..) {
+            Some("RMC") => read_rmc(&mut fix, fields),
+            Some("GGA") => read_gga(&mut fix, fields),
+            _ => None,
+        };
+        if read.is_none() {
+            return false;
+        }
+        self.fix = fix;
+        self.sentences = self.sentences.saturating_add(1);
+        true
+    }
+}
+
+/// Returns the fields of a sentence after the `$`, if its checksum is right
+/// or it has none.
+fn checked(line: &str) -> Option<&str> {
+    let line = line.strip_prefix('$')?;
+    let Some((sentence, checksum)) = line.split_once('*') else {
+        return Some(line);
+    };
+    let expected = u8::from_str_radix(checksum, 16).ok()?;
+    let actual = sentence.bytes().fold(0, |sum, byte| sum ^ byte);
+    (actual == expected).then_some(sentence)
+}
+
+fn read_rmc<'a>(fix: &mut Fix, mut fields: impl Iterator<Item = &'a str>) -> Option<()> {
+    let time = fields.next()?;
+    let status = fields.next()?;
+    let latitude = (fields.next()?, fields.next()?);
+    let longitude = (fields.next()?, fields.next()?);
+    let _speed = fields.next()?;
+    let _course = fields.next()?;
+    let date = fields.next()?;
+
+    fix.valid = status == "A";
+    fix.time = parse_time(time);
+    fix.date = parse_date(date);
+    fix.latitude = parse_coordinate(latitude, 2, 'N', 'S');
+    fix.longitude = parse_coordinate(longitude, 3, 'E', 'W');
+    Some(())
+}
+
+fn read_gga<'a>(fix: &mut Fix, mut fields: impl Iterator<Item = &'a str>) -> Option<()> {
+    let time = fields.next()?;
+    let latitude = (fields.next()?, fields.next()?);
+    let longitude = (fields.next()?, fields.next()?);
+    let quality = fields.next()?;
+    let satellites = fields.next()?;
+
+    fix.valid = !matches!(quality, "" | "0");
+    fix.time = parse_time(time);
+    fix.latitude = parse_coordinate(latitude, 2, 'N', 'S');
+    fix.longitude = parse_coordinate(longitude, 3, 'E', 'W');
+    fix.satellites = satellites.parse().ok();
+    Some(())
+}
+
+/// Parses two decimal digits at the start of `s`.
+fn two_digits(s: &str) -> Option<u8> {
+    s.get(..2)?.parse().ok()
+}
+
+/// Parses a time as `hhmmss`, ignoring any fraction of a second.
+fn parse_time(s: &str) -> Option<Time> {
+    let time = Time {
+        hour: two_digits(s)?,
+        minute: two_digits(s.get(2..)?)?,
+        second: two_digits(s.get(4..)?)?,
+    };
+    (time.hour < 24 && time.minute < 60 && time.second < 61).then_some(time)
+}
+
+/// Parses a date as `ddmmyy`.
+fn parse_date(s: &str) -> Option<(u8, u8, u16)> {
+    if s.len() != 6 {
+        return None;
+    }
+    let day = two_digits(s)?;
+    let month = two_digits(&s[2..])?;
+    let year = two_digits(&s[4..])?;
+    Some((day, month, 2000 + u16::from(year)))
+}
+
+/// Parses degrees and minutes as `dddmm.mmmm`, with `digits` digits of
+/// degrees, and the hemisphere.
+fn parse_coordinate(
+    (value, hemisphere): (&str, &str),
+    digits: usize,
+    positive: char,
+    negative: char,
+) -> Option<Coordinate> {
+    let degrees: i32 = value.get(..digits)?.parse().ok()?;
+    let minutes = value.get(digits..)?;
+    let (whole, fraction) = minutes.split_once('.').unwrap_or((minutes, ""));
+    if whole.len() != 2 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // Minutes in units of 0.00001, which a sixth of turns into millionths
+    // of a degree.
+    let mut minutes: i32 = whole.parse().ok()?;
+    for i in 0..5 {
+        let digit = fraction.as_bytes().get(i).map_or(0, |b| b - b'0');
+        minutes = minutes * 10 + i32::from(digit);
+    }
+    let value = degrees * 1_000_000 + minutes / 6;
+    match hemisphere.chars().next()? {
+        c if c == positive => Some(Coordinate(value)),
+        c if c == negative => Some(Coordinate(-value)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(parser: &mut Parser, sentence: &str) -> bool {
+        let mut read = false;
+        for byte in sentence.bytes().chain(*b"\r\n") {
+            read |= parser.feed(byte);
+        }
+        read
+    }
+
+    fn display<T: uDisplay>(value: T) -> heapless::String<32> {
+        let mut s = heapless::String::new();
+        uwrite!(s, "{}", value).unwrap();
+        s
+    }
+
+    #[test]
+    fn reads_rmc() {
+        let mut parser = Parser::new();
+        assert!(feed(
+            &mut parser,
+            "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A"
+        ));
+        let fix = parser.fix;
+        assert!(fix.valid);
+        assert_eq!(fix.latitude, Some(Coordinate(48_117_300)));
+        assert_eq!(fix.longitude, Some(Coordinate(11_516_666)));
+        assert_eq!(display(fix.date_time().unwrap()), "2094-03-23T12:35:19");
+    }
+
+    #[test]
+    fn reads_gga() {
+        let mut parser = Parser::new();
+        assert!(feed(
+            &mut parser,
+            "$GNGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,*68"
+        ));
+        let fix = parser.fix;
+        assert!(fix.valid);
+        assert_eq!(fix.satellites, Some(8));
+        assert_eq!(fix.longitude, Some(Coordinate(-6_505_620)));
+        assert_eq!(display(fix.longitude.unwrap()), "-6.505620");
+        assert_eq!(display(fix.time.unwrap()), "09:27:50");
+        assert!(fix.date_time().is_none());
+    }
+
+    #[test]
+    fn reads_no_fix() {
+        let mut parser = Parser::new();
+        assert!(feed(&mut parser, "$GPRMC,,V,,,,,,,,,,N*53"));
+        assert!(!parser.fix.valid);
+        assert_eq!(parser.fix.latitude, None);
+        assert_eq!(parser.sentences, 1);
+    }
+
+    #[test]
+    fn skips_bad_and_other_sentences() {
+        let mut parser = Parser::new();
+        assert!(!feed(
+            &mut parser,
+            "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6B"
+        ));
+        assert!(!feed(&mut parser, "$GPGSV,1,1,00*79"));
+        assert!(!feed(&mut parser, "noise without a start"));
+        assert_eq!(parser.sentences, 0);
+    }
+
+    #[test]
+    fn displays_small_coordinates() {
+        assert_eq!(display(Coordinate(5)), "0.000005");
+        assert_eq!(display(Coordinate(-120_000_000)), "-120.000000");
+    }
+}
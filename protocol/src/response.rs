@@ -0,0 +1,114 @@
+//! Single line responses, for programs rather than people.
+//!
+//! In machine mode the text a command prints is collected into one line,
+//! which goes out after `OK` or `ERR <code>` once the command is done.
+
+use heapless::String;
+use ufmt::{uWrite, uwrite};
+
+/// Ends a response that was cut.
+pub const TRUNCATED: &str = "...";
+
+/// Adds output to a response, with lines joined by spaces and each
+/// `key: value` turned into `key=value`. JSON responses are converted when
+/// they are complete, see [`crate::json`], so they only drop empty lines.
+///
+/// Output that doesn't fit is cut, and ends in [`TRUNCATED`] then. The
+/// marker's room is kept free, so a response that takes up the last few bytes
+/// is marked as well.
+pub fn collect<const N: usize>(response: &mut String<N>, json: bool, s: &str) {
+    if json {
+        for c in s.chars() {
+            if c == '\n' && (response.is_empty() || response.ends_with('\n')) {
+                continue;
+            }
+            push(response, c);
+        }
+        return;
+    }
+    for c in s.chars() {
+        if c == ' ' || c == '\n' {
+            if response.ends_with(':') {
+                response.pop();
+                push(response, '=');
+                continue;
+            }
+            if response.is_empty() || response.ends_with(' ') {
+                continue;
+            }
+            push(response, ' ');
+            continue;
+        }
+        push(response, c);
+    }
+}
+
+fn push<const N: usize>(response: &mut String<N>, c: char) {
+    if response.len() + c.len_utf8() + TRUNCATED.len() <= N {
+        let _ = response.push(c);
+    } else if !response.ends_with(TRUNCATED) {
+        let _ = response.push_str(TRUNCATED);
+    }
+}
+
+/// Writes the line that ends a command, such as `OK a0=512` or
+/// `ERR bad_pin invalid pin: 5`.
+pub fn write_line<W: uWrite + ?Sized>(
+    w: &mut W,
+    error: Option<&str>,
+    response: &str,
+) -> Result<(), W::Error> {
+    match error {
+        None => uwrite!(w, "OK")?,
+        Some(code) => uwrite!(w, "ERR {}", code)?,
+    }
+    if !response.is_empty() {
+        uwrite!(w, " {}", response)?;
+    }
+    uwrite!(w, "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collected(json: bool, parts: &[&str]) -> String<64> {
+        let mut response = String::new();
+        for part in parts {
+            collect(&mut response, json, part);
+        }
+        response
+    }
+
+    #[test]
+    fn lines_are_joined() {
+        assert_eq!(collected(false, &["a0: 512\n", "a1: 3\n"]), "a0=512 a1=3 ");
+        assert_eq!(collected(false, &["\n\nhello  world\n"]), "hello world ");
+    }
+
+    #[test]
+    fn json_keeps_lines() {
+        assert_eq!(collected(true, &["\na: 1\n\n", "b: 2\n"]), "a: 1\nb: 2\n");
+    }
+
+    #[test]
+    fn overlong_output_is_cut() {
+        let mut response: String<6> = String::new();
+        collect(&mut response, false, "abcdefgh");
+        assert_eq!(response, "abc...");
+        collect(&mut response, false, "ijk");
+        assert_eq!(response, "abc...");
+        let mut response: String<6> = String::new();
+        collect(&mut response, true, "abc\ndef");
+        assert_eq!(response, "abc...");
+    }
+
+    #[test]
+    fn lines() {
+        let mut out: String<64> = String::new();
+        write_line(&mut out, None, "").unwrap();
+        write_line(&mut out, None, "a0=512").unwrap();
+        write_line(&mut out, Some("bad_pin"), "invalid pin: 5").unwrap();
+        assert_eq!(out, "OK\nOK a0=512\nERR bad_pin invalid pin: 5\n");
+    }
+}
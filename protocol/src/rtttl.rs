@@ -0,0 +1,226 @@
+//! Ring Tone Text Transfer Language melodies, as in
+//! `pass:d=16,o=6,b=180:c,e,g,8c7`.
+//!
+//! A melody is a name, the default duration, octave, and tempo, and the
+//! notes, each an optional duration, a note from `a` to `g` with an optional
+//! `#`, or `p` for a pause, and an optional octave and dot. Octaves 3-8 are
+//! supported, and a dot makes a note half as long again.
+
+/// Melodies that `melody` plays by name.
+pub const JINGLES: [(&str, &str); 3] = [
+    ("pass", "pass:d=16,o=6,b=180:c,e,g,8c7"),
+    ("fail", "fail:d=4,o=4,b=120:8g,8p,2c"),
+    ("ready", "ready:d=8,o=5,b=200:c,g,c6"),
+];
+
+/// Looks up one of [`JINGLES`].
+pub fn jingle(name: &str) -> Option<&'static str> {
+    JINGLES
+        .iter()
+        .find(|&&(jingle, _)| jingle == name)
+        .map(|&(_, song)| song)
+}
+
+/// The settings between the name and the notes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Defaults {
+    /// The fraction of a whole note a note lasts without a duration of its
+    /// own.
+    pub duration: u8,
+    pub octave: u8,
+    /// Quarter notes per minute.
+    pub bpm: u16,
+}
+
+impl Default for Defaults {
+    /// The defaults of the specification, for settings a melody leaves out.
+    fn default() -> Self {
+        Defaults {
+            duration: 4,
+            octave: 6,
+            bpm: 63,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Note {
+    /// The pitch in Hz, or 0 for a pause.
+    pub frequency: u16,
+    pub duration_ms: u16,
+}
+
+/// The frequencies of octave 4, from c to b.
+const OCTAVE_4: [u16; 12] = [262, 277, 294, 311, 330, 349, 370, 392, 415, 440, 466, 494];
+
+const OCTAVES: core::ops::RangeInclusive<u8> = 3..=8;
+const DURATIONS: [u8; 6] = [1, 2, 4, 8, 16, 32];
+
+/// Checks a whole melody, and returns its settings and where its notes
+/// start.
+pub fn parse(song: &str) -> Option<(Defaults, usize)> {
+    let (_name, rest) = song.split_once(':')?;
+    let (settings, notes) = rest.split_once(':')?;
+    let mut defaults = Defaults::default();
+    for setting in settings.split(',').filter(|s| !s.is_empty()) {
+        let (key, value) = setting.split_once('=')?;
+        match key.trim() {
+            "d" => defaults.duration = value.trim().parse().ok()?,
+            "o" => defaults.octave = value.trim().parse().ok()?,
+            "b" => defaults.bpm = value.trim().parse().ok()?,
+            _ => return None,
+        }
+    }
+    if !DURATIONS.contains(&defaults.duration)
+        || !OCTAVES.contains(&defaults.octave)
+        || defaults.bpm == 0
+    {
+        return None;
+    }
+    let start = song.len() - notes.len();
+    let mut position = start;
+    while position < song.len() {
+        let (_, len) = next_note(&song[position..], &defaults)?;
+        position += len;
+    }
+    Some((defaults, start))
+}
+
+/// Reads the note at the start of `notes`, and returns it along with how
+/// many bytes it took up, including the comma after it. Returns `None` at
+/// the end, or if the note doesn't parse.
+pub fn next_note(notes: &str, defaults: &Defaults) -> Option<(Note, usize)> {
+    let (note, len) = match notes.find(',') {
+        Some(end) => (&notes[..end], end + 1),
+        None => (notes, notes.len()),
+    };
+    let note = note.trim();
+    let bytes = note.as_bytes();
+    let mut i = 0;
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while bytes.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        note[start..*i].parse::<u8>().ok()
+    };
+
+    let duration = digits(&mut i).unwrap_or(defaults.duration);
+    if !DURATIONS.contains(&duration) {
+        return None;
+    }
+    let semitone = match bytes.get(i)?.to_ascii_lowercase() {
+        b'c' => Some(0),
+        b'd' => Some(2),
+        b'e' => Some(4),
+        b'f' => Some(5),
+        b'g' => Some(7),
+        b'a' => Some(9),
+        b'b' => Some(11),
+        b'p' => None,
+        _ => return None,
+    };
+    i += 1;
+    let sharp = bytes.get(i) == Some(&b'#');
+    if sharp {
+        i += 1;
+    }
+    let mut dotted = bytes.get(i) == Some(&b'.');
+    if dotted {
+        i += 1;
+    }
+    let octave = digits(&mut i).unwrap_or(defaults.octave);
+    if !OCTAVES.contains(&octave) {
+        return None;
+    }
+    if bytes.get(i) == Some(&b'.') {
+        dotted = true;
+        i += 1;
+    }
+    if i != bytes.len() {
+        return None;
+    }
+
+    let frequency = semitone.map_or(0, |semitone| {
+        let index = semitone + usize::from(sharp);
+        // A b sharp is the c of the next octave.
+        let (index, octave) = if index == 12 {
+            (0, octave + 1)
+        } else {
+            (index, octave)
+        };
+        let frequency = u32::from(OCTAVE_4[index]) << octave.saturating_sub(4);
+        (frequency >> 4u8.saturating_sub(octave)) as u16
+    });
+    let whole_ms = 240_000 / u32::from(defaults.bpm);
+    let mut duration_ms = whole_ms / u32::from(duration);
+    if dotted {
+        duration_ms += duration_ms / 2;
+    }
+    let note = Note {
+        frequency,
+        duration_ms: duration_ms.min(u32::from(u16::MAX)) as u16,
+    };
+    Some((note, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+
+    fn notes(song: &str) -> Vec<Note, 8> {
+        let (defaults, mut position) = parse(song).unwrap();
+        let mut notes = Vec::new();
+        while let Some((note, len)) = next_note(&song[position..], &defaults) {
+            notes.push(note).unwrap();
+            position += len;
+        }
+        notes
+    }
+
+    fn note(frequency: u16, duration_ms: u16) -> Note {
+        Note {
+            frequency,
+            duration_ms,
+        }
+    }
+
+    #[test]
+    fn reads_notes() {
+        assert_eq!(
+            notes("test:d=4,o=5,b=120:c,8e6,p,g#.,2a4.,b#"),
+            [
+                note(524, 500),
+                note(1320, 250),
+                note(0, 500),
+                note(830, 750),
+                note(440, 1500),
+                note(1048, 500),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_defaults() {
+        assert_eq!(notes("low::c3"), [note(131, 952)]);
+    }
+
+    #[test]
+    fn rejects_bad_melodies() {
+        assert!(parse("no settings").is_none());
+        assert!(parse("x:d=3:c").is_none());
+        assert!(parse("x:o=9:c").is_none());
+        assert!(parse("x:d=4:h").is_none());
+        assert!(parse("x:d=4:c,,e").is_none());
+    }
+
+    #[test]
+    fn jingles_parse() {
+        for (name, song) in JINGLES {
+            assert!(parse(song).is_some(), "{}", name);
+        }
+        assert_eq!(jingle("pass"), Some(JINGLES[0].1));
+    }
+}
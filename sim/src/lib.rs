@@ -0,0 +1,219 @@
+//! The console on the host, with simulated peripherals.
+//!
+//! [`Sim`] runs lines of commands the way the firmware does, through the
+//! same parser and the same [`protocol::io`] code, against fake pins and
+//! analog inputs laid out like an Uno's. Commands that need other hardware
+//! are refused. Tests drive the inputs, run commands, and check the outputs
+//! and what was printed.
+
+use protocol::args::console::Base;
+use protocol::io::{self, Io};
+use protocol::{Command, Error, LED_PIN};
+use ufmt::uwriteln;
+
+/// Maximum length of an input line, as on the firmware.
+const INPUT_LENGTH: usize = 64;
+
+/// The general purpose digital pins, as on the firmware.
+const DIGITAL_PINS: [u8; 10] = [2, 3, 4, 6, 7, 8, 9, 10, 11, 12];
+
+/// The Uno's analog inputs, a4 and a5 included since there is no I2C bus to
+/// take them.
+const ANALOG_INPUTS: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pin {
+    /// An input, at the level something outside drives it to.
+    Input {
+        high: bool,
+    },
+    Output {
+        high: bool,
+    },
+}
+
+pub struct Sim {
+    /// Indexed by pin number, with the pins that commands can't use left as
+    /// inputs that never change.
+    pins: [Pin; 14],
+    analog: [u16; ANALOG_INPUTS],
+    base: Base,
+    output: String,
+}
+
+impl Default for Sim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sim {
+    /// A board as after a reset, with every pin a low input.
+    pub fn new() -> Self {
+        Sim {
+            pins: [Pin::Input { high: false }; 14],
+            analog: [0; ANALOG_INPUTS],
+            base: Base::Decimal,
+            output: String::new(),
+        }
+    }
+
+    pub fn pin(&self, pin: u8) -> Pin {
+        self.pins[usize::from(pin)]
+    }
+
+    /// Drives an input from outside. Outputs keep their level.
+    pub fn drive(&mut self, pin: u8, high: bool) {
+        if let Pin::Input { .. } = self.pins[usize::from(pin)] {
+            self.pins[usize::from(pin)] = Pin::Input { high };
+        }
+    }
+
+    /// Sets the reading of an analog input, from 0 to 1023.
+    pub fn set_analog(&mut self, pin: u8, value: u16) {
+        self.analog[usize::from(pin)] = value.min(1023);
+    }
+
+    /// Takes what the commands printed so far.
+    pub fn take_output(&mut self) -> String {
+        core::mem::take(&mut self.output)
+    }
+
+    /// Runs a line of `;`-separated commands, stopping at the first that
+    /// fails.
+    pub fn run_line(&mut self, line: &str) -> Result<(), Error> {
+        io::run_line::<INPUT_LENGTH, _>(self, line)
+    }
+}
+
+impl Io for Sim {
+    type Output = String;
+
+    const DIGITAL_PINS: &'static str = "2-4, 6-12, led";
+    const ANALOG_PINS: &'static str = "0-5";
+
+    fn output(&mut self) -> &mut String {
+        &mut self.output
+    }
+
+    fn base(&self) -> Base {
+        self.base
+    }
+
+    fn digital_read(&mut self, pin: u8) -> Option<bool> {
+        if pin != LED_PIN && !DIGITAL_PINS.contains(&pin) {
+            return None;
+        }
+        match self.pins[usize::from(pin)] {
+            Pin::Input { high } | Pin::Output { high } => Some(high),
+        }
+    }
+
+    fn digital_write(&mut self, pin: u8, high: bool) -> Option<()> {
+        if pin != LED_PIN && !DIGITAL_PINS.contains(&pin) {
+            return None;
+        }
+        self.pins[usize::from(pin)] = Pin::Output { high };
+        Some(())
+    }
+
+    fn analog_read(&mut self, pin: u8) -> Option<u16> {
+        self.analog.get(usize::from(pin)).copied()
+    }
+
+    fn run(&mut self, input: &str, command: Command<'_>) -> Result<(), Error> {
+        if let Command::Base(base) = command {
+            self.base = base;
+            return Ok(());
+        }
+        match io::execute(self, &command) {
+            Some(result) => result,
+            None => {
+                let _ = uwriteln!(&mut self.output, "not simulated: {}", input);
+                Err(Error::UnknownCommand)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_drives_the_pin() {
+        let mut sim = Sim::new();
+        sim.run_line("set 7 high").unwrap();
+        assert_eq!(sim.pin(7), Pin::Output { high: true });
+        sim.run_line("SET d7 LOW").unwrap();
+        assert_eq!(sim.pin(7), Pin::Output { high: false });
+        assert_eq!(sim.take_output(), "");
+    }
+
+    #[test]
+    fn get_reads_inputs_and_outputs() {
+        let mut sim = Sim::new();
+        sim.drive(4, true);
+        sim.run_line("get 4; set 9 high; get 9").unwrap();
+        assert_eq!(sim.take_output(), "d4: true\nd9: true\n");
+        assert_eq!(sim.pin(4), Pin::Input { high: true });
+    }
+
+    #[test]
+    fn outputs_ignore_the_outside() {
+        let mut sim = Sim::new();
+        sim.run_line("set 3 low").unwrap();
+        sim.drive(3, true);
+        sim.run_line("get 3").unwrap();
+        assert_eq!(sim.take_output(), "d3: false\n");
+    }
+
+    #[test]
+    fn led() {
+        let mut sim = Sim::new();
+        sim.run_line("led on").unwrap();
+        assert_eq!(sim.pin(LED_PIN), Pin::Output { high: true });
+        sim.run_line("get led").unwrap();
+        assert_eq!(sim.take_output(), "d13: true\n");
+    }
+
+    #[test]
+    fn adc_in_each_base() {
+        let mut sim = Sim::new();
+        sim.set_analog(2, 512);
+        sim.run_line("adc 2; base hex; adc a2; base bin; adc 2")
+            .unwrap();
+        assert_eq!(sim.take_output(), "a2: 512\na2: 0x200\na2: 0b1000000000\n");
+    }
+
+    #[test]
+    fn bad_pins() {
+        let mut sim = Sim::new();
+        assert_eq!(sim.run_line("set 5 high"), Err(Error::BadPin));
+        assert_eq!(sim.run_line("adc 6"), Err(Error::BadPin));
+        assert_eq!(
+            sim.take_output(),
+            "unknown pin: 5, valid pins are 2-4, 6-12, led\nunknown pin: 6, valid pins are 0-5\n"
+        );
+        assert_eq!(sim.pin(5), Pin::Input { high: false });
+    }
+
+    #[test]
+    fn a_failing_command_stops_the_line() {
+        let mut sim = Sim::new();
+        assert_eq!(
+            sim.run_line("set 2 high; frobnicate; set 3 high"),
+            Err(Error::UnknownCommand)
+        );
+        assert_eq!(sim.pin(2), Pin::Output { high: true });
+        assert_eq!(sim.pin(3), Pin::Input { high: false });
+        assert_eq!(sim.take_output(), "invalid command: frobnicate\n");
+    }
+
+    #[test]
+    fn other_hardware_is_refused() {
+        let mut sim = Sim::new();
+        assert!(sim.run_line("i2c scan").is_err());
+        assert_eq!(sim.take_output(), "not simulated: i2c scan\n");
+    }
+}
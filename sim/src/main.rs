@@ -0,0 +1,25 @@
+//! Runs the console on stdin and stdout, with simulated peripherals.
+
+use std::io::{self, BufRead, Write};
+
+use mechanical_crab_sim::Sim;
+
+fn main() {
+    let mut sim = Sim::new();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "> ");
+    let _ = stdout.flush();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let result = sim.run_line(&line);
+        let _ = write!(stdout, "{}", sim.take_output());
+        if let Err(error) = result {
+            let _ = writeln!(stdout, "error: {}", error.code());
+        }
+        let _ = write!(stdout, "> ");
+        let _ = stdout.flush();
+    }
+}